@@ -1,5 +1,8 @@
+use std::{collections::HashSet, time::Instant};
+
 use adw::{prelude::*, subclass::prelude::*};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use futures_channel::oneshot;
 use gtk::{
     gio,
@@ -8,30 +11,48 @@ use gtk::{
 
 use crate::{
     camera::Camera,
+    camera_profile::CameraProfileSettingsExt,
+    code_parser::CodeParserSettingsExt,
     date_time_boxed::DateTimeBoxed,
     date_time_updater::DateTimeUpdater,
     db,
-    detected_wo_id_item::DetectedWoIdItem,
+    debounce::{DebounceSettingsExt, DetectionSource},
+    detected_wo_id_item::{DetectedWoIdItem, DetectedWoIdKind},
     detected_wo_id_list::DetectedWoIdList,
     detector::Detector,
     entity::Entity,
     entity_data::EntityData,
     entity_entry_tracker::EntityIdSet,
     entity_id::EntityId,
+    event_clip::{self, EventClip, EventClipKind, EventClipSettingsExt},
     jpeg_image::JpegImage,
     limit_reached::{LimitReached, LimitReachedSettingsExt},
+    local_reader::{LocalReader, LocalReaderSettingsExt},
+    location_id::LocationId,
+    motion_detector::MotionDetectorSettingsExt,
+    mqtt_client::{MqttClient, MqttSettingsExt, MqttTopic},
+    pending_detection::PendingDetection,
+    pending_detection_list::PendingDetectionList,
     relay::{Relay, RelayState},
     rfid_reader::RfidReader,
     settings::{OperationMode, Settings},
     sound::Sound,
-    timeline::Timeline,
+    timeline::{Detection, Timeline},
+    timeline_item::TimelineItem,
     timeline_item_kind::TimelineItemKind,
     ui::{EntityDataDialog, SendDialog, TestWindow, ToastId, Window},
+    webhook::{WebhookEvent, WebhookEventData, WebhookQueue, WebhookSettingsExt},
+    zone::{self, ZoneDef, ZoneSettingsExt},
+    zone_move::ZoneMove,
     APP_ID, GRESOURCE_PREFIX,
 };
 
+pub const DETECTED_INVALID_MESSAGE: &str = "Invalid code detected";
+pub const DETECTED_WO_ID_MESSAGE: &str = "Detected unregistered entity!";
+pub const TAILGATING_DETECTED_MESSAGE: &str = "Possible tailgating detected!";
+
 mod imp {
-    use std::cell::OnceCell;
+    use std::cell::{Cell, OnceCell, RefCell};
 
     use super::*;
 
@@ -46,10 +67,18 @@ mod imp {
         pub(super) detector: Detector,
 
         pub(super) relay: OnceCell<Relay>,
+        pub(super) mqtt_client: OnceCell<MqttClient>,
 
         pub(super) env: OnceCell<heed::Env>,
         pub(super) timeline: OnceCell<Timeline>,
         pub(super) detected_wo_id_list: OnceCell<DetectedWoIdList>,
+        pub(super) pending_detection_list: OnceCell<PendingDetectionList>,
+        pub(super) webhook_queue: OnceCell<WebhookQueue>,
+
+        pub(super) is_gathering_entity_data: Cell<bool>,
+
+        pub(super) alerted_limit_reached: Cell<Option<LimitReached>>,
+        pub(super) alerted_zone_overstayed: RefCell<HashSet<(EntityId, String)>>,
     }
 
     #[glib::object_subclass]
@@ -107,27 +136,73 @@ mod imp {
             self.settings.connect_camera_ip_addr_changed(clone!(
                 #[weak]
                 obj,
-                move |settings| {
-                    let ip_addr = settings.camera_ip_addr();
-                    if let Err(err) = obj.camera().set_ip_addr(ip_addr) {
-                        tracing::error!("Failed to set camera IP address: {:?}", err);
-                    }
+                move |_| {
+                    obj.update_camera_profile();
                 }
             ));
             self.settings.connect_aux_camera_ip_addrs_changed(clone!(
                 #[weak]
                 obj,
-                move |settings| {
-                    obj.detector().unbind_aux_cameras();
-
-                    let cameras = settings
-                        .aux_camera_ip_addrs()
-                        .into_iter()
-                        .map(Camera::new)
-                        .collect::<Vec<_>>();
-                    obj.detector().bind_aux_cameras(&cameras);
+                move |_| {
+                    obj.rebind_aux_cameras();
+                }
+            ));
+            self.settings.connect_camera_profiles_json_changed(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.update_camera_profile();
+                    obj.rebind_aux_cameras();
                 }
             ));
+            self.settings
+                .connect_enable_local_motion_detection_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_local_motion_detector_config();
+                    }
+                ));
+            self.settings
+                .connect_local_motion_detection_sensitivity_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_local_motion_detector_config();
+                    }
+                ));
+            self.settings
+                .connect_local_motion_detection_regions_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_local_motion_detector_config();
+                    }
+                ));
+            self.settings
+                .connect_enable_event_clip_recording_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_event_clip_config();
+                    }
+                ));
+            self.settings
+                .connect_event_clip_pre_event_secs_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_event_clip_config();
+                    }
+                ));
+            self.settings
+                .connect_event_clip_post_event_secs_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_event_clip_config();
+                    }
+                ));
             self.settings.connect_relay_ip_addr_changed(clone!(
                 #[weak]
                 obj,
@@ -136,6 +211,22 @@ mod imp {
                     obj.relay().set_ip_addr(ip_addr);
                 }
             ));
+            self.settings.connect_mqtt_config_changed(clone!(
+                #[weak]
+                obj,
+                move |settings| {
+                    obj.mqtt_client().set_config(settings.mqtt_config());
+                }
+            ));
+            self.settings.connect_webhooks_json_changed(clone!(
+                #[weak]
+                obj,
+                move |settings| {
+                    if let Some(targets) = settings.webhook_targets() {
+                        obj.webhook_queue().set_targets(targets);
+                    }
+                }
+            ));
             self.settings.connect_rfid_reader_ip_addr_changed(clone!(
                 #[weak]
                 obj,
@@ -144,6 +235,28 @@ mod imp {
                     obj.rfid_reader().set_ip_addr(ip_addr);
                 }
             ));
+            self.settings.connect_debounce_windows_changed(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.update_debounce_windows();
+                }
+            ));
+            self.settings.connect_code_parsers_json_changed(clone!(
+                #[weak]
+                obj,
+                move |settings| {
+                    obj.detector()
+                        .set_code_parser_registry(settings.code_parser_registry());
+                }
+            ));
+            self.settings.connect_local_reader_devices_changed(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.rebind_local_readers();
+                }
+            ));
             self.settings.connect_enable_detection_wo_id_changed(clone!(
                 #[weak]
                 obj,
@@ -152,6 +265,16 @@ mod imp {
                         .set_enable_detection_wo_id(settings.enable_detection_wo_id());
                 }
             ));
+            self.settings
+                .connect_enable_tailgating_detection_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |settings| {
+                        obj.detector().set_enable_tailgating_detection(
+                            settings.enable_tailgating_detection(),
+                        );
+                    }
+                ));
             self.settings.connect_enable_n_inside_hook_changed(clone!(
                 #[weak]
                 obj,
@@ -168,37 +291,40 @@ mod imp {
                     }
                 ));
 
-            let camera = Camera::new(self.settings.camera_ip_addr());
+            let camera = obj.create_camera(&self.settings.camera_ip_addr());
             self.camera.set(camera).unwrap();
-
-            let aux_cameras = self
-                .settings
-                .aux_camera_ip_addrs()
-                .into_iter()
-                .filter(|ip_addr| !ip_addr.is_empty())
-                .map(Camera::new)
-                .collect::<Vec<_>>();
+            obj.update_event_clip_config();
 
             let rfid_reader = RfidReader::new(self.settings.rfid_reader_ip_addr());
             self.rfid_reader.set(rfid_reader).unwrap();
 
+            let mqtt_client = MqttClient::new(self.settings.mqtt_config());
+            self.mqtt_client.set(mqtt_client).unwrap();
+
             self.detector.bind_camera(obj.camera());
-            self.detector.bind_aux_cameras(&aux_cameras);
+            obj.rebind_aux_cameras();
             self.detector.bind_rfid_reader(obj.rfid_reader());
+            self.detector.bind_mqtt_client(obj.mqtt_client());
+            obj.rebind_local_readers();
 
+            obj.update_debounce_windows();
+            self.detector
+                .set_code_parser_registry(self.settings.code_parser_registry());
             self.detector
                 .set_enable_detection_wo_id(self.settings.enable_detection_wo_id());
+            self.detector
+                .set_enable_tailgating_detection(self.settings.enable_tailgating_detection());
             self.detector.connect_detected(clone!(
                 #[weak]
                 obj,
-                move |_, entity_id, entity_data| {
+                move |_, entity_id, entity_data, zone| {
                     glib::spawn_future_local(clone!(
                         #[strong]
                         entity_id,
                         #[strong]
                         entity_data,
                         async move {
-                            obj.handle_detected(&entity_id, entity_data).await;
+                            obj.handle_detected(&entity_id, entity_data, zone).await;
                         }
                     ));
                 }
@@ -219,15 +345,32 @@ mod imp {
                     }
                 }
             ));
+            self.detector.connect_tailgating_detected(clone!(
+                #[weak]
+                obj,
+                move |_, dt, image| {
+                    if let Err(err) = obj.handle_tailgating_detected(dt, image) {
+                        tracing::error!("Failed to handle tailgating detected: {:?}", err);
+                    }
+                }
+            ));
 
             let relay = Relay::new(self.settings.relay_ip_addr());
             self.relay.set(relay).unwrap();
 
             match init_env() {
-                Ok((env, timeline, detected_wo_id_list)) => {
+                Ok((env, timeline, detected_wo_id_list, pending_detection_list, webhook_queue)) => {
                     self.env.set(env).unwrap();
                     self.timeline.set(timeline).unwrap();
                     self.detected_wo_id_list.set(detected_wo_id_list).unwrap();
+                    self.pending_detection_list
+                        .set(pending_detection_list)
+                        .unwrap();
+
+                    if let Some(targets) = self.settings.webhook_targets() {
+                        webhook_queue.set_targets(targets);
+                    }
+                    self.webhook_queue.set(webhook_queue).unwrap();
                 }
                 Err(err) => {
                     tracing::debug!("Failed to init env: {:?}", err);
@@ -241,6 +384,114 @@ mod imp {
                 move |_| {
                     obj.update_relay_state();
                     obj.alert_if_limit_reached();
+                    obj.publish_n_inside();
+                }
+            ));
+            obj.timeline()
+                .entity_entry_tracker()
+                .connect_overstayed_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_, _| {
+                        obj.publish_overstayed();
+                    }
+                ));
+            obj.timeline()
+                .stock_limit_reached_tracker()
+                .connect_n_lower_limit_reached_notify(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.publish_stock_limit();
+                    }
+                ));
+            obj.timeline()
+                .stock_limit_reached_tracker()
+                .connect_n_upper_limit_reached_notify(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.publish_stock_limit();
+                    }
+                ));
+            obj.timeline()
+                .entity_expired_tracker()
+                .connect_expiring(clone!(
+                    #[weak]
+                    obj,
+                    move |_, EntityIdSet(entity_ids)| {
+                        let entities = entity_ids
+                            .iter()
+                            .map(|id| {
+                                obj.timeline()
+                                    .entity_list()
+                                    .get(id)
+                                    .expect("entity must exist")
+                            })
+                            .filter(|entity| entity.is_inside())
+                            .collect::<Vec<_>>();
+
+                        // Only alert for the ones that are still in stock.
+                        if entities.is_empty() {
+                            return;
+                        }
+
+                        obj.add_message_toast(&expiring_message(&entities));
+                    }
+                ));
+            obj.timeline()
+                .entity_expired_tracker()
+                .connect_expired(clone!(
+                    #[weak]
+                    obj,
+                    move |_, EntityIdSet(entity_ids)| {
+                        let entities = entity_ids
+                            .iter()
+                            .map(|id| {
+                                obj.timeline()
+                                    .entity_list()
+                                    .get(id)
+                                    .expect("entity must exist")
+                            })
+                            .filter(|entity| entity.is_inside())
+                            .collect::<Vec<_>>();
+
+                        // Only alert for the ones that are still in stock.
+                        if entities.is_empty() {
+                            return;
+                        }
+
+                        let now = obj.timeline().clock().now();
+                        for entity in &entities {
+                            obj.enqueue_webhook_for_entity(
+                                WebhookEvent::ExpiredEntity,
+                                now,
+                                entity.id(),
+                                "Entity expired",
+                            );
+                        }
+
+                        obj.add_message_toast(&expired_message(&entities));
+
+                        Sound::CriticalAlert.play();
+                    }
+                ));
+            obj.mqtt_client().connect_connected(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.publish_n_inside();
+                    obj.publish_overstayed();
+                    obj.publish_stock_limit();
+                }
+            ));
+            obj.mqtt_client().connect_message_received(clone!(
+                #[weak]
+                obj,
+                move |mqtt_client, topic, payload| {
+                    if mqtt_client.topic(MqttTopic::RelaySet).as_deref() == Some(topic) {
+                        obj.handle_relay_command(payload);
+                    }
                 }
             ));
             obj.timeline()
@@ -253,49 +504,42 @@ mod imp {
                             return;
                         }
 
-                        match entity_ids.iter().collect::<Vec<_>>().as_slice() {
-                            [] => return,
-                            [id] => {
-                                let entity = obj
-                                    .timeline()
+                        let now = obj.timeline().clock().now();
+                        for entity_id in entity_ids {
+                            obj.record_event_clip(
+                                now,
+                                EventClipKind::Overstayed,
+                                Some(entity_id.clone()),
+                            );
+                            obj.enqueue_webhook_for_entity(
+                                WebhookEvent::Overstayed,
+                                now,
+                                entity_id,
+                                "Entity overstayed",
+                            );
+                        }
+
+                        let entities = entity_ids
+                            .iter()
+                            .map(|id| {
+                                obj.timeline()
                                     .entity_list()
                                     .get(id)
-                                    .expect("entity must exist");
-
-                                obj.add_message_toast(&format!(
-                                    "“{}” overstayed",
-                                    id_or_name(&entity)
-                                ));
-                            }
-                            [id1, id2] => {
-                                let entity1 = obj
-                                    .timeline()
-                                    .entity_list()
-                                    .get(id1)
-                                    .expect("entity must exist");
-                                let entity2 = obj
-                                    .timeline()
-                                    .entity_list()
-                                    .get(id2)
-                                    .expect("entity must exist");
-
-                                obj.add_message_toast(&format!(
-                                    "“{}” and “{}” overstayed",
-                                    id_or_name(&entity1),
-                                    id_or_name(&entity2),
-                                ));
-                            }
-                            ids => {
-                                obj.add_message_toast(&format!(
-                                    "{} entities overstayed",
-                                    ids.len()
-                                ));
-                            }
-                        }
+                                    .expect("entity must exist")
+                            })
+                            .collect::<Vec<_>>();
+                        obj.add_message_toast(&overstayed_message(&entities));
 
                         Sound::CriticalAlert.play();
                     }
                 ));
+            self.date_time_updater.connect_update(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.alert_if_zone_overstayed();
+                }
+            ));
 
             obj.setup_actions();
             obj.setup_accels();
@@ -373,6 +617,10 @@ impl Application {
         self.imp().relay.get().unwrap()
     }
 
+    pub fn mqtt_client(&self) -> &MqttClient {
+        self.imp().mqtt_client.get().unwrap()
+    }
+
     pub fn env(&self) -> &heed::Env {
         self.imp().env.get().unwrap()
     }
@@ -385,6 +633,14 @@ impl Application {
         self.imp().detected_wo_id_list.get().unwrap()
     }
 
+    pub fn pending_detection_list(&self) -> &PendingDetectionList {
+        self.imp().pending_detection_list.get().unwrap()
+    }
+
+    pub fn webhook_queue(&self) -> &WebhookQueue {
+        self.imp().webhook_queue.get().unwrap()
+    }
+
     pub fn present_test_window(&self) {
         TestWindow::new(self).present();
     }
@@ -408,27 +664,169 @@ impl Application {
             .unwrap_or_else(|| Window::new(self))
     }
 
-    fn alert_if_limit_reached(&self) {
+    fn create_camera(&self, ip_addr_or_profile_name: &str) -> Camera {
+        let profile = self
+            .settings()
+            .resolve_camera_profile(ip_addr_or_profile_name);
+        let camera = Camera::new(profile);
+
+        let config = self.settings().local_motion_detector_config();
+        if let Err(err) = camera.set_local_motion_detector_config(config) {
+            tracing::error!("Failed to set local motion detector config: {:?}", err);
+        }
+
+        camera
+    }
+
+    fn update_camera_profile(&self) {
         let settings = self.settings();
 
-        match settings.compute_limit_reached(self.timeline().n_inside()) {
-            Some(LimitReached::Lower) if settings.enable_lower_limit_reached_alert() => {
-                self.add_message_toast_with_id(ToastId::LimitReached, "Amount Depleted");
+        let profile = settings.resolve_camera_profile(&settings.camera_ip_addr());
+        if let Err(err) = self.camera().set_profile(profile) {
+            tracing::error!("Failed to set camera profile: {:?}", err);
+        }
+    }
+
+    fn rebind_aux_cameras(&self) {
+        self.detector().unbind_aux_cameras();
+
+        let cameras = self
+            .settings()
+            .aux_camera_ip_addrs()
+            .iter()
+            .filter(|ip_addr_or_profile_name| !ip_addr_or_profile_name.is_empty())
+            .map(|ip_addr_or_profile_name| self.create_camera(ip_addr_or_profile_name))
+            .collect::<Vec<_>>();
+        self.detector().bind_aux_cameras(&cameras);
+    }
 
-                Sound::CriticalAlert.play();
-            }
-            Some(LimitReached::Upper) if settings.enable_upper_limit_reached_alert() => {
-                self.add_message_toast_with_id(ToastId::LimitReached, "Capacity Exceeded");
+    fn rebind_local_readers(&self) {
+        self.detector().unbind_local_readers();
+
+        let readers = self
+            .settings()
+            .local_reader_specs()
+            .into_iter()
+            .map(LocalReader::new)
+            .collect::<Vec<_>>();
+        self.detector().bind_local_readers(&readers);
+    }
 
-                Sound::CriticalAlert.play();
+    fn update_local_motion_detector_config(&self) {
+        let config = self.settings().local_motion_detector_config();
+
+        let cameras = [self.camera().clone()]
+            .into_iter()
+            .chain(self.detector().aux_cameras());
+        for camera in cameras {
+            if let Err(err) = camera.set_local_motion_detector_config(config.clone()) {
+                tracing::error!("Failed to set local motion detector config: {:?}", err);
             }
-            _ => {
-                self.remove_message_toast_with_id(ToastId::LimitReached);
+        }
+    }
+
+    fn update_event_clip_config(&self) {
+        let config = self.settings().event_clip_config();
+        if let Err(err) = self.camera().set_event_clip_config(config) {
+            tracing::error!("Failed to set event clip config: {:?}", err);
+        }
+    }
+
+    /// Records a clip around the event in the background.
+    ///
+    /// Returns `None` if event clip recording is disabled.
+    fn record_event_clip(
+        &self,
+        dt: DateTime<Utc>,
+        kind: EventClipKind,
+        entity_id: Option<EntityId>,
+    ) -> Option<EventClip> {
+        self.settings().event_clip_config()?;
+
+        let now = self.timeline().clock().now();
+        let Some(event_instant) = event_clip::event_instant(dt, now, Instant::now()) else {
+            tracing::debug!("Skipped {:?} clip at {}, as its frames are gone", kind, dt);
+            return None;
+        };
+
+        let clip = EventClip::new(dt, kind, entity_id);
+
+        glib::spawn_future_local(clone!(
+            #[strong(rename_to = obj)]
+            self,
+            #[strong]
+            clip,
+            async move {
+                if let Err(err) = obj.camera().record_event_clip(&clip, event_instant).await {
+                    tracing::error!("Failed to record event clip: {:?}", err);
+                    return;
+                }
+
+                let quota_bytes = obj.settings().event_clip_storage_quota_bytes();
+                if let Err(err) = event_clip::enforce_quota(quota_bytes) {
+                    tracing::error!("Failed to enforce event clip quota: {:?}", err);
+                }
             }
+        ));
+
+        Some(clip)
+    }
+
+    fn alert_if_limit_reached(&self) {
+        let imp = self.imp();
+        let settings = self.settings();
+
+        let Some((limit_reached, message)) =
+            limit_reached_alert(settings, self.timeline().n_inside())
+        else {
+            self.remove_message_toast_with_id(ToastId::LimitReached);
+            imp.alerted_limit_reached.set(None);
+            return;
+        };
+
+        self.add_message_toast_with_id(ToastId::LimitReached, message);
+
+        Sound::CriticalAlert.play();
+
+        // Only notify webhooks once until the limit is no longer reached.
+        if imp.alerted_limit_reached.replace(Some(limit_reached)) != Some(limit_reached) {
+            self.enqueue_webhook(WebhookEventData::new(
+                WebhookEvent::LimitReached,
+                self.timeline().clock().now(),
+                message,
+            ));
+        }
+    }
+
+    fn enqueue_webhook(&self, data: WebhookEventData) {
+        if let Err(err) = self.webhook_queue().enqueue(&data) {
+            tracing::error!("Failed to enqueue webhook: {:?}", err);
         }
     }
 
-    async fn handle_detected(&self, entity_id: &EntityId, entity_data: Option<EntityData>) {
+    fn enqueue_webhook_for_entity(
+        &self,
+        event: WebhookEvent,
+        dt: DateTime<Utc>,
+        entity_id: &EntityId,
+        message: &str,
+    ) {
+        let entity = self.timeline().entity_list().get(entity_id);
+
+        self.enqueue_webhook(WebhookEventData {
+            entity_id: Some(entity_id.clone()),
+            entity_name: entity.as_ref().and_then(|e| e.data().name().cloned()),
+            stock_id: entity.as_ref().and_then(|e| e.stock_id()),
+            ..WebhookEventData::new(event, dt, message)
+        });
+    }
+
+    async fn handle_detected(
+        &self,
+        entity_id: &EntityId,
+        entity_data: Option<EntityData>,
+        zone: Option<String>,
+    ) {
         let timeline = self.timeline();
         let operation_mode = self.settings().operation_mode();
 
@@ -441,22 +839,30 @@ impl Application {
 
             entity.data().clone()
         } else if operation_mode != OperationMode::Counter {
-            tracing::debug!("Gathering entity data from user");
+            // Record the detection time right away, so it is neither lost nor
+            // delayed while waiting for the user to enter the entity data.
+            let pending = PendingDetection::new(timeline.clock().now(), entity_id.clone(), zone);
+            if let Err(err) = self.pending_detection_list().insert(pending) {
+                tracing::error!("Failed to queue pending detection: {:?}", err);
 
-            match EntityDataDialog::gather_data(
-                entity_id,
-                &EntityData::new(),
-                [],
-                Some(&self.window()),
-            )
-            .await
-            {
-                Ok(data) => data,
-                Err(oneshot::Canceled) => {
-                    tracing::debug!("Gathering entity data was canceled; ignoring detected entity");
-                    return;
-                }
+                self.add_message_toast("Can't handle entity");
+
+                Sound::DetectedError.play();
+
+                return;
+            }
+
+            if self.imp().is_gathering_entity_data.get() {
+                tracing::debug!("Already gathering entity data; queued detected entity");
+
+                self.add_message_toast(&format!("“{}” is queued for later", entity_id));
+
+                return;
             }
+
+            self.resolve_pending_detections(entity_id).await;
+
+            return;
         } else {
             tracing::debug!("Using empty entity data for counter mode");
 
@@ -467,65 +873,89 @@ impl Application {
 
         // TODO If the mode is inventory or refrigerator, don't handle the detected entity
         // if it doesn't have a stock id.
-        let entity_name = data.name().cloned();
-        match timeline.handle_detected(entity_id, data) {
-            Ok(item) => {
-                match item.kind() {
-                    TimelineItemKind::Entry => {
-                        let message = match entity_name {
-                            Some(name) if operation_mode.is_for_person() => {
-                                format!("Welcome, {}!", name)
-                            }
-                            Some(name) => {
-                                format!("{name} {}", operation_mode.enter_verb())
-                            }
-                            None => {
-                                format!("{entity_id} {}", operation_mode.enter_verb())
-                            }
-                        };
-                        self.add_message_toast_with_id(ToastId::Detected, &message);
-                    }
-                    TimelineItemKind::Exit => {
-                        let message = match entity_name {
-                            Some(name) if operation_mode.is_for_person() => {
-                                format!("Goodbye, {}!", name)
-                            }
-                            Some(name) => {
-                                format!("{name} {}", operation_mode.exit_verb())
-                            }
-                            None => {
-                                format!("{entity_id} {}", operation_mode.exit_verb())
-                            }
-                        };
-                        self.add_message_toast_with_id(ToastId::Detected, &message);
-                    }
+        match timeline.handle_detected(entity_id, data, zone) {
+            Ok(Detection::Item(item)) => {
+                self.handle_timeline_item_added(&item);
+            }
+            Ok(Detection::ZoneMove(zone_move)) => {
+                self.handle_zone_moved(&zone_move);
+            }
+            Err(err) => {
+                tracing::error!("Failed to handle entity: {:?}", err);
+
+                self.add_message_toast("Can't handle entity");
+
+                Sound::DetectedError.play();
+            }
+        }
+    }
+
+    /// Gathers the data of an entity with pending detections from the user,
+    /// then adds its timeline items back-dated to the detection times.
+    ///
+    /// If canceled, the detections stay in the queue.
+    pub async fn resolve_pending_detections(&self, entity_id: &EntityId) {
+        let imp = self.imp();
+
+        if imp.is_gathering_entity_data.get() {
+            self.add_message_toast("Finish entering the current entity data first");
+            return;
+        }
+
+        if !self.pending_detection_list().contains_entity(entity_id) {
+            tracing::debug!("No pending detections for `{}`", entity_id);
+            return;
+        }
+
+        tracing::debug!("Gathering entity data from user");
+
+        imp.is_gathering_entity_data.set(true);
+        let res =
+            EntityDataDialog::gather_data(entity_id, &EntityData::new(), [], Some(&self.window()))
+                .await;
+        imp.is_gathering_entity_data.set(false);
+
+        let data = match res {
+            Ok(data) => data,
+            Err(oneshot::Canceled) => {
+                tracing::debug!("Gathering entity data was canceled; keeping detections queued");
+
+                self.add_message_toast(&format!("“{}” is queued for later", entity_id));
+
+                return;
+            }
+        };
+
+        tracing::debug!(?data, "Handling pending detected entity `{}`", entity_id);
+
+        self.detector().record_entity_data(entity_id, &data);
+
+        // Include the detections that were queued while the dialog was open.
+        let reads = self
+            .pending_detection_list()
+            .for_entity(entity_id)
+            .iter()
+            .map(|pending| (pending.dt(), pending.zone().map(|zone| zone.to_string())))
+            .collect::<Vec<_>>();
+
+        match self
+            .timeline()
+            .handle_detected_backdated(entity_id, data, reads)
+        {
+            Ok(detections) => {
+                if let Err(err) = self.pending_detection_list().remove_for_entity(entity_id) {
+                    tracing::error!("Failed to remove pending detections: {:?}", err);
                 }
 
-                let entity = timeline
-                    .entity_list()
-                    .get(item.entity_id())
-                    .expect("entity must exist");
-
-                if !entity
-                    .data()
-                    .allowed_dt_range()
-                    .copied()
-                    .unwrap_or_default()
-                    .contains(item.dt())
-                    && item.kind().is_entry()
-                {
-                    self.add_message_toast_with_id(
-                        ToastId::Detected,
-                        &format!("“{}” is not allowed!", id_or_name(&entity)),
-                    );
-
-                    Sound::CriticalAlert.play();
-                } else {
-                    Sound::DetectedSuccess.play();
+                for detection in &detections {
+                    match detection {
+                        Detection::Item(item) => self.handle_timeline_item_added(item),
+                        Detection::ZoneMove(zone_move) => self.handle_zone_moved(zone_move),
+                    }
                 }
             }
             Err(err) => {
-                tracing::error!("Failed to handle entity: {:?}", err);
+                tracing::error!("Failed to handle pending entity: {:?}", err);
 
                 self.add_message_toast("Can't handle entity");
 
@@ -534,23 +964,166 @@ impl Application {
         }
     }
 
+    fn handle_timeline_item_added(&self, item: &TimelineItem) {
+        let operation_mode = self.settings().operation_mode();
+
+        let entity = self
+            .timeline()
+            .entity_list()
+            .get(item.entity_id())
+            .expect("entity must exist");
+
+        self.publish_timeline_item(item);
+
+        let message = detected_message(operation_mode, &entity, item.kind());
+        self.add_message_toast_with_id(ToastId::Detected, &message);
+
+        let webhook_event = match item.kind() {
+            TimelineItemKind::Entry => WebhookEvent::Entry,
+            TimelineItemKind::Exit => WebhookEvent::Exit,
+        };
+        self.enqueue_webhook_for_entity(webhook_event, item.dt(), item.entity_id(), &message);
+
+        let zone_defs = self.settings().zone_defs();
+        if let Some(message) = denied_entry_message(&entity, item).or_else(|| {
+            item.zone()
+                .filter(|_| item.kind().is_entry())
+                .and_then(|zone| denied_zone_message(&zone_defs, &entity, zone, item.dt()))
+        }) {
+            self.add_message_toast_with_id(ToastId::Detected, &message);
+
+            self.record_event_clip(
+                item.dt(),
+                EventClipKind::DeniedEntry,
+                Some(entity.id().clone()),
+            );
+
+            Sound::CriticalAlert.play();
+        } else {
+            Sound::DetectedSuccess.play();
+        }
+
+        if let Some(message) = fefo_skipped_message(self.timeline(), &entity, item) {
+            self.add_message_toast(&message);
+        }
+
+        if let Some(location_id) = entity.location().filter(|_| item.kind().is_entry()) {
+            if let Some(message) = over_capacity_message(self.timeline(), &location_id) {
+                self.add_message_toast(&message);
+            }
+        }
+    }
+
+    fn handle_zone_moved(&self, zone_move: &ZoneMove) {
+        let entity = self
+            .timeline()
+            .entity_list()
+            .get(&zone_move.entity_id)
+            .expect("entity must exist");
+
+        self.add_message_toast_with_id(ToastId::Detected, &zone_moved_message(&entity, zone_move));
+
+        let zone_defs = self.settings().zone_defs();
+        if let Some(message) = zone_move
+            .to
+            .as_deref()
+            .and_then(|zone| denied_zone_message(&zone_defs, &entity, zone, zone_move.dt))
+        {
+            self.add_message_toast_with_id(ToastId::Detected, &message);
+
+            self.record_event_clip(
+                zone_move.dt,
+                EventClipKind::DeniedEntry,
+                Some(entity.id().clone()),
+            );
+
+            Sound::CriticalAlert.play();
+        } else {
+            Sound::DetectedSuccess.play();
+        }
+    }
+
+    fn alert_if_zone_overstayed(&self) {
+        let imp = self.imp();
+
+        let timeline = self.timeline();
+        let now = timeline.clock().now();
+        let overstayed = newly_zone_overstayed(
+            timeline,
+            &self.settings().zone_defs(),
+            now,
+            &mut imp.alerted_zone_overstayed.borrow_mut(),
+        );
+
+        if overstayed.is_empty() {
+            return;
+        }
+
+        for (entity, _) in &overstayed {
+            self.record_event_clip(now, EventClipKind::Overstayed, Some(entity.id().clone()));
+        }
+
+        self.add_message_toast(&zone_overstayed_message(&overstayed));
+
+        Sound::CriticalAlert.play();
+    }
+
     fn handle_detected_invalid(&self, _code: &str) {
         Sound::DetectedError.play();
 
-        self.add_message_toast("Invalid code detected");
+        self.add_message_toast(DETECTED_INVALID_MESSAGE);
     }
 
     fn handle_detected_wo_id(&self, dt: &DateTimeBoxed, image: Option<&JpegImage>) -> Result<()> {
         Sound::CriticalAlert.play();
 
-        self.add_message_toast("Detected unregistered entity!");
+        self.add_message_toast(DETECTED_WO_ID_MESSAGE);
+
+        self.enqueue_webhook(WebhookEventData::new(
+            WebhookEvent::DetectedWoId,
+            dt.0,
+            "Detected unregistered entity",
+        ));
+
+        let clip = self.record_event_clip(dt.0, EventClipKind::DetectedWoId, None);
+        let item =
+            DetectedWoIdItem::new(dt.0, image.cloned(), DetectedWoIdKind::Unregistered, clip);
+        self.detected_wo_id_list().insert(item)?;
+
+        Ok(())
+    }
+
+    fn handle_tailgating_detected(
+        &self,
+        dt: &DateTimeBoxed,
+        image: Option<&JpegImage>,
+    ) -> Result<()> {
+        Sound::CriticalAlert.play();
+
+        self.add_message_toast(TAILGATING_DETECTED_MESSAGE);
+
+        self.enqueue_webhook(WebhookEventData::new(
+            WebhookEvent::DetectedWoId,
+            dt.0,
+            "Possible tailgating detected",
+        ));
 
-        let item = DetectedWoIdItem::new(dt.0, image.cloned());
+        let clip = self.record_event_clip(dt.0, EventClipKind::DetectedWoId, None);
+        let item = DetectedWoIdItem::new(dt.0, image.cloned(), DetectedWoIdKind::Tailgating, clip);
         self.detected_wo_id_list().insert(item)?;
 
         Ok(())
     }
 
+    fn update_debounce_windows(&self) {
+        let settings = self.settings();
+
+        for source in DetectionSource::all() {
+            self.detector()
+                .set_debounce_window(*source, settings.debounce_window(*source));
+        }
+    }
+
     fn update_relay_state(&self) {
         glib::spawn_future_local(clone!(
             #[strong(rename_to = obj)]
@@ -579,6 +1152,71 @@ impl Application {
         Ok(())
     }
 
+    /// Sets the relay state as requested over MQTT.
+    ///
+    /// Note that this is overridden on the next n inside hook update.
+    fn handle_relay_command(&self, payload: &str) {
+        let state = match payload.parse::<RelayState>() {
+            Ok(state) => state,
+            Err(err) => {
+                tracing::warn!("Ignoring relay command: {:?}", err);
+                return;
+            }
+        };
+
+        glib::spawn_future_local(clone!(
+            #[strong(rename_to = obj)]
+            self,
+            async move {
+                if let Err(err) = obj.relay().set_state(state).await {
+                    tracing::error!("Failed to set relay state from MQTT: {:?}", err);
+                }
+            }
+        ));
+    }
+
+    fn publish_timeline_item(&self, item: &TimelineItem) {
+        let stock_id = self
+            .timeline()
+            .entity_list()
+            .get(item.entity_id())
+            .and_then(|entity| entity.stock_id());
+
+        let payload = serde_json::json!({
+            "dt": item.dt().to_rfc3339(),
+            "kind": item.kind().to_string().to_lowercase(),
+            "entity_id": item.entity_id().to_string(),
+            "stock_id": stock_id.map(|stock_id| stock_id.to_string()),
+        });
+        self.mqtt_client()
+            .publish(MqttTopic::Timeline, &payload.to_string(), false);
+    }
+
+    fn publish_n_inside(&self) {
+        self.mqtt_client().publish(
+            MqttTopic::NInside,
+            &self.timeline().n_inside().to_string(),
+            true,
+        );
+    }
+
+    fn publish_overstayed(&self) {
+        let n_overstayed = self.timeline().entity_entry_tracker().n_overstayed();
+        self.mqtt_client()
+            .publish(MqttTopic::Overstayed, &n_overstayed.to_string(), true);
+    }
+
+    fn publish_stock_limit(&self) {
+        let tracker = self.timeline().stock_limit_reached_tracker();
+
+        let payload = serde_json::json!({
+            "n_lower_limit_reached": tracker.n_lower_limit_reached(),
+            "n_upper_limit_reached": tracker.n_upper_limit_reached(),
+        });
+        self.mqtt_client()
+            .publish(MqttTopic::StockLimit, &payload.to_string(), true);
+    }
+
     fn setup_actions(&self) {
         let show_test_window_action = gio::ActionEntry::builder("show-test-window")
             .activate(|obj: &Self, _, _| {
@@ -600,13 +1238,224 @@ impl Application {
     }
 }
 
-fn init_env() -> Result<(heed::Env, Timeline, DetectedWoIdList)> {
+fn init_env() -> Result<(
+    heed::Env,
+    Timeline,
+    DetectedWoIdList,
+    PendingDetectionList,
+    WebhookQueue,
+)> {
     let env = db::new_env()?;
 
     let timeline = Timeline::load_from_env(env.clone())?;
     let detected_wo_id_list = DetectedWoIdList::load_from_env(env.clone())?;
+    let pending_detection_list = PendingDetectionList::load_from_env(env.clone())?;
+    let webhook_queue = WebhookQueue::load_from_env(env.clone())?;
+
+    Ok((
+        env,
+        timeline,
+        detected_wo_id_list,
+        pending_detection_list,
+        webhook_queue,
+    ))
+}
+
+pub fn detected_message(
+    operation_mode: OperationMode,
+    entity: &Entity,
+    kind: TimelineItemKind,
+) -> String {
+    let entity_id = entity.id();
+    let entity_name = entity.data().name().cloned();
+
+    match kind {
+        TimelineItemKind::Entry => match entity_name {
+            Some(name) if operation_mode.is_for_person() => {
+                format!("Welcome, {}!", name)
+            }
+            Some(name) => {
+                format!("{name} {}", operation_mode.enter_verb())
+            }
+            None => {
+                format!("{entity_id} {}", operation_mode.enter_verb())
+            }
+        },
+        TimelineItemKind::Exit => match entity_name {
+            Some(name) if operation_mode.is_for_person() => {
+                format!("Goodbye, {}!", name)
+            }
+            Some(name) => {
+                format!("{name} {}", operation_mode.exit_verb())
+            }
+            None => {
+                format!("{entity_id} {}", operation_mode.exit_verb())
+            }
+        },
+    }
+}
+
+/// Returns the message to alert with if the item is an entry outside the
+/// allowed range of the entity.
+pub fn denied_entry_message(entity: &Entity, item: &TimelineItem) -> Option<String> {
+    let is_allowed = entity
+        .data()
+        .allowed_dt_range()
+        .copied()
+        .unwrap_or_default()
+        .contains(item.dt());
+
+    (!is_allowed && item.kind().is_entry())
+        .then(|| format!("“{}” is not allowed!", id_or_name(entity)))
+}
+
+/// Returns the message to alert with if entering the zone at `dt` is outside
+/// its allowed range.
+pub fn denied_zone_message(
+    zone_defs: &[ZoneDef],
+    entity: &Entity,
+    zone: &str,
+    dt: DateTime<Utc>,
+) -> Option<String> {
+    zone_defs
+        .iter()
+        .find(|def| def.name == zone)
+        .filter(|def| !def.is_allowed(dt))
+        .map(|_| format!("“{}” is not allowed in “{}”!", id_or_name(entity), zone))
+}
+
+pub fn zone_moved_message(entity: &Entity, zone_move: &ZoneMove) -> String {
+    format!(
+        "“{}” moved to “{}”",
+        id_or_name(entity),
+        zone_move.to.as_deref().unwrap_or(zone::NO_ZONE_NAME)
+    )
+}
+
+/// Returns the entities that overstayed in their zone and were not alerted
+/// yet, then marks them as alerted.
+///
+/// Entities that are no longer overstayed are forgotten, so they are alerted
+/// again the next time.
+pub fn newly_zone_overstayed(
+    timeline: &Timeline,
+    zone_defs: &[ZoneDef],
+    now: DateTime<Utc>,
+    alerted: &mut HashSet<(EntityId, String)>,
+) -> Vec<(Entity, String)> {
+    let overstayed = timeline.zone_overstayed_entities(zone_defs, now);
+
+    alerted.retain(|(entity_id, zone)| {
+        overstayed
+            .iter()
+            .any(|(entity, z)| entity.id() == entity_id && z == zone)
+    });
+
+    overstayed
+        .into_iter()
+        .filter(|(entity, zone)| alerted.insert((entity.id().clone(), zone.clone())))
+        .collect()
+}
 
-    Ok((env, timeline, detected_wo_id_list))
+pub fn zone_overstayed_message(overstayed: &[(Entity, String)]) -> String {
+    match overstayed {
+        [(entity, zone)] => format!("“{}” overstayed in “{}”", id_or_name(entity), zone),
+        overstayed => format!("{} entities overstayed in their zones", overstayed.len()),
+    }
+}
+
+/// Returns the message to warn with if the item is an exit while entities of
+/// the same stock that expire sooner are still inside.
+pub fn fefo_skipped_message(
+    timeline: &Timeline,
+    entity: &Entity,
+    item: &TimelineItem,
+) -> Option<String> {
+    if !item.kind().is_exit() {
+        return None;
+    }
+
+    match timeline.fefo_skipped(entity, item.dt()).as_slice() {
+        [] => None,
+        [skipped] => Some(format!(
+            "“{}” should have been used first",
+            id_or_name(skipped)
+        )),
+        skipped => Some(format!(
+            "{} items expiring sooner should have been used first",
+            skipped.len()
+        )),
+    }
+}
+
+/// Returns a message for the innermost location, from the given one outwards,
+/// that is over its capacity.
+pub fn over_capacity_message(timeline: &Timeline, location_id: &LocationId) -> Option<String> {
+    location_id
+        .ancestors()
+        .filter_map(|id| timeline.location_list().get(&id))
+        .find(|location| location.is_over_capacity())
+        .map(|location| {
+            format!(
+                "“{}” is over capacity ({}/{})",
+                location.id().name(),
+                location.n_inside(),
+                location.data().capacity.unwrap_or(0)
+            )
+        })
+}
+
+pub fn overstayed_message(entities: &[Entity]) -> String {
+    match entities {
+        [entity] => format!("“{}” overstayed", id_or_name(entity)),
+        [entity1, entity2] => format!(
+            "“{}” and “{}” overstayed",
+            id_or_name(entity1),
+            id_or_name(entity2),
+        ),
+        entities => format!("{} entities overstayed", entities.len()),
+    }
+}
+
+pub fn expiring_message(entities: &[Entity]) -> String {
+    match entities {
+        [entity] => format!("“{}” is expiring soon", id_or_name(entity)),
+        [entity1, entity2] => format!(
+            "“{}” and “{}” are expiring soon",
+            id_or_name(entity1),
+            id_or_name(entity2),
+        ),
+        entities => format!("{} items are expiring soon", entities.len()),
+    }
+}
+
+pub fn expired_message(entities: &[Entity]) -> String {
+    match entities {
+        [entity] => format!("“{}” expired", id_or_name(entity)),
+        [entity1, entity2] => format!(
+            "“{}” and “{}” expired",
+            id_or_name(entity1),
+            id_or_name(entity2),
+        ),
+        entities => format!("{} items expired", entities.len()),
+    }
+}
+
+/// Returns the limit reached and the message to alert with, if the limit is
+/// reached and its alert is enabled.
+pub fn limit_reached_alert(
+    settings: &Settings,
+    n_inside: u32,
+) -> Option<(LimitReached, &'static str)> {
+    match settings.compute_limit_reached(n_inside) {
+        Some(LimitReached::Lower) if settings.enable_lower_limit_reached_alert() => {
+            Some((LimitReached::Lower, "Amount Depleted"))
+        }
+        Some(LimitReached::Upper) if settings.enable_upper_limit_reached_alert() => {
+            Some((LimitReached::Upper, "Capacity Exceeded"))
+        }
+        _ => None,
+    }
 }
 
 fn id_or_name(entity: &Entity) -> String {
@@ -0,0 +1,135 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use genpdf::{
+    elements::{FrameCellDecorator, Image, LinearLayout, Paragraph, TableLayout},
+    style::{self, StyledString},
+    Alignment, Document, Element, Margins, SimplePageDecorator,
+};
+use gtk::gio;
+use image::{DynamicImage, GrayImage, Luma};
+use qrcode::{Color, QrCode};
+
+use crate::report::pdf::{DEFAULT_FONT_FAMILY, DOC_MARGINS_MM};
+
+const N_COLUMNS: usize = 3;
+const CELL_PADDING_MM: f64 = 4.0;
+
+const QR_MODULE_PX: u32 = 8;
+const QR_QUIET_ZONE_MODULES: u32 = 4;
+
+#[derive(Debug, Clone)]
+pub struct Badge {
+    pub title: String,
+    pub subtitle: String,
+    /// The content of the QR code.
+    pub code: String,
+}
+
+/// Builds a PDF sheet of badges, laid out in a grid with cut lines.
+pub async fn build_pdf(badges: Vec<Badge>) -> Result<Vec<u8>> {
+    gio::spawn_blocking(move || {
+        let now = Instant::now();
+
+        let ret = build_pdf_inner(badges);
+
+        tracing::trace!("Built badges PDF in {:?}", now.elapsed());
+
+        ret
+    })
+    .await
+    .unwrap()
+}
+
+fn build_pdf_inner(badges: Vec<Badge>) -> Result<Vec<u8>> {
+    let mut doc = Document::new(DEFAULT_FONT_FAMILY.clone());
+    doc.set_minimal_conformance();
+    doc.set_title("Badges");
+
+    let mut decorator = SimplePageDecorator::new();
+    decorator.set_margins(DOC_MARGINS_MM);
+    doc.set_page_decorator(decorator);
+
+    let mut table = TableLayout::new(vec![1; N_COLUMNS]);
+    table.set_cell_decorator(FrameCellDecorator::new(true, true, true));
+
+    for chunk in badges.chunks(N_COLUMNS) {
+        let mut row = chunk
+            .iter()
+            .map(|badge| badge_element(badge).map(|e| Box::new(e) as Box<dyn Element>))
+            .collect::<Result<Vec<_>>>()?;
+        row.resize_with(N_COLUMNS, || Box::new(Paragraph::new("")));
+
+        table.push_row(row)?;
+    }
+
+    doc.push(table);
+
+    let mut bytes = Vec::new();
+    doc.render(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn badge_element(badge: &Badge) -> Result<impl Element> {
+    let mut layout = LinearLayout::vertical();
+    layout
+        .push(Image::from_dynamic_image(qr_image(&badge.code)?)?.with_alignment(Alignment::Center));
+    layout.push(
+        Paragraph::new(StyledString::new(badge.title.clone(), style::Effect::Bold))
+            .aligned(Alignment::Center),
+    );
+    layout.push(
+        Paragraph::new(badge.subtitle.clone())
+            .aligned(Alignment::Center)
+            .styled(style::Style::new().with_font_size(8)),
+    );
+
+    Ok(layout.padded(Margins::all(CELL_PADDING_MM)))
+}
+
+fn qr_image(code: &str) -> Result<DynamicImage> {
+    let qrcode = QrCode::new(code)?;
+    let colors = qrcode.to_colors();
+
+    let n_modules = qrcode.width() as u32;
+    let size = (n_modules + 2 * QR_QUIET_ZONE_MODULES) * QR_MODULE_PX;
+
+    let image = GrayImage::from_fn(size, size, |x, y| {
+        let module_x = (x / QR_MODULE_PX).checked_sub(QR_QUIET_ZONE_MODULES);
+        let module_y = (y / QR_MODULE_PX).checked_sub(QR_QUIET_ZONE_MODULES);
+
+        match (module_x, module_y) {
+            (Some(mx), Some(my))
+                if mx < n_modules
+                    && my < n_modules
+                    && colors[(my * n_modules + mx) as usize] == Color::Dark =>
+            {
+                Luma([0])
+            }
+            _ => Luma([255]),
+        }
+    });
+
+    // genpdf doesn't support grayscale images.
+    Ok(DynamicImage::ImageRgb8(
+        DynamicImage::ImageLuma8(image).to_rgb8(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qr_image_size() {
+        // Version 1 QR codes are 21 modules wide.
+        let image = qr_image("1234").unwrap().to_luma8();
+        let size = (21 + 2 * QR_QUIET_ZONE_MODULES) * QR_MODULE_PX;
+        assert_eq!(image.dimensions(), (size, size));
+
+        // Quiet zone is light, while the finder pattern corner is dark.
+        assert_eq!(image.get_pixel(0, 0), &Luma([255]));
+        let offset = QR_QUIET_ZONE_MODULES * QR_MODULE_PX;
+        assert_eq!(image.get_pixel(offset, offset), &Luma([0]));
+    }
+}
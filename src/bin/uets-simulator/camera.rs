@@ -0,0 +1,274 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use async_net::TcpStream;
+use futures_util::AsyncWriteExt;
+use gtk::glib;
+use image::{imageops, DynamicImage, GrayImage, ImageOutputFormat, Luma};
+use qrcode::{Color, QrCode};
+
+use crate::{
+    http::{self, Request},
+    server::{self, Connections},
+};
+
+/// Same as the IP Webcam Android app.
+const PORT: u16 = 8080;
+
+const FRAME_WIDTH: u32 = 640;
+const FRAME_HEIGHT: u32 = 480;
+const FRAME_INTERVAL: Duration = Duration::from_millis(200);
+const JPEG_QUALITY: u8 = 85;
+
+const QR_QUIET_ZONE_MODULES: u32 = 4;
+
+/// How long motion stays active after it is triggered.
+const MOTION_DURATION: Duration = Duration::from_secs(2);
+
+const MJPEG_BOUNDARY: &str = "frame";
+
+#[derive(Debug)]
+struct State {
+    blank_frame: Rc<Vec<u8>>,
+    frame: RefCell<Rc<Vec<u8>>>,
+    code_generation: Cell<u64>,
+    /// Timestamp in milliseconds and whether motion is active.
+    motion_active: Cell<(u64, bool)>,
+    motion_generation: Cell<u64>,
+}
+
+/// Serves an MJPEG stream, `shot.jpg` and `sensors.json`, like the IP
+/// Webcam Android app.
+///
+/// Codes are rendered as QR codes in the frames, so they go through the
+/// same decoding as a real camera.
+#[derive(Debug)]
+pub struct Camera {
+    connections: Rc<Connections>,
+    state: Rc<State>,
+}
+
+impl Camera {
+    pub fn new() -> Result<Self> {
+        let blank_frame = Rc::new(render_frame(None)?);
+
+        Ok(Self {
+            connections: Rc::default(),
+            state: Rc::new(State {
+                frame: RefCell::new(blank_frame.clone()),
+                blank_frame,
+                code_generation: Cell::new(0),
+                motion_active: Cell::new((unix_millis(), false)),
+                motion_generation: Cell::new(0),
+            }),
+        })
+    }
+
+    pub fn connections(&self) -> &Connections {
+        &self.connections
+    }
+
+    pub async fn run(&self, bind_addr: &str) -> Result<()> {
+        server::serve((bind_addr, PORT), self.connections.clone(), |stream| {
+            handle_request(self.state.clone(), stream)
+        })
+        .await
+    }
+
+    /// Shows the code in front of the camera for the given duration.
+    pub fn show_code(&self, code: &str, duration: Duration) -> Result<()> {
+        let state = &self.state;
+
+        state.frame.replace(Rc::new(render_frame(Some(code))?));
+
+        let generation = state.code_generation.get() + 1;
+        state.code_generation.set(generation);
+
+        tracing::info!("Showing code `{}` for {:?}", code, duration);
+
+        let state = state.clone();
+        glib::spawn_future_local(async move {
+            glib::timeout_future(duration).await;
+
+            if state.code_generation.get() == generation {
+                state.frame.replace(state.blank_frame.clone());
+            }
+        });
+
+        self.trigger_motion();
+
+        Ok(())
+    }
+
+    pub fn trigger_motion(&self) {
+        let state = &self.state;
+
+        state.motion_active.set((unix_millis(), true));
+
+        let generation = state.motion_generation.get() + 1;
+        state.motion_generation.set(generation);
+
+        tracing::info!("Motion detected");
+
+        let state = state.clone();
+        glib::spawn_future_local(async move {
+            glib::timeout_future(MOTION_DURATION).await;
+
+            if state.motion_generation.get() == generation {
+                state.motion_active.set((unix_millis(), false));
+            }
+        });
+    }
+}
+
+async fn handle_request(state: Rc<State>, mut stream: TcpStream) -> Result<()> {
+    let request = Request::read(&stream).await?;
+
+    match request.path.as_str() {
+        "/video" => {
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={MJPEG_BOUNDARY}\r\nConnection: close\r\n\r\n"
+            );
+            stream.write_all(head.as_bytes()).await?;
+
+            // Only ends when the client goes away or the connection is dropped.
+            loop {
+                let frame = state.frame.borrow().clone();
+                let part_head = format!(
+                    "--{MJPEG_BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                    frame.len()
+                );
+                stream.write_all(part_head.as_bytes()).await?;
+                stream.write_all(&frame).await?;
+                stream.write_all(b"\r\n").await?;
+
+                glib::timeout_future(FRAME_INTERVAL).await;
+            }
+        }
+        "/shot.jpg" => {
+            let frame = state.frame.borrow().clone();
+            http::respond(&mut stream, "200 OK", "image/jpeg", &frame).await?;
+        }
+        "/sensors.json" => {
+            let (ts, is_active) = state.motion_active.get();
+            let body = sensors_json(ts, is_active);
+            http::respond(
+                &mut stream,
+                "200 OK",
+                "application/json",
+                body.to_string().as_bytes(),
+            )
+            .await?;
+        }
+        "/enabletorch" => {
+            tracing::info!("Torch enabled");
+            http::respond_ok(&mut stream).await?;
+        }
+        "/disabletorch" => {
+            tracing::info!("Torch disabled");
+            http::respond_ok(&mut stream).await?;
+        }
+        _ => http::respond_not_found(&mut stream).await?,
+    }
+
+    Ok(())
+}
+
+/// Returns the motion sensor data in IP Webcam's `sensors.json` format.
+fn sensors_json(ts: u64, is_active: bool) -> serde_json::Value {
+    serde_json::json!({
+        "motion_active": {
+            "unit": "",
+            "data": [[ts, [if is_active { 1.0 } else { 0.0 }]]],
+        }
+    })
+}
+
+fn render_frame(code: Option<&str>) -> Result<Vec<u8>> {
+    let mut frame = GrayImage::from_pixel(FRAME_WIDTH, FRAME_HEIGHT, Luma([200]));
+
+    if let Some(code) = code {
+        let qr = qr_image(code)?;
+        let x = (FRAME_WIDTH.saturating_sub(qr.width())) / 2;
+        let y = (FRAME_HEIGHT.saturating_sub(qr.height())) / 2;
+        imageops::overlay(&mut frame, &qr, x, y);
+    }
+
+    let mut bytes = Vec::new();
+    DynamicImage::ImageLuma8(frame).write_to(&mut bytes, ImageOutputFormat::Jpeg(JPEG_QUALITY))?;
+
+    Ok(bytes)
+}
+
+fn qr_image(code: &str) -> Result<GrayImage> {
+    let qrcode = QrCode::new(code)?;
+    let colors = qrcode.to_colors();
+
+    let n_modules = qrcode.width() as u32;
+    let module_px = (FRAME_HEIGHT * 3 / 4 / (n_modules + 2 * QR_QUIET_ZONE_MODULES)).max(1);
+    let size = (n_modules + 2 * QR_QUIET_ZONE_MODULES) * module_px;
+
+    let image = GrayImage::from_fn(size, size, |x, y| {
+        let module_x = (x / module_px).checked_sub(QR_QUIET_ZONE_MODULES);
+        let module_y = (y / module_px).checked_sub(QR_QUIET_ZONE_MODULES);
+
+        match (module_x, module_y) {
+            (Some(mx), Some(my))
+                if mx < n_modules
+                    && my < n_modules
+                    && colors[(my * n_modules + mx) as usize] == Color::Dark =>
+            {
+                Luma([0])
+            }
+            _ => Luma([255]),
+        }
+    });
+
+    Ok(image)
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::GenericImageView;
+
+    use super::*;
+
+    #[test]
+    fn frame_is_jpeg() {
+        for code in [None, Some("qr-0001")] {
+            let bytes = render_frame(code).unwrap();
+            assert_eq!(&bytes[..2], &[0xFF, 0xD8]);
+
+            let image = image::load_from_memory(&bytes).unwrap();
+            assert_eq!(image.width(), FRAME_WIDTH);
+            assert_eq!(image.height(), FRAME_HEIGHT);
+        }
+    }
+
+    #[test]
+    fn qr_image_fits_frame() {
+        let image = qr_image(&"x".repeat(200)).unwrap();
+        assert!(image.width() <= FRAME_HEIGHT);
+        assert_eq!(image.get_pixel(0, 0), &Luma([255]));
+    }
+
+    #[test]
+    fn sensors_json_format() {
+        assert_eq!(
+            sensors_json(1000, true),
+            serde_json::json!({
+                "motion_active": { "unit": "", "data": [[1000, [1.0]]] }
+            })
+        );
+    }
+}
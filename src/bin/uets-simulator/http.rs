@@ -0,0 +1,103 @@
+//! Just enough of HTTP/1.1 to serve GET requests from the app.
+
+use anyhow::{bail, Context, Result};
+use async_net::TcpStream;
+use futures_util::{io::BufReader, AsyncBufReadExt, AsyncWriteExt};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+}
+
+impl Request {
+    /// Reads the request line and skips the headers.
+    pub async fn read(stream: &TcpStream) -> Result<Self> {
+        let mut reader = BufReader::new(stream.clone());
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        Self::parse(&request_line)
+    }
+
+    fn parse(request_line: &str) -> Result<Self> {
+        let mut parts = request_line.split_whitespace();
+
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            bail!("Invalid request line `{}`", request_line.trim());
+        };
+
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (target, None),
+        };
+
+        Ok(Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+        })
+    }
+}
+
+pub async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await.context("Failed to flush response")?;
+
+    Ok(())
+}
+
+pub async fn respond_ok(stream: &mut TcpStream) -> Result<()> {
+    respond(stream, "200 OK", "text/plain", b"").await
+}
+
+pub async fn respond_not_found(stream: &mut TcpStream) -> Result<()> {
+    respond(stream, "404 Not Found", "text/plain", b"Not Found").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_request_line() {
+        assert_eq!(
+            Request::parse("GET /sensors.json?sense=motion_active HTTP/1.1\r\n").unwrap(),
+            Request {
+                method: "GET".into(),
+                path: "/sensors.json".into(),
+                query: Some("sense=motion_active".into()),
+            }
+        );
+        assert_eq!(
+            Request::parse("GET /state HTTP/1.1\r\n").unwrap(),
+            Request {
+                method: "GET".into(),
+                path: "/state".into(),
+                query: None,
+            }
+        );
+        assert!(Request::parse("").is_err());
+        assert!(Request::parse("GET").is_err());
+    }
+}
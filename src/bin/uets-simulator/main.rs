@@ -0,0 +1,317 @@
+//! Simulates the RFID reader, relay and camera, so the app can be developed,
+//! demoed and tested without the actual hardware.
+//!
+//! Usage: `cargo run --bin uets-simulator [SCENARIO_JSON]`
+
+mod camera;
+mod http;
+mod relay;
+mod rfid_reader;
+mod scenario;
+mod server;
+
+use std::{
+    cell::Cell,
+    env,
+    future::Future,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use gtk::glib;
+
+use self::{
+    camera::Camera,
+    relay::Relay,
+    rfid_reader::RfidReader,
+    scenario::{Device, DropConnections, Rng, Scenario, ScriptedEventKind},
+    server::Connections,
+};
+
+const USAGE: &str = "Usage: uets-simulator [SCENARIO_JSON]
+
+Simulates the RFID reader, relay and camera. If no scenario is given, all
+devices are simulated with random arrivals.
+
+By default, the devices are bound to the following addresses, which must be
+set in the app's settings:
+  RFID reader  127.0.0.1 (port 8888)
+  Relay        127.0.0.2 (port 8888)
+  Camera       127.0.0.3 (port 8080)
+
+The camera serves an MJPEG stream at /video instead of RTSP, so it must be set
+up as a camera profile with an HTTP source.";
+
+struct Simulator {
+    scenario: Scenario,
+    start_time: Instant,
+    main_loop: glib::MainLoop,
+    has_failed: Cell<bool>,
+    rfid_reader: Option<RfidReader>,
+    relay: Option<Relay>,
+    camera: Option<Camera>,
+}
+
+impl Simulator {
+    fn new(scenario: Scenario, main_loop: glib::MainLoop) -> Result<Self> {
+        Ok(Self {
+            rfid_reader: scenario.rfid_reader.as_ref().map(|_| RfidReader::default()),
+            relay: scenario.relay.as_ref().map(|_| Relay::default()),
+            camera: scenario
+                .camera
+                .as_ref()
+                .map(|_| Camera::new())
+                .transpose()?,
+            scenario,
+            start_time: Instant::now(),
+            main_loop,
+            has_failed: Cell::new(false),
+        })
+    }
+
+    fn start(self: &Rc<Self>) {
+        let scenario = &self.scenario;
+
+        if let Some(rfid_reader_scenario) = &scenario.rfid_reader {
+            self.spawn_device(Device::RfidReader, |this| async move {
+                let bind_addr = &this.scenario.rfid_reader.as_ref().unwrap().bind_addr;
+                this.rfid_reader.as_ref().unwrap().run(bind_addr).await
+            });
+            self.spawn(|this| this.run_rfid_arrivals());
+            self.spawn_connection_drops(Device::RfidReader, rfid_reader_scenario.drop_connections);
+        }
+
+        if let Some(relay_scenario) = &scenario.relay {
+            self.spawn_device(Device::Relay, |this| async move {
+                let bind_addr = &this.scenario.relay.as_ref().unwrap().bind_addr;
+                this.relay.as_ref().unwrap().run(bind_addr).await
+            });
+            self.spawn_connection_drops(Device::Relay, relay_scenario.drop_connections);
+        }
+
+        if let Some(camera_scenario) = &scenario.camera {
+            self.spawn_device(Device::Camera, |this| async move {
+                let bind_addr = &this.scenario.camera.as_ref().unwrap().bind_addr;
+                this.camera.as_ref().unwrap().run(bind_addr).await
+            });
+            self.spawn(|this| this.run_camera_arrivals());
+            self.spawn(|this| this.run_unregistered_motions());
+            self.spawn_connection_drops(Device::Camera, camera_scenario.drop_connections);
+        }
+
+        self.spawn(|this| this.run_script());
+
+        if let Some(duration) = scenario.duration() {
+            self.spawn(move |this| async move {
+                glib::timeout_future(duration).await;
+
+                tracing::info!("Scenario finished after {:?}", duration);
+                this.main_loop.quit();
+            });
+        }
+    }
+
+    fn spawn<F, Fut>(self: &Rc<Self>, f: F)
+    where
+        F: FnOnce(Rc<Self>) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        glib::spawn_future_local(f(self.clone()));
+    }
+
+    /// Runs the device server, quitting if it fails, e.g., when the address
+    /// can't be bound.
+    fn spawn_device<F, Fut>(self: &Rc<Self>, device: Device, f: F)
+    where
+        F: FnOnce(Rc<Self>) -> Fut,
+        Fut: Future<Output = Result<()>> + 'static,
+    {
+        let fut = f(self.clone());
+        self.spawn(move |this| async move {
+            if let Err(err) = fut.await {
+                tracing::error!("Failed to run {:?}: {:?}", device, err);
+                this.has_failed.set(true);
+                this.main_loop.quit();
+            }
+        });
+    }
+
+    fn spawn_connection_drops(self: &Rc<Self>, device: Device, config: Option<DropConnections>) {
+        let Some(config) = config else {
+            return;
+        };
+
+        if config.every_secs <= 0.0 {
+            tracing::warn!("Ignored connection drops with non-positive interval");
+            return;
+        }
+
+        self.spawn(move |this| async move {
+            loop {
+                glib::timeout_future(Duration::from_secs_f64(config.every_secs)).await;
+                this.drop_connections(device, config.downtime_secs);
+            }
+        });
+    }
+
+    async fn run_rfid_arrivals(self: Rc<Self>) {
+        let scenario = self.scenario.rfid_reader.as_ref().unwrap();
+        let mut rng = self.rng(1);
+
+        while let Some(delay) = rng.next_arrival(scenario.arrivals_per_min) {
+            glib::timeout_future(delay).await;
+
+            if let Some(id) = rng.choose(&scenario.entity_ids) {
+                self.rfid_read(id).await;
+            }
+        }
+    }
+
+    async fn run_camera_arrivals(self: Rc<Self>) {
+        let scenario = self.scenario.camera.as_ref().unwrap();
+        let mut rng = self.rng(2);
+
+        while let Some(delay) = rng.next_arrival(scenario.arrivals_per_min) {
+            glib::timeout_future(delay).await;
+
+            if let Some(code) = rng.choose(&scenario.codes) {
+                self.show_code(code);
+            }
+        }
+    }
+
+    async fn run_unregistered_motions(self: Rc<Self>) {
+        let scenario = self.scenario.camera.as_ref().unwrap();
+        let mut rng = self.rng(3);
+
+        while let Some(delay) = rng.next_arrival(scenario.unregistered_motion_per_min) {
+            glib::timeout_future(delay).await;
+
+            tracing::info!("Unregistered entity passed by");
+            self.camera.as_ref().unwrap().trigger_motion();
+        }
+    }
+
+    async fn run_script(self: Rc<Self>) {
+        let mut events = self.scenario.events.clone();
+        events.sort_by(|a, b| a.at_secs.total_cmp(&b.at_secs));
+
+        for event in events {
+            let at = self.start_time + Duration::from_secs_f64(event.at_secs.max(0.0));
+            glib::timeout_future(at.saturating_duration_since(Instant::now())).await;
+
+            match &event.kind {
+                ScriptedEventKind::RfidRead { id } => {
+                    if self.rfid_reader.is_some() {
+                        self.rfid_read(id).await;
+                    } else {
+                        tracing::warn!("Ignored RFID read of `{}` with no RFID reader", id);
+                    }
+                }
+                ScriptedEventKind::CameraCode { code } => {
+                    if self.camera.is_some() {
+                        self.show_code(code);
+                    } else {
+                        tracing::warn!("Ignored code `{}` with no camera", code);
+                    }
+                }
+                ScriptedEventKind::Motion => {
+                    if let Some(camera) = &self.camera {
+                        camera.trigger_motion();
+                    } else {
+                        tracing::warn!("Ignored motion with no camera");
+                    }
+                }
+                ScriptedEventKind::DropConnections {
+                    device,
+                    downtime_secs,
+                } => {
+                    self.drop_connections(*device, *downtime_secs);
+                }
+            }
+        }
+    }
+
+    /// Reads the ID, and also triggers motion on the camera, as someone
+    /// passing by the reader would.
+    async fn rfid_read(&self, id: &str) {
+        if let Some(camera) = &self.camera {
+            camera.trigger_motion();
+        }
+
+        self.rfid_reader.as_ref().unwrap().read(id).await;
+    }
+
+    fn show_code(&self, code: &str) {
+        let scenario = self.scenario.camera.as_ref().unwrap();
+        let duration = Duration::from_secs_f64(scenario.code_visible_secs.max(0.0));
+
+        if let Err(err) = self.camera.as_ref().unwrap().show_code(code, duration) {
+            tracing::error!("Failed to show code `{}`: {:?}", code, err);
+        }
+    }
+
+    fn drop_connections(&self, device: Device, downtime_secs: f64) {
+        let Some(connections) = self.connections(device) else {
+            tracing::warn!("Ignored connection drop of disabled {:?}", device);
+            return;
+        };
+
+        tracing::info!("Dropping connections of {:?}", device);
+        connections.drop_all(Duration::from_secs_f64(downtime_secs.max(0.0)));
+    }
+
+    fn connections(&self, device: Device) -> Option<&Connections> {
+        match device {
+            Device::RfidReader => self.rfid_reader.as_ref().map(|d| d.connections()),
+            Device::Relay => self.relay.as_ref().map(|d| d.connections()),
+            Device::Camera => self.camera.as_ref().map(|d| d.connections()),
+        }
+    }
+
+    /// Returns a generator for one stream of events, so each stream is
+    /// reproducible regardless of how the others interleave.
+    fn rng(&self, stream: u64) -> Rng {
+        Rng::new(self.scenario.seed.wrapping_add(stream))
+    }
+}
+
+fn main() -> glib::ExitCode {
+    tracing_subscriber::fmt::init();
+
+    let scenario = match env::args_os().nth(1) {
+        Some(arg) if arg == "-h" || arg == "--help" => {
+            println!("{USAGE}");
+            return glib::ExitCode::SUCCESS;
+        }
+        Some(path) => match Scenario::load(Path::new(&path)) {
+            Ok(scenario) => scenario,
+            Err(err) => {
+                tracing::error!("{:?}", err);
+                return glib::ExitCode::FAILURE;
+            }
+        },
+        None => Scenario::default(),
+    };
+
+    let main_loop = glib::MainLoop::new(None, false);
+
+    let simulator = match Simulator::new(scenario, main_loop.clone()) {
+        Ok(simulator) => Rc::new(simulator),
+        Err(err) => {
+            tracing::error!("Failed to set up simulator: {:?}", err);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+    simulator.start();
+
+    main_loop.run();
+
+    if simulator.has_failed.get() {
+        glib::ExitCode::FAILURE
+    } else {
+        glib::ExitCode::SUCCESS
+    }
+}
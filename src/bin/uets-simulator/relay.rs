@@ -0,0 +1,55 @@
+use std::{cell::Cell, rc::Rc};
+
+use anyhow::Result;
+
+use crate::{
+    http::{self, Request},
+    server::{self, Connections},
+};
+
+/// Same as the ESP8266 firmware.
+const PORT: u16 = 8888;
+
+/// Serves `/state`, `/high` and `/low`, like the ESP8266 relay.
+#[derive(Debug, Default)]
+pub struct Relay {
+    connections: Rc<Connections>,
+    is_high: Rc<Cell<bool>>,
+}
+
+impl Relay {
+    pub fn connections(&self) -> &Connections {
+        &self.connections
+    }
+
+    pub async fn run(&self, bind_addr: &str) -> Result<()> {
+        server::serve((bind_addr, PORT), self.connections.clone(), |mut stream| {
+            let is_high = self.is_high.clone();
+            async move {
+                let request = Request::read(&stream).await?;
+
+                match request.path.as_str() {
+                    // The firmware calls it `/status`.
+                    "/state" | "/status" => {
+                        let body = if is_high.get() { "1" } else { "0" };
+                        http::respond(&mut stream, "200 OK", "text/plain", body.as_bytes()).await?;
+                    }
+                    "/high" => {
+                        is_high.set(true);
+                        tracing::info!("Relay set to high");
+                        http::respond_ok(&mut stream).await?;
+                    }
+                    "/low" => {
+                        is_high.set(false);
+                        tracing::info!("Relay set to low");
+                        http::respond_ok(&mut stream).await?;
+                    }
+                    _ => http::respond_not_found(&mut stream).await?,
+                }
+
+                Ok(())
+            }
+        })
+        .await
+    }
+}
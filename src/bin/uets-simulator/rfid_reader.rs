@@ -0,0 +1,54 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use futures_util::{AsyncReadExt, AsyncWriteExt};
+
+use crate::server::{self, Connections};
+
+/// Same as the ESP8266 firmware.
+const PORT: u16 = 8888;
+
+/// Streams newline-separated IDs to every connected client, like the
+/// ESP8266 RFID reader.
+#[derive(Debug, Default)]
+pub struct RfidReader {
+    connections: Rc<Connections>,
+}
+
+impl RfidReader {
+    pub fn connections(&self) -> &Connections {
+        &self.connections
+    }
+
+    pub async fn run(&self, bind_addr: &str) -> Result<()> {
+        server::serve(
+            (bind_addr, PORT),
+            self.connections.clone(),
+            |mut stream| async move {
+                // The app never writes anything, so this only returns once the
+                // connection is closed by either side.
+                let mut buf = [0; 64];
+                while stream.read(&mut buf).await? > 0 {}
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    pub async fn read(&self, id: &str) {
+        let streams = self.connections.streams();
+
+        if streams.is_empty() {
+            tracing::info!("Read `{}` with no connected clients", id);
+            return;
+        }
+
+        tracing::info!("Read `{}`", id);
+
+        for mut stream in streams {
+            if let Err(err) = stream.write_all(format!("{id}\n").as_bytes()).await {
+                tracing::warn!("Failed to write to client: {:?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// What the simulated devices do and when.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Seed for the random arrivals, so runs can be reproduced.
+    #[serde(default)]
+    pub seed: u64,
+    /// Quits after this many seconds, or runs until interrupted if unset.
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    #[serde(default)]
+    pub rfid_reader: Option<RfidReaderScenario>,
+    #[serde(default)]
+    pub relay: Option<RelayScenario>,
+    #[serde(default)]
+    pub camera: Option<CameraScenario>,
+    /// Events that happen at fixed times, on top of the random ones.
+    #[serde(default)]
+    pub events: Vec<ScriptedEvent>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            duration_secs: None,
+            rfid_reader: Some(RfidReaderScenario::default()),
+            relay: Some(RelayScenario::default()),
+            camera: Some(CameraScenario::default()),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario at {}", path.display()))?;
+        serde_json::from_str(&json).context("Invalid scenario JSON")
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration_secs.map(Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RfidReaderScenario {
+    /// Bound with the same port as the relay, so they must differ, e.g.,
+    /// `127.0.0.1` and `127.0.0.2`.
+    pub bind_addr: String,
+    /// IDs picked from on random arrivals.
+    pub entity_ids: Vec<String>,
+    pub arrivals_per_min: f64,
+    pub drop_connections: Option<DropConnections>,
+}
+
+impl Default for RfidReaderScenario {
+    fn default() -> Self {
+        Self {
+            bind_addr: "127.0.0.1".to_string(),
+            entity_ids: vec![
+                "a1b2c3d4".to_string(),
+                "0badf00d".to_string(),
+                "deadbeef".to_string(),
+                "12ab34cd".to_string(),
+            ],
+            arrivals_per_min: 4.0,
+            drop_connections: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelayScenario {
+    pub bind_addr: String,
+    pub drop_connections: Option<DropConnections>,
+}
+
+impl Default for RelayScenario {
+    fn default() -> Self {
+        Self {
+            bind_addr: "127.0.0.2".to_string(),
+            drop_connections: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraScenario {
+    pub bind_addr: String,
+    /// Codes picked from on random arrivals, shown as QR codes in the stream.
+    pub codes: Vec<String>,
+    pub arrivals_per_min: f64,
+    /// How long a code stays in front of the camera.
+    pub code_visible_secs: f64,
+    /// Rate of motion without any ID read, i.e., unregistered entities.
+    pub unregistered_motion_per_min: f64,
+    pub drop_connections: Option<DropConnections>,
+}
+
+impl Default for CameraScenario {
+    fn default() -> Self {
+        Self {
+            bind_addr: "127.0.0.3".to_string(),
+            codes: vec!["qr-0001".to_string(), "qr-0002".to_string()],
+            arrivals_per_min: 2.0,
+            code_visible_secs: 3.0,
+            unregistered_motion_per_min: 0.5,
+            drop_connections: None,
+        }
+    }
+}
+
+/// Periodically closes all connections and refuses new ones for a while.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DropConnections {
+    pub every_secs: f64,
+    pub downtime_secs: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScriptedEvent {
+    /// Seconds since the start of the simulation.
+    pub at_secs: f64,
+    #[serde(flatten)]
+    pub kind: ScriptedEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ScriptedEventKind {
+    RfidRead { id: String },
+    CameraCode { code: String },
+    Motion,
+    DropConnections { device: Device, downtime_secs: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Device {
+    RfidReader,
+    Relay,
+    Camera,
+}
+
+/// Small seeded PRNG (SplitMix64), so scenarios are reproducible without
+/// pulling in a dependency.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+
+        let index = (self.next_u64() % items.len() as u64) as usize;
+        Some(&items[index])
+    }
+
+    /// Returns the delay until the next arrival of a Poisson process with
+    /// the given rate, or `None` if nothing ever arrives.
+    pub fn next_arrival(&mut self, per_min: f64) -> Option<Duration> {
+        if per_min <= 0.0 {
+            return None;
+        }
+
+        let secs = -(1.0 - self.next_f64()).ln() * 60.0 / per_min;
+        Some(Duration::from_secs_f64(secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scenario() {
+        let scenario = serde_json::from_str::<Scenario>(
+            r#"{
+                "seed": 7,
+                "duration_secs": 60,
+                "rfid_reader": {
+                    "entity_ids": ["a1"],
+                    "drop_connections": { "every_secs": 30, "downtime_secs": 5 }
+                },
+                "events": [
+                    { "at_secs": 1, "type": "rfid-read", "id": "a1" },
+                    { "at_secs": 2.5, "type": "motion" },
+                    { "at_secs": 3, "type": "drop-connections", "device": "camera", "downtime_secs": 2 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.seed, 7);
+        assert_eq!(scenario.duration(), Some(Duration::from_secs(60)));
+        assert!(scenario.relay.is_none());
+        assert!(scenario.camera.is_none());
+
+        let rfid_reader = scenario.rfid_reader.unwrap();
+        assert_eq!(rfid_reader.bind_addr, "127.0.0.1");
+        assert_eq!(rfid_reader.entity_ids, ["a1"]);
+        assert_eq!(
+            rfid_reader.drop_connections,
+            Some(DropConnections {
+                every_secs: 30.0,
+                downtime_secs: 5.0
+            })
+        );
+
+        assert_eq!(
+            scenario.events[0].kind,
+            ScriptedEventKind::RfidRead { id: "a1".into() }
+        );
+        assert_eq!(scenario.events[1].at_secs, 2.5);
+        assert_eq!(scenario.events[1].kind, ScriptedEventKind::Motion);
+        assert_eq!(
+            scenario.events[2].kind,
+            ScriptedEventKind::DropConnections {
+                device: Device::Camera,
+                downtime_secs: 2.0
+            }
+        );
+
+        assert!(serde_json::from_str::<Scenario>(r#"{ "sed": 1 }"#).is_err());
+    }
+
+    #[test]
+    fn rng_is_deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+
+        let mut c = Rng::new(43);
+        assert_ne!(Rng::new(42).next_u64(), c.next_u64());
+    }
+
+    #[test]
+    fn rng_ranges() {
+        let mut rng = Rng::new(1);
+
+        for _ in 0..1000 {
+            let value = rng.next_f64();
+            assert!((0.0..1.0).contains(&value));
+        }
+
+        assert_eq!(rng.choose::<u8>(&[]), None);
+        assert_eq!(rng.choose(&[3]), Some(&3));
+
+        assert_eq!(rng.next_arrival(0.0), None);
+
+        // Mean inter-arrival time should be close to 60 / rate.
+        let n = 10_000;
+        let total = (0..n)
+            .map(|_| rng.next_arrival(6.0).unwrap().as_secs_f64())
+            .sum::<f64>();
+        assert!((total / n as f64 - 10.0).abs() < 0.5);
+    }
+}
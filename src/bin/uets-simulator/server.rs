@@ -0,0 +1,111 @@
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    net::Shutdown,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use async_net::{TcpListener, TcpStream};
+use gtk::glib;
+
+/// Tracks the open connections of a device, so they can be dropped on demand.
+#[derive(Debug, Default)]
+pub struct Connections {
+    streams: RefCell<Vec<(u64, TcpStream)>>,
+    next_id: Cell<u64>,
+    down_until: Cell<Option<Instant>>,
+}
+
+impl Connections {
+    /// Registers the stream, or closes it and returns `None` if the device
+    /// is currently down.
+    fn add(&self, stream: &TcpStream) -> Option<u64> {
+        if self.is_down() {
+            shutdown(stream);
+            return None;
+        }
+
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+
+        self.streams.borrow_mut().push((id, stream.clone()));
+
+        Some(id)
+    }
+
+    fn remove(&self, id: u64) {
+        self.streams
+            .borrow_mut()
+            .retain(|(other_id, _)| *other_id != id);
+    }
+
+    pub fn streams(&self) -> Vec<TcpStream> {
+        self.streams
+            .borrow()
+            .iter()
+            .map(|(_, stream)| stream.clone())
+            .collect()
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.down_until
+            .get()
+            .is_some_and(|down_until| Instant::now() < down_until)
+    }
+
+    /// Closes all connections and refuses new ones for the given duration.
+    pub fn drop_all(&self, downtime: Duration) {
+        let streams = self.streams.take();
+
+        tracing::info!(
+            "Dropping {} connections, down for {:?}",
+            streams.len(),
+            downtime
+        );
+
+        for (_, stream) in &streams {
+            shutdown(stream);
+        }
+
+        self.down_until.set(Some(Instant::now() + downtime));
+    }
+}
+
+/// Accepts connections forever, handling each one on its own task.
+pub async fn serve<F, Fut>(addr: (&str, u16), connections: Rc<Connections>, handle: F) -> Result<()>
+where
+    F: Fn(TcpStream) -> Fut,
+    Fut: Future<Output = Result<()>> + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("Listening on {}", listener.local_addr()?);
+
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+
+        let Some(id) = connections.add(&stream) else {
+            tracing::debug!("Refused connection from {} while down", peer_addr);
+            continue;
+        };
+
+        tracing::debug!("Accepted connection from {}", peer_addr);
+
+        let fut = handle(stream);
+        let connections = connections.clone();
+        glib::spawn_future_local(async move {
+            if let Err(err) = fut.await {
+                tracing::debug!("Connection from {} ended: {:?}", peer_addr, err);
+            }
+
+            connections.remove(id);
+        });
+    }
+}
+
+fn shutdown(stream: &TcpStream) {
+    if let Err(err) = stream.shutdown(Shutdown::Both) {
+        tracing::warn!("Failed to shutdown stream: {:?}", err);
+    }
+}
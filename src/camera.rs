@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::{bail, ensure, Context, Result};
 use gst::{prelude::*, subclass::prelude::*};
@@ -8,17 +11,32 @@ use gtk::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{jpeg_image::JpegImage, remote::Remote, utils};
+use crate::{
+    camera_profile::{CameraProfile, CameraSource},
+    event_clip::{self, EventClip, EventClipConfig, FrameBuffer},
+    jpeg_image::JpegImage,
+    motion_detector::{self, MotionDetector, MotionDetectorConfig},
+    remote::Remote,
+    utils,
+};
 
 const GTK_SINK_NAME: &str = "gtksink";
-const RTSP_SRC_NAME: &str = "rtspsrc";
+const SRC_NAME: &str = "src";
+const MOTION_SINK_NAME: &str = "motionsink";
+const CLIP_SINK_NAME: &str = "clipsink";
 
-const PORT: u16 = 8080;
+const MOTION_MESSAGE_NAME: &str = "motion";
 
 const SENSOR_REQUEST_INTERVAL: Duration = Duration::from_millis(200);
 
 const MOTION_ACTIVE_RESET_DELAY: Duration = Duration::from_secs(3);
 
+const RECOVERY_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECOVERY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+const FROZEN_FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, glib::Boxed)]
 #[boxed_type(name = "UetsCameraState")]
 pub enum CameraState {
@@ -37,6 +55,14 @@ impl CameraState {
     }
 }
 
+/// Counters of the stream failures and recoveries since startup.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CameraStats {
+    pub n_errors: u32,
+    pub n_frozen: u32,
+    pub n_restarts: u32,
+}
+
 mod imp {
     use std::{
         cell::{Cell, RefCell},
@@ -58,7 +84,20 @@ mod imp {
         pub(super) state: RefCell<CameraState>,
 
         pub(super) pipeline: RefCell<Option<(gst::Pipeline, BusWatchGuard)>>,
-        pub(super) ip_addr: RefCell<String>,
+        pub(super) last_frame_instant: RefCell<Option<Arc<Mutex<Instant>>>>,
+        pub(super) watchdog_source_id: RefCell<Option<glib::SourceId>>,
+
+        pub(super) is_supervised: Cell<bool>,
+        pub(super) n_consecutive_failures: Cell<u32>,
+        pub(super) recovery_source_id: RefCell<Option<glib::SourceId>>,
+        pub(super) stats: Cell<CameraStats>,
+        pub(super) profile: RefCell<Option<CameraProfile>>,
+
+        pub(super) is_motion_detection_enabled: Cell<bool>,
+        pub(super) local_motion_detector_config: RefCell<Option<MotionDetectorConfig>>,
+
+        pub(super) event_clip_config: Cell<Option<EventClipConfig>>,
+        pub(super) clip_frame_buffer: RefCell<Option<Arc<Mutex<FrameBuffer<gst::Buffer>>>>>,
 
         pub(super) sensor_request_handle: RefCell<Option<glib::JoinHandle<()>>>,
 
@@ -113,13 +152,11 @@ glib::wrapper! {
 }
 
 impl Camera {
-    pub fn new(ip_addr: String) -> Self {
+    pub fn new(profile: CameraProfile) -> Self {
         let this = glib::Object::new::<Self>();
 
-        debug_assert!(!ip_addr.is_empty());
-
         let imp = this.imp();
-        imp.ip_addr.replace(ip_addr);
+        imp.profile.replace(Some(profile));
 
         this
     }
@@ -146,12 +183,24 @@ impl Camera {
         )
     }
 
-    pub fn set_ip_addr(&self, ip_addr: String) -> Result<()> {
+    pub fn stats(&self) -> CameraStats {
+        self.imp().stats.get()
+    }
+
+    pub fn profile(&self) -> CameraProfile {
+        self.imp().profile.borrow().clone().unwrap()
+    }
+
+    pub fn set_profile(&self, profile: CameraProfile) -> Result<()> {
         let imp = self.imp();
 
-        debug_assert!(!ip_addr.is_empty());
+        if Some(&profile) == imp.profile.borrow().as_ref() {
+            return Ok(());
+        }
+
+        imp.profile.replace(Some(profile));
 
-        imp.ip_addr.replace(ip_addr);
+        self.update_sensor_request_loop();
 
         self.restart()
     }
@@ -159,6 +208,88 @@ impl Camera {
     pub fn set_enable_motion_detection(&self, is_enabled: bool) {
         let imp = self.imp();
 
+        imp.is_motion_detection_enabled.set(is_enabled);
+
+        self.update_sensor_request_loop();
+    }
+
+    /// Sets the config of the motion detector running on the decoded frames.
+    ///
+    /// If `None`, motion is retrieved from the camera's sensor endpoint instead.
+    pub fn set_local_motion_detector_config(
+        &self,
+        config: Option<MotionDetectorConfig>,
+    ) -> Result<()> {
+        let imp = self.imp();
+
+        if config == *imp.local_motion_detector_config.borrow() {
+            return Ok(());
+        }
+
+        imp.local_motion_detector_config.replace(config);
+
+        self.update_sensor_request_loop();
+
+        if self.state().is_running() {
+            self.restart()?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the config of the rolling frame buffer used to record event clips.
+    ///
+    /// If `None`, no frames are buffered and clips can't be recorded.
+    pub fn set_event_clip_config(&self, config: Option<EventClipConfig>) -> Result<()> {
+        let imp = self.imp();
+
+        if config == imp.event_clip_config.get() {
+            return Ok(());
+        }
+
+        imp.event_clip_config.set(config);
+
+        if self.state().is_running() {
+            self.restart()?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the frames around the event instant, waiting until the
+    /// post-event duration has elapsed, and saves them to the clip.
+    pub async fn record_event_clip(&self, clip: &EventClip, event_instant: Instant) -> Result<()> {
+        let imp = self.imp();
+
+        let (Some(config), Some(frame_buffer)) = (
+            imp.event_clip_config.get(),
+            imp.clip_frame_buffer.borrow().clone(),
+        ) else {
+            bail!("Event clip recording is not enabled");
+        };
+
+        let end_instant = event_instant + config.post_event_duration;
+        glib::timeout_future(end_instant.saturating_duration_since(Instant::now())).await;
+
+        let frames = frame_buffer
+            .lock()
+            .unwrap()
+            .frames_between(event_instant - config.pre_event_duration, end_instant);
+
+        tracing::debug!("Saving clip with {} frames", frames.len());
+
+        clip.save(frames).await?;
+
+        Ok(())
+    }
+
+    fn update_sensor_request_loop(&self) {
+        let imp = self.imp();
+
+        let is_enabled = imp.is_motion_detection_enabled.get()
+            && imp.local_motion_detector_config.borrow().is_none()
+            && self.profile().motion_url.is_some();
+
         if is_enabled {
             if imp.sensor_request_handle.borrow().is_none() {
                 let handle = utils::spawn_future_local_idle(clone!(
@@ -191,12 +322,35 @@ impl Camera {
         self.dispose_pipeline();
         self.set_state(CameraState::Loading);
 
-        let pipeline = match gst::parse::launch(&format!("rtspsrc latency=300 name={RTSP_SRC_NAME} ! decodebin ! tee name=t ! queue ! videoconvert ! zbar ! fakesink t. ! queue ! videoconvert ! gtk4paintablesink name={GTK_SINK_NAME}")) {
+        imp.is_supervised.set(true);
+
+        let profile = self.profile();
+
+        let mut pipeline_description = format!("{} {} name={SRC_NAME} ! decodebin ! tee name=t ! queue ! videoconvert ! zbar ! fakesink t. ! queue ! videoconvert ! gtk4paintablesink name={GTK_SINK_NAME}", profile.source.element_factory_name(), profile.source.element_extra_properties());
+        if imp.local_motion_detector_config.borrow().is_some() {
+            pipeline_description.push_str(&format!(
+                " t. ! queue leaky=downstream max-size-buffers=1 ! videorate drop-only=true ! videoconvert ! videoscale ! video/x-raw,format=GRAY8,width={},height={},framerate={}/1 ! fakesink name={MOTION_SINK_NAME} signal-handoffs=true sync=false",
+                motion_detector::FRAME_WIDTH,
+                motion_detector::FRAME_HEIGHT,
+                motion_detector::FRAME_RATE,
+            ));
+        }
+        if imp.event_clip_config.get().is_some() {
+            pipeline_description.push_str(&format!(
+                " t. ! queue leaky=downstream max-size-buffers=1 ! videorate drop-only=true ! videoconvert ! videoscale ! video/x-raw,width={},height={},framerate={}/1 ! jpegenc quality=70 ! fakesink name={CLIP_SINK_NAME} signal-handoffs=true sync=false",
+                event_clip::FRAME_WIDTH,
+                event_clip::FRAME_HEIGHT,
+                event_clip::FRAME_RATE,
+            ));
+        }
+
+        let pipeline = match gst::parse::launch(&pipeline_description) {
             Ok(pipeline) => pipeline.downcast::<gst::Pipeline>().unwrap(),
             Err(err) => {
                 self.set_state(CameraState::Error {
                     message: err.to_string(),
                 });
+                self.schedule_recovery();
                 return Err(err.into());
             }
         };
@@ -211,20 +365,80 @@ impl Camera {
             ))
             .unwrap();
 
-        let uri = format!("rtsp://{}:{PORT}/h264.sdp", imp.ip_addr.borrow());
-        let rtspsrc = pipeline.by_name(RTSP_SRC_NAME).unwrap();
-        rtspsrc.set_property("location", uri);
+        let src = pipeline.by_name(SRC_NAME).unwrap();
+        match &profile.source {
+            CameraSource::Rtsp { url } | CameraSource::Http { url } => {
+                src.set_property("location", url.as_str());
+
+                if let Some(credentials) = &profile.credentials {
+                    src.set_property("user-id", credentials.username.as_str());
+                    src.set_property("user-pw", credentials.password.as_str());
+                }
+            }
+            CameraSource::V4l2 { device } => {
+                src.set_property("device", device.as_str());
+            }
+            CameraSource::File { path } => {
+                src.set_property("location", path.as_str());
+            }
+        }
+
+        if let Some(config) = imp.local_motion_detector_config.borrow().clone() {
+            let motion_sink = pipeline.by_name(MOTION_SINK_NAME).unwrap();
+            setup_motion_sink(&motion_sink, config);
+        }
+
+        if let Some(config) = imp.event_clip_config.get() {
+            let frame_buffer = Arc::new(Mutex::new(FrameBuffer::new(config.buffer_duration())));
+            let clip_sink = pipeline.by_name(CLIP_SINK_NAME).unwrap();
+            setup_clip_sink(&clip_sink, frame_buffer.clone());
+            imp.clip_frame_buffer.replace(Some(frame_buffer));
+        } else {
+            imp.clip_frame_buffer.replace(None);
+        }
+
+        let last_frame_instant = Arc::new(Mutex::new(Instant::now()));
+        let gtksink_pad = pipeline
+            .by_name(GTK_SINK_NAME)
+            .unwrap()
+            .static_pad("sink")
+            .unwrap();
+        gtksink_pad.add_probe(
+            gst::PadProbeType::BUFFER,
+            clone!(
+                #[strong]
+                last_frame_instant,
+                move |_, _| {
+                    *last_frame_instant.lock().unwrap() = Instant::now();
+                    gst::PadProbeReturn::Ok
+                }
+            ),
+        );
+        imp.last_frame_instant.replace(Some(last_frame_instant));
 
         imp.pipeline
             .replace(Some((pipeline.clone(), bus_watch_guard)));
         self.notify_paintable();
 
+        let watchdog_source_id = glib::timeout_add_local(
+            WATCHDOG_INTERVAL,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || obj.handle_watchdog_tick()
+            ),
+        );
+        imp.watchdog_source_id.replace(Some(watchdog_source_id));
+
         let state_change = match pipeline.set_state(gst::State::Playing) {
             Ok(state_change) => state_change,
             Err(err) => {
                 self.set_state(CameraState::Error {
                     message: err.to_string(),
                 });
+                self.schedule_recovery();
                 return Err(err.into());
             }
         };
@@ -236,6 +450,15 @@ impl Camera {
     }
 
     pub fn stop(&self) {
+        let imp = self.imp();
+
+        imp.is_supervised.set(false);
+        imp.n_consecutive_failures.set(0);
+
+        if let Some(source_id) = imp.recovery_source_id.take() {
+            source_id.remove();
+        }
+
         self.dispose_pipeline();
         self.set_state(CameraState::Idle);
     }
@@ -249,8 +472,12 @@ impl Camera {
     }
 
     pub async fn capture_jpeg(&self) -> Result<JpegImage> {
+        let url = self
+            .profile()
+            .snapshot_url
+            .context("Profile has no snapshot URL")?;
         let bytes = self
-            .http_get("shot.jpg")
+            .http_get(&url)
             .await?
             .body_bytes()
             .await
@@ -260,13 +487,15 @@ impl Camera {
     }
 
     pub async fn set_flash(&self, is_enabled: bool) -> Result<()> {
-        let path = if is_enabled {
-            "enabletorch"
+        let profile = self.profile();
+        let url = if is_enabled {
+            profile.torch_on_url
         } else {
-            "disabletorch"
-        };
+            profile.torch_off_url
+        }
+        .context("Profile has no torch URL")?;
 
-        self.http_get(path).await?;
+        self.http_get(&url).await?;
 
         Ok(())
     }
@@ -282,18 +511,18 @@ impl Camera {
         self.notify_state();
     }
 
-    async fn http_get(&self, path: &str) -> Result<surf::Response> {
-        let imp = self.imp();
-
-        let uri = format!("http://{}:{PORT}/{path}", imp.ip_addr.borrow());
-        let response = surf::RequestBuilder::new(
+    async fn http_get(&self, uri: &str) -> Result<surf::Response> {
+        let mut request = surf::RequestBuilder::new(
             surf::http::Method::Get,
             uri.parse()
                 .with_context(|| format!("Failed to parse URI: {}", uri))?,
-        )
-        .send()
-        .await
-        .map_err(|err| err.into_inner())?;
+        );
+
+        if let Some(credentials) = &self.profile().credentials {
+            request = request.header("Authorization", credentials.basic_auth_header());
+        }
+
+        let response = request.send().await.map_err(|err| err.into_inner())?;
 
         ensure!(
             response.status().is_success(),
@@ -307,6 +536,12 @@ impl Camera {
     fn dispose_pipeline(&self) {
         let imp = self.imp();
 
+        if let Some(source_id) = imp.watchdog_source_id.take() {
+            source_id.remove();
+        }
+
+        imp.last_frame_instant.replace(None);
+
         if let Some((pipeline, _bus_watch_guard)) = imp.pipeline.take() {
             if let Err(err) = pipeline.set_state(gst::State::Null) {
                 tracing::warn!("Failed to set pipeline to Null: {:?}", err);
@@ -315,11 +550,96 @@ impl Camera {
         }
     }
 
+    fn update_stats(&self, f: impl FnOnce(&mut CameraStats)) {
+        let imp = self.imp();
+
+        let mut stats = imp.stats.get();
+        f(&mut stats);
+        imp.stats.set(stats);
+    }
+
+    /// Restarts the stream after a delay that grows with the number of
+    /// consecutive failures.
+    fn schedule_recovery(&self) {
+        let imp = self.imp();
+
+        if !imp.is_supervised.get() || imp.recovery_source_id.borrow().is_some() {
+            return;
+        }
+
+        let n_failures = imp.n_consecutive_failures.get();
+        imp.n_consecutive_failures.set(n_failures.saturating_add(1));
+
+        let delay = recovery_delay(n_failures);
+        tracing::debug!("Scheduling camera recovery in {:?}", delay);
+
+        let source_id = glib::timeout_add_local_once(
+            delay,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move || {
+                    let imp = obj.imp();
+                    imp.recovery_source_id.replace(None);
+
+                    obj.update_stats(|stats| stats.n_restarts += 1);
+
+                    // Not `restart`, as `stop` resets the failure count. On
+                    // failure, `start` schedules the next recovery itself.
+                    if let Err(err) = obj.start() {
+                        tracing::warn!("Failed to recover camera: {:?}", err);
+                    }
+                }
+            ),
+        );
+        imp.recovery_source_id.replace(Some(source_id));
+    }
+
+    fn handle_watchdog_tick(&self) -> glib::ControlFlow {
+        let imp = self.imp();
+
+        if self.state() != CameraState::Loaded {
+            return glib::ControlFlow::Continue;
+        }
+
+        let Some(elapsed) = imp
+            .last_frame_instant
+            .borrow()
+            .as_ref()
+            .map(|instant| instant.lock().unwrap().elapsed())
+        else {
+            return glib::ControlFlow::Continue;
+        };
+
+        if elapsed < WATCHDOG_INTERVAL {
+            imp.n_consecutive_failures.set(0);
+        }
+
+        if elapsed < FROZEN_FRAME_TIMEOUT {
+            return glib::ControlFlow::Continue;
+        }
+
+        tracing::warn!("No new frames received for {:?}", elapsed);
+
+        self.update_stats(|stats| stats.n_frozen += 1);
+        self.set_state(CameraState::Error {
+            message: "Stream is frozen".to_string(),
+        });
+        self.schedule_recovery();
+
+        imp.watchdog_source_id.replace(None);
+        glib::ControlFlow::Break
+    }
+
     async fn handle_sensor_request(&self) -> Result<()> {
         let imp = self.imp();
 
+        let url = self
+            .profile()
+            .motion_url
+            .context("Profile has no motion URL")?;
         let data = self
-            .http_get("sensors.json?sense=motion_active")
+            .http_get(&url)
             .await?
             .body_json::<SensorData>()
             .await
@@ -391,6 +711,15 @@ impl Camera {
 
                     tracing::trace!("Detected barcode: {} ({})", symbol, symbol_type);
                     self.emit_by_name::<()>("code-detected", &[&symbol]);
+                } else if e.has_name(MOTION_MESSAGE_NAME) {
+                    let structure = e.structure().unwrap();
+                    let is_active = structure.get::<bool>("active").unwrap();
+
+                    tracing::debug!(is_active, "Local motion state changed");
+
+                    if is_active && imp.is_motion_detection_enabled.get() {
+                        self.emit_by_name::<()>("motion-detected", &[]);
+                    }
                 }
 
                 glib::ControlFlow::Continue
@@ -398,6 +727,11 @@ impl Camera {
             MessageView::Eos(_) => {
                 tracing::debug!("Eos signal received from record bus");
 
+                self.set_state(CameraState::Error {
+                    message: "Stream ended".to_string(),
+                });
+                self.schedule_recovery();
+
                 glib::ControlFlow::Break
             }
             MessageView::StateChanged(sc) => {
@@ -432,9 +766,11 @@ impl Camera {
             MessageView::Error(e) => {
                 tracing::error!("Received error message on bus: {:?}", e);
 
+                self.update_stats(|stats| stats.n_errors += 1);
                 self.set_state(CameraState::Error {
                     message: e.error().to_string(),
                 });
+                self.schedule_recovery();
 
                 glib::ControlFlow::Break
             }
@@ -459,14 +795,74 @@ impl Camera {
 
 impl Remote for Camera {
     fn ip_addr(&self) -> String {
-        self.imp().ip_addr.borrow().clone()
+        self.profile()
+            .remote_addr()
+            .map(|(host, _)| host)
+            .unwrap_or_default()
     }
 
     fn port(&self) -> u16 {
-        PORT
+        self.profile()
+            .remote_addr()
+            .map(|(_, port)| port)
+            .unwrap_or_default()
     }
 }
 
+fn recovery_delay(n_failures: u32) -> Duration {
+    RECOVERY_INITIAL_DELAY
+        .saturating_mul(2_u32.saturating_pow(n_failures))
+        .min(RECOVERY_MAX_DELAY)
+}
+
+/// Runs the motion detector on the frames received by the sink, and posts
+/// a message on the bus whenever the motion state changes.
+fn setup_motion_sink(motion_sink: &gst::Element, config: MotionDetectorConfig) {
+    let motion_detector = Mutex::new(MotionDetector::new(config));
+
+    motion_sink.connect("handoff", false, move |values| {
+        let element = values[0].get::<gst::Element>().unwrap();
+        let buffer = values[1].get::<gst::Buffer>().unwrap();
+
+        let map = match buffer.map_readable() {
+            Ok(map) => map,
+            Err(err) => {
+                tracing::warn!("Failed to map buffer: {:?}", err);
+                return None;
+            }
+        };
+
+        let state_change = motion_detector
+            .lock()
+            .unwrap()
+            .process_frame(map.as_slice());
+        if let Some(is_active) = state_change {
+            let structure = gst::Structure::builder(MOTION_MESSAGE_NAME)
+                .field("active", is_active)
+                .build();
+            let message = gst::message::Element::builder(structure)
+                .src(&element)
+                .build();
+            if let Err(err) = element.post_message(message) {
+                tracing::warn!("Failed to post motion message: {:?}", err);
+            }
+        }
+
+        None
+    });
+}
+
+/// Keeps the encoded frames received by the sink in the frame buffer.
+fn setup_clip_sink(clip_sink: &gst::Element, frame_buffer: Arc<Mutex<FrameBuffer<gst::Buffer>>>) {
+    clip_sink.connect("handoff", false, move |values| {
+        let buffer = values[1].get::<gst::Buffer>().unwrap();
+
+        frame_buffer.lock().unwrap().push(Instant::now(), buffer);
+
+        None
+    });
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct SensorDataField {
     unit: String,
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+/// Port used by the IP Webcam Android app.
+const IP_WEBCAM_PORT: u16 = 8080;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum CameraSource {
+    /// H.264 or MJPEG stream over RTSP.
+    Rtsp { url: String },
+    /// MJPEG stream over HTTP.
+    Http { url: String },
+    /// Local V4L2 device, e.g., `/dev/video0`.
+    V4l2 { device: String },
+    /// Local video file, useful for testing.
+    File { path: String },
+}
+
+impl CameraSource {
+    /// Returns the name of the GStreamer element used as the source.
+    pub fn element_factory_name(&self) -> &'static str {
+        match self {
+            Self::Rtsp { .. } => "rtspsrc",
+            Self::Http { .. } => "souphttpsrc",
+            Self::V4l2 { .. } => "v4l2src",
+            Self::File { .. } => "filesrc",
+        }
+    }
+
+    /// Returns the extra properties set in the pipeline description.
+    pub fn element_extra_properties(&self) -> &'static str {
+        match self {
+            Self::Rtsp { .. } => "latency=300",
+            Self::Http { .. } => "is-live=true",
+            Self::V4l2 { .. } | Self::File { .. } => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl CameraCredentials {
+    pub fn basic_auth_header(&self) -> String {
+        let encoded =
+            glib::base64_encode(format!("{}:{}", self.username, self.password).as_bytes());
+        format!("Basic {}", encoded)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraProfile {
+    pub name: String,
+    pub source: CameraSource,
+    /// URL that returns a JPEG snapshot.
+    #[serde(default)]
+    pub snapshot_url: Option<String>,
+    /// URL requested to turn on the torch.
+    #[serde(default)]
+    pub torch_on_url: Option<String>,
+    /// URL requested to turn off the torch.
+    #[serde(default)]
+    pub torch_off_url: Option<String>,
+    /// URL that returns the motion sensor data in IP Webcam's `sensors.json` format.
+    #[serde(default)]
+    pub motion_url: Option<String>,
+    /// Used for both the stream and the HTTP endpoints.
+    #[serde(default)]
+    pub credentials: Option<CameraCredentials>,
+}
+
+impl CameraProfile {
+    /// Returns the profile for a camera running the IP Webcam Android app.
+    pub fn ip_webcam(ip_addr: &str) -> Self {
+        let base_url = format!("http://{ip_addr}:{IP_WEBCAM_PORT}");
+
+        Self {
+            name: ip_addr.to_string(),
+            source: CameraSource::Rtsp {
+                url: format!("rtsp://{ip_addr}:{IP_WEBCAM_PORT}/h264.sdp"),
+            },
+            snapshot_url: Some(format!("{base_url}/shot.jpg")),
+            torch_on_url: Some(format!("{base_url}/enabletorch")),
+            torch_off_url: Some(format!("{base_url}/disabletorch")),
+            motion_url: Some(format!("{base_url}/sensors.json?sense=motion_active")),
+            credentials: None,
+        }
+    }
+
+    /// Returns the host and port of the stream, if it is on the network.
+    pub fn remote_addr(&self) -> Option<(String, u16)> {
+        let url = match &self.source {
+            CameraSource::Rtsp { url } | CameraSource::Http { url } => url,
+            CameraSource::V4l2 { .. } | CameraSource::File { .. } => return None,
+        };
+
+        let url = url.parse::<surf::Url>().ok()?;
+        let host = url.host_str()?.to_string();
+        let port = url.port_or_known_default().or_else(|| match url.scheme() {
+            "rtsp" => Some(554),
+            _ => None,
+        })?;
+
+        Some((host, port))
+    }
+}
+
+pub trait CameraProfileSettingsExt {
+    fn camera_profiles(&self) -> Vec<CameraProfile>;
+
+    /// Returns the profile with the given name, or the IP Webcam profile
+    /// if there is none and the value is treated as an IP address.
+    fn resolve_camera_profile(&self, ip_addr_or_name: &str) -> CameraProfile;
+}
+
+impl CameraProfileSettingsExt for Settings {
+    fn camera_profiles(&self) -> Vec<CameraProfile> {
+        parse_profiles(&self.camera_profiles_json())
+            .inspect_err(|err| tracing::warn!("Failed to parse camera profiles: {:?}", err))
+            .unwrap_or_default()
+    }
+
+    fn resolve_camera_profile(&self, ip_addr_or_name: &str) -> CameraProfile {
+        self.camera_profiles()
+            .into_iter()
+            .find(|profile| profile.name == ip_addr_or_name)
+            .unwrap_or_else(|| CameraProfile::ip_webcam(ip_addr_or_name))
+    }
+}
+
+fn parse_profiles(json: &str) -> Result<Vec<CameraProfile>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    serde_json::from_str(json).context("Invalid camera profiles JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_webcam_remote_addr() {
+        let profile = CameraProfile::ip_webcam("192.168.1.2");
+        assert_eq!(
+            profile.remote_addr(),
+            Some(("192.168.1.2".to_string(), IP_WEBCAM_PORT))
+        );
+    }
+
+    #[test]
+    fn remote_addr_default_ports() {
+        let mut profile = CameraProfile::ip_webcam("");
+
+        profile.source = CameraSource::Rtsp {
+            url: "rtsp://cam.local/stream".into(),
+        };
+        assert_eq!(profile.remote_addr(), Some(("cam.local".to_string(), 554)));
+
+        profile.source = CameraSource::Http {
+            url: "http://cam.local/video.mjpg".into(),
+        };
+        assert_eq!(profile.remote_addr(), Some(("cam.local".to_string(), 80)));
+
+        profile.source = CameraSource::V4l2 {
+            device: "/dev/video0".into(),
+        };
+        assert_eq!(profile.remote_addr(), None);
+    }
+
+    #[test]
+    fn parse_profiles_json() {
+        assert_eq!(parse_profiles("").unwrap(), Vec::new());
+        assert!(parse_profiles("{").is_err());
+
+        let profiles = parse_profiles(
+            r#"[
+                {
+                    "name": "gate",
+                    "source": { "type": "rtsp", "url": "rtsp://10.0.0.2/live" },
+                    "snapshot_url": "http://10.0.0.2/snap.jpg",
+                    "credentials": { "username": "admin", "password": "1234" }
+                },
+                {
+                    "name": "test",
+                    "source": { "type": "file", "path": "/tmp/test.mp4" }
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(
+            profiles[0].source,
+            CameraSource::Rtsp {
+                url: "rtsp://10.0.0.2/live".into()
+            }
+        );
+        assert_eq!(
+            profiles[0]
+                .credentials
+                .as_ref()
+                .unwrap()
+                .basic_auth_header(),
+            "Basic YWRtaW46MTIzNA=="
+        );
+        assert_eq!(
+            profiles[1].source,
+            CameraSource::File {
+                path: "/tmp/test.mp4".into()
+            }
+        );
+        assert_eq!(profiles[1].snapshot_url, None);
+        assert_eq!(profiles[1].motion_url, None);
+    }
+}
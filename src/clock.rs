@@ -0,0 +1,175 @@
+use std::{cell::RefCell, rc::Rc, time::Instant};
+
+use chrono::{DateTime, Local, TimeDelta, Utc};
+
+/// Source of the current time, so time-dependent logic can be tested and
+/// replayed.
+#[derive(Debug, Default, Clone)]
+pub enum Clock {
+    #[default]
+    System,
+    Manual(ManualClock),
+}
+
+impl Clock {
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Self::System => Utc::now(),
+            Self::Manual(clock) => clock.now(),
+        }
+    }
+
+    pub fn now_local(&self) -> DateTime<Local> {
+        self.now().with_timezone(&Local)
+    }
+
+    /// Returns a monotonic instant that follows this clock, for measuring
+    /// durations with it.
+    pub fn instant(&self) -> Instant {
+        match self {
+            Self::System => Instant::now(),
+            Self::Manual(clock) => clock.instant(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ManualClockState {
+    origin_dt: DateTime<Utc>,
+    origin_instant: Instant,
+    anchor_dt: DateTime<Utc>,
+    anchor_instant: Instant,
+    speed: f64,
+}
+
+/// A clock that only moves when told to, or at a multiple of real time when
+/// fast-forwarding.
+///
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock(Rc<RefCell<ManualClockState>>);
+
+impl ManualClock {
+    /// Creates a frozen clock at the given time.
+    pub fn new(start_dt: DateTime<Utc>) -> Self {
+        let instant = Instant::now();
+        Self(Rc::new(RefCell::new(ManualClockState {
+            origin_dt: start_dt,
+            origin_instant: instant,
+            anchor_dt: start_dt,
+            anchor_instant: instant,
+            speed: 0.0,
+        })))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        let state = self.0.borrow();
+        now_of(&state, Instant::now())
+    }
+
+    /// Returns the instant as far from the creation of the clock as the
+    /// current time is from its start time.
+    ///
+    /// Times before the start time are clamped to it.
+    pub fn instant(&self) -> Instant {
+        let state = self.0.borrow();
+        let elapsed = now_of(&state, Instant::now()) - state.origin_dt;
+        state.origin_instant + elapsed.to_std().unwrap_or_default()
+    }
+
+    pub fn set(&self, dt: DateTime<Utc>) {
+        let mut state = self.0.borrow_mut();
+        state.anchor_dt = dt;
+        state.anchor_instant = Instant::now();
+    }
+
+    pub fn advance(&self, delta: TimeDelta) {
+        self.set(self.now() + delta);
+    }
+
+    /// Sets how many seconds pass for every real second, where `0.0` freezes
+    /// the clock.
+    pub fn set_speed(&self, speed: f64) {
+        let mut state = self.0.borrow_mut();
+
+        let instant = Instant::now();
+        state.anchor_dt = now_of(&state, instant);
+        state.anchor_instant = instant;
+        state.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.0.borrow().speed
+    }
+}
+
+fn now_of(state: &ManualClockState, instant: Instant) -> DateTime<Utc> {
+    if state.speed == 0.0 {
+        return state.anchor_dt;
+    }
+
+    let elapsed = instant.duration_since(state.anchor_instant);
+    let scaled = elapsed.mul_f64(state.speed);
+    state.anchor_dt + TimeDelta::from_std(scaled).unwrap_or(TimeDelta::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn dt(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    #[test]
+    fn manual_is_frozen() {
+        let clock = ManualClock::new(dt("2024-11-03T01:00:00Z"));
+        assert_eq!(clock.now(), dt("2024-11-03T01:00:00Z"));
+
+        clock.advance(TimeDelta::hours(2));
+        assert_eq!(clock.now(), dt("2024-11-03T03:00:00Z"));
+
+        clock.set(dt("2025-01-01T00:00:00Z"));
+        assert_eq!(clock.now(), dt("2025-01-01T00:00:00Z"));
+
+        let shared = Clock::Manual(clock.clone());
+        clock.advance(TimeDelta::minutes(1));
+        assert_eq!(shared.now(), dt("2025-01-01T00:01:00Z"));
+    }
+
+    #[test]
+    fn manual_instant() {
+        let clock = ManualClock::new(dt("2024-11-03T01:00:00Z"));
+        let start = clock.instant();
+        assert_eq!(clock.instant(), start);
+
+        clock.advance(TimeDelta::seconds(90));
+        assert_eq!(clock.instant() - start, Duration::from_secs(90));
+
+        clock.set(dt("2024-11-03T00:00:00Z"));
+        assert_eq!(clock.instant(), start);
+    }
+
+    #[test]
+    fn manual_fast_forward() {
+        let start_dt = dt("2024-11-03T01:00:00Z");
+        let instant = Instant::now();
+        let state = ManualClockState {
+            origin_dt: start_dt,
+            origin_instant: instant,
+            anchor_dt: start_dt,
+            anchor_instant: instant,
+            speed: 60.0,
+        };
+
+        let later = state.anchor_instant + Duration::from_secs(2);
+        assert_eq!(now_of(&state, later), start_dt + TimeDelta::minutes(2));
+
+        let clock = ManualClock::new(start_dt);
+        clock.set_speed(-1.0);
+        assert_eq!(clock.speed(), 0.0);
+        assert_eq!(clock.now(), start_dt);
+    }
+}
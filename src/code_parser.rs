@@ -0,0 +1,524 @@
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
+use inflections::case;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    date_time,
+    entity_data::{EntityData, EntityDataField, EntityDataFieldTy},
+    entity_id::EntityId,
+    gs1::Gs1ElementString,
+    jpeg_image::JpegImage,
+    settings::{OperationMode, Settings},
+    sex::Sex,
+    stock_id::StockId,
+};
+
+/// Parses a scanned code into an entity.
+pub trait CodeParser {
+    fn name(&self) -> &str;
+
+    fn is_applicable(&self, operation_mode: OperationMode) -> bool;
+
+    fn parse(&self, code: &str) -> Option<(EntityId, EntityData)>;
+}
+
+/// Parsers tried in order until one of them parses the code.
+pub struct CodeParserRegistry {
+    parsers: Vec<Box<dyn CodeParser>>,
+}
+
+impl CodeParserRegistry {
+    /// Returns a registry with only the built-in parsers.
+    pub fn new() -> Self {
+        Self {
+            parsers: vec![
+                Box::new(NationalIdParser),
+                Box::new(QrifyingCeaParser),
+                Box::new(Gs1Parser),
+            ],
+        }
+    }
+
+    /// Returns a registry with the configured parsers, which take precedence
+    /// over the built-in ones.
+    pub fn with_configured(specs: Vec<ConfiguredParserSpec>) -> Self {
+        let mut this = Self {
+            parsers: Vec::new(),
+        };
+
+        for spec in specs {
+            let name = spec.name.clone();
+            match ConfiguredParser::new(spec) {
+                Ok(parser) => this.register(parser),
+                Err(err) => tracing::warn!("Skipping invalid code parser `{}`: {:?}", name, err),
+            }
+        }
+
+        this.parsers.extend(Self::new().parsers);
+
+        this
+    }
+
+    pub fn register(&mut self, parser: impl CodeParser + 'static) {
+        self.parsers.push(Box::new(parser));
+    }
+
+    pub fn parse(
+        &self,
+        code: &str,
+        operation_mode: OperationMode,
+    ) -> Option<(EntityId, EntityData)> {
+        self.parsers
+            .iter()
+            .filter(|parser| parser.is_applicable(operation_mode))
+            .find_map(|parser| {
+                let ret = parser.parse(code);
+
+                if ret.is_some() {
+                    tracing::trace!("Parsed code with `{}`", parser.name());
+                }
+
+                ret
+            })
+    }
+}
+
+impl Default for CodeParserRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait CodeParserSettingsExt {
+    fn code_parser_registry(&self) -> CodeParserRegistry;
+}
+
+impl CodeParserSettingsExt for Settings {
+    fn code_parser_registry(&self) -> CodeParserRegistry {
+        let specs = parse_specs(&self.code_parsers_json())
+            .inspect_err(|err| tracing::warn!("Failed to parse code parsers: {:?}", err))
+            .unwrap_or_default();
+        CodeParserRegistry::with_configured(specs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum CodeMatcher {
+    /// Keys are named capture groups of the pattern.
+    Regex { pattern: String },
+    /// Keys are dot-separated paths into the JSON payload, e.g., `subject.PCN`
+    /// or `items.0.id`.
+    JsonPath,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfiguredParserSpec {
+    pub name: String,
+    /// Lowercase operation mode names. Applies to all modes if empty.
+    #[serde(default)]
+    pub operation_modes: Vec<String>,
+    #[serde(flatten)]
+    pub matcher: CodeMatcher,
+    /// Key of the entity ID.
+    pub id: String,
+    /// Keys of the entity data fields.
+    #[serde(default)]
+    pub fields: IndexMap<EntityDataFieldTy, String>,
+}
+
+struct ConfiguredParser {
+    spec: ConfiguredParserSpec,
+    regex: Option<Regex>,
+    operation_modes: Vec<OperationMode>,
+}
+
+impl ConfiguredParser {
+    fn new(spec: ConfiguredParserSpec) -> Result<Self> {
+        let regex = match &spec.matcher {
+            CodeMatcher::Regex { pattern } => Some(Regex::new(pattern)?),
+            CodeMatcher::JsonPath => None,
+        };
+
+        let operation_modes = spec
+            .operation_modes
+            .iter()
+            .map(|name| {
+                OperationMode::all()
+                    .iter()
+                    .find(|mode| mode.to_string().eq_ignore_ascii_case(name))
+                    .copied()
+                    .with_context(|| format!("Unknown operation mode `{}`", name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            spec,
+            regex,
+            operation_modes,
+        })
+    }
+}
+
+impl CodeParser for ConfiguredParser {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn is_applicable(&self, operation_mode: OperationMode) -> bool {
+        self.operation_modes.is_empty() || self.operation_modes.contains(&operation_mode)
+    }
+
+    fn parse(&self, code: &str) -> Option<(EntityId, EntityData)> {
+        let value_of: Box<dyn Fn(&str) -> Option<String> + '_> = match &self.regex {
+            Some(regex) => {
+                let captures = regex.captures(code)?;
+                Box::new(move |key| captures.name(key).map(|m| m.as_str().to_string()))
+            }
+            None => {
+                let value = serde_json::from_str::<serde_json::Value>(code).ok()?;
+                Box::new(move |key| json_path(&value, key).and_then(json_to_string))
+            }
+        };
+
+        let id = value_of(&self.spec.id).filter(|id| !id.is_empty())?;
+
+        let fields = self
+            .spec
+            .fields
+            .iter()
+            .filter_map(|(field_ty, key)| {
+                let string = value_of(key)?;
+                field_from_str(*field_ty, &string)
+                    .inspect_err(|err| {
+                        tracing::warn!("Failed to parse {} from `{}`: {:?}", field_ty, string, err)
+                    })
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+
+        Some((EntityId::new(id), EntityData::from_fields(fields)))
+    }
+}
+
+/// BPSU CEA QR codes in the form of `name_id_email_program`.
+struct QrifyingCeaParser;
+
+impl CodeParser for QrifyingCeaParser {
+    fn name(&self) -> &str {
+        "qrifying-cea"
+    }
+
+    fn is_applicable(&self, operation_mode: OperationMode) -> bool {
+        operation_mode.is_for_person()
+    }
+
+    fn parse(&self, code: &str) -> Option<(EntityId, EntityData)> {
+        let mut substrings = code.splitn(4, '_');
+        let name = substrings.next()?;
+        let student_id = substrings.next()?;
+        let bpsu_email = substrings.next()?;
+        let program = substrings.next()?;
+
+        Some((
+            EntityId::new(student_id),
+            EntityData::from_fields([
+                EntityDataField::Name(name.to_string()),
+                EntityDataField::Email(bpsu_email.to_string()),
+                EntityDataField::Program(program.to_string()),
+            ]),
+        ))
+    }
+}
+
+/// Philippine national ID QR codes.
+struct NationalIdParser;
+
+impl CodeParser for NationalIdParser {
+    fn name(&self) -> &str {
+        "national-id"
+    }
+
+    fn is_applicable(&self, operation_mode: OperationMode) -> bool {
+        operation_mode.is_for_person()
+    }
+
+    fn parse(&self, code: &str) -> Option<(EntityId, EntityData)> {
+        #[derive(Serialize, Deserialize)]
+        pub struct Subject {
+            #[serde(rename = "lName")]
+            last_name: String,
+            #[serde(rename = "fName")]
+            first_name: String,
+            #[serde(rename = "mName")]
+            middle_name: String,
+            #[serde(rename = "sex")]
+            sex: String,
+            #[serde(rename = "DOB")]
+            date_of_birth: String,
+            #[serde(rename = "POB")]
+            place_of_birth: String,
+            #[serde(rename = "PCN")]
+            pcn: String,
+        }
+
+        #[derive(Serialize, Deserialize)]
+        pub struct Data {
+            #[serde(rename = "DateIssued")]
+            date_issued: String,
+            #[serde(rename = "Issuer")]
+            issuer: String,
+            #[serde(rename = "subject")]
+            subject: Subject,
+        }
+
+        let data = serde_json::from_str::<Data>(code)
+            .inspect_err(|err| tracing::debug!("Failed to deserialize national id data: {:?}", err))
+            .ok()?;
+
+        let mut fields = vec![EntityDataField::Name(format!(
+            "{}, {} {}",
+            case::to_title_case(&data.subject.last_name),
+            case::to_title_case(&data.subject.first_name),
+            data.subject
+                .middle_name
+                .chars()
+                .next()
+                .map(|c| format!("{}.", c.to_uppercase()))
+                .unwrap_or_default(),
+        ))];
+
+        match data.subject.sex.parse::<Sex>() {
+            Ok(sex) => fields.push(EntityDataField::Sex(sex)),
+            Err(err) => tracing::warn!("Failed to parse sex: {:?}", err),
+        }
+
+        Some((
+            EntityId::new(data.subject.pcn),
+            EntityData::from_fields(fields),
+        ))
+    }
+}
+
+/// GS1-128 and GS1 DataMatrix product labels, which must have a serial
+/// number to identify the entity.
+struct Gs1Parser;
+
+impl CodeParser for Gs1Parser {
+    fn name(&self) -> &str {
+        "gs1"
+    }
+
+    fn is_applicable(&self, operation_mode: OperationMode) -> bool {
+        matches!(
+            operation_mode,
+            OperationMode::Inventory | OperationMode::Refrigerator
+        )
+    }
+
+    fn parse(&self, code: &str) -> Option<(EntityId, EntityData)> {
+        let elements = Gs1ElementString::parse(code)
+            .inspect_err(|err| tracing::debug!("Failed to parse GS1 element string: {:?}", err))
+            .ok()?;
+
+        let Some(serial) = elements.serial() else {
+            tracing::debug!("GS1 element string has no serial number");
+
+            return None;
+        };
+
+        let mut fields = Vec::new();
+
+        if let Some(gtin) = elements.gtin() {
+            fields.push(EntityDataField::StockId(StockId::new(gtin)));
+        }
+
+        match elements.expiration_dt() {
+            Some(Ok(dt)) => fields.push(EntityDataField::ExpirationDt(dt)),
+            Some(Err(err)) => tracing::warn!("Failed to parse GS1 expiration date: {:?}", err),
+            None => {}
+        }
+
+        if let Some(batch) = elements.batch() {
+            fields.push(EntityDataField::Lot(batch.to_string()));
+        }
+
+        Some((EntityId::new(serial), EntityData::from_fields(fields)))
+    }
+}
+
+fn field_from_str(field_ty: EntityDataFieldTy, s: &str) -> Result<EntityDataField> {
+    let field = match field_ty {
+        EntityDataFieldTy::StockId => EntityDataField::StockId(StockId::new(s)),
+        EntityDataFieldTy::Location => EntityDataField::Location(s.to_string()),
+        EntityDataFieldTy::ExpirationDt => EntityDataField::ExpirationDt(date_time::parse(s)?),
+        EntityDataFieldTy::Lot => EntityDataField::Lot(s.to_string()),
+        EntityDataFieldTy::Quantity => EntityDataField::Quantity(s.parse()?),
+        EntityDataFieldTy::AllowedDtRange => bail!("Allowed date range is not supported"),
+        EntityDataFieldTy::Photo => EntityDataField::Photo(JpegImage::from_base64(s)),
+        EntityDataFieldTy::Name => EntityDataField::Name(s.to_string()),
+        EntityDataFieldTy::Sex => EntityDataField::Sex(s.parse()?),
+        EntityDataFieldTy::Email => EntityDataField::Email(s.to_string()),
+        EntityDataFieldTy::Program => EntityDataField::Program(s.to_string()),
+        EntityDataFieldTy::Custom => bail!("Custom fields are not supported"),
+    };
+    Ok(field)
+}
+
+fn json_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            serde_json::Value::Object(map) => map.get(segment),
+            serde_json::Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+fn json_to_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(string) => Some(string.clone()),
+        serde_json::Value::Number(number) => Some(number.to_string()),
+        serde_json::Value::Bool(bool) => Some(bool.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_specs(json: &str) -> Result<Vec<ConfiguredParserSpec>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    serde_json::from_str(json).context("Invalid code parsers JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(json: &str) -> CodeParserRegistry {
+        CodeParserRegistry::with_configured(parse_specs(json).unwrap())
+    }
+
+    #[test]
+    fn builtin_qrifying_cea() {
+        let registry = CodeParserRegistry::new();
+
+        let (id, data) = registry
+            .parse(
+                "Juan Dela Cruz_2021-00123_jdc@bpsu.edu.ph_BSCpE",
+                OperationMode::Attendance,
+            )
+            .unwrap();
+        assert_eq!(id, EntityId::new("2021-00123"));
+        assert_eq!(data.name().unwrap(), "Juan Dela Cruz");
+        assert_eq!(data.program().unwrap(), "BSCpE");
+
+        assert!(registry
+            .parse("a_b_c_d", OperationMode::Inventory)
+            .is_none());
+    }
+
+    #[test]
+    fn builtin_gs1() {
+        let registry = CodeParserRegistry::new();
+
+        let code = "]d2010950600013435217250131\x1d10ABC123\x1d21SN0001";
+        let (id, data) = registry.parse(code, OperationMode::Refrigerator).unwrap();
+        assert_eq!(id, EntityId::new("SN0001"));
+        assert_eq!(data.stock_id(), Some(&StockId::new("09506000134352")));
+        assert_eq!(data.lot().unwrap(), "ABC123");
+        assert!(data.expiration_dt().is_some());
+
+        assert!(registry.parse(code, OperationMode::Attendance).is_none());
+        assert!(registry
+            .parse("(01)09506000134352(10)ABC123", OperationMode::Inventory)
+            .is_none());
+    }
+
+    #[test]
+    fn configured_regex() {
+        let registry = registry(
+            r#"[{
+                "name": "warehouse",
+                "operation_modes": ["inventory"],
+                "type": "regex",
+                "pattern": "^WH:(?P<id>[0-9]+):(?P<sku>[A-Z0-9-]+)(?::(?P<loc>.+))?$",
+                "id": "id",
+                "fields": { "StockId": "sku", "Location": "loc" }
+            }]"#,
+        );
+
+        let (id, data) = registry
+            .parse("WH:42:ABC-1:Shelf 3", OperationMode::Inventory)
+            .unwrap();
+        assert_eq!(id, EntityId::new("42"));
+        assert_eq!(data.stock_id(), Some(&StockId::new("ABC-1")));
+        assert_eq!(data.location().unwrap(), "Shelf 3");
+
+        let (_, data) = registry
+            .parse("WH:42:ABC-1", OperationMode::Inventory)
+            .unwrap();
+        assert_eq!(data.location(), None);
+
+        assert!(registry
+            .parse("WH:42:ABC-1", OperationMode::Attendance)
+            .is_none());
+    }
+
+    #[test]
+    fn configured_json_path() {
+        let registry = registry(
+            r#"[{
+                "name": "custom-json",
+                "type": "json-path",
+                "id": "person.ids.0",
+                "fields": { "Name": "person.name", "Sex": "person.sex" }
+            }]"#,
+        );
+
+        let (id, data) = registry
+            .parse(
+                r#"{"person": {"ids": [1001, 1002], "name": "Ana", "sex": "F"}}"#,
+                OperationMode::Counter,
+            )
+            .unwrap();
+        assert_eq!(id, EntityId::new("1001"));
+        assert_eq!(data.name().unwrap(), "Ana");
+        assert_eq!(data.sex(), Some(&Sex::Female));
+
+        // Falls back to built-in parsers.
+        assert!(registry.parse("a_b_c_d", OperationMode::Counter).is_some());
+        assert!(registry
+            .parse(r#"{"person": {}}"#, OperationMode::Parking)
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_specs() {
+        assert!(parse_specs("[{\"name\": \"x\"}]").is_err());
+
+        let registry = registry(
+            r#"[
+                { "name": "bad-regex", "type": "regex", "pattern": "(", "id": "id" },
+                { "name": "bad-mode", "operation_modes": ["x"], "type": "json-path", "id": "id" }
+            ]"#,
+        );
+        assert_eq!(
+            registry.parsers.len(),
+            CodeParserRegistry::new().parsers.len()
+        );
+    }
+
+    #[test]
+    fn json_path_lookup() {
+        let value = serde_json::json!({ "a": { "b": [{ "c": true }] } });
+        assert_eq!(
+            json_path(&value, "a.b.0.c"),
+            Some(&serde_json::Value::Bool(true))
+        );
+        assert_eq!(json_path(&value, "a.b.1.c"), None);
+        assert_eq!(json_path(&value, "a.x"), None);
+    }
+}
@@ -0,0 +1,336 @@
+use std::{collections::HashSet, fmt};
+
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, Local, Utc};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    date_time,
+    settings::{OperationMode, Settings},
+};
+
+/// Type of the values of a custom field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum CustomFieldTy {
+    Text,
+    Number,
+    Date,
+    Choice { choices: Vec<String> },
+}
+
+/// A deployment-specific entity data field, e.g., "Ward" or "Supplier".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomFieldDef {
+    /// Key the values are stored with, which is also used as the search
+    /// query identifier, e.g., `ward:A`.
+    pub key: String,
+    /// Defaults to the key.
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub ty: CustomFieldTy,
+    /// Lowercase operation mode names. Applies to all modes if empty.
+    #[serde(default)]
+    pub operation_modes: Vec<String>,
+}
+
+impl CustomFieldDef {
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.key)
+    }
+
+    pub fn is_applicable(&self, operation_mode: OperationMode) -> bool {
+        self.operation_modes.is_empty()
+            || self
+                .operation_modes
+                .iter()
+                .any(|name| operation_mode.to_string().eq_ignore_ascii_case(name))
+    }
+
+    /// Returns whether a spreadsheet column with the given title holds the
+    /// values of this field.
+    pub fn matches_col_title(&self, title: &str) -> bool {
+        let title = title.trim();
+        title.eq_ignore_ascii_case(&self.key) || title.eq_ignore_ascii_case(self.label())
+    }
+
+    pub fn parse_value(&self, s: &str) -> Result<CustomFieldValue> {
+        let s = s.trim();
+        ensure!(!s.is_empty(), "Empty value");
+
+        let value = match &self.ty {
+            CustomFieldTy::Text => CustomFieldValue::Text(s.to_string()),
+            CustomFieldTy::Number => {
+                let number = s.parse::<f64>()?;
+                ensure!(number.is_finite(), "Number must be finite");
+                CustomFieldValue::Number(number)
+            }
+            CustomFieldTy::Date => CustomFieldValue::Date(date_time::parse(s)?),
+            CustomFieldTy::Choice { choices } => {
+                let choice = choices
+                    .iter()
+                    .find(|choice| choice.eq_ignore_ascii_case(s))
+                    .with_context(|| format!("`{}` is not one of {:?}", s, choices))?;
+                CustomFieldValue::Choice(choice.clone())
+            }
+        };
+        Ok(value)
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(!self.key.is_empty(), "Key must not be empty");
+        ensure!(
+            !self.key.contains(|c: char| c.is_whitespace() || c == ':'),
+            "Key must not contain whitespace or colons"
+        );
+
+        if let CustomFieldTy::Choice { choices } = &self.ty {
+            ensure!(!choices.is_empty(), "Choices must not be empty");
+        }
+
+        for name in &self.operation_modes {
+            if !OperationMode::all()
+                .iter()
+                .any(|mode| mode.to_string().eq_ignore_ascii_case(name))
+            {
+                bail!("Unknown operation mode `{}`", name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CustomFieldValue {
+    Text(String),
+    Number(f64),
+    Date(DateTime<Utc>),
+    Choice(String),
+}
+
+// Numbers are always finite, as they are only created by parsing.
+impl Eq for CustomFieldValue {}
+
+impl fmt::Display for CustomFieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => fmt::Display::fmt(text, f),
+            Self::Number(number) => fmt::Display::fmt(number, f),
+            Self::Date(dt) => f.write_str(&date_time::format::human_readable_date(*dt)),
+            Self::Choice(choice) => fmt::Display::fmt(choice, f),
+        }
+    }
+}
+
+impl CustomFieldValue {
+    /// Returns whether the value matches a search query value.
+    ///
+    /// Texts match by substring, while the others must be equal. Dates are
+    /// compared by local day.
+    pub fn matches_query(&self, query: &str) -> bool {
+        match self {
+            Self::Text(text) => text.to_lowercase().contains(&query.to_lowercase()),
+            Self::Number(number) => query.parse::<f64>().is_ok_and(|q| q == *number),
+            Self::Date(dt) => date_time::parse(query).is_ok_and(|q| {
+                q.with_timezone(&Local).date_naive() == dt.with_timezone(&Local).date_naive()
+            }),
+            Self::Choice(choice) => choice.eq_ignore_ascii_case(query),
+        }
+    }
+}
+
+/// Values of the custom fields of an entity, by [`CustomFieldDef::key`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomFields(IndexMap<String, CustomFieldValue>);
+
+impl CustomFields {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&CustomFieldValue> {
+        self.0.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CustomFieldValue)> + '_ {
+        self.0.iter().map(|(key, value)| (key.as_str(), value))
+    }
+}
+
+impl FromIterator<(String, CustomFieldValue)> for CustomFields {
+    fn from_iter<T: IntoIterator<Item = (String, CustomFieldValue)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl fmt::Display for CustomFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (key, value)) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait CustomFieldSettingsExt {
+    /// Returns the custom fields applicable to the current operation mode.
+    fn custom_field_defs(&self) -> Vec<CustomFieldDef>;
+}
+
+impl CustomFieldSettingsExt for Settings {
+    fn custom_field_defs(&self) -> Vec<CustomFieldDef> {
+        let operation_mode = self.operation_mode();
+        parse_defs(&self.custom_entity_fields_json())
+            .inspect_err(|err| tracing::warn!("Failed to parse custom entity fields: {:?}", err))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|def| def.is_applicable(operation_mode))
+            .collect()
+    }
+}
+
+/// Parses the definitions, skipping the invalid ones and the ones with
+/// duplicate keys.
+fn parse_defs(json: &str) -> Result<Vec<CustomFieldDef>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let defs = serde_json::from_str::<Vec<CustomFieldDef>>(json)
+        .context("Invalid custom entity fields JSON")?;
+
+    let mut keys = HashSet::new();
+    let valid_defs = defs
+        .into_iter()
+        .filter(|def| {
+            if let Err(err) = def.validate() {
+                tracing::warn!("Skipping invalid custom field `{}`: {:?}", def.key, err);
+                return false;
+            }
+
+            if !keys.insert(def.key.clone()) {
+                tracing::warn!("Skipping duplicate custom field `{}`", def.key);
+                return false;
+            }
+
+            true
+        })
+        .collect();
+    Ok(valid_defs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity_data::{EntityData, EntityDataField};
+
+    fn defs() -> Vec<CustomFieldDef> {
+        parse_defs(
+            r#"[
+                { "key": "ward", "label": "Ward", "type": "choice", "choices": ["A", "B"] },
+                { "key": "serial", "type": "text", "operation_modes": ["inventory"] },
+                { "key": "weight", "label": "Weight (kg)", "type": "number" },
+                { "key": "received", "type": "date" },
+                { "key": "bad key", "type": "text" },
+                { "key": "empty", "type": "choice", "choices": [] },
+                { "key": "ward", "type": "text" },
+                { "key": "mode", "type": "text", "operation_modes": ["lab"] }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let defs = defs();
+        assert_eq!(
+            defs.iter().map(|def| def.key.as_str()).collect::<Vec<_>>(),
+            ["ward", "serial", "weight", "received"]
+        );
+        assert_eq!(defs[0].label(), "Ward");
+        assert_eq!(defs[1].label(), "serial");
+
+        assert!(defs[0].is_applicable(OperationMode::Attendance));
+        assert!(defs[1].is_applicable(OperationMode::Inventory));
+        assert!(!defs[1].is_applicable(OperationMode::Attendance));
+
+        assert!(defs[2].matches_col_title(" weight (KG) "));
+        assert!(defs[2].matches_col_title("Weight"));
+        assert!(!defs[2].matches_col_title("Gross Weight"));
+
+        assert!(parse_defs("").unwrap().is_empty());
+        assert!(parse_defs("[{\"key\": \"x\"}]").is_err());
+    }
+
+    #[test]
+    fn parse_value() {
+        let defs = defs();
+
+        assert_eq!(
+            defs[0].parse_value("a").unwrap(),
+            CustomFieldValue::Choice("A".into())
+        );
+        assert!(defs[0].parse_value("C").is_err());
+
+        assert_eq!(
+            defs[1].parse_value(" SN-1 ").unwrap(),
+            CustomFieldValue::Text("SN-1".into())
+        );
+        assert!(defs[1].parse_value("  ").is_err());
+
+        assert_eq!(
+            defs[2].parse_value("2.5").unwrap(),
+            CustomFieldValue::Number(2.5)
+        );
+        assert!(defs[2].parse_value("NaN").is_err());
+        assert!(defs[2].parse_value("heavy").is_err());
+
+        assert!(defs[3].parse_value("2024-11-03").is_ok());
+        assert!(defs[3].parse_value("someday").is_err());
+    }
+
+    #[test]
+    fn matches_query() {
+        assert!(CustomFieldValue::Text("Acme Corp".into()).matches_query("acme"));
+        assert!(!CustomFieldValue::Text("Acme Corp".into()).matches_query("globex"));
+
+        assert!(CustomFieldValue::Number(3.0).matches_query("3"));
+        assert!(!CustomFieldValue::Number(3.0).matches_query("3.5"));
+
+        assert!(CustomFieldValue::Choice("Visitor".into()).matches_query("visitor"));
+        assert!(!CustomFieldValue::Choice("Visitor".into()).matches_query("visit"));
+
+        let dt = date_time::parse("2024-11-03").unwrap();
+        assert!(CustomFieldValue::Date(dt).matches_query("2024-11-03"));
+        assert!(!CustomFieldValue::Date(dt).matches_query("2024-11-04"));
+    }
+
+    #[test]
+    fn entity_data_serde() {
+        let data = EntityData::from_fields([
+            EntityDataField::Name("Juan".into()),
+            EntityDataField::Custom(CustomFields::from_iter([
+                ("ward".to_string(), CustomFieldValue::Choice("A".into())),
+                ("weight".to_string(), CustomFieldValue::Number(2.5)),
+            ])),
+        ]);
+
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(serde_json::from_str::<EntityData>(&json).unwrap(), data);
+        assert_eq!(
+            data.custom_value("ward"),
+            Some(&CustomFieldValue::Choice("A".into()))
+        );
+        assert_eq!(data.custom_value("serial"), None);
+
+        // Data stored before custom fields existed is still readable.
+        let old_data = serde_json::from_str::<EntityData>(r#"[{"Name":"Juan"}]"#).unwrap();
+        assert_eq!(old_data.custom_fields(), None);
+    }
+}
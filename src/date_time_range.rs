@@ -8,7 +8,7 @@ use chrono::{
 use gtk::{glib, pango};
 use serde::{Deserialize, Serialize};
 
-use crate::date_time;
+use crate::{clock::Clock, date_time};
 
 const MIN_TIME: NaiveTime = NaiveTime::MIN;
 
@@ -78,16 +78,16 @@ impl DateTimeRange {
         }
     }
 
-    pub fn today() -> Self {
-        let now = Local::now().naive_local();
+    pub fn today(clock: &Clock) -> Self {
+        let now = clock.now_local().naive_local();
         Self::from_naive_local(
             NaiveDateTime::new(now.date(), MIN_TIME),
             NaiveDateTime::new(now.date(), MAX_TIME),
         )
     }
 
-    pub fn yesterday() -> Self {
-        let now = Local::now().naive_local();
+    pub fn yesterday(clock: &Clock) -> Self {
+        let now = clock.now_local().naive_local();
         let yesterday = now.date().pred_opt().unwrap();
         Self::from_naive_local(
             NaiveDateTime::new(yesterday, MIN_TIME),
@@ -95,8 +95,8 @@ impl DateTimeRange {
         )
     }
 
-    pub fn this_week() -> Self {
-        let now = Local::now().naive_local();
+    pub fn this_week(clock: &Clock) -> Self {
+        let now = clock.now_local().naive_local();
         let today = now.date();
 
         let weekday = today.weekday();
@@ -114,8 +114,8 @@ impl DateTimeRange {
         )
     }
 
-    pub fn this_month() -> Self {
-        let now = Local::now().naive_local();
+    pub fn this_month(clock: &Clock) -> Self {
+        let now = clock.now_local().naive_local();
         let start_of_month = NaiveDate::from_ymd_opt(now.year(), now.month(), 1).unwrap();
         let end_of_month = NaiveDate::from_ymd_opt(
             now.year(),
@@ -130,8 +130,8 @@ impl DateTimeRange {
         )
     }
 
-    pub fn this_year() -> Self {
-        let now = Local::now().naive_local();
+    pub fn this_year(clock: &Clock) -> Self {
+        let now = clock.now_local().naive_local();
         let start_of_year = NaiveDate::from_ymd_opt(now.year(), 1, 1).unwrap();
         let end_of_year = NaiveDate::from_ymd_opt(now.year(), 12, 31).unwrap();
 
@@ -145,24 +145,24 @@ impl DateTimeRange {
         self.eq_ignore_subsec(&Self::all_time())
     }
 
-    pub fn is_today(&self) -> bool {
-        self.eq_ignore_subsec(&Self::today())
+    pub fn is_today(&self, clock: &Clock) -> bool {
+        self.eq_ignore_subsec(&Self::today(clock))
     }
 
-    pub fn is_yesterday(&self) -> bool {
-        self.eq_ignore_subsec(&Self::yesterday())
+    pub fn is_yesterday(&self, clock: &Clock) -> bool {
+        self.eq_ignore_subsec(&Self::yesterday(clock))
     }
 
-    pub fn is_this_week(&self) -> bool {
-        self.eq_ignore_subsec(&Self::this_week())
+    pub fn is_this_week(&self, clock: &Clock) -> bool {
+        self.eq_ignore_subsec(&Self::this_week(clock))
     }
 
-    pub fn is_this_month(&self) -> bool {
-        self.eq_ignore_subsec(&Self::this_month())
+    pub fn is_this_month(&self, clock: &Clock) -> bool {
+        self.eq_ignore_subsec(&Self::this_month(clock))
     }
 
-    pub fn is_this_year(&self) -> bool {
-        self.eq_ignore_subsec(&Self::this_year())
+    pub fn is_this_year(&self, clock: &Clock) -> bool {
+        self.eq_ignore_subsec(&Self::this_year(clock))
     }
 
     pub fn is_empty(&self) -> bool {
@@ -206,24 +206,24 @@ impl DateTimeRange {
         }
     }
 
-    pub fn short_label_markup(&self) -> String {
-        if self.is_today() {
+    pub fn short_label_markup(&self, clock: &Clock) -> String {
+        if self.is_today(clock) {
             return "<b>Today</b>".to_string();
         }
 
-        if self.is_yesterday() {
+        if self.is_yesterday(clock) {
             return "<b>Yesterday</b>".to_string();
         }
 
-        if self.is_this_week() {
+        if self.is_this_week(clock) {
             return "<b>This Week</b>".to_string();
         }
 
-        if self.is_this_month() {
+        if self.is_this_month(clock) {
             return "<b>This Month</b>".to_string();
         }
 
-        if self.is_this_year() {
+        if self.is_this_year(clock) {
             return "<b>This Year</b>".to_string();
         }
 
@@ -279,6 +279,7 @@ fn days_in_month(year: i32, month: u32) -> u32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
 
     #[test]
     fn parse() {
@@ -320,4 +321,30 @@ mod tests {
             "All Time".parse().unwrap(),
         );
     }
+
+    #[test]
+    fn presets() {
+        let now = DateTime::parse_from_rfc3339("2024-11-06T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let clock = Clock::Manual(ManualClock::new(now));
+
+        let today = DateTimeRange::today(&clock);
+        assert!(today.contains(now));
+        assert!(today.is_today(&clock));
+        assert!(!today.is_yesterday(&clock));
+        assert!(!DateTimeRange::yesterday(&clock).contains(now));
+
+        for range in [
+            DateTimeRange::this_week(&clock),
+            DateTimeRange::this_month(&clock),
+            DateTimeRange::this_year(&clock),
+        ] {
+            assert!(range.contains(now));
+            assert!(range.start.unwrap() <= today.start.unwrap());
+            assert!(range.end.unwrap() >= today.end.unwrap());
+        }
+
+        assert_eq!(today.short_label_markup(&clock), "<b>Today</b>");
+    }
 }
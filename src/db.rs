@@ -1,5 +1,6 @@
 use std::{
     fs,
+    path::Path,
     time::{Duration, Instant},
 };
 
@@ -10,11 +11,12 @@ use heed::types::SerdeJson;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    entity_data::EntityData, entity_id::EntityId, jpeg_image::JpegImage, stock_data::StockData,
-    stock_id::StockId, APP_ID,
+    detected_wo_id_item::DetectedWoIdKind, entity_data::EntityData, entity_id::EntityId,
+    event_clip::EventClip, jpeg_image::JpegImage, location_data::LocationData,
+    location_id::LocationId, quantity::Quantity, stock_data::StockData, stock_id::StockId, APP_ID,
 };
 
-const N_NAMED_DBS: u32 = 4;
+const N_NAMED_DBS: u32 = 9;
 
 pub type TimelineDbType = heed::Database<SerdeJson<DateTime<Utc>>, SerdeJson<RawTimelineItem>>;
 pub const TIMELINE_DB_NAME: &str = "timeline";
@@ -25,19 +27,75 @@ pub const ENTITIES_DB_NAME: &str = "entities";
 pub type StocksDbType = heed::Database<SerdeJson<StockId>, SerdeJson<StockData>>;
 pub const STOCKS_DB_NAME: &str = "stocks";
 
+pub type LocationsDbType = heed::Database<SerdeJson<LocationId>, SerdeJson<LocationData>>;
+pub const LOCATIONS_DB_NAME: &str = "locations";
+
+pub type LocationMovesDbType =
+    heed::Database<SerdeJson<(DateTime<Utc>, EntityId)>, SerdeJson<RawLocationMove>>;
+pub const LOCATION_MOVES_DB_NAME: &str = "location_moves";
+
+pub type ZoneMovesDbType =
+    heed::Database<SerdeJson<(DateTime<Utc>, EntityId)>, SerdeJson<RawZoneMove>>;
+pub const ZONE_MOVES_DB_NAME: &str = "zone_moves";
+
 pub type DetectedWoIdDbType =
     heed::Database<SerdeJson<DateTime<Utc>>, SerdeJson<RawDetectedWoIdItem>>;
 pub const DETECTED_WO_ID_DB_NAME: &str = "detected_wo_id";
 
+pub type PendingDetectionsDbType =
+    heed::Database<SerdeJson<DateTime<Utc>>, SerdeJson<RawPendingDetection>>;
+pub const PENDING_DETECTIONS_DB_NAME: &str = "pending_detections";
+
+pub type WebhookQueueDbType = heed::Database<SerdeJson<String>, SerdeJson<RawWebhookDelivery>>;
+pub const WEBHOOK_QUEUE_DB_NAME: &str = "webhook_queue";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawTimelineItem {
     pub is_entry: bool,
     pub entity_id: EntityId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<Quantity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consumed: Option<Quantity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawLocationMove {
+    pub from: Option<LocationId>,
+    pub to: Option<LocationId>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawZoneMove {
+    pub from: Option<String>,
+    pub to: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawDetectedWoIdItem {
     pub image: Option<JpegImage>,
+    #[serde(default)]
+    pub kind: DetectedWoIdKind,
+    #[serde(default)]
+    pub clip: Option<EventClip>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawPendingDetection {
+    pub entity_id: EntityId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawWebhookDelivery {
+    pub url: String,
+    pub body: String,
+    pub created_dt: DateTime<Utc>,
+    pub n_attempts: u32,
+    pub next_attempt_dt: DateTime<Utc>,
 }
 
 pub fn new_env() -> Result<heed::Env> {
@@ -45,11 +103,16 @@ pub fn new_env() -> Result<heed::Env> {
     fs::create_dir_all(&path)
         .with_context(|| format!("Failed to create db dir at {}", path.display()))?;
 
+    open_env(&path)
+}
+
+/// Opens an env at an existing dir, e.g., a temporary one for scratch data.
+pub fn open_env(path: &Path) -> Result<heed::Env> {
     let env = unsafe {
         heed::EnvOpenOptions::new()
             .max_dbs(N_NAMED_DBS)
             .map_size(2 * 1024 * 1024 * 1024) // 2 GB
-            .open(&path)
+            .open(path)
             .with_context(|| format!("Failed to open heed env at {}", path.display()))?
     };
 
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use gtk::glib::clone;
+
+use crate::{settings::Settings, signal_handler_id_group::SignalHandlerIdGroup};
+
+/// Where a detection came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DetectionSource {
+    Camera,
+    RfidReader,
+    LocalReader,
+    Mqtt,
+}
+
+impl DetectionSource {
+    pub fn all() -> &'static [DetectionSource] {
+        &[
+            DetectionSource::Camera,
+            DetectionSource::RfidReader,
+            DetectionSource::LocalReader,
+            DetectionSource::Mqtt,
+        ]
+    }
+}
+
+/// Suppresses repeated reads of the same key within a window.
+///
+/// The window slides on every read, so a card held over an antenna or a code
+/// held in front of a camera is only handled once until it is taken away for
+/// longer than the window.
+#[derive(Debug)]
+pub struct Debouncer<K> {
+    window: Duration,
+    last_seen: HashMap<K, Instant>,
+}
+
+impl<K: Hash + Eq> Debouncer<K> {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Returns whether the read should be handled, i.e., the key was not
+    /// seen within the window.
+    pub fn check(&mut self, key: K, now: Instant) -> bool {
+        let window = self.window;
+        self.last_seen
+            .retain(|_, last_seen| now.saturating_duration_since(*last_seen) < window);
+
+        self.last_seen.insert(key, now).is_none()
+    }
+}
+
+pub trait DebounceSettingsExt {
+    fn debounce_window(&self, source: DetectionSource) -> Duration;
+
+    fn connect_debounce_windows_changed(&self, f: impl Fn(&Self) + 'static)
+        -> SignalHandlerIdGroup;
+}
+
+impl DebounceSettingsExt for Settings {
+    fn debounce_window(&self, source: DetectionSource) -> Duration {
+        let secs = match source {
+            DetectionSource::Camera => self.camera_debounce_secs(),
+            DetectionSource::RfidReader => self.rfid_reader_debounce_secs(),
+            DetectionSource::LocalReader => self.local_reader_debounce_secs(),
+            DetectionSource::Mqtt => self.mqtt_debounce_secs(),
+        };
+        Duration::from_secs(secs as u64)
+    }
+
+    fn connect_debounce_windows_changed(
+        &self,
+        f: impl Fn(&Self) + 'static,
+    ) -> SignalHandlerIdGroup {
+        let handler_ids = SignalHandlerIdGroup::new();
+
+        let f = Rc::new(f);
+
+        let handler_id = self.connect_camera_debounce_secs_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        let handler_id = self.connect_rfid_reader_debounce_secs_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        let handler_id = self.connect_local_reader_debounce_secs_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        let handler_id = self.connect_mqtt_debounce_secs_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        handler_ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debouncer() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut debouncer = Debouncer::new(Duration::from_secs(1));
+        assert!(debouncer.check("a", at(0)));
+        assert!(debouncer.check("b", at(100)));
+
+        // Held over the reader, so the window keeps sliding
+        assert!(!debouncer.check("a", at(500)));
+        assert!(!debouncer.check("a", at(1400)));
+        assert!(!debouncer.check("a", at(2300)));
+
+        // Taken away for longer than the window
+        assert!(debouncer.check("a", at(3400)));
+        assert!(debouncer.check("b", at(3400)));
+    }
+
+    #[test]
+    fn debouncer_zero_window() {
+        let now = Instant::now();
+
+        let mut debouncer = Debouncer::new(Duration::ZERO);
+        assert!(debouncer.check("a", now));
+        assert!(debouncer.check("a", now));
+    }
+}
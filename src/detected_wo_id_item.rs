@@ -1,7 +1,34 @@
+use std::fmt;
+
 use chrono::{DateTime, Utc};
 use gtk::{glib, subclass::prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{db, event_clip::EventClip, jpeg_image::JpegImage};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DetectedWoIdKind {
+    /// Motion was detected without any ID read.
+    #[default]
+    Unregistered,
+    /// More motion bursts were detected than ID reads.
+    Tailgating,
+}
+
+impl DetectedWoIdKind {
+    pub fn is_tailgating(&self) -> bool {
+        matches!(self, Self::Tailgating)
+    }
+}
 
-use crate::{db, jpeg_image::JpegImage};
+impl fmt::Display for DetectedWoIdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unregistered => write!(f, "Unregistered"),
+            Self::Tailgating => write!(f, "Tailgating"),
+        }
+    }
+}
 
 mod imp {
     use std::cell::OnceCell;
@@ -12,6 +39,8 @@ mod imp {
     pub struct DetectedWoIdItem {
         pub(super) dt: OnceCell<DateTime<Utc>>,
         pub(super) image: OnceCell<Option<JpegImage>>,
+        pub(super) kind: OnceCell<DetectedWoIdKind>,
+        pub(super) clip: OnceCell<Option<EventClip>>,
     }
 
     #[glib::object_subclass]
@@ -28,23 +57,32 @@ glib::wrapper! {
 }
 
 impl DetectedWoIdItem {
-    pub fn new(dt: DateTime<Utc>, image: Option<JpegImage>) -> Self {
+    pub fn new(
+        dt: DateTime<Utc>,
+        image: Option<JpegImage>,
+        kind: DetectedWoIdKind,
+        clip: Option<EventClip>,
+    ) -> Self {
         let this = glib::Object::new::<Self>();
 
         let imp = this.imp();
         imp.dt.set(dt).unwrap();
         imp.image.set(image).unwrap();
+        imp.kind.set(kind).unwrap();
+        imp.clip.set(clip).unwrap();
 
         this
     }
 
     pub fn from_db(dt: DateTime<Utc>, raw: db::RawDetectedWoIdItem) -> Self {
-        Self::new(dt, raw.image)
+        Self::new(dt, raw.image, raw.kind, raw.clip)
     }
 
     pub fn to_db(&self) -> db::RawDetectedWoIdItem {
         db::RawDetectedWoIdItem {
             image: self.image().clone(),
+            kind: self.kind(),
+            clip: self.clip(),
         }
     }
 
@@ -55,4 +93,12 @@ impl DetectedWoIdItem {
     pub fn image(&self) -> Option<JpegImage> {
         self.imp().image.get().unwrap().clone()
     }
+
+    pub fn kind(&self) -> DetectedWoIdKind {
+        *self.imp().kind.get().unwrap()
+    }
+
+    pub fn clip(&self) -> Option<EventClip> {
+        self.imp().clip.get().unwrap().clone()
+    }
 }
@@ -0,0 +1,358 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::PathBuf,
+    rc::Rc,
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use gtk::glib;
+
+use crate::{
+    application::{self, Application},
+    clock::{Clock, ManualClock},
+    code_parser::CodeParserSettingsExt,
+    date_time, db,
+    debounce::{DebounceSettingsExt, DetectionSource},
+    detection_trace::DetectionTrace,
+    detector::Detector,
+    entity_data::EntityData,
+    entity_entry_tracker::EntityIdSet,
+    entity_id::EntityId,
+    limit_reached::LimitReached,
+    settings::OperationMode,
+    timeline::{Detection, Timeline},
+    timeline_item_kind::TimelineItemKind,
+    zone::{self, ZoneSettingsExt},
+};
+
+/// Something that would have been shown to the user while the trace was
+/// recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayEvent {
+    Item {
+        entity_id: EntityId,
+        kind: TimelineItemKind,
+    },
+    /// The entity was inside and moved to another zone.
+    ZoneMove {
+        entity_id: EntityId,
+        to: Option<String>,
+    },
+    Toast(String),
+    /// The entity is unknown, so its data would have been asked from the
+    /// user.
+    NeedsEntityData(EntityId),
+}
+
+impl fmt::Display for ReplayEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Item { entity_id, kind } => write!(f, "{kind:?} of “{entity_id}”"),
+            Self::ZoneMove { entity_id, to } => write!(
+                f,
+                "Move of “{entity_id}” to “{}”",
+                to.as_deref().unwrap_or(zone::NO_ZONE_NAME)
+            ),
+            Self::Toast(message) => write!(f, "Toast: {message}"),
+            Self::NeedsEntityData(entity_id) => write!(f, "Needs data of “{entity_id}”"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub events: Vec<(DateTime<Utc>, ReplayEvent)>,
+}
+
+impl ReplayReport {
+    pub fn n_items(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|(_, event)| matches!(event, ReplayEvent::Item { .. }))
+            .count()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (dt, event) in &self.events {
+            writeln!(f, "{}  {}", date_time::format::parseable(*dt), event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes the dir when dropped, even if the replay failed.
+struct ScratchDir(PathBuf);
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.0) {
+            tracing::warn!("Failed to remove scratch dir: {:?}", err);
+        }
+    }
+}
+
+/// Replays the trace into a scratch timeline with the current settings.
+///
+/// The inputs go through a fresh detector on a manual clock set to each
+/// input time, so debouncing, tailgating and alert timeouts behave as they
+/// did while recording. Overstays are checked on every input.
+pub fn replay(trace: &DetectionTrace) -> Result<ReplayReport> {
+    let app = Application::get();
+    let settings = app.settings();
+
+    let scratch_dir = ScratchDir(
+        glib::mkdtemp(glib::tmp_dir().join("uets-replay-XXXXXX"))
+            .context("Failed to create scratch dir")?,
+    );
+    let timeline = Timeline::load_from_env(db::open_env(&scratch_dir.0)?)?;
+
+    let start_dt = trace.inputs.first().map_or_else(Utc::now, |(dt, _)| *dt);
+    let clock = ManualClock::new(start_dt);
+    timeline.set_clock(Clock::Manual(clock.clone()));
+
+    let detector = Detector::new();
+    detector.set_clock(Clock::Manual(clock.clone()));
+    for source in DetectionSource::all() {
+        detector.set_debounce_window(*source, settings.debounce_window(*source));
+    }
+    detector.set_code_parser_registry(settings.code_parser_registry());
+    detector.set_enable_detection_wo_id(settings.enable_detection_wo_id());
+    detector.set_enable_tailgating_detection(settings.enable_tailgating_detection());
+
+    let replayer = Rc::new(Replayer {
+        clock: clock.clone(),
+        timeline: timeline.clone(),
+        operation_mode: settings.operation_mode(),
+        trace_entities: trace.entities.iter().cloned().collect(),
+        alerted_limit_reached: Cell::new(None),
+        alerted_zone_overstayed: RefCell::new(HashSet::new()),
+        events: RefCell::new(Vec::new()),
+    });
+    replayer.connect_signals(&detector);
+
+    for (dt, input) in &trace.inputs {
+        while let Some(timeout_dt) = detector.next_timeout_dt().filter(|t| t <= dt) {
+            clock.set(timeout_dt);
+            detector.fire_due_timeouts();
+        }
+
+        clock.set(*dt);
+        timeline.entity_entry_tracker().check_overstayed();
+        replayer.check_zone_overstayed();
+
+        detector.replay_input(input);
+    }
+
+    while let Some(timeout_dt) = detector.next_timeout_dt() {
+        clock.set(timeout_dt);
+        detector.fire_due_timeouts();
+    }
+
+    Ok(ReplayReport {
+        events: replayer.events.take(),
+    })
+}
+
+struct Replayer {
+    clock: ManualClock,
+    timeline: Timeline,
+    operation_mode: OperationMode,
+    trace_entities: HashMap<EntityId, EntityData>,
+    alerted_limit_reached: Cell<Option<LimitReached>>,
+    alerted_zone_overstayed: RefCell<HashSet<(EntityId, String)>>,
+    events: RefCell<Vec<(DateTime<Utc>, ReplayEvent)>>,
+}
+
+impl Replayer {
+    fn connect_signals(self: &Rc<Self>, detector: &Detector) {
+        let this = Rc::downgrade(self);
+        detector.connect_detected(move |_, entity_id, entity_data, zone| {
+            if let Some(this) = this.upgrade() {
+                this.handle_detected(entity_id, entity_data, zone);
+            }
+        });
+
+        let this = Rc::downgrade(self);
+        detector.connect_detected_invalid(move |_, _| {
+            if let Some(this) = this.upgrade() {
+                this.push_toast(application::DETECTED_INVALID_MESSAGE);
+            }
+        });
+
+        let this = Rc::downgrade(self);
+        detector.connect_detected_wo_id(move |_, _, _| {
+            if let Some(this) = this.upgrade() {
+                this.push_toast(application::DETECTED_WO_ID_MESSAGE);
+            }
+        });
+
+        let this = Rc::downgrade(self);
+        detector.connect_tailgating_detected(move |_, _, _| {
+            if let Some(this) = this.upgrade() {
+                this.push_toast(application::TAILGATING_DETECTED_MESSAGE);
+            }
+        });
+
+        let this = Rc::downgrade(self);
+        self.timeline.connect_n_inside_notify(move |timeline| {
+            if let Some(this) = this.upgrade() {
+                this.handle_n_inside_changed(timeline.n_inside());
+            }
+        });
+
+        let this = Rc::downgrade(self);
+        self.timeline.entity_entry_tracker().connect_overstayed(
+            move |_, EntityIdSet(entity_ids)| {
+                let Some(this) = this.upgrade() else {
+                    return;
+                };
+
+                if entity_ids.is_empty() {
+                    return;
+                }
+
+                let entities = entity_ids
+                    .iter()
+                    .map(|id| {
+                        this.timeline
+                            .entity_list()
+                            .get(id)
+                            .expect("entity must exist")
+                    })
+                    .collect::<Vec<_>>();
+                this.push_toast(&application::overstayed_message(&entities));
+            },
+        );
+    }
+
+    fn push(&self, event: ReplayEvent) {
+        self.events.borrow_mut().push((self.clock.now(), event));
+    }
+
+    fn push_toast(&self, message: &str) {
+        self.push(ReplayEvent::Toast(message.to_string()));
+    }
+
+    fn handle_detected(
+        &self,
+        entity_id: &EntityId,
+        entity_data: Option<EntityData>,
+        zone: Option<String>,
+    ) {
+        let data = if let Some(data) = entity_data {
+            data
+        } else if let Some(entity) = self.timeline.entity_list().get(entity_id) {
+            entity.data().clone()
+        } else if let Some(data) = self.trace_entities.get(entity_id) {
+            data.clone()
+        } else if self.operation_mode != OperationMode::Counter {
+            self.push(ReplayEvent::NeedsEntityData(entity_id.clone()));
+            return;
+        } else {
+            EntityData::new()
+        };
+
+        let item = match self.timeline.handle_detected(entity_id, data, zone) {
+            Ok(Detection::Item(item)) => item,
+            Ok(Detection::ZoneMove(zone_move)) => {
+                let entity = self
+                    .timeline
+                    .entity_list()
+                    .get(&zone_move.entity_id)
+                    .expect("entity must exist");
+
+                self.push(ReplayEvent::ZoneMove {
+                    entity_id: zone_move.entity_id.clone(),
+                    to: zone_move.to.clone(),
+                });
+                self.push_toast(&application::zone_moved_message(&entity, &zone_move));
+
+                let zone_defs = Application::get().settings().zone_defs();
+                if let Some(message) = zone_move.to.as_deref().and_then(|zone| {
+                    application::denied_zone_message(&zone_defs, &entity, zone, zone_move.dt)
+                }) {
+                    self.push_toast(&message);
+                }
+                return;
+            }
+            Err(err) => {
+                tracing::warn!("Failed to handle replayed entity: {:?}", err);
+
+                self.push_toast("Can't handle entity");
+                return;
+            }
+        };
+
+        let entity = self
+            .timeline
+            .entity_list()
+            .get(item.entity_id())
+            .expect("entity must exist");
+
+        self.push(ReplayEvent::Item {
+            entity_id: item.entity_id().clone(),
+            kind: item.kind(),
+        });
+        self.push_toast(&application::detected_message(
+            self.operation_mode,
+            &entity,
+            item.kind(),
+        ));
+
+        let zone_defs = Application::get().settings().zone_defs();
+        if let Some(message) = application::denied_entry_message(&entity, &item).or_else(|| {
+            item.zone()
+                .filter(|_| item.kind().is_entry())
+                .and_then(|zone| {
+                    application::denied_zone_message(&zone_defs, &entity, zone, item.dt())
+                })
+        }) {
+            self.push_toast(&message);
+        }
+
+        if let Some(message) = application::fefo_skipped_message(&self.timeline, &entity, &item) {
+            self.push_toast(&message);
+        }
+
+        if let Some(location_id) = entity.location().filter(|_| item.kind().is_entry()) {
+            if let Some(message) = application::over_capacity_message(&self.timeline, &location_id)
+            {
+                self.push_toast(&message);
+            }
+        }
+    }
+
+    fn check_zone_overstayed(&self) {
+        let overstayed = application::newly_zone_overstayed(
+            &self.timeline,
+            &Application::get().settings().zone_defs(),
+            self.clock.now(),
+            &mut self.alerted_zone_overstayed.borrow_mut(),
+        );
+
+        if !overstayed.is_empty() {
+            self.push_toast(&application::zone_overstayed_message(&overstayed));
+        }
+    }
+
+    /// Only pushes a toast when the limit becomes reached, as the app
+    /// replaces the same toast while the limit stays reached.
+    fn handle_n_inside_changed(&self, n_inside: u32) {
+        let app = Application::get();
+
+        let alert = application::limit_reached_alert(app.settings(), n_inside);
+        let limit_reached = alert.map(|(limit_reached, _)| limit_reached);
+        if self.alerted_limit_reached.replace(limit_reached) == limit_reached {
+            return;
+        }
+
+        if let Some((_, message)) = alert {
+            self.push_toast(message);
+        }
+    }
+}
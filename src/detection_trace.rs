@@ -0,0 +1,324 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{entity_data::EntityData, entity_id::EntityId};
+
+const VERSION: u32 = 1;
+
+/// A raw input to the detector, before debouncing and parsing. Reads keep
+/// the reader they came from, if known, so that replays find their zones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum DetectionInput {
+    RfidRead {
+        id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reader: Option<String>,
+    },
+    LocalRead {
+        id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reader: Option<String>,
+    },
+    MqttMessage {
+        payload: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reader: Option<String>,
+    },
+    Code {
+        code: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reader: Option<String>,
+    },
+    Motion,
+    /// An ID entered in the test window.
+    Simulated {
+        id: String,
+    },
+}
+
+impl DetectionInput {
+    /// Returns the ID this input refers to without parsing, if any.
+    fn raw_entity_id(&self) -> Option<EntityId> {
+        match self {
+            Self::RfidRead { id, .. } | Self::LocalRead { id, .. } | Self::Simulated { id } => {
+                Some(EntityId::new(id.as_str()))
+            }
+            Self::MqttMessage { payload, .. } => {
+                let id = payload.trim();
+                (!id.is_empty()).then(|| EntityId::new(id))
+            }
+            Self::Code { .. } | Self::Motion => None,
+        }
+    }
+}
+
+/// One line of a trace file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum TraceLine {
+    Header {
+        version: u32,
+    },
+    Entity {
+        id: EntityId,
+        data: EntityData,
+    },
+    Input {
+        dt: DateTime<Utc>,
+        input: DetectionInput,
+    },
+}
+
+/// Recorded detector inputs, along with the data of the entities they refer
+/// to, so the trace can be replayed on another machine.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DetectionTrace {
+    pub entities: Vec<(EntityId, EntityData)>,
+    pub inputs: Vec<(DateTime<Utc>, DetectionInput)>,
+}
+
+impl DetectionTrace {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open trace at {}", path.display()))?;
+        Self::read(BufReader::new(file))
+    }
+
+    fn read(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines();
+
+        let Some(first_line) = lines.next() else {
+            bail!("Trace is empty");
+        };
+        match serde_json::from_str::<TraceLine>(&first_line?).context("Invalid trace header")? {
+            TraceLine::Header { version } if version == VERSION => {}
+            TraceLine::Header { version } => bail!("Unsupported trace version {}", version),
+            _ => bail!("Trace must start with a header"),
+        }
+
+        let mut this = Self::default();
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+
+            // The last line may be cut off if the app quit while recording.
+            let Ok(trace_line) = serde_json::from_str::<TraceLine>(&line) else {
+                tracing::warn!("Ignored invalid trace line {}", index + 2);
+                continue;
+            };
+
+            match trace_line {
+                TraceLine::Header { .. } => bail!("Unexpected header at line {}", index + 2),
+                TraceLine::Entity { id, data } => this.entities.push((id, data)),
+                TraceLine::Input { dt, input } => this.inputs.push((dt, input)),
+            }
+        }
+
+        Ok(this)
+    }
+}
+
+/// Writes detector inputs to a trace file as they come in.
+///
+/// Each input is written as its own line and flushed right away, so the
+/// trace is usable even if the app is killed.
+pub struct DetectionTraceWriter {
+    writer: BufWriter<File>,
+    written_entities: HashSet<EntityId>,
+}
+
+impl DetectionTraceWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create trace at {}", path.display()))?;
+
+        let mut this = Self {
+            writer: BufWriter::new(file),
+            written_entities: HashSet::new(),
+        };
+        this.write_line(&TraceLine::Header { version: VERSION })?;
+
+        Ok(this)
+    }
+
+    /// Writes the input, preceded by the data of the entity it refers to
+    /// until it is known, as returned by `entity_data`.
+    pub fn write(
+        &mut self,
+        dt: DateTime<Utc>,
+        input: &DetectionInput,
+        entity_data: impl FnOnce(&EntityId) -> Option<EntityData>,
+    ) -> Result<()> {
+        if let Some(id) = input.raw_entity_id() {
+            if !self.written_entities.contains(&id) {
+                if let Some(data) = entity_data(&id) {
+                    self.write_entity(&id, data)?;
+                }
+            }
+        }
+
+        self.write_line(&TraceLine::Input {
+            dt,
+            input: input.clone(),
+        })
+    }
+
+    /// Writes the data of the entity, unless it was already written, e.g.,
+    /// when the data of an unknown entity is entered after its first read.
+    pub fn write_entity(&mut self, id: &EntityId, data: EntityData) -> Result<()> {
+        if self.written_entities.contains(id) {
+            return Ok(());
+        }
+
+        self.write_line(&TraceLine::Entity {
+            id: id.clone(),
+            data,
+        })?;
+        self.written_entities.insert(id.clone());
+
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &TraceLine) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, line)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gtk::glib;
+
+    use super::*;
+    use crate::entity_data::EntityDataField;
+
+    fn dt(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    #[test]
+    fn read() {
+        let trace = DetectionTrace::read(Cursor::new(
+            [
+                r#"{"type":"header","version":1}"#,
+                r#"{"type":"input","dt":"2024-11-03T01:00:00Z","input":{"type":"rfid-read","id":"a1"}}"#,
+                r#"{"type":"input","dt":"2024-11-03T01:00:01Z","input":{"type":"motion"}}"#,
+                r#"{"type":"input","dt":"2024-11-03T01:00"#,
+            ]
+            .join("\n"),
+        ))
+        .unwrap();
+
+        assert!(trace.entities.is_empty());
+        assert_eq!(
+            trace.inputs,
+            [
+                (
+                    dt("2024-11-03T01:00:00Z"),
+                    DetectionInput::RfidRead {
+                        id: "a1".into(),
+                        reader: None,
+                    }
+                ),
+                (dt("2024-11-03T01:00:01Z"), DetectionInput::Motion),
+            ]
+        );
+
+        assert!(DetectionTrace::read(Cursor::new("")).is_err());
+        assert!(DetectionTrace::read(Cursor::new(r#"{"type":"header","version":0}"#)).is_err());
+        assert!(DetectionTrace::read(Cursor::new(r#"{"type":"motion"}"#)).is_err());
+    }
+
+    #[test]
+    fn write_and_read() {
+        let dir = glib::mkdtemp(glib::tmp_dir().join("uets-trace-test-XXXXXX")).unwrap();
+        let path = dir.join("trace.jsonl");
+
+        let data = EntityData::from_fields([EntityDataField::Name("Juan".into())]);
+
+        let mut writer = DetectionTraceWriter::create(&path).unwrap();
+        let inputs = [
+            (
+                dt("2024-11-03T01:00:00Z"),
+                DetectionInput::RfidRead {
+                    id: "a1".into(),
+                    reader: Some("192.168.1.20".into()),
+                },
+            ),
+            (
+                dt("2024-11-03T01:00:01Z"),
+                DetectionInput::Code {
+                    code: "qr-0001".into(),
+                    reader: None,
+                },
+            ),
+            (
+                dt("2024-11-03T01:00:02Z"),
+                DetectionInput::MqttMessage {
+                    payload: " a1 ".into(),
+                    reader: Some("lab-1".into()),
+                },
+            ),
+            (
+                dt("2024-11-03T01:00:03Z"),
+                DetectionInput::LocalRead {
+                    id: "b2".into(),
+                    reader: Some("/dev/ttyUSB0".into()),
+                },
+            ),
+        ];
+        for (dt, input) in &inputs {
+            writer
+                .write(*dt, input, |id| {
+                    (*id == EntityId::new("a1")).then(|| data.clone())
+                })
+                .unwrap();
+        }
+        drop(writer);
+
+        let trace = DetectionTrace::load(&path).unwrap();
+        assert_eq!(trace.entities, [(EntityId::new("a1"), data)]);
+        assert_eq!(trace.inputs, inputs);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn write_entity_known_later() {
+        let dir = glib::mkdtemp(glib::tmp_dir().join("uets-trace-test-XXXXXX")).unwrap();
+        let path = dir.join("trace.jsonl");
+
+        let data = EntityData::from_fields([EntityDataField::Name("Juan".into())]);
+        let input = DetectionInput::Simulated { id: "a1".into() };
+
+        let mut writer = DetectionTraceWriter::create(&path).unwrap();
+        writer
+            .write(dt("2024-11-03T01:00:00Z"), &input, |_| None)
+            .unwrap();
+        writer
+            .write(dt("2024-11-03T01:00:01Z"), &input, |_| Some(data.clone()))
+            .unwrap();
+        writer
+            .write_entity(&EntityId::new("a1"), EntityData::new())
+            .unwrap();
+        drop(writer);
+
+        let trace = DetectionTrace::load(&path).unwrap();
+        assert_eq!(trace.entities, [(EntityId::new("a1"), data)]);
+        assert_eq!(trace.inputs.len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
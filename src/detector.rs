@@ -1,31 +1,41 @@
-use std::time::Duration;
+use std::{path::Path, time::Duration};
 
-use chrono::Utc;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 use gtk::{
     glib::{self, clone, closure_local},
     prelude::*,
     subclass::prelude::*,
 };
-use inflections::case;
-use serde::{Deserialize, Serialize};
 
 use crate::{
     camera::Camera,
+    clock::Clock,
+    code_parser::CodeParserRegistry,
     date_time_boxed::DateTimeBoxed,
-    entity_data::{EntityData, EntityDataField},
+    debounce::{Debouncer, DetectionSource},
+    detection_trace::{DetectionInput, DetectionTraceWriter},
+    entity_data::EntityData,
     entity_id::EntityId,
     jpeg_image::JpegImage,
+    local_reader::LocalReader,
+    mqtt_client::MqttClient,
     remote::Remote,
     rfid_reader::RfidReader,
-    sex::Sex,
+    signed_qr,
+    tailgating::{MotionAttribution, TailgatingTracker},
+    zone::ZoneSettingsExt,
     Application,
 };
 
-const CAMERA_LAST_DETECTED_RESET_DELAY: Duration = Duration::from_secs(2);
 const DETECTED_WO_ID_ALERT_DELAY: Duration = Duration::from_secs(5);
 
 mod imp {
-    use std::{cell::RefCell, sync::OnceLock};
+    use std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        sync::OnceLock,
+    };
 
     use gtk::glib::subclass::Signal;
 
@@ -35,11 +45,20 @@ mod imp {
     pub struct Detector {
         pub(super) camera: RefCell<Option<Camera>>,
         pub(super) aux_cameras: RefCell<Vec<(Camera, Vec<glib::SignalHandlerId>)>>,
-        pub(super) camera_last_detected: RefCell<Option<String>>,
-        pub(super) camera_last_detected_reset_timeout: RefCell<Option<glib::SourceId>>,
+        pub(super) local_readers: RefCell<Vec<(LocalReader, glib::SignalHandlerId)>>,
+        pub(super) debouncers: RefCell<HashMap<DetectionSource, Debouncer<String>>>,
+        pub(super) code_parser_registry: RefCell<CodeParserRegistry>,
 
+        pub(super) is_detection_wo_id_enabled: Cell<bool>,
         pub(super) detected_wo_id_capture: RefCell<Option<(DateTimeBoxed, Option<JpegImage>)>>,
         pub(super) detected_wo_id_alert_timeout: RefCell<Option<glib::SourceId>>,
+        pub(super) detected_wo_id_alert_deadline: Cell<Option<DateTime<Utc>>>,
+
+        pub(super) is_tailgating_detection_enabled: Cell<bool>,
+        pub(super) tailgating_tracker: RefCell<TailgatingTracker>,
+
+        pub(super) clock: RefCell<Clock>,
+        pub(super) trace_writer: RefCell<Option<DetectionTraceWriter>>,
     }
 
     #[glib::object_subclass]
@@ -55,7 +74,11 @@ mod imp {
             SIGNALS.get_or_init(|| {
                 vec![
                     Signal::builder("detected")
-                        .param_types([EntityId::static_type(), Option::<EntityData>::static_type()])
+                        .param_types([
+                            EntityId::static_type(),
+                            Option::<EntityData>::static_type(),
+                            Option::<String>::static_type(),
+                        ])
                         .build(),
                     Signal::builder("detected-invalid")
                         .param_types([String::static_type()])
@@ -66,6 +89,12 @@ mod imp {
                             Option::<JpegImage>::static_type(),
                         ])
                         .build(),
+                    Signal::builder("tailgating-detected")
+                        .param_types([
+                            DateTimeBoxed::static_type(),
+                            Option::<JpegImage>::static_type(),
+                        ])
+                        .build(),
                 ]
             })
         }
@@ -83,12 +112,17 @@ impl Detector {
 
     pub fn connect_detected<F>(&self, f: F) -> glib::SignalHandlerId
     where
-        F: Fn(&Self, &EntityId, Option<EntityData>) + 'static,
+        F: Fn(&Self, &EntityId, Option<EntityData>, Option<String>) + 'static,
     {
         self.connect_closure(
             "detected",
             false,
-            closure_local!(|obj: &Self, id: &EntityId, data: Option<EntityData>| f(obj, id, data)),
+            closure_local!(|obj: &Self,
+                            id: &EntityId,
+                            data: Option<EntityData>,
+                            zone: Option<String>| {
+                f(obj, id, data, zone)
+            }),
         )
     }
 
@@ -116,6 +150,19 @@ impl Detector {
         )
     }
 
+    pub fn connect_tailgating_detected<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &DateTimeBoxed, Option<&JpegImage>) + 'static,
+    {
+        self.connect_closure(
+            "tailgating-detected",
+            false,
+            closure_local!(
+                |obj: &Self, dt: &DateTimeBoxed, image: Option<&JpegImage>| f(obj, dt, image)
+            ),
+        )
+    }
+
     pub fn bind_camera(&self, camera: &Camera) {
         let imp = self.imp();
 
@@ -163,20 +210,190 @@ impl Detector {
         rfid_reader.connect_detected(clone!(
             #[weak(rename_to = obj)]
             self,
-            move |_, id| {
-                let entity_id = EntityId::new(id);
-                obj.emit_detected(&entity_id, None);
+            move |rfid_reader, id| {
+                obj.handle_input(
+                    &DetectionInput::RfidRead {
+                        id: id.to_string(),
+                        reader: Some(rfid_reader.ip_addr()),
+                    },
+                    None,
+                );
+            }
+        ));
+    }
+
+    pub fn bind_mqtt_client(&self, mqtt_client: &MqttClient) {
+        mqtt_client.connect_message_received(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |mqtt_client, topic, payload| {
+                let Some(reader) = mqtt_client.detect_reader(topic) else {
+                    return;
+                };
+
+                obj.handle_input(
+                    &DetectionInput::MqttMessage {
+                        payload: payload.to_string(),
+                        reader: reader.map(|reader| reader.to_string()),
+                    },
+                    None,
+                );
             }
         ));
     }
 
+    pub fn bind_local_readers(&self, readers: &[LocalReader]) {
+        let imp = self.imp();
+
+        for reader in readers {
+            let handler_id = reader.connect_detected(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |reader, id| {
+                    obj.handle_input(
+                        &DetectionInput::LocalRead {
+                            id: id.to_string(),
+                            reader: Some(reader.spec().path.clone()),
+                        },
+                        None,
+                    );
+                }
+            ));
+            imp.local_readers
+                .borrow_mut()
+                .push((reader.clone(), handler_id));
+        }
+
+        tracing::debug!(
+            readers = ?readers.iter().map(|r| &r.spec().path).collect::<Vec<_>>(),
+            "Bound local readers"
+        );
+    }
+
+    pub fn unbind_local_readers(&self) {
+        let imp = self.imp();
+
+        for (reader, handler_id) in imp.local_readers.take() {
+            reader.disconnect(handler_id);
+        }
+    }
+
     pub fn simulate_detected(&self, id: &EntityId, data: Option<&EntityData>) {
-        self.emit_detected(id, data);
+        if let Some(data) = data {
+            self.record_entity_data(id, data);
+        }
+        self.record(&DetectionInput::Simulated { id: id.to_string() });
+
+        self.emit_detected(id, data, None);
+    }
+
+    /// Handles a raw input as if it came from a device, e.g., when replaying
+    /// a recorded trace.
+    ///
+    /// Motion is handled without a captured image.
+    pub fn replay_input(&self, input: &DetectionInput) {
+        self.handle_input(input, None);
+    }
+
+    /// Fires the timeouts that are due according to the clock.
+    ///
+    /// This must be called after advancing a manual clock, as timeouts only
+    /// run on their own with the system clock.
+    pub fn fire_due_timeouts(&self) {
+        let imp = self.imp();
+
+        if imp
+            .detected_wo_id_alert_deadline
+            .get()
+            .is_some_and(|deadline| deadline <= self.clock().now())
+        {
+            imp.detected_wo_id_alert_deadline.set(None);
+
+            self.detected_wo_id_alert();
+        }
+    }
+
+    /// Returns when the next timeout is due, if any.
+    pub fn next_timeout_dt(&self) -> Option<DateTime<Utc>> {
+        self.imp().detected_wo_id_alert_deadline.get()
+    }
+
+    pub fn set_clock(&self, clock: Clock) {
+        self.imp().clock.replace(clock);
+    }
+
+    /// Starts writing all raw inputs to a trace file at the given path,
+    /// replacing the current recording, if any.
+    pub fn start_recording(&self, path: &Path) -> Result<()> {
+        let writer = DetectionTraceWriter::create(path)?;
+        self.imp().trace_writer.replace(Some(writer));
+
+        tracing::debug!(?path, "Started recording detections");
+
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) {
+        if self.imp().trace_writer.take().is_some() {
+            tracing::debug!("Stopped recording detections");
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.imp().trace_writer.borrow().is_some()
+    }
+
+    /// Records the data of an entity that was unknown when it was read, so
+    /// its later reads replay the same way.
+    pub fn record_entity_data(&self, id: &EntityId, data: &EntityData) {
+        self.with_trace_writer(|writer| writer.write_entity(id, data.clone()));
+    }
+
+    /// Sets the window within which repeated reads of the same entity from
+    /// the source are ignored.
+    pub fn set_debounce_window(&self, source: DetectionSource, window: Duration) {
+        self.imp()
+            .debouncers
+            .borrow_mut()
+            .entry(source)
+            .and_modify(|debouncer| debouncer.set_window(window))
+            .or_insert_with(|| Debouncer::new(window));
+    }
+
+    pub fn set_code_parser_registry(&self, registry: CodeParserRegistry) {
+        self.imp().code_parser_registry.replace(registry);
     }
 
     pub fn set_enable_detection_wo_id(&self, is_enabled: bool) {
         let imp = self.imp();
 
+        imp.is_detection_wo_id_enabled.set(is_enabled);
+
+        if !is_enabled {
+            self.stop_detected_wo_id_alert_timeout();
+        }
+
+        self.update_motion_detection();
+    }
+
+    pub fn set_enable_tailgating_detection(&self, is_enabled: bool) {
+        let imp = self.imp();
+
+        imp.is_tailgating_detection_enabled.set(is_enabled);
+
+        if !is_enabled {
+            imp.tailgating_tracker.borrow_mut().reset();
+        }
+
+        self.update_motion_detection();
+    }
+
+    fn update_motion_detection(&self) {
+        let imp = self.imp();
+
+        let is_enabled =
+            imp.is_detection_wo_id_enabled.get() || imp.is_tailgating_detection_enabled.get();
+
         if let Some(camera) = imp.camera.borrow().as_ref() {
             camera.set_enable_motion_detection(is_enabled);
         }
@@ -186,73 +403,252 @@ impl Detector {
         }
     }
 
-    fn emit_detected(&self, id: &EntityId, data: Option<&EntityData>) {
-        self.emit_by_name::<()>("detected", &[id, &data]);
+    /// `zone` is the zone of the reader, if it belongs to one.
+    fn emit_detected(&self, id: &EntityId, data: Option<&EntityData>, zone: Option<String>) {
+        self.emit_by_name::<()>("detected", &[id, &data, &zone]);
+
+        if self.imp().is_tailgating_detection_enabled.get() {
+            self.register_tailgating_read();
+        }
 
         self.stop_detected_wo_id_alert_timeout();
     }
 
+    fn clock(&self) -> Clock {
+        self.imp().clock.borrow().clone()
+    }
+
+    fn handle_input(&self, input: &DetectionInput, camera: Option<&Camera>) {
+        self.record(input);
+
+        match input {
+            DetectionInput::RfidRead { id, reader } => {
+                self.handle_id_read(DetectionSource::RfidReader, reader.as_deref(), id);
+            }
+            DetectionInput::LocalRead { id, reader } => {
+                self.handle_id_read(DetectionSource::LocalReader, reader.as_deref(), id);
+            }
+            DetectionInput::MqttMessage { payload, reader } => {
+                let id = payload.trim();
+                if id.is_empty() {
+                    self.emit_by_name::<()>("detected-invalid", &[&payload]);
+                    return;
+                }
+
+                self.handle_id_read(DetectionSource::Mqtt, reader.as_deref(), id);
+            }
+            DetectionInput::Code { code, reader } => {
+                self.handle_code(reader.as_deref(), code);
+            }
+            DetectionInput::Motion => {
+                self.handle_motion(camera);
+            }
+            DetectionInput::Simulated { id } => {
+                self.emit_detected(&EntityId::new(id.as_str()), None, None);
+            }
+        }
+    }
+
+    fn record(&self, input: &DetectionInput) {
+        let now = self.clock().now();
+        self.with_trace_writer(|writer| {
+            writer.write(now, input, |id| {
+                Application::get()
+                    .timeline()
+                    .entity_list()
+                    .get(id)
+                    .map(|entity| entity.data().clone())
+            })
+        });
+    }
+
+    fn with_trace_writer(&self, func: impl FnOnce(&mut DetectionTraceWriter) -> Result<()>) {
+        let imp = self.imp();
+
+        let mut trace_writer = imp.trace_writer.borrow_mut();
+        let Some(writer) = trace_writer.as_mut() else {
+            return;
+        };
+
+        if let Err(err) = func(writer) {
+            tracing::error!("Failed to record detection, stopping: {:?}", err);
+            trace_writer.take();
+        }
+    }
+
+    /// Handles an ID read at a reader, where `reader` identifies it among
+    /// the ones of its type, e.g., the device path of a local reader.
+    fn handle_id_read(&self, source: DetectionSource, reader: Option<&str>, id: &str) {
+        if !self.debounce(source, id) {
+            return;
+        }
+
+        let entity_id = EntityId::new(id);
+        let zone = Application::get()
+            .settings()
+            .zone_for_reader(source, reader);
+        self.emit_detected(&entity_id, None, zone);
+    }
+
+    fn handle_code(&self, camera: Option<&str>, code: &str) {
+        // Keyed by the code itself, which is cheaper than parsing it on
+        // every frame, and also keeps invalid codes from being reported
+        // repeatedly.
+        if !self.debounce(DetectionSource::Camera, code) {
+            return;
+        }
+
+        tracing::debug!("Detected code: {}", code);
+
+        if let Some((id, data)) = self.entity_from_qrcode(code) {
+            let zone = Application::get()
+                .settings()
+                .zone_for_reader(DetectionSource::Camera, camera);
+            self.emit_detected(&id, Some(&data), zone);
+        } else {
+            self.emit_by_name::<()>("detected-invalid", &[&code]);
+        }
+    }
+
+    /// Handles motion, capturing an image from the camera, if any, when it
+    /// is not covered by an ID read.
+    fn handle_motion(&self, camera: Option<&Camera>) {
+        let imp = self.imp();
+
+        let now = self.clock().now();
+
+        let attribution = self.attribute_motion();
+        if attribution == MotionAttribution::Read
+            || (attribution == MotionAttribution::Unattributed
+                && !imp.is_detection_wo_id_enabled.get())
+        {
+            return;
+        }
+
+        let Some(camera) = camera else {
+            self.handle_unattributed_motion(attribution, now, None);
+            return;
+        };
+
+        glib::spawn_future_local(clone!(
+            #[strong(rename_to = obj)]
+            self,
+            #[strong]
+            camera,
+            async move {
+                let image = camera
+                    .capture_jpeg()
+                    .await
+                    .inspect_err(|err| tracing::warn!("Failed to capture image: {:?}", err))
+                    .ok();
+
+                obj.handle_unattributed_motion(attribution, now, image);
+            }
+        ));
+    }
+
+    fn handle_unattributed_motion(
+        &self,
+        attribution: MotionAttribution,
+        dt: DateTime<Utc>,
+        image: Option<JpegImage>,
+    ) {
+        let imp = self.imp();
+
+        if attribution == MotionAttribution::Tailgating {
+            self.emit_by_name::<()>("tailgating-detected", &[&DateTimeBoxed(dt), &image]);
+            return;
+        }
+
+        imp.detected_wo_id_capture
+            .replace(Some((DateTimeBoxed(dt), image)));
+
+        if imp.detected_wo_id_alert_deadline.get().is_some() {
+            self.detected_wo_id_alert();
+        } else {
+            self.start_detected_wo_id_alert_timeout();
+        }
+    }
+
+    /// Returns whether the read should be handled.
+    fn debounce(&self, source: DetectionSource, key: &str) -> bool {
+        let now = self.clock().instant();
+        let is_handled = self
+            .imp()
+            .debouncers
+            .borrow_mut()
+            .get_mut(&source)
+            .map_or(true, |debouncer| debouncer.check(key.to_string(), now));
+
+        if !is_handled {
+            tracing::trace!(?source, "Ignored repeated read of `{}`", key);
+        }
+
+        is_handled
+    }
+
+    fn entity_from_qrcode(&self, code: &str) -> Option<(EntityId, EntityData)> {
+        let app = Application::get();
+        let settings = app.settings();
+
+        if signed_qr::is_signed(code) {
+            return signed_qr::verify(code, &settings.qr_signing_key(), self.clock().now())
+                .inspect_err(|err| tracing::warn!("Rejected signed code: {:?}", err))
+                .ok();
+        }
+
+        if settings.require_signed_qr_codes() {
+            tracing::debug!("Rejected unsigned code as signed codes are required");
+
+            return None;
+        }
+
+        self.imp()
+            .code_parser_registry
+            .borrow()
+            .parse(code, settings.operation_mode())
+    }
+
+    /// Opens or extends the tailgating window for an ID read.
+    ///
+    /// This must be called before the pending detected without ID capture
+    /// is cleared, as that motion belongs to the entity that was just read.
+    fn register_tailgating_read(&self) {
+        let imp = self.imp();
+
+        let has_pending_motion = imp.detected_wo_id_capture.borrow().is_some();
+        imp.tailgating_tracker
+            .borrow_mut()
+            .register_read(self.clock().instant(), has_pending_motion);
+    }
+
+    fn attribute_motion(&self) -> MotionAttribution {
+        self.imp()
+            .tailgating_tracker
+            .borrow_mut()
+            .attribute_motion(self.clock().instant())
+    }
+
     fn bind_camera_inner(&self, camera: &Camera) -> Vec<glib::SignalHandlerId> {
         let handler_ids = vec![
             camera.connect_code_detected(clone!(
                 #[weak(rename_to = obj)]
                 self,
-                move |_, code| {
-                    let imp = obj.imp();
-
-                    if imp
-                        .camera_last_detected
-                        .borrow()
-                        .as_ref()
-                        .is_some_and(|last_detected| last_detected == code)
-                    {
-                        return;
-                    }
-
-                    tracing::debug!("Detected code: {}", code);
-
-                    if let Some((id, data)) = entity_from_qrcode(code) {
-                        obj.emit_detected(&id, Some(&data));
-                    } else {
-                        obj.emit_by_name::<()>("detected-invalid", &[&code]);
-                    }
-
-                    imp.camera_last_detected.replace(Some(code.to_string()));
-                    obj.restart_camera_last_detected_reset_timeout();
+                move |camera, code| {
+                    obj.handle_input(
+                        &DetectionInput::Code {
+                            code: code.to_string(),
+                            reader: Some(camera.ip_addr()),
+                        },
+                        None,
+                    );
                 }
             )),
             camera.connect_motion_detected(clone!(
                 #[weak(rename_to = obj)]
                 self,
                 move |camera| {
-                    glib::spawn_future_local(clone!(
-                        #[strong]
-                        obj,
-                        #[strong]
-                        camera,
-                        async move {
-                            let imp = obj.imp();
-
-                            let now = Utc::now();
-
-                            let image = camera
-                                .capture_jpeg()
-                                .await
-                                .inspect_err(|err| {
-                                    tracing::warn!("Failed to capture image: {:?}", err)
-                                })
-                                .ok();
-
-                            imp.detected_wo_id_capture
-                                .replace(Some((DateTimeBoxed(now), image)));
-
-                            if imp.detected_wo_id_alert_timeout.borrow().is_some() {
-                                obj.detected_wo_id_alert();
-                            } else {
-                                obj.start_detected_wo_id_alert_timeout();
-                            }
-                        }
-                    ));
+                    obj.handle_input(&DetectionInput::Motion, Some(camera));
                 }
             )),
         ];
@@ -267,6 +663,16 @@ impl Detector {
     fn start_detected_wo_id_alert_timeout(&self) {
         let imp = self.imp();
 
+        let clock = self.clock();
+        imp.detected_wo_id_alert_deadline
+            .set(Some(clock.now() + DETECTED_WO_ID_ALERT_DELAY));
+
+        // Manual clocks don't follow real time, so these are fired through
+        // `fire_due_timeouts` instead.
+        if matches!(clock, Clock::Manual(_)) {
+            return;
+        }
+
         let source_id = glib::timeout_add_local_once(
             DETECTED_WO_ID_ALERT_DELAY,
             clone!(
@@ -275,6 +681,7 @@ impl Detector {
                 move || {
                     let imp = obj.imp();
                     imp.detected_wo_id_alert_timeout.replace(None);
+                    imp.detected_wo_id_alert_deadline.set(None);
 
                     obj.detected_wo_id_alert();
                 },
@@ -289,6 +696,7 @@ impl Detector {
         if let Some(source_id) = imp.detected_wo_id_alert_timeout.take() {
             source_id.remove();
         }
+        imp.detected_wo_id_alert_deadline.set(None);
 
         imp.detected_wo_id_capture.replace(None);
     }
@@ -302,30 +710,6 @@ impl Detector {
             tracing::warn!("No detected without ID capture data");
         }
     }
-
-    fn restart_camera_last_detected_reset_timeout(&self) {
-        let imp = self.imp();
-
-        if let Some(source_id) = imp.camera_last_detected_reset_timeout.take() {
-            source_id.remove();
-        }
-
-        let source_id = glib::timeout_add_local_once(
-            CAMERA_LAST_DETECTED_RESET_DELAY,
-            clone!(
-                #[weak(rename_to = obj)]
-                self,
-                move || {
-                    let imp = obj.imp();
-                    imp.camera_last_detected_reset_timeout.replace(None);
-
-                    imp.camera_last_detected.replace(None);
-                },
-            ),
-        );
-        imp.camera_last_detected_reset_timeout
-            .replace(Some(source_id));
-    }
 }
 
 impl Default for Detector {
@@ -333,100 +717,3 @@ impl Default for Detector {
         Self::new()
     }
 }
-
-fn entity_from_qrcode(code: &str) -> Option<(EntityId, EntityData)> {
-    entity_from_national_id(code).or_else(|| entity_from_qrifying_cea(code))
-}
-
-fn entity_from_qrifying_cea(code: &str) -> Option<(EntityId, EntityData)> {
-    if !Application::get()
-        .settings()
-        .operation_mode()
-        .is_for_person()
-    {
-        tracing::trace!("Operation mode is not for person");
-
-        return None;
-    }
-
-    let mut substrings = code.splitn(4, '_');
-    let name = substrings.next()?;
-    let student_id = substrings.next()?;
-    let bpsu_email = substrings.next()?;
-    let program = substrings.next()?;
-
-    Some((
-        EntityId::new(student_id),
-        EntityData::from_fields([
-            EntityDataField::Name(name.to_string()),
-            EntityDataField::Email(bpsu_email.to_string()),
-            EntityDataField::Program(program.to_string()),
-        ]),
-    ))
-}
-
-fn entity_from_national_id(code: &str) -> Option<(EntityId, EntityData)> {
-    if !Application::get()
-        .settings()
-        .operation_mode()
-        .is_for_person()
-    {
-        tracing::trace!("Operation mode is not for person");
-
-        return None;
-    }
-
-    #[derive(Serialize, Deserialize)]
-    pub struct Subject {
-        #[serde(rename = "lName")]
-        last_name: String,
-        #[serde(rename = "fName")]
-        first_name: String,
-        #[serde(rename = "mName")]
-        middle_name: String,
-        #[serde(rename = "sex")]
-        sex: String,
-        #[serde(rename = "DOB")]
-        date_of_birth: String,
-        #[serde(rename = "POB")]
-        place_of_birth: String,
-        #[serde(rename = "PCN")]
-        pcn: String,
-    }
-
-    #[derive(Serialize, Deserialize)]
-    pub struct Data {
-        #[serde(rename = "DateIssued")]
-        date_issued: String,
-        #[serde(rename = "Issuer")]
-        issuer: String,
-        #[serde(rename = "subject")]
-        subject: Subject,
-    }
-
-    let data = serde_json::from_str::<Data>(code)
-        .inspect_err(|err| tracing::debug!("Failed to deserialize national id data: {:?}", err))
-        .ok()?;
-
-    let mut fields = vec![EntityDataField::Name(format!(
-        "{}, {} {}",
-        case::to_title_case(&data.subject.last_name),
-        case::to_title_case(&data.subject.first_name),
-        data.subject
-            .middle_name
-            .chars()
-            .next()
-            .map(|c| format!("{}.", c.to_uppercase()))
-            .unwrap_or_default(),
-    ))];
-
-    match data.subject.sex.parse::<Sex>() {
-        Ok(sex) => fields.push(EntityDataField::Sex(sex)),
-        Err(err) => tracing::warn!("Failed to parse sex: {:?}", err),
-    }
-
-    Some((
-        EntityId::new(data.subject.pcn),
-        EntityData::from_fields(fields),
-    ))
-}
@@ -3,8 +3,8 @@ use gtk::{glib, prelude::*, subclass::prelude::*};
 
 use crate::{
     date_time, date_time_range::DateTimeRange, entity_data::EntityData, entity_id::EntityId,
-    format, log::Log, settings::OperationMode, stock_id::StockId,
-    timeline_item_kind::TimelineItemKind,
+    format, location_id::LocationId, log::Log, settings::OperationMode, stock_id::StockId,
+    timeline_item_kind::TimelineItemKind, zone,
 };
 
 mod imp {
@@ -22,10 +22,18 @@ mod imp {
         pub(super) data: RefCell<EntityData>,
         #[property(get = Self::is_inside)]
         pub(super) is_inside: PhantomData<bool>,
+        /// Zones the entity went through since it entered, e.g., "Gate → Library".
+        #[property(get = Self::path)]
+        pub(super) path: PhantomData<String>,
 
         pub(super) id: OnceCell<EntityId>,
 
         pub(super) action_log: RefCell<Log<TimelineItemKind>>,
+        /// Where the entity was moved to. Empty if it was never moved.
+        pub(super) location_log: RefCell<Log<Option<LocationId>>>,
+        /// Zones the entity went through since it entered, the current one
+        /// last. Empty while outside.
+        pub(super) zone_path_log: RefCell<Log<Vec<Option<String>>>>,
     }
 
     #[glib::object_subclass]
@@ -55,6 +63,10 @@ mod imp {
                 .latest()
                 .is_some_and(|kind| kind.is_entry())
         }
+
+        fn path(&self) -> String {
+            zone::format_path(&self.obj().zone_path())
+        }
     }
 }
 
@@ -82,6 +94,56 @@ impl Entity {
         self.imp().data.borrow().stock_id().cloned()
     }
 
+    pub fn location(&self) -> Option<LocationId> {
+        self.imp()
+            .data
+            .borrow()
+            .location()
+            .and_then(|location| LocationId::parse(location))
+    }
+
+    pub fn location_for_dt(&self, dt: DateTime<Utc>) -> Option<LocationId> {
+        match self.imp().location_log.borrow().for_dt(dt) {
+            Some(location) => location.clone(),
+            None => self.location(),
+        }
+    }
+
+    pub fn location_for_dt_range(&self, dt_range: &DateTimeRange) -> Option<LocationId> {
+        if let Some(end) = dt_range.end {
+            self.location_for_dt(end)
+        } else {
+            self.location()
+        }
+    }
+
+    pub fn zone_path(&self) -> Vec<Option<String>> {
+        self.imp()
+            .zone_path_log
+            .borrow()
+            .latest()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn zone_path_for_dt_range(&self, dt_range: &DateTimeRange) -> Vec<Option<String>> {
+        if let Some(end) = dt_range.end {
+            self.imp()
+                .zone_path_log
+                .borrow()
+                .for_dt(end)
+                .cloned()
+                .unwrap_or_default()
+        } else {
+            self.zone_path()
+        }
+    }
+
+    /// Returns when the entity got into its current zone.
+    pub fn zone_entered_dt(&self) -> Option<DateTime<Utc>> {
+        self.imp().zone_path_log.borrow().latest_dt()
+    }
+
     pub fn is_inside_for_dt(&self, dt: DateTime<Utc>) -> bool {
         self.imp()
             .action_log
@@ -98,19 +160,23 @@ impl Entity {
         }
     }
 
+    pub fn action_for_dt(&self, dt: DateTime<Utc>) -> Option<(DateTime<Utc>, TimelineItemKind)> {
+        self.imp()
+            .action_log
+            .borrow()
+            .for_dt_full(dt)
+            .map(|(dt, kind)| (dt, *kind))
+    }
+
     pub fn action_for_dt_range(
         &self,
         dt_range: &DateTimeRange,
     ) -> Option<(DateTime<Utc>, TimelineItemKind)> {
-        let imp = self.imp();
-
         if let Some(end) = dt_range.end {
-            imp.action_log
-                .borrow()
-                .for_dt_full(end)
-                .map(|(dt, kind)| (dt, *kind))
+            self.action_for_dt(end)
         } else {
-            imp.action_log
+            self.imp()
+                .action_log
                 .borrow()
                 .latest_full()
                 .map(|(dt, kind)| (dt, *kind))
@@ -131,6 +197,20 @@ impl Entity {
         }
     }
 
+    pub fn with_location_log_mut(&self, f: impl FnOnce(&mut Log<Option<LocationId>>)) {
+        f(&mut self.imp().location_log.borrow_mut());
+    }
+
+    pub fn with_zone_path_log_mut(&self, f: impl FnOnce(&mut Log<Vec<Option<String>>>)) {
+        let prev_zone_path = self.zone_path();
+
+        f(&mut self.imp().zone_path_log.borrow_mut());
+
+        if prev_zone_path != self.zone_path() {
+            self.notify_path();
+        }
+    }
+
     pub fn status_text(
         &self,
         for_dt_range: &DateTimeRange,
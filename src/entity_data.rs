@@ -6,7 +6,12 @@ use indexmap::IndexMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
-    date_time_range::DateTimeRange, jpeg_image::JpegImage, settings::OperationMode, sex::Sex,
+    custom_field::{CustomFieldValue, CustomFields},
+    date_time_range::DateTimeRange,
+    jpeg_image::JpegImage,
+    quantity::Quantity,
+    settings::OperationMode,
+    sex::Sex,
     stock_id::StockId,
 };
 
@@ -58,12 +63,15 @@ entity_data_field! {
     StockId(StockId) => "Stock Name",
     Location(String) => "Location",
     ExpirationDt(DateTime<Utc>) => "Expiration Date",
+    Lot(String) => "Lot",
+    Quantity(Quantity) => "Quantity",
     AllowedDtRange(DateTimeRange) => "Allowed Date Range",
     Photo(JpegImage) => "Photo",
     Name(String) => "Name",
     Sex(Sex) => "Sex",
     Email(String) => "Email",
-    Program(String) => "Program"
+    Program(String) => "Program",
+    Custom(CustomFields) => "Custom Fields"
 }
 
 macro_rules! entity_data_getter {
@@ -114,12 +122,28 @@ impl EntityData {
     entity_data_getter!(stock_id, StockId, &StockId);
     entity_data_getter!(location, Location, &String);
     entity_data_getter!(expiration_dt, ExpirationDt, &DateTime<Utc>);
+    entity_data_getter!(lot, Lot, &String);
+    entity_data_getter!(quantity, Quantity, &Quantity);
     entity_data_getter!(allowed_dt_range, AllowedDtRange, &DateTimeRange);
     entity_data_getter!(photo, Photo, &JpegImage);
     entity_data_getter!(name, Name, &String);
     entity_data_getter!(sex, Sex, &Sex);
     entity_data_getter!(email, Email, &String);
     entity_data_getter!(program, Program, &String);
+    entity_data_getter!(custom_fields, Custom, &CustomFields);
+
+    pub fn with_quantity(self, quantity: Option<Quantity>) -> Self {
+        Self::from_fields(
+            self.0
+                .into_values()
+                .filter(|f| f.ty() != EntityDataFieldTy::Quantity)
+                .chain(quantity.map(EntityDataField::Quantity)),
+        )
+    }
+
+    pub fn custom_value(&self, key: &str) -> Option<&CustomFieldValue> {
+        self.custom_fields().and_then(|fields| fields.get(key))
+    }
 }
 
 impl Serialize for EntityData {
@@ -155,6 +179,7 @@ impl ValidEntityFields {
             f!(EntityDataFieldTy::Sex),
             f!(EntityDataFieldTy::Email),
             f!(EntityDataFieldTy::Program),
+            f!(EntityDataFieldTy::Custom),
         ];
         Self(match operation_mode {
             OperationMode::Counter => person_valid_entity_fields,
@@ -163,19 +188,26 @@ impl ValidEntityFields {
                 f!(EntityDataFieldTy::AllowedDtRange),
                 f!(EntityDataFieldTy::Photo),
                 f!(EntityDataFieldTy::Location),
+                f!(EntityDataFieldTy::Custom),
             ],
             OperationMode::Inventory => &[
                 f!(req EntityDataFieldTy::StockId),
                 f!(EntityDataFieldTy::Location),
                 f!(EntityDataFieldTy::ExpirationDt),
+                f!(EntityDataFieldTy::Lot),
+                f!(EntityDataFieldTy::Quantity),
                 f!(EntityDataFieldTy::AllowedDtRange),
                 f!(EntityDataFieldTy::Photo),
+                f!(EntityDataFieldTy::Custom),
             ],
             OperationMode::Refrigerator => &[
                 f!(req EntityDataFieldTy::StockId),
                 f!(EntityDataFieldTy::ExpirationDt),
+                f!(EntityDataFieldTy::Lot),
+                f!(EntityDataFieldTy::Quantity),
                 f!(EntityDataFieldTy::AllowedDtRange),
                 f!(EntityDataFieldTy::Photo),
+                f!(EntityDataFieldTy::Custom),
             ],
         })
     }
@@ -1,20 +1,27 @@
 use std::{collections::HashSet, time::Duration};
 
-use chrono::{TimeDelta, Utc};
+use chrono::TimeDelta;
 use gtk::{
     glib::{self, clone, closure_local},
     prelude::*,
     subclass::prelude::*,
 };
 
-use crate::{application::Application, entity_id::EntityId, settings::Settings};
+use crate::{
+    application::Application, clock::Clock, entity_id::EntityId, entity_list::EntityList,
+    settings::Settings,
+};
 
 #[derive(Clone, glib::Boxed)]
 #[boxed_type(name = "UetsOverstayed")]
 pub struct EntityIdSet(pub HashSet<EntityId>);
 
 mod imp {
-    use std::{cell::RefCell, marker::PhantomData, sync::OnceLock};
+    use std::{
+        cell::{OnceCell, RefCell},
+        marker::PhantomData,
+        sync::OnceLock,
+    };
 
     use glib::subclass::Signal;
 
@@ -32,6 +39,9 @@ mod imp {
         pub(crate) emitted_overstayed: RefCell<HashSet<EntityId>>,
 
         pub(crate) check_overstayed_timeout_id: RefCell<Option<glib::SourceId>>,
+
+        pub(super) entity_list: OnceCell<EntityList>,
+        pub(super) clock: RefCell<Clock>,
     }
 
     #[glib::object_subclass]
@@ -121,6 +131,14 @@ impl EntityEntryTracker {
         )
     }
 
+    pub fn bind_entity_list(&self, entity_list: &EntityList) {
+        self.imp().entity_list.set(entity_list.clone()).unwrap();
+    }
+
+    pub fn set_clock(&self, clock: Clock) {
+        self.imp().clock.replace(clock);
+    }
+
     pub fn handle_entry(&self, entity_id: &EntityId) {
         let imp = self.imp();
 
@@ -149,14 +167,20 @@ impl EntityEntryTracker {
         self.notify_n_overstayed();
     }
 
-    fn check_overstayed(&self) {
+    /// Checks for entities that overstayed according to the clock.
+    ///
+    /// This is also done periodically, but must be called after advancing a
+    /// manual clock.
+    pub fn check_overstayed(&self) {
         let imp = self.imp();
 
         let app = Application::get();
         let settings = app.settings();
 
-        let entity_list = app.timeline().entity_list();
-        let dt_now = Utc::now();
+        let Some(entity_list) = imp.entity_list.get() else {
+            return;
+        };
+        let dt_now = imp.clock.borrow().now();
 
         let mut overstayed_entities = HashSet::new();
         for entity_id in imp.inside_entities.borrow().iter() {
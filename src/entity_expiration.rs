@@ -1,9 +1,8 @@
 use chrono::{DateTime, Duration, Utc};
 
-use crate::entity::Entity;
-
-const NEARLY_EXPIRING_ALLOWANCE: Duration = Duration::weeks(1);
+use crate::{entity::Entity, settings::Settings, stock_data::StockData, stock_list::StockList};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EntityExpiration {
     NotExpiring,
     Expiring,
@@ -11,12 +10,14 @@ pub enum EntityExpiration {
 }
 
 impl EntityExpiration {
-    pub fn for_expiration_dt(expiration_dt: DateTime<Utc>) -> Option<Self> {
-        let now = Utc::now();
-
+    pub fn for_expiration_dt(
+        expiration_dt: DateTime<Utc>,
+        now: DateTime<Utc>,
+        expiring_soon_window: Duration,
+    ) -> Option<Self> {
         let expiration = if expiration_dt < now {
             EntityExpiration::Expired
-        } else if expiration_dt - now < NEARLY_EXPIRING_ALLOWANCE {
+        } else if expiration_dt - now < expiring_soon_window {
             EntityExpiration::Expiring
         } else {
             EntityExpiration::NotExpiring
@@ -25,13 +26,96 @@ impl EntityExpiration {
     }
 }
 
+pub trait EntityExpirationSettingsExt {
+    /// Returns how long before expiration entities of a stock with the given
+    /// data are expiring soon, falling back to the global window.
+    fn expiring_soon_window(&self, stock_data: Option<&StockData>) -> Duration;
+}
+
+impl EntityExpirationSettingsExt for Settings {
+    fn expiring_soon_window(&self, stock_data: Option<&StockData>) -> Duration {
+        let days = stock_data
+            .and_then(|data| data.expiring_soon_window_days)
+            .unwrap_or_else(|| self.expiring_soon_window_days());
+        Duration::days(days.into())
+    }
+}
+
 pub trait EntityExpirationEntityExt {
-    fn expiration(&self) -> Option<EntityExpiration>;
+    /// Returns the expiration of the entity, using the expiring soon window
+    /// of its stock in the list.
+    fn expiration(
+        &self,
+        now: DateTime<Utc>,
+        settings: &Settings,
+        stock_list: &StockList,
+    ) -> Option<EntityExpiration>;
 }
 
 impl EntityExpirationEntityExt for Entity {
-    fn expiration(&self) -> Option<EntityExpiration> {
+    fn expiration(
+        &self,
+        now: DateTime<Utc>,
+        settings: &Settings,
+        stock_list: &StockList,
+    ) -> Option<EntityExpiration> {
         let expiration_dt = *self.data().expiration_dt()?;
-        EntityExpiration::for_expiration_dt(expiration_dt)
+        let stock_data = self
+            .stock_id()
+            .and_then(|stock_id| stock_list.get(&stock_id))
+            .map(|stock| stock.data());
+        let expiring_soon_window = settings.expiring_soon_window(stock_data.as_ref());
+        EntityExpiration::for_expiration_dt(expiration_dt, now, expiring_soon_window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_expiration_dt() {
+        let now = DateTime::parse_from_rfc3339("2024-11-03T01:00:00Z")
+            .unwrap()
+            .to_utc();
+
+        let window = Duration::weeks(1);
+
+        assert!(matches!(
+            EntityExpiration::for_expiration_dt(now - Duration::seconds(1), now, window),
+            Some(EntityExpiration::Expired)
+        ));
+        assert!(matches!(
+            EntityExpiration::for_expiration_dt(now + Duration::days(6), now, window),
+            Some(EntityExpiration::Expiring)
+        ));
+        assert!(matches!(
+            EntityExpiration::for_expiration_dt(now + Duration::weeks(2), now, window),
+            Some(EntityExpiration::NotExpiring)
+        ));
+    }
+
+    #[test]
+    fn for_expiration_dt_custom_window() {
+        let now = DateTime::parse_from_rfc3339("2024-11-03T01:00:00Z")
+            .unwrap()
+            .to_utc();
+
+        assert!(matches!(
+            EntityExpiration::for_expiration_dt(now + Duration::days(6), now, Duration::days(2)),
+            Some(EntityExpiration::NotExpiring)
+        ));
+        assert!(matches!(
+            EntityExpiration::for_expiration_dt(now + Duration::days(20), now, Duration::days(30)),
+            Some(EntityExpiration::Expiring)
+        ));
+        assert!(matches!(
+            EntityExpiration::for_expiration_dt(now + Duration::days(1), now, Duration::zero()),
+            Some(EntityExpiration::NotExpiring)
+        ));
+        assert!(matches!(
+            EntityExpiration::for_expiration_dt(now - Duration::seconds(1), now, Duration::zero()),
+            Some(EntityExpiration::Expired)
+        ));
     }
 }
@@ -1,26 +1,52 @@
+use std::{collections::HashSet, time::Duration};
+
 use gtk::{
-    glib::{self, clone},
+    glib::{self, clone, closure_local},
     prelude::*,
     subclass::prelude::*,
 };
 
 use crate::{
+    clock::Clock,
+    entity_entry_tracker::EntityIdSet,
     entity_expiration::{EntityExpiration, EntityExpirationEntityExt},
+    entity_id::EntityId,
     entity_list::EntityList,
+    stock_list::StockList,
+    Application,
 };
 
+/// How often to check for entities that crossed into expiring soon or
+/// expired as time passed.
+const CHECK_EXPIRATION_INTERVAL: Duration = Duration::from_secs(60);
+
 mod imp {
-    use std::cell::{Cell, OnceCell};
+    use std::{
+        cell::{Cell, OnceCell, RefCell},
+        sync::OnceLock,
+    };
+
+    use glib::subclass::Signal;
 
     use super::*;
 
     #[derive(Default, glib::Properties)]
     #[properties(wrapper_type = super::EntityExpiredTracker)]
     pub struct EntityExpiredTracker {
+        #[property(get)]
+        pub(super) n_expiring: Cell<u32>,
         #[property(get)]
         pub(super) n_expired: Cell<u32>,
 
+        pub(super) expiring_entities: RefCell<HashSet<EntityId>>,
+        pub(super) expired_entities: RefCell<HashSet<EntityId>>,
+
+        pub(super) check_expiration_timeout_id: RefCell<Option<glib::SourceId>>,
+
         pub(super) entity_list: OnceCell<EntityList>,
+        pub(super) stock_list: OnceCell<StockList>,
+
+        pub(super) clock: RefCell<Clock>,
     }
 
     #[glib::object_subclass]
@@ -36,7 +62,54 @@ mod imp {
 
             let obj = self.obj();
 
-            obj.update_n_expired();
+            Application::get()
+                .settings()
+                .connect_expiring_soon_window_days_changed(clone!(
+                    #[weak(rename_to = obj)]
+                    obj,
+                    move |_| {
+                        obj.check_expiration();
+                    }
+                ));
+
+            let source_id = glib::timeout_add_local_full(
+                CHECK_EXPIRATION_INTERVAL,
+                glib::Priority::LOW,
+                clone!(
+                    #[weak(rename_to = obj)]
+                    obj,
+                    #[upgrade_or_panic]
+                    move || {
+                        obj.check_expiration();
+
+                        glib::ControlFlow::Continue
+                    }
+                ),
+            );
+            self.check_expiration_timeout_id.replace(Some(source_id));
+
+            obj.check_expiration();
+        }
+
+        fn dispose(&self) {
+            if let Some(source_id) = self.check_expiration_timeout_id.take() {
+                source_id.remove();
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+
+            SIGNALS.get_or_init(|| {
+                vec![
+                    Signal::builder("expiring")
+                        .param_types([EntityIdSet::static_type()])
+                        .build(),
+                    Signal::builder("expired")
+                        .param_types([EntityIdSet::static_type()])
+                        .build(),
+                ]
+            })
         }
     }
 }
@@ -50,46 +123,143 @@ impl EntityExpiredTracker {
         glib::Object::new()
     }
 
-    pub fn bind_entity_list(&self, entity_list: &EntityList) {
+    /// Emitted with the entities that became expiring soon since the last
+    /// check.
+    ///
+    /// This is not emitted for entities that are already expiring soon when
+    /// the lists are bound, nor for entities that became expired directly.
+    pub fn connect_expiring<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &EntityIdSet) + 'static,
+    {
+        self.connect_closure(
+            "expiring",
+            false,
+            closure_local!(|obj: &Self, ids: &EntityIdSet| f(obj, ids)),
+        )
+    }
+
+    /// Emitted with the entities that became expired since the last check.
+    ///
+    /// This is not emitted for entities that are already expired when the
+    /// lists are bound.
+    pub fn connect_expired<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &EntityIdSet) + 'static,
+    {
+        self.connect_closure(
+            "expired",
+            false,
+            closure_local!(|obj: &Self, ids: &EntityIdSet| f(obj, ids)),
+        )
+    }
+
+    pub fn set_clock(&self, clock: Clock) {
+        self.imp().clock.replace(clock);
+        self.check_expiration();
+    }
+
+    pub fn bind_lists(&self, entity_list: &EntityList, stock_list: &StockList) {
         let imp = self.imp();
 
         entity_list.connect_items_changed(clone!(
             #[weak(rename_to = obj)]
             self,
             move |_, _, _, _| {
-                obj.update_n_expired();
+                obj.check_expiration();
+            }
+        ));
+        stock_list.connect_items_changed(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_, _, _, _| {
+                obj.check_expiration();
             }
         ));
 
         imp.entity_list.set(entity_list.clone()).unwrap();
+        imp.stock_list.set(stock_list.clone()).unwrap();
+
+        let (expiring_entities, expired_entities) = self.compute_expiration_entities();
+        imp.expiring_entities.replace(expiring_entities);
+        imp.expired_entities.replace(expired_entities);
 
-        self.update_n_expired();
+        self.check_expiration();
     }
 
-    fn update_n_expired(&self) {
+    /// Checks for entities that became expiring soon or expired according to
+    /// the clock.
+    ///
+    /// This is also done periodically, but must be called after advancing a
+    /// manual clock.
+    pub fn check_expiration(&self) {
         let imp = self.imp();
 
-        let n_expired = imp
-            .entity_list
-            .get()
-            .map(|entity_list| {
-                entity_list
-                    .iter()
-                    .filter(|entity| {
-                        entity
-                            .expiration()
-                            .is_some_and(|e| matches!(e, EntityExpiration::Expired))
-                    })
-                    .count()
-            })
-            .unwrap_or(0) as u32;
+        let (expiring_entities, expired_entities) = self.compute_expiration_entities();
+        let n_expiring = expiring_entities.len() as u32;
+        let n_expired = expired_entities.len() as u32;
+
+        let prev_expiring_entities = imp.expiring_entities.replace(expiring_entities.clone());
+        let prev_expired_entities = imp.expired_entities.replace(expired_entities.clone());
+
+        let newly_expiring = expiring_entities
+            .difference(&prev_expiring_entities)
+            .filter(|id| !prev_expired_entities.contains(id))
+            .cloned()
+            .collect::<HashSet<_>>();
+        if !newly_expiring.is_empty() {
+            self.emit_by_name::<()>("expiring", &[&EntityIdSet(newly_expiring)]);
+        }
+
+        let newly_expired = expired_entities
+            .difference(&prev_expired_entities)
+            .cloned()
+            .collect::<HashSet<_>>();
+        if !newly_expired.is_empty() {
+            self.emit_by_name::<()>("expired", &[&EntityIdSet(newly_expired)]);
+        }
 
-        if n_expired == self.n_expired() {
-            return;
+        if n_expiring != self.n_expiring() {
+            imp.n_expiring.set(n_expiring);
+            self.notify_n_expiring();
+        }
+
+        if n_expired != self.n_expired() {
+            imp.n_expired.set(n_expired);
+            self.notify_n_expired();
+        }
+    }
+
+    /// Returns the entities that are expiring soon and the ones that are
+    /// expired.
+    fn compute_expiration_entities(&self) -> (HashSet<EntityId>, HashSet<EntityId>) {
+        let imp = self.imp();
+
+        let (Some(entity_list), Some(stock_list)) = (imp.entity_list.get(), imp.stock_list.get())
+        else {
+            return (HashSet::new(), HashSet::new());
+        };
+
+        let app = Application::get();
+        let settings = app.settings();
+
+        let now = imp.clock.borrow().now();
+
+        let mut expiring_entities = HashSet::new();
+        let mut expired_entities = HashSet::new();
+        for entity in entity_list.iter() {
+            match entity.expiration(now, settings, stock_list) {
+                Some(EntityExpiration::Expiring) => {
+                    expiring_entities.insert(entity.id().clone());
+                }
+                Some(EntityExpiration::Expired) => {
+                    expired_entities.insert(entity.id().clone());
+                }
+                Some(EntityExpiration::NotExpiring) | None => {}
+            }
         }
 
-        imp.n_expired.set(n_expired);
-        self.notify_n_expired();
+        (expiring_entities, expired_entities)
     }
 }
 
@@ -0,0 +1,505 @@
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use gst::prelude::*;
+use gtk::{gio, glib};
+use serde::{Deserialize, Serialize};
+
+use crate::{entity_id::EntityId, settings::Settings, APP_ID};
+
+/// Rate of the frames recorded in clips.
+pub const FRAME_RATE: u32 = 10;
+/// Width of the frames recorded in clips.
+pub const FRAME_WIDTH: u32 = 640;
+/// Height of the frames recorded in clips.
+pub const FRAME_HEIGHT: u32 = 480;
+
+const FILE_EXTENSION: &str = "avi";
+
+/// How long after an event its clip can still be recorded, e.g., detected
+/// without ID alerts fire 5 s after the motion.
+const MAX_EVENT_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventClipConfig {
+    /// Duration recorded before the event.
+    pub pre_event_duration: Duration,
+    /// Duration recorded after the event.
+    pub post_event_duration: Duration,
+}
+
+impl EventClipConfig {
+    pub fn total_duration(&self) -> Duration {
+        self.pre_event_duration + self.post_event_duration
+    }
+
+    /// Duration of frames to buffer, so the pre-event frames are still there
+    /// for events that are recorded late.
+    pub fn buffer_duration(&self) -> Duration {
+        self.total_duration() + MAX_EVENT_DELAY
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventClipKind {
+    DetectedWoId,
+    DeniedEntry,
+    Overstayed,
+}
+
+impl EventClipKind {
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::DetectedWoId => "woid",
+            Self::DeniedEntry => "denied",
+            Self::Overstayed => "overstayed",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "woid" => Some(Self::DetectedWoId),
+            "denied" => Some(Self::DeniedEntry),
+            "overstayed" => Some(Self::Overstayed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EventClipKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DetectedWoId => write!(f, "Detected Without ID"),
+            Self::DeniedEntry => write!(f, "Denied Entry"),
+            Self::Overstayed => write!(f, "Overstayed"),
+        }
+    }
+}
+
+/// A video clip recorded around an event, stored in the clips directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EventClip {
+    dt: DateTime<Utc>,
+    kind: EventClipKind,
+    entity_id: Option<EntityId>,
+}
+
+impl EventClip {
+    pub fn new(dt: DateTime<Utc>, kind: EventClipKind, entity_id: Option<EntityId>) -> Self {
+        Self {
+            dt,
+            kind,
+            entity_id,
+        }
+    }
+
+    /// Returns the clips of the given entity, sorted from oldest to newest.
+    pub fn all_for_entity(entity_id: &EntityId) -> Result<Vec<Self>> {
+        let clips = all_file_names()?
+            .into_iter()
+            .filter_map(|file_name| file_name.parse::<Self>().ok())
+            .filter(|clip| clip.entity_id.as_ref() == Some(entity_id))
+            .collect();
+        Ok(clips)
+    }
+
+    pub fn dt(&self) -> DateTime<Utc> {
+        self.dt
+    }
+
+    pub fn kind(&self) -> EventClipKind {
+        self.kind
+    }
+
+    pub fn path(&self) -> PathBuf {
+        dir().join(self.to_string())
+    }
+
+    /// Whether the clip has finished recording.
+    pub fn exists(&self) -> bool {
+        self.path().exists()
+    }
+
+    /// Opens the clip with the default video player.
+    pub async fn launch(&self, parent: &impl IsA<gtk::Window>) -> Result<()> {
+        ensure!(self.exists(), "Clip is not yet saved");
+
+        let file = gio::File::for_path(self.path());
+        gtk::FileLauncher::new(Some(&file))
+            .launch_future(Some(parent))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Saves the JPEG frames as an MJPEG clip.
+    pub async fn save(&self, frames: Vec<gst::Buffer>) -> Result<()> {
+        ensure!(!frames.is_empty(), "No frames to save");
+
+        let dir = dir();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create clips dir at {}", dir.display()))?;
+
+        let pipeline = gst::parse::launch(&format!(
+            "appsrc name=src format=time caps=image/jpeg,width={FRAME_WIDTH},height={FRAME_HEIGHT},framerate={FRAME_RATE}/1 ! jpegparse ! avimux ! filesink name=sink"
+        ))?
+        .downcast::<gst::Pipeline>()
+        .unwrap();
+
+        let sink = pipeline.by_name("sink").unwrap();
+        sink.set_property(
+            "location",
+            self.path().to_str().context("Clip path is not UTF-8")?,
+        );
+
+        pipeline.set_state(gst::State::Playing)?;
+
+        let src = pipeline.by_name("src").unwrap();
+        for (i, mut frame) in frames.into_iter().enumerate() {
+            {
+                let frame = frame.make_mut();
+                frame.set_pts(gst::ClockTime::from_mseconds(
+                    i as u64 * 1000 / FRAME_RATE as u64,
+                ));
+                frame.set_duration(gst::ClockTime::from_mseconds(1000 / FRAME_RATE as u64));
+            }
+
+            let ret = src.emit_by_name::<gst::FlowReturn>("push-buffer", &[&frame]);
+            if let Err(err) = ret.into_result() {
+                let _ = pipeline.set_state(gst::State::Null);
+                bail!("Failed to push frame: {:?}", err);
+            }
+        }
+        let ret = src.emit_by_name::<gst::FlowReturn>("end-of-stream", &[]);
+        if let Err(err) = ret.into_result() {
+            let _ = pipeline.set_state(gst::State::Null);
+            bail!("Failed to end stream: {:?}", err);
+        }
+
+        let mut messages = pipeline.bus().unwrap().stream();
+        let ret = loop {
+            let Some(message) = messages.next().await else {
+                break Err(anyhow!("Bus stream ended unexpectedly"));
+            };
+
+            match message.view() {
+                gst::MessageView::Eos(_) => break Ok(()),
+                gst::MessageView::Error(e) => break Err(Error::from(e.error())),
+                _ => {}
+            }
+        };
+
+        pipeline.set_state(gst::State::Null)?;
+
+        ret.with_context(|| format!("Failed to save clip at {}", self.path().display()))
+    }
+}
+
+impl fmt::Display for EventClip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Zero-padded so that sorting by file name is the same as sorting by time.
+        write!(f, "{:016}-{}", self.dt.timestamp_millis(), self.kind.tag())?;
+
+        if let Some(entity_id) = &self.entity_id {
+            write!(f, "-")?;
+
+            for byte in entity_id.to_string().bytes() {
+                write!(f, "{:02x}", byte)?;
+            }
+        }
+
+        write!(f, ".{FILE_EXTENSION}")
+    }
+}
+
+impl std::str::FromStr for EventClip {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let stem = s
+            .strip_suffix(&format!(".{FILE_EXTENSION}"))
+            .with_context(|| format!("Invalid clip extension in `{}`", s))?;
+
+        let mut parts = stem.splitn(3, '-');
+
+        let millis = parts
+            .next()
+            .context("Missing timestamp")?
+            .parse::<i64>()
+            .context("Invalid timestamp")?;
+        let dt = DateTime::from_timestamp_millis(millis).context("Out of range timestamp")?;
+
+        let kind_tag = parts.next().context("Missing kind")?;
+        let kind = EventClipKind::from_tag(kind_tag)
+            .with_context(|| format!("Invalid kind `{}`", kind_tag))?;
+
+        let entity_id = parts
+            .next()
+            .map(|hex| {
+                ensure!(
+                    hex.is_ascii() && hex.len() % 2 == 0,
+                    "Invalid entity ID `{}`",
+                    hex
+                );
+
+                let bytes = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("Invalid entity ID `{}`", hex))?;
+                Ok(EntityId::new(String::from_utf8(bytes)?))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            dt,
+            kind,
+            entity_id,
+        })
+    }
+}
+
+impl TryFrom<String> for EventClip {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<EventClip> for String {
+    fn from(value: EventClip) -> Self {
+        value.to_string()
+    }
+}
+
+/// Keeps the most recent frames within a duration.
+#[derive(Debug)]
+pub struct FrameBuffer<T> {
+    max_duration: Duration,
+    frames: VecDeque<(Instant, T)>,
+}
+
+impl<T: Clone> FrameBuffer<T> {
+    pub fn new(max_duration: Duration) -> Self {
+        Self {
+            max_duration,
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, instant: Instant, frame: T) {
+        self.frames.push_back((instant, frame));
+
+        while self
+            .frames
+            .front()
+            .is_some_and(|(first, _)| instant.duration_since(*first) > self.max_duration)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Returns the frames received within `start..=end`.
+    pub fn frames_between(&self, start: Instant, end: Instant) -> Vec<T> {
+        self.frames
+            .iter()
+            .filter(|(instant, _)| (start..=end).contains(instant))
+            .map(|(_, frame)| frame.clone())
+            .collect()
+    }
+}
+
+/// Returns the instant of an event at `dt`, or `None` if it happened too long
+/// ago to have its frames buffered, e.g., for back-dated detections.
+pub fn event_instant(
+    dt: DateTime<Utc>,
+    now_dt: DateTime<Utc>,
+    now_instant: Instant,
+) -> Option<Instant> {
+    let age = (now_dt - dt).to_std().unwrap_or_default();
+
+    if age > MAX_EVENT_DELAY {
+        return None;
+    }
+
+    now_instant.checked_sub(age)
+}
+
+/// Removes the oldest clips until the total size is within the quota.
+pub fn enforce_quota(quota_bytes: u64) -> Result<()> {
+    let dir = dir();
+
+    let mut file_names = all_file_names()?;
+    file_names.sort();
+
+    let sizes = file_names
+        .iter()
+        .map(|file_name| {
+            let path = dir.join(file_name);
+            fs::metadata(&path)
+                .map(|m| m.len())
+                .with_context(|| format!("Failed to get metadata of {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let n_to_remove = n_oldest_to_remove(&sizes, quota_bytes);
+    for file_name in &file_names[..n_to_remove] {
+        let path = dir.join(file_name);
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove clip at {}", path.display()))?;
+
+        tracing::debug!("Removed clip at {} due to quota", path.display());
+    }
+
+    Ok(())
+}
+
+/// Returns how many of the oldest sizes must be dropped for the total to be within the quota.
+fn n_oldest_to_remove(sizes: &[u64], quota_bytes: u64) -> usize {
+    let mut total = sizes.iter().sum::<u64>();
+
+    sizes
+        .iter()
+        .take_while(|size| {
+            let is_over = total > quota_bytes;
+            total -= **size;
+            is_over
+        })
+        .count()
+}
+
+fn dir() -> PathBuf {
+    glib::user_data_dir().join(format!("{}/clips", APP_ID))
+}
+
+fn all_file_names() -> Result<Vec<String>> {
+    let dir = dir();
+
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file_names = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read clips dir at {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|file_name| file_name.ends_with(&format!(".{FILE_EXTENSION}")))
+        .collect();
+
+    Ok(file_names)
+}
+
+pub trait EventClipSettingsExt {
+    /// Returns `None` if event clip recording is disabled.
+    fn event_clip_config(&self) -> Option<EventClipConfig>;
+
+    fn event_clip_storage_quota_bytes(&self) -> u64;
+}
+
+impl EventClipSettingsExt for Settings {
+    fn event_clip_config(&self) -> Option<EventClipConfig> {
+        if !self.enable_event_clip_recording() {
+            return None;
+        }
+
+        Some(EventClipConfig {
+            pre_event_duration: Duration::from_secs(self.event_clip_pre_event_secs() as u64),
+            post_event_duration: Duration::from_secs(self.event_clip_post_event_secs() as u64),
+        })
+    }
+
+    fn event_clip_storage_quota_bytes(&self) -> u64 {
+        self.event_clip_storage_quota_mb() as u64 * 1024 * 1024
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn clip_file_name_roundtrip() {
+        let dt = DateTime::from_timestamp_millis(1_700_000_000_123).unwrap();
+
+        let clips = [
+            EventClip::new(dt, EventClipKind::DetectedWoId, None),
+            EventClip::new(dt, EventClipKind::DetectedWoId, Some(EntityId::new("a-b"))),
+            EventClip::new(dt, EventClipKind::DeniedEntry, Some(EntityId::new("abc"))),
+            EventClip::new(dt, EventClipKind::Overstayed, Some(EntityId::new("ñ 1"))),
+        ];
+
+        for clip in clips {
+            assert_eq!(clip.to_string().parse::<EventClip>().unwrap(), clip);
+        }
+
+        assert_eq!(
+            EventClip::new(dt, EventClipKind::DeniedEntry, Some(EntityId::new("abc"))).to_string(),
+            "0001700000000123-denied-616263.avi"
+        );
+
+        assert!("0001700000000123-denied.mp4".parse::<EventClip>().is_err());
+        assert!("0001700000000123-unknown.avi".parse::<EventClip>().is_err());
+        assert!("0001700000000123-denied-6.avi"
+            .parse::<EventClip>()
+            .is_err());
+        assert!("0001700000000123-wo-id.avi".parse::<EventClip>().is_err());
+    }
+
+    #[test]
+    fn frame_buffer() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        let mut buffer = FrameBuffer::new(Duration::from_secs(2));
+        for secs in 0..5 {
+            buffer.push(at(secs), secs);
+        }
+
+        assert_eq!(buffer.frames_between(at(0), at(10)), vec![2, 3, 4]);
+        assert_eq!(buffer.frames_between(at(3), at(3)), vec![3]);
+        assert_eq!(buffer.frames_between(at(5), at(10)), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn event_instant_for_dt() {
+        let now_dt = Utc::now();
+        let now_instant = Instant::now() + Duration::from_secs(60);
+
+        assert_eq!(
+            event_instant(now_dt, now_dt, now_instant),
+            Some(now_instant)
+        );
+        assert_eq!(
+            event_instant(now_dt - TimeDelta::seconds(5), now_dt, now_instant),
+            Some(now_instant - Duration::from_secs(5))
+        );
+        assert_eq!(
+            event_instant(now_dt + TimeDelta::seconds(1), now_dt, now_instant),
+            Some(now_instant)
+        );
+        assert_eq!(
+            event_instant(now_dt - TimeDelta::minutes(5), now_dt, now_instant),
+            None
+        );
+    }
+
+    #[test]
+    fn n_oldest_to_remove_within_quota() {
+        assert_eq!(n_oldest_to_remove(&[], 0), 0);
+        assert_eq!(n_oldest_to_remove(&[1, 2, 3], 6), 0);
+        assert_eq!(n_oldest_to_remove(&[1, 2, 3], 5), 1);
+        assert_eq!(n_oldest_to_remove(&[1, 2, 3], 3), 2);
+        assert_eq!(n_oldest_to_remove(&[1, 2, 3], 0), 3);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+
+use crate::{
+    date_time_range::DateTimeRange, entity::Entity, entity_id::EntityId, stock_id::StockId,
+    timeline::Timeline, timeline_item_kind::TimelineItemKind,
+};
+
+/// Position of an entity in the order its stock should be used.
+///
+/// Entities expiring first go out first, with the ones without expiration
+/// last. Ties go by which entered first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct PickKey {
+    expiration: ExpirationKey,
+    entry_dt: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct ExpirationKey {
+    has_no_expiration: bool,
+    expiration_dt: Option<DateTime<Utc>>,
+}
+
+impl ExpirationKey {
+    fn new(expiration_dt: Option<DateTime<Utc>>) -> Self {
+        Self {
+            has_no_expiration: expiration_dt.is_none(),
+            expiration_dt,
+        }
+    }
+}
+
+/// Entities of a stock that are inside, in the order they should be used.
+#[derive(Debug, Default)]
+pub struct PickQueue {
+    keys: HashMap<EntityId, PickKey>,
+    queue: BTreeSet<(PickKey, EntityId)>,
+}
+
+impl PickQueue {
+    pub fn insert(
+        &mut self,
+        entity_id: EntityId,
+        expiration_dt: Option<DateTime<Utc>>,
+        entry_dt: DateTime<Utc>,
+    ) {
+        let key = PickKey {
+            expiration: ExpirationKey::new(expiration_dt),
+            entry_dt,
+        };
+
+        if let Some(prev_key) = self.keys.insert(entity_id.clone(), key) {
+            self.queue.remove(&(prev_key, entity_id.clone()));
+        }
+        self.queue.insert((key, entity_id));
+    }
+
+    pub fn remove(&mut self, entity_id: &EntityId) {
+        if let Some(key) = self.keys.remove(entity_id) {
+            self.queue.remove(&(key, entity_id.clone()));
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EntityId> + '_ {
+        self.queue.iter().map(|(_, entity_id)| entity_id)
+    }
+
+    /// Returns the entities that expire strictly before the given expiration,
+    /// which should be used before an entity with it.
+    ///
+    /// Entities with the same expiration are interchangeable, so they are
+    /// not included even if they entered earlier.
+    pub fn expiring_before(
+        &self,
+        expiration_dt: Option<DateTime<Utc>>,
+    ) -> impl Iterator<Item = &EntityId> + '_ {
+        let expiration = ExpirationKey::new(expiration_dt);
+        self.queue
+            .iter()
+            .take_while(move |(key, _)| key.expiration < expiration)
+            .map(|(_, entity_id)| entity_id)
+    }
+}
+
+/// Inside entities of a stock that share a lot.
+pub struct LotGroup {
+    pub lot: Option<String>,
+    /// In the order they should be used.
+    pub entities: Vec<Entity>,
+}
+
+/// Groups the entities by lot, keeping the order of the first entity of each
+/// lot.
+pub fn group_by_lot(entities: impl IntoIterator<Item = Entity>) -> Vec<LotGroup> {
+    let mut groups = IndexMap::<Option<String>, Vec<Entity>>::new();
+    for entity in entities {
+        let lot = entity.data().lot().cloned();
+        groups.entry(lot).or_default().push(entity);
+    }
+
+    groups
+        .into_iter()
+        .map(|(lot, entities)| LotGroup { lot, entities })
+        .collect()
+}
+
+/// Whether an exit left no entity of the same stock that expires sooner
+/// inside.
+pub struct FefoCheck {
+    pub dt: DateTime<Utc>,
+    pub n_skipped: usize,
+}
+
+impl FefoCheck {
+    pub fn is_compliant(&self) -> bool {
+        self.n_skipped == 0
+    }
+}
+
+impl Timeline {
+    /// Returns the entities of the stock that are inside at the given time,
+    /// in the order they should be used.
+    pub fn pick_order_for_dt(&self, stock_id: &StockId, dt: DateTime<Utc>) -> Vec<Entity> {
+        let mut entities = HashMap::new();
+        let mut queue = PickQueue::default();
+
+        for entity in self
+            .entity_list()
+            .iter()
+            .filter(|entity| entity.stock_id().as_ref() == Some(stock_id))
+        {
+            if let Some((entry_dt, TimelineItemKind::Entry)) = entity.action_for_dt(dt) {
+                queue.insert(
+                    entity.id().clone(),
+                    entity.data().expiration_dt().copied(),
+                    entry_dt,
+                );
+                entities.insert(entity.id().clone(), entity);
+            }
+        }
+
+        queue
+            .iter()
+            .map(|entity_id| entities.remove(entity_id).unwrap())
+            .collect()
+    }
+
+    /// Returns the entities of the same stock that expire sooner than the
+    /// given one and were left inside when it exited at `exit_dt`, the one
+    /// expiring first first.
+    pub fn fefo_skipped(&self, entity: &Entity, exit_dt: DateTime<Utc>) -> Vec<Entity> {
+        let Some(stock_id) = entity.stock_id() else {
+            return Vec::new();
+        };
+
+        let expiration = ExpirationKey::new(entity.data().expiration_dt().copied());
+        self.pick_order_for_dt(&stock_id, exit_dt)
+            .into_iter()
+            .take_while(|other| {
+                ExpirationKey::new(other.data().expiration_dt().copied()) < expiration
+            })
+            .collect()
+    }
+
+    /// Returns the FEFO checks of the exits of the stock within the range.
+    pub fn fefo_checks(&self, stock_id: &StockId, dt_range: &DateTimeRange) -> Vec<FefoCheck> {
+        let mut queue = PickQueue::default();
+        let mut checks = Vec::new();
+
+        // Items before the range are needed to know which are inside.
+        let all_time = DateTimeRange::all_time();
+        for item in self.iter_stock(&all_time, stock_id) {
+            if dt_range.end.is_some_and(|end| item.dt() > end) {
+                break;
+            }
+
+            let expiration_dt = self
+                .entity_list()
+                .get(item.entity_id())
+                .and_then(|entity| entity.data().expiration_dt().copied());
+
+            match item.kind() {
+                TimelineItemKind::Entry => {
+                    queue.insert(item.entity_id().clone(), expiration_dt, item.dt());
+                }
+                TimelineItemKind::Exit => {
+                    queue.remove(item.entity_id());
+
+                    if dt_range.contains(item.dt()) {
+                        checks.push(FefoCheck {
+                            dt: item.dt(),
+                            n_skipped: queue.expiring_before(expiration_dt).count(),
+                        });
+                    }
+                }
+            }
+        }
+
+        checks
+    }
+}
+
+/// Returns the percentage of compliant checks, or `None` if there are none.
+pub fn compliance_percent<'a>(checks: impl IntoIterator<Item = &'a FefoCheck>) -> Option<f64> {
+    let (n_compliant, n_total) = checks
+        .into_iter()
+        .fold((0, 0), |(n_compliant, n_total), check| {
+            (n_compliant + check.is_compliant() as u32, n_total + 1)
+        });
+
+    (n_total > 0).then(|| f64::from(n_compliant) / f64::from(n_total) * 100.0)
+}
+
+/// Returns the compliance percent after each check.
+pub fn compliance_over_time(checks: &[FefoCheck]) -> Vec<(DateTime<Utc>, f64)> {
+    let mut n_compliant = 0;
+    checks
+        .iter()
+        .zip(1..)
+        .map(|(check, n_total)| {
+            n_compliant += check.is_compliant() as u32;
+            (
+                check.dt,
+                f64::from(n_compliant) / f64::from(n_total) * 100.0,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    fn ids<'a>(iter: impl Iterator<Item = &'a EntityId>) -> Vec<String> {
+        iter.map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn pick_queue() {
+        let mut queue = PickQueue::default();
+        queue.insert(EntityId::new("no-exp"), None, dt("2024-11-01T00:00:00Z"));
+        queue.insert(
+            EntityId::new("late"),
+            Some(dt("2024-12-01T00:00:00Z")),
+            dt("2024-11-01T00:00:00Z"),
+        );
+        queue.insert(
+            EntityId::new("early-b"),
+            Some(dt("2024-11-10T00:00:00Z")),
+            dt("2024-11-03T00:00:00Z"),
+        );
+        queue.insert(
+            EntityId::new("early-a"),
+            Some(dt("2024-11-10T00:00:00Z")),
+            dt("2024-11-02T00:00:00Z"),
+        );
+
+        assert_eq!(ids(queue.iter()), ["early-a", "early-b", "late", "no-exp"]);
+
+        assert_eq!(
+            ids(queue.expiring_before(Some(dt("2024-12-01T00:00:00Z")))),
+            ["early-a", "early-b"]
+        );
+        assert!(ids(queue.expiring_before(Some(dt("2024-11-10T00:00:00Z")))).is_empty());
+        assert_eq!(
+            ids(queue.expiring_before(None)),
+            ["early-a", "early-b", "late"]
+        );
+
+        queue.remove(&EntityId::new("early-a"));
+        queue.insert(
+            EntityId::new("late"),
+            Some(dt("2024-11-05T00:00:00Z")),
+            dt("2024-11-04T00:00:00Z"),
+        );
+        assert_eq!(ids(queue.iter()), ["late", "early-b", "no-exp"]);
+    }
+
+    #[test]
+    fn compliance() {
+        let check = |n_skipped| FefoCheck {
+            dt: dt("2024-11-01T00:00:00Z"),
+            n_skipped,
+        };
+
+        assert_eq!(compliance_percent(&[]), None);
+        assert_eq!(compliance_percent(&[check(0), check(2)]), Some(50.0));
+        assert_eq!(
+            compliance_percent(&[check(0), check(0), check(0), check(1)]),
+            Some(75.0)
+        );
+
+        assert_eq!(
+            compliance_over_time(&[check(1), check(0), check(0), check(0)])
+                .into_iter()
+                .map(|(_, percent)| percent.round())
+                .collect::<Vec<_>>(),
+            [0.0, 50.0, 67.0, 75.0]
+        );
+    }
+}
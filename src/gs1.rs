@@ -0,0 +1,291 @@
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, Local, Months, NaiveDate, NaiveTime, Utc};
+
+/// Group separator, which scanners transmit in place of FNC1.
+const GROUP_SEPARATOR: char = '\x1d';
+
+/// Symbology identifiers that scanners may prefix GS1 data with, i.e.,
+/// GS1-128, GS1 DataMatrix, GS1 QR Code, and GS1 DataBar.
+const SYMBOLOGY_IDENTIFIERS: &[&str] = &["]C1", "]d2", "]Q3", "]e0"];
+
+pub const AI_GTIN: &str = "01";
+pub const AI_BATCH: &str = "10";
+pub const AI_EXPIRATION_DATE: &str = "17";
+pub const AI_SERIAL: &str = "21";
+
+/// Parsed GS1 element string, e.g., from a GS1-128 or GS1 DataMatrix code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gs1ElementString(Vec<(String, String)>);
+
+impl Gs1ElementString {
+    /// Parses raw scanner output, where variable-length elements are
+    /// terminated by a group separator, or the human-readable form, where
+    /// application identifiers are in parentheses (e.g., `(01)…(21)…`).
+    pub fn parse(code: &str) -> Result<Self> {
+        let code = SYMBOLOGY_IDENTIFIERS
+            .iter()
+            .find_map(|id| code.strip_prefix(id))
+            .unwrap_or(code)
+            .trim_start_matches(GROUP_SEPARATOR)
+            .trim_end();
+
+        ensure!(!code.is_empty(), "Empty element string");
+
+        let elements = if code.starts_with('(') {
+            parse_human_readable(code)?
+        } else {
+            parse_raw(code)?
+        };
+
+        if let Some(gtin) = elements
+            .iter()
+            .find_map(|(ai, data)| (ai == AI_GTIN).then_some(data))
+        {
+            ensure!(is_valid_gtin(gtin), "Invalid GTIN `{}`", gtin);
+        }
+
+        Ok(Self(elements))
+    }
+
+    pub fn get(&self, ai: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(element_ai, _)| element_ai == ai)
+            .map(|(_, data)| data.as_str())
+    }
+
+    pub fn gtin(&self) -> Option<&str> {
+        self.get(AI_GTIN)
+    }
+
+    pub fn batch(&self) -> Option<&str> {
+        self.get(AI_BATCH)
+    }
+
+    pub fn serial(&self) -> Option<&str> {
+        self.get(AI_SERIAL)
+    }
+
+    /// Returns the expiration date at local midnight.
+    pub fn expiration_dt(&self) -> Option<Result<DateTime<Utc>>> {
+        self.get(AI_EXPIRATION_DATE).map(parse_date)
+    }
+}
+
+fn parse_raw(code: &str) -> Result<Vec<(String, String)>> {
+    let mut elements = Vec::new();
+
+    let mut rest = code;
+    while !rest.is_empty() {
+        let (ai_len, data_len) = ai_lengths(rest)?;
+        ensure!(
+            rest.len() >= ai_len && rest.is_char_boundary(ai_len),
+            "Truncated application identifier"
+        );
+        let (ai, after_ai) = rest.split_at(ai_len);
+
+        let (data, after_data) = match data_len {
+            Some(data_len) => {
+                ensure!(
+                    after_ai.len() >= data_len && after_ai.is_char_boundary(data_len),
+                    "Truncated data for AI {}",
+                    ai
+                );
+                after_ai.split_at(data_len)
+            }
+            None => after_ai
+                .split_once(GROUP_SEPARATOR)
+                .unwrap_or((after_ai, "")),
+        };
+
+        elements.push((ai.to_string(), data.to_string()));
+
+        // Some encoders also terminate fixed-length elements.
+        rest = after_data.trim_start_matches(GROUP_SEPARATOR);
+    }
+
+    Ok(elements)
+}
+
+fn parse_human_readable(code: &str) -> Result<Vec<(String, String)>> {
+    let mut elements = Vec::new();
+
+    let mut rest = code;
+    while !rest.is_empty() {
+        let after_open = rest
+            .strip_prefix('(')
+            .context("Expected opening parenthesis")?;
+        let (ai, after_ai) = after_open
+            .split_once(')')
+            .context("Expected closing parenthesis")?;
+        ensure!(
+            !ai.is_empty() && ai.chars().all(|c| c.is_ascii_digit()),
+            "Invalid application identifier `{}`",
+            ai
+        );
+
+        let data_end = after_ai.find('(').unwrap_or(after_ai.len());
+        let (data, after_data) = after_ai.split_at(data_end);
+
+        if let (_, Some(data_len)) = ai_lengths(ai)? {
+            ensure!(
+                data.len() == data_len,
+                "Expected {} characters for AI {}",
+                data_len,
+                ai
+            );
+        }
+
+        elements.push((ai.to_string(), data.to_string()));
+
+        rest = after_data;
+    }
+
+    Ok(elements)
+}
+
+/// Returns the length of the application identifier at the start of `s` and,
+/// if predefined, the length of its data.
+///
+/// This only covers the commonly used application identifiers.
+fn ai_lengths(s: &str) -> Result<(usize, Option<usize>)> {
+    let prefix = s
+        .get(..2)
+        .filter(|prefix| prefix.chars().all(|c| c.is_ascii_digit()))
+        .with_context(|| format!("Invalid application identifier in `{}`", s))?;
+
+    let ret = match prefix.parse::<u8>().unwrap() {
+        0 => (2, Some(18)),
+        1..=3 => (2, Some(14)),
+        4 => (2, Some(16)),
+        11..=19 => (2, Some(6)),
+        20 => (2, Some(2)),
+        10 | 21 | 22 | 30 | 37 | 90..=99 => (2, None),
+        23..=25 | 40 | 42 => (3, None),
+        41 => (3, Some(13)),
+        31..=36 => (4, Some(6)),
+        39 | 43 | 70..=72 | 80..=82 => (4, None),
+        _ => bail!("Unsupported application identifier in `{}`", s),
+    };
+    Ok(ret)
+}
+
+fn is_valid_gtin(gtin: &str) -> bool {
+    if gtin.len() != 14 || !gtin.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+
+    let digits = gtin.bytes().map(|b| (b - b'0') as u32).collect::<Vec<_>>();
+    let (check_digit, body) = digits.split_last().unwrap();
+
+    let sum = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d * 3 } else { *d })
+        .sum::<u32>();
+
+    (10 - sum % 10) % 10 == *check_digit
+}
+
+/// Parses a `YYMMDD` date, where a day of `00` means the last day of the
+/// month.
+fn parse_date(s: &str) -> Result<DateTime<Utc>> {
+    ensure!(
+        s.len() == 6 && s.chars().all(|c| c.is_ascii_digit()),
+        "Invalid date `{}`",
+        s
+    );
+
+    let year = 2000 + s[0..2].parse::<i32>()?;
+    let month = s[2..4].parse::<u32>()?;
+    let day = s[4..6].parse::<u32>()?;
+
+    let date = if day == 0 {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .and_then(|first_day| first_day.checked_add_months(Months::new(1)))
+            .and_then(|next_month_first_day| next_month_first_day.pred_opt())
+    } else {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+    .with_context(|| format!("Invalid date `{}`", s))?;
+
+    Ok(date
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .single()
+        .context("Invalid date")?
+        .to_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw() {
+        let code = "]d2010950600013435217250131\x1d10ABC123\x1d21SN0001";
+        let elements = Gs1ElementString::parse(code).unwrap();
+        assert_eq!(elements.gtin(), Some("09506000134352"));
+        assert_eq!(elements.get(AI_EXPIRATION_DATE), Some("250131"));
+        assert_eq!(elements.batch(), Some("ABC123"));
+        assert_eq!(elements.serial(), Some("SN0001"));
+    }
+
+    #[test]
+    fn human_readable() {
+        let code = "(01)09506000134352(10)ABC123(21)SN0001(17)250100";
+        let elements = Gs1ElementString::parse(code).unwrap();
+        assert_eq!(elements.gtin(), Some("09506000134352"));
+        assert_eq!(elements.batch(), Some("ABC123"));
+        assert_eq!(elements.serial(), Some("SN0001"));
+        assert_eq!(
+            elements
+                .expiration_dt()
+                .unwrap()
+                .unwrap()
+                .with_timezone(&Local)
+                .date_naive(),
+            NaiveDate::from_ymd_opt(2025, 1, 31).unwrap()
+        );
+
+        assert!(Gs1ElementString::parse("(01)0950600013435").is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Gs1ElementString::parse("").is_err());
+        assert!(Gs1ElementString::parse("Juan_2021_a@b.c_BSCpE").is_err());
+        // Wrong check digit
+        assert!(Gs1ElementString::parse("0109506000134353").is_err());
+        // Truncated fixed-length data
+        assert!(Gs1ElementString::parse("01095060001343").is_err());
+    }
+
+    #[test]
+    fn gtin_check_digit() {
+        assert!(is_valid_gtin("09506000134352"));
+        assert!(is_valid_gtin("00012345600012"));
+        assert!(!is_valid_gtin("00012345600013"));
+        assert!(!is_valid_gtin("1234"));
+    }
+
+    #[test]
+    fn date() {
+        let date = |s| parse_date(s).unwrap().with_timezone(&Local).date_naive();
+        assert_eq!(
+            date("240229"),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+        assert_eq!(
+            date("240200"),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+        assert_eq!(
+            date("241200"),
+            NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()
+        );
+        assert!(parse_date("241301").is_err());
+        assert!(parse_date("2412").is_err());
+    }
+}
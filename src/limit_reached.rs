@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
 
 use gtk::glib::clone;
 
@@ -23,35 +23,54 @@ impl LimitReached {
 }
 
 pub trait LimitReachedLabelExt {
-    fn set_label_from_limit_reached(&self, count: u32, settings: &Settings);
+    fn set_label_from_limit_reached(
+        &self,
+        count: impl Into<f64> + fmt::Display,
+        settings: &Settings,
+    );
 }
 
 impl LimitReachedLabelExt for gtk::Label {
-    fn set_label_from_limit_reached(&self, count: u32, settings: &Settings) {
+    fn set_label_from_limit_reached(
+        &self,
+        count: impl Into<f64> + fmt::Display,
+        settings: &Settings,
+    ) {
+        let text = count.to_string();
         if settings.compute_limit_reached(count).is_some() {
-            self.set_markup(&format::red_markup(&count.to_string()))
+            self.set_markup(&format::red_markup(&text))
         } else {
-            self.set_text(&count.to_string());
+            self.set_text(&text);
         }
     }
 }
 
 pub trait LimitReachedInformationRowExt {
-    fn set_value_from_limit_reached(&self, count: u32, settings: &Settings);
+    fn set_value_from_limit_reached(
+        &self,
+        count: impl Into<f64> + fmt::Display,
+        settings: &Settings,
+    );
 }
 
 impl LimitReachedInformationRowExt for InformationRow {
-    fn set_value_from_limit_reached(&self, count: u32, settings: &Settings) {
+    fn set_value_from_limit_reached(
+        &self,
+        count: impl Into<f64> + fmt::Display,
+        settings: &Settings,
+    ) {
+        let text = count.to_string();
         if settings.compute_limit_reached(count).is_some() {
-            self.set_markup(format::red_markup(&count.to_string()));
+            self.set_markup(format::red_markup(&text));
         } else {
-            self.set_text(count.to_string());
+            self.set_text(text);
         }
     }
 }
 
 pub trait LimitReachedSettingsExt {
-    fn compute_limit_reached(&self, count: u32) -> Option<LimitReached>;
+    /// Counts may be fractional, e.g., for stocks counted in kilograms.
+    fn compute_limit_reached(&self, count: impl Into<f64>) -> Option<LimitReached>;
     fn connect_limit_reached_threshold_changed(
         &self,
         f: impl Fn(&Self) + 'static,
@@ -59,9 +78,10 @@ pub trait LimitReachedSettingsExt {
 }
 
 impl LimitReachedSettingsExt for Settings {
-    fn compute_limit_reached(&self, count: u32) -> Option<LimitReached> {
-        let lower = self.lower_limit_reached_threshold();
-        let upper = self.upper_limit_reached_threshold();
+    fn compute_limit_reached(&self, count: impl Into<f64>) -> Option<LimitReached> {
+        let count = count.into();
+        let lower = f64::from(self.lower_limit_reached_threshold());
+        let upper = f64::from(self.upper_limit_reached_threshold());
 
         if lower >= upper {
             tracing::warn!("Lower >= upper limit");
@@ -0,0 +1,692 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::fs::OpenOptionsExt,
+    },
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, ensure, Context, Error, Result};
+use futures_channel::mpsc;
+use futures_util::StreamExt;
+use gtk::{
+    glib::{self, clone, closure_local},
+    prelude::*,
+    subclass::prelude::*,
+};
+
+use crate::settings::Settings;
+
+/// How long a read blocks before checking whether the reader was stopped.
+const POLL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Delay before reopening a device that failed, e.g., was unplugged.
+const REOPEN_DELAY: Duration = Duration::from_secs(5);
+
+const DEFAULT_BAUD_RATE: u32 = 9600;
+const DEFAULT_N_ID_BYTES: usize = 4;
+
+/// `_IOW('E', 0x90, int)`
+const EVIOCGRAB: libc::c_ulong = 0x40044590;
+
+const EV_KEY: u16 = 0x01;
+const KEY_PRESSED: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdBase {
+    Decimal,
+    Hex,
+}
+
+impl FromStr for IdBase {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dec" => Ok(Self::Decimal),
+            "hex" => Ok(Self::Hex),
+            _ => bail!("Invalid ID base `{}`", s),
+        }
+    }
+}
+
+impl fmt::Display for IdBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decimal => write!(f, "dec"),
+            Self::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+/// Converts the IDs sent by a device to the format used by the other readers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdNormalization {
+    /// Base of the IDs sent by the device. If `None`, IDs are used as is.
+    pub from: Option<IdBase>,
+    pub to: IdBase,
+    pub reverse_bytes: bool,
+    /// Number of bytes of the ID, used when reversing bytes and padding hex.
+    pub n_bytes: usize,
+}
+
+impl Default for IdNormalization {
+    fn default() -> Self {
+        Self {
+            from: None,
+            to: IdBase::Hex,
+            reverse_bytes: false,
+            n_bytes: DEFAULT_N_ID_BYTES,
+        }
+    }
+}
+
+impl IdNormalization {
+    pub fn apply(&self, raw: &str) -> Result<String> {
+        let raw = raw.trim();
+
+        let Some(from) = self.from else {
+            return Ok(raw.to_string());
+        };
+
+        let value = match from {
+            IdBase::Decimal => raw.parse::<u64>(),
+            IdBase::Hex => u64::from_str_radix(raw, 16),
+        }
+        .with_context(|| format!("Invalid {} ID `{}`", from, raw))?;
+
+        ensure!(
+            (1..=8).contains(&self.n_bytes),
+            "Number of ID bytes must be within 1 and 8"
+        );
+        ensure!(
+            self.n_bytes == 8 || value >> (self.n_bytes * 8) == 0,
+            "ID `{}` does not fit in {} bytes",
+            raw,
+            self.n_bytes
+        );
+
+        let value = if self.reverse_bytes {
+            value.swap_bytes() >> ((8 - self.n_bytes) * 8)
+        } else {
+            value
+        };
+
+        let ret = match self.to {
+            IdBase::Decimal => value.to_string(),
+            IdBase::Hex => format!("{:0width$x}", value, width = self.n_bytes * 2),
+        };
+
+        Ok(ret)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalReaderKind {
+    /// Serial reader that sends newline or STX/ETX delimited IDs.
+    Serial { baud_rate: u32 },
+    /// HID keyboard-wedge reader that types the ID followed by Enter.
+    Evdev,
+}
+
+/// A reader connected to the device, parsed from a string such as
+/// `serial:/dev/ttyUSB0,baud=9600,from=dec,to=hex,bytes=4,reverse`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalReaderSpec {
+    pub kind: LocalReaderKind,
+    pub path: String,
+    pub normalization: IdNormalization,
+}
+
+impl FromStr for LocalReaderSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, rest) = s
+            .trim()
+            .split_once(':')
+            .with_context(|| format!("Expected `<kind>:<path>`, got `{}`", s))?;
+
+        let mut parts = rest.split(',').map(|p| p.trim());
+
+        let path = parts.next().unwrap_or_default();
+        ensure!(!path.is_empty(), "Missing device path in `{}`", s);
+
+        let mut baud_rate = DEFAULT_BAUD_RATE;
+        let mut normalization = IdNormalization::default();
+        for part in parts {
+            match part.split_once('=') {
+                Some(("baud", value)) => {
+                    baud_rate = value
+                        .parse()
+                        .with_context(|| format!("Invalid baud rate `{}`", value))?;
+                }
+                Some(("from", value)) => normalization.from = Some(value.parse()?),
+                Some(("to", value)) => normalization.to = value.parse()?,
+                Some(("bytes", value)) => {
+                    let n_bytes = value
+                        .parse()
+                        .with_context(|| format!("Invalid number of bytes `{}`", value))?;
+                    ensure!(
+                        (1..=8).contains(&n_bytes),
+                        "Number of ID bytes must be within 1 and 8, got `{}`",
+                        value
+                    );
+                    normalization.n_bytes = n_bytes;
+                }
+                None if part == "reverse" => normalization.reverse_bytes = true,
+                _ => bail!("Unknown option `{}`", part),
+            }
+        }
+
+        let kind = match kind {
+            "serial" => LocalReaderKind::Serial { baud_rate },
+            "evdev" => LocalReaderKind::Evdev,
+            _ => bail!("Unknown reader kind `{}`", kind),
+        };
+
+        Ok(Self {
+            kind,
+            path: path.to_string(),
+            normalization,
+        })
+    }
+}
+
+mod imp {
+    use std::{
+        cell::{OnceCell, RefCell},
+        sync::OnceLock,
+    };
+
+    use glib::{subclass::Signal, JoinHandle};
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct LocalReader {
+        pub(super) spec: OnceCell<LocalReaderSpec>,
+        pub(super) stop_flag: RefCell<Option<Arc<AtomicBool>>>,
+        pub(super) handle: RefCell<Option<JoinHandle<()>>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for LocalReader {
+        const NAME: &'static str = "UetsLocalReader";
+        type Type = super::LocalReader;
+    }
+
+    impl ObjectImpl for LocalReader {
+        fn dispose(&self) {
+            let obj = self.obj();
+
+            obj.stop();
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+
+            SIGNALS.get_or_init(|| {
+                vec![Signal::builder("detected")
+                    .param_types([String::static_type()])
+                    .build()]
+            })
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct LocalReader(ObjectSubclass<imp::LocalReader>);
+}
+
+impl LocalReader {
+    pub fn new(spec: LocalReaderSpec) -> Self {
+        let this = glib::Object::new::<Self>();
+
+        let imp = this.imp();
+        imp.spec.set(spec).unwrap();
+
+        this.start();
+
+        this
+    }
+
+    pub fn connect_detected<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str) + 'static,
+    {
+        self.connect_closure(
+            "detected",
+            false,
+            closure_local!(|obj: &Self, id: &str| f(obj, id)),
+        )
+    }
+
+    pub fn spec(&self) -> &LocalReaderSpec {
+        self.imp().spec.get().unwrap()
+    }
+
+    fn start(&self) {
+        let imp = self.imp();
+
+        let spec = self.spec().clone();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let (tx, mut rx) = mpsc::unbounded::<String>();
+
+        let thread_stop_flag = stop_flag.clone();
+        let thread_ret = thread::Builder::new()
+            .name(format!("local-reader-{}", spec.path))
+            .spawn(move || {
+                while !thread_stop_flag.load(Ordering::Relaxed) {
+                    if let Err(err) = read_device(&spec, &thread_stop_flag, &tx) {
+                        tracing::warn!("Failed to read from `{}`: {:?}", spec.path, err);
+                        thread::sleep(REOPEN_DELAY);
+                    }
+                }
+            });
+        if let Err(err) = thread_ret {
+            tracing::error!("Failed to spawn reader thread: {:?}", err);
+            return;
+        }
+
+        let handle = glib::spawn_future_local(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            async move {
+                while let Some(raw_id) = rx.next().await {
+                    match obj.spec().normalization.apply(&raw_id) {
+                        Ok(id) => {
+                            tracing::debug!("Read `{}` as `{}`", raw_id, id);
+                            obj.emit_by_name::<()>("detected", &[&id]);
+                        }
+                        Err(err) => {
+                            tracing::warn!("Failed to normalize ID: {:?}", err);
+                        }
+                    }
+                }
+            }
+        ));
+
+        imp.stop_flag.replace(Some(stop_flag));
+        imp.handle.replace(Some(handle));
+    }
+
+    fn stop(&self) {
+        let imp = self.imp();
+
+        if let Some(stop_flag) = imp.stop_flag.take() {
+            stop_flag.store(true, Ordering::Relaxed);
+        }
+
+        if let Some(handle) = imp.handle.take() {
+            handle.abort();
+        }
+    }
+}
+
+pub trait LocalReaderSettingsExt {
+    fn local_reader_specs(&self) -> Vec<LocalReaderSpec>;
+}
+
+impl LocalReaderSettingsExt for Settings {
+    fn local_reader_specs(&self) -> Vec<LocalReaderSpec> {
+        self.local_reader_devices()
+            .iter()
+            .filter_map(|s| {
+                s.parse::<LocalReaderSpec>()
+                    .inspect_err(|err| tracing::warn!("Failed to parse local reader: {:?}", err))
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+/// Reads IDs from the device until it is stopped or fails.
+fn read_device(
+    spec: &LocalReaderSpec,
+    stop_flag: &AtomicBool,
+    tx: &mpsc::UnboundedSender<String>,
+) -> Result<()> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOCTTY)
+        .open(&spec.path)
+        .with_context(|| format!("Failed to open `{}`", spec.path))?;
+
+    tracing::debug!("Opened `{}`", spec.path);
+
+    match spec.kind {
+        LocalReaderKind::Serial { baud_rate } => {
+            configure_serial(file.as_raw_fd(), baud_rate)?;
+            read_serial(file, stop_flag, tx)
+        }
+        LocalReaderKind::Evdev => {
+            grab_evdev(file.as_raw_fd())?;
+            read_evdev(file, stop_flag, tx)
+        }
+    }
+}
+
+fn read_serial(
+    mut file: File,
+    stop_flag: &AtomicBool,
+    tx: &mpsc::UnboundedSender<String>,
+) -> Result<()> {
+    let mut line = Vec::new();
+    let mut buf = [0; 64];
+
+    while !stop_flag.load(Ordering::Relaxed) {
+        if !poll_readable(file.as_raw_fd())? {
+            continue;
+        }
+
+        let n_read = match file.read(&mut buf) {
+            Ok(0) => bail!("Device disconnected"),
+            Ok(n_read) => n_read,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        for byte in &buf[..n_read] {
+            match byte {
+                // STX
+                0x02 => line.clear(),
+                // ETX, LF, CR
+                0x03 | b'\n' | b'\r' => {
+                    let id = String::from_utf8_lossy(&line).trim().to_string();
+                    if !id.is_empty() {
+                        tx.unbounded_send(id)?;
+                    }
+                    line.clear();
+                }
+                byte => line.push(*byte),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_evdev(
+    mut file: File,
+    stop_flag: &AtomicBool,
+    tx: &mpsc::UnboundedSender<String>,
+) -> Result<()> {
+    const EVENT_SIZE: usize = std::mem::size_of::<libc::input_event>();
+
+    let mut line = String::new();
+    let mut buf = [0; EVENT_SIZE * 16];
+
+    while !stop_flag.load(Ordering::Relaxed) {
+        if !poll_readable(file.as_raw_fd())? {
+            continue;
+        }
+
+        let n_read = match file.read(&mut buf) {
+            Ok(0) => bail!("Device disconnected"),
+            Ok(n_read) => n_read,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        for chunk in buf[..n_read].chunks_exact(EVENT_SIZE) {
+            // SAFETY: The chunk has the size of an `input_event`, and the kernel
+            // only writes whole events.
+            let event =
+                unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const libc::input_event) };
+
+            if event.type_ != EV_KEY || event.value != KEY_PRESSED {
+                continue;
+            }
+
+            match key_code_to_char(event.code) {
+                Some('\n') if !line.is_empty() => {
+                    tx.unbounded_send(std::mem::take(&mut line))?;
+                }
+                Some('\n') | None => {}
+                Some(c) => line.push(c),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Maps the key codes typed by keyboard-wedge readers. Shift is ignored,
+/// so letters are always lowercase, like the hex IDs of the RFID reader.
+fn key_code_to_char(code: u16) -> Option<char> {
+    let c = match code {
+        2..=10 => (b'1' + (code - 2) as u8) as char,
+        11 => '0',
+        12 => '-',
+        16 => 'q',
+        17 => 'w',
+        18 => 'e',
+        19 => 'r',
+        20 => 't',
+        21 => 'y',
+        22 => 'u',
+        23 => 'i',
+        24 => 'o',
+        25 => 'p',
+        28 | 96 => '\n',
+        30 => 'a',
+        31 => 's',
+        32 => 'd',
+        33 => 'f',
+        34 => 'g',
+        35 => 'h',
+        36 => 'j',
+        37 => 'k',
+        38 => 'l',
+        44 => 'z',
+        45 => 'x',
+        46 => 'c',
+        47 => 'v',
+        48 => 'b',
+        49 => 'n',
+        50 => 'm',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Returns whether the fd is readable, waiting up to `POLL_TIMEOUT`.
+fn poll_readable(fd: RawFd) -> Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    // SAFETY: `poll_fd` is a valid pointer to a single `pollfd`.
+    let ret = unsafe { libc::poll(&mut poll_fd, 1, POLL_TIMEOUT.as_millis() as libc::c_int) };
+    match ret {
+        -1 => {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                Ok(false)
+            } else {
+                Err(err.into())
+            }
+        }
+        0 => Ok(false),
+        _ => {
+            ensure!(
+                poll_fd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) == 0,
+                "Device disconnected"
+            );
+            Ok(true)
+        }
+    }
+}
+
+fn configure_serial(fd: RawFd, baud_rate: u32) -> Result<()> {
+    let speed = match baud_rate {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        _ => bail!("Unsupported baud rate `{}`", baud_rate),
+    };
+
+    // SAFETY: `termios` is a plain C struct that is fully initialized by `tcgetattr`.
+    unsafe {
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to get serial attributes");
+        }
+
+        libc::cfmakeraw(&mut termios);
+        libc::cfsetispeed(&mut termios, speed);
+        libc::cfsetospeed(&mut termios, speed);
+        termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to set serial attributes");
+        }
+    }
+
+    Ok(())
+}
+
+/// Grabs the device so that the typed IDs are not received by other clients,
+/// including the UI.
+fn grab_evdev(fd: RawFd) -> Result<()> {
+    // SAFETY: `EVIOCGRAB` takes an int argument.
+    let ret = unsafe { libc::ioctl(fd, EVIOCGRAB as _, 1 as libc::c_int) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error()).context("Failed to grab evdev device");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spec() {
+        assert_eq!(
+            "serial:/dev/ttyUSB0".parse::<LocalReaderSpec>().unwrap(),
+            LocalReaderSpec {
+                kind: LocalReaderKind::Serial {
+                    baud_rate: DEFAULT_BAUD_RATE
+                },
+                path: "/dev/ttyUSB0".to_string(),
+                normalization: IdNormalization::default(),
+            }
+        );
+        assert_eq!(
+            "evdev:/dev/input/event3, from=dec, to=hex, bytes=3, reverse"
+                .parse::<LocalReaderSpec>()
+                .unwrap(),
+            LocalReaderSpec {
+                kind: LocalReaderKind::Evdev,
+                path: "/dev/input/event3".to_string(),
+                normalization: IdNormalization {
+                    from: Some(IdBase::Decimal),
+                    to: IdBase::Hex,
+                    reverse_bytes: true,
+                    n_bytes: 3,
+                },
+            }
+        );
+
+        assert!("/dev/ttyUSB0".parse::<LocalReaderSpec>().is_err());
+        assert!("serial:".parse::<LocalReaderSpec>().is_err());
+        assert!("usb:/dev/ttyUSB0".parse::<LocalReaderSpec>().is_err());
+        assert!("serial:/dev/ttyUSB0,baud=fast"
+            .parse::<LocalReaderSpec>()
+            .is_err());
+        assert!("serial:/dev/ttyUSB0,from=oct"
+            .parse::<LocalReaderSpec>()
+            .is_err());
+        assert!("serial:/dev/ttyUSB0,unknown"
+            .parse::<LocalReaderSpec>()
+            .is_err());
+        assert!("serial:/dev/ttyUSB0,bytes=many"
+            .parse::<LocalReaderSpec>()
+            .is_err());
+        assert!("serial:/dev/ttyUSB0,bytes=0"
+            .parse::<LocalReaderSpec>()
+            .is_err());
+        assert!("serial:/dev/ttyUSB0,bytes=9"
+            .parse::<LocalReaderSpec>()
+            .is_err());
+    }
+
+    #[test]
+    fn normalize_id() {
+        let normalization = |from, to, reverse_bytes| IdNormalization {
+            from,
+            to,
+            reverse_bytes,
+            n_bytes: 4,
+        };
+
+        assert_eq!(
+            normalization(None, IdBase::Hex, true)
+                .apply(" abc ")
+                .unwrap(),
+            "abc"
+        );
+        assert_eq!(
+            normalization(Some(IdBase::Decimal), IdBase::Hex, false)
+                .apply("0012345678")
+                .unwrap(),
+            "00bc614e"
+        );
+        assert_eq!(
+            normalization(Some(IdBase::Decimal), IdBase::Hex, true)
+                .apply("0012345678")
+                .unwrap(),
+            "4e61bc00"
+        );
+        assert_eq!(
+            normalization(Some(IdBase::Hex), IdBase::Decimal, true)
+                .apply("4e61bc00")
+                .unwrap(),
+            "12345678"
+        );
+        assert_eq!(
+            normalization(Some(IdBase::Hex), IdBase::Hex, false)
+                .apply("BC614E")
+                .unwrap(),
+            "00bc614e"
+        );
+
+        assert!(normalization(Some(IdBase::Decimal), IdBase::Hex, false)
+            .apply("12ab")
+            .is_err());
+        assert!(normalization(Some(IdBase::Hex), IdBase::Hex, false)
+            .apply("0102030405")
+            .is_err());
+    }
+
+    #[test]
+    fn key_codes() {
+        let typed = [2, 11, 10, 30, 50, 12]
+            .into_iter()
+            .filter_map(key_code_to_char)
+            .collect::<String>();
+        assert_eq!(typed, "109am-");
+
+        assert_eq!(key_code_to_char(28), Some('\n'));
+        assert_eq!(key_code_to_char(42), None); // Left shift
+    }
+}
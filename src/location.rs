@@ -0,0 +1,98 @@
+use std::fmt;
+
+use gtk::{glib, prelude::*, subclass::prelude::*};
+
+use crate::{location_data::LocationData, location_id::LocationId, log::Log};
+
+mod imp {
+    use std::{
+        cell::{OnceCell, RefCell},
+        marker::PhantomData,
+    };
+
+    use super::*;
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::Location)]
+    pub struct Location {
+        #[property(get, set = Self::set_data, explicit_notify)]
+        pub(super) data: RefCell<LocationData>,
+        /// Number of entities inside the location, including those in the
+        /// locations it contains.
+        #[property(get = Self::n_inside)]
+        pub(super) n_inside: PhantomData<u32>,
+
+        pub(super) id: OnceCell<LocationId>,
+
+        pub(super) n_inside_log: RefCell<Log<u32>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Location {
+        const NAME: &'static str = "UetsLocation";
+        type Type = super::Location;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Location {}
+
+    impl Location {
+        fn set_data(&self, data: LocationData) {
+            let obj = self.obj();
+
+            if data == *self.data.borrow() {
+                return;
+            }
+
+            self.data.replace(data);
+            obj.notify_data();
+        }
+
+        fn n_inside(&self) -> u32 {
+            self.n_inside_log.borrow().latest().copied().unwrap_or(0)
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Location(ObjectSubclass<imp::Location>);
+}
+
+impl Location {
+    pub fn new(id: LocationId, data: LocationData) -> Self {
+        let this = glib::Object::builder::<Self>()
+            .property("data", data)
+            .build();
+
+        let imp = this.imp();
+        imp.id.set(id).unwrap();
+
+        this
+    }
+
+    pub fn id(&self) -> &LocationId {
+        self.imp().id.get().unwrap()
+    }
+
+    pub fn is_over_capacity(&self) -> bool {
+        self.data()
+            .capacity
+            .is_some_and(|capacity| self.n_inside() > capacity)
+    }
+
+    pub fn with_n_inside_log_mut(&self, f: impl FnOnce(&mut Log<u32>)) {
+        let prev_n_inside = self.n_inside();
+
+        f(&mut self.imp().n_inside_log.borrow_mut());
+
+        if prev_n_inside != self.n_inside() {
+            self.notify_n_inside();
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Location").field("id", self.id()).finish()
+    }
+}
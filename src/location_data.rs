@@ -0,0 +1,11 @@
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, glib::Boxed)]
+#[boxed_type(name = "UetsLocationData")]
+pub struct LocationData {
+    /// Maximum number of entities inside the location, including those in
+    /// the locations it contains. If not set, there is no limit.
+    #[serde(default)]
+    pub capacity: Option<u32>,
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+
+const SEPARATOR: char = '/';
+
+/// Path of a location in the hierarchy, from the outermost, e.g.,
+/// "Warehouse A/Aisle 3/Shelf 2".
+///
+/// Segments may also be separated by ">" when parsed, so "Warehouse A >
+/// Aisle 3" is the same location as "Warehouse A/Aisle 3".
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, glib::Boxed)]
+#[serde(transparent)]
+#[boxed_type(name = "UetsLocationId")]
+pub struct LocationId(Box<str>);
+
+impl LocationId {
+    /// Returns `None` if the path has no segment.
+    pub fn parse(path: &str) -> Option<Self> {
+        let path = path
+            .split([SEPARATOR, '>'])
+            .map(|segment| segment.trim())
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join(&SEPARATOR.to_string());
+
+        (!path.is_empty()).then(|| Self(path.into()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the last segment, e.g., "Shelf 2".
+    pub fn name(&self) -> &str {
+        self.0.rsplit(SEPARATOR).next().unwrap()
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        self.0
+            .rsplit_once(SEPARATOR)
+            .map(|(parent, _)| Self(parent.into()))
+    }
+
+    /// Returns this and the locations containing it, innermost first.
+    pub fn ancestors(&self) -> impl Iterator<Item = Self> {
+        std::iter::successors(Some(self.clone()), |id| id.parent())
+    }
+
+    /// Whether `other` is this or inside it.
+    pub fn contains(&self, other: &LocationId) -> bool {
+        other
+            .0
+            .strip_prefix(&*self.0)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(SEPARATOR))
+    }
+}
+
+impl fmt::Debug for LocationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for LocationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l(s: &str) -> LocationId {
+        LocationId::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(l("Warehouse A").as_str(), "Warehouse A");
+        assert_eq!(
+            l(" Warehouse A / Aisle 3/Shelf 2 ").as_str(),
+            "Warehouse A/Aisle 3/Shelf 2"
+        );
+        assert_eq!(l("Warehouse A > Aisle 3").as_str(), "Warehouse A/Aisle 3");
+        assert_eq!(l("Warehouse A//Aisle 3/").as_str(), "Warehouse A/Aisle 3");
+        assert_eq!(LocationId::parse(""), None);
+        assert_eq!(LocationId::parse(" / > "), None);
+    }
+
+    #[test]
+    fn hierarchy() {
+        let shelf = l("Warehouse A/Aisle 3/Shelf 2");
+        assert_eq!(shelf.name(), "Shelf 2");
+        assert_eq!(shelf.parent(), Some(l("Warehouse A/Aisle 3")));
+        assert_eq!(
+            shelf.ancestors().collect::<Vec<_>>(),
+            [
+                l("Warehouse A/Aisle 3/Shelf 2"),
+                l("Warehouse A/Aisle 3"),
+                l("Warehouse A")
+            ]
+        );
+
+        let warehouse = l("Warehouse A");
+        assert_eq!(warehouse.name(), "Warehouse A");
+        assert_eq!(warehouse.parent(), None);
+
+        assert!(warehouse.contains(&shelf));
+        assert!(warehouse.contains(&warehouse));
+        assert!(!shelf.contains(&warehouse));
+        assert!(!warehouse.contains(&l("Warehouse AB")));
+        assert!(!l("Warehouse A/Aisle 3").contains(&l("Warehouse A/Aisle 30")));
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use gtk::{gio, glib, prelude::*, subclass::prelude::*};
+use indexmap::{map::Entry, IndexMap};
+
+use crate::{location::Location, location_id::LocationId};
+
+mod imp {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct LocationList {
+        pub(super) list: RefCell<IndexMap<LocationId, Location>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for LocationList {
+        const NAME: &'static str = "UetsLocationList";
+        type Type = super::LocationList;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    impl ObjectImpl for LocationList {}
+
+    impl ListModelImpl for LocationList {
+        fn item_type(&self) -> glib::Type {
+            Location::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.list.borrow().len() as u32
+        }
+
+        fn item(&self, position: u32) -> Option<glib::Object> {
+            self.list
+                .borrow()
+                .get_index(position as usize)
+                .map(|(_, v)| v.upcast_ref::<glib::Object>())
+                .cloned()
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct LocationList(ObjectSubclass<imp::LocationList>)
+        @implements gio::ListModel;
+}
+
+impl LocationList {
+    pub fn from_raw(value: IndexMap<LocationId, Location>) -> Self {
+        let this = glib::Object::new::<Self>();
+
+        let imp = this.imp();
+        imp.list.replace(value);
+
+        this
+    }
+
+    pub fn contains(&self, id: &LocationId) -> bool {
+        self.imp().list.borrow().contains_key(id)
+    }
+
+    pub fn get(&self, id: &LocationId) -> Option<Location> {
+        self.imp().list.borrow().get(id).cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Location> + '_ {
+        ListModelExtManual::iter(self).map(|item| item.unwrap())
+    }
+
+    pub fn insert(&self, location: Location) -> bool {
+        let imp = self.imp();
+
+        let (index, removed, added) = match imp.list.borrow_mut().entry(location.id().clone()) {
+            Entry::Occupied(entry) => (entry.index(), 1, 1),
+            Entry::Vacant(entry) => {
+                let index = entry.index();
+                entry.insert(location);
+                (index, 0, 1)
+            }
+        };
+
+        self.items_changed(index as u32, removed, added);
+
+        removed == 0
+    }
+
+    pub fn insert_many(&self, locations: Vec<Location>) -> u32 {
+        let mut updated_indices = HashSet::new();
+        let mut n_appended = 0;
+
+        {
+            let mut list = self.imp().list.borrow_mut();
+
+            for location in locations {
+                let (index, prev_value) = list.insert_full(location.id().clone(), location);
+
+                if prev_value.is_some() {
+                    updated_indices.insert(index);
+                } else {
+                    n_appended += 1;
+                }
+            }
+        }
+
+        let index_of_first_append = self.n_items() - n_appended;
+
+        // Emit about the appended items first, so GTK would know about
+        // the new items and it won't error out because the n_items
+        // does not match what GTK expect
+        if n_appended != 0 {
+            self.items_changed(index_of_first_append, 0, n_appended);
+        }
+
+        // This is emitted individually because each updated item
+        // may be on different indices
+        for index in updated_indices {
+            // Only emit if the updated item is before the first appended item
+            // because it is already handled by the emission above
+            if (index as u32) < index_of_first_append {
+                self.items_changed(index as u32, 1, 1);
+            }
+        }
+
+        n_appended
+    }
+
+    pub fn clear(&self) {
+        let imp = self.imp();
+
+        let prev_len = imp.list.borrow().len();
+
+        if prev_len == 0 {
+            return;
+        }
+
+        imp.list.borrow_mut().clear();
+        self.items_changed(0, prev_len as u32, 0);
+    }
+}
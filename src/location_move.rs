@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+
+use crate::{db, entity_id::EntityId, location_id::LocationId};
+
+/// A record of an entity being moved from one location to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationMove {
+    pub dt: DateTime<Utc>,
+    pub entity_id: EntityId,
+    pub from: Option<LocationId>,
+    pub to: Option<LocationId>,
+}
+
+impl LocationMove {
+    pub fn from_db((dt, entity_id): (DateTime<Utc>, EntityId), raw: db::RawLocationMove) -> Self {
+        Self {
+            dt,
+            entity_id,
+            from: raw.from,
+            to: raw.to,
+        }
+    }
+
+    pub fn db_key(&self) -> (DateTime<Utc>, EntityId) {
+        (self.dt, self.entity_id.clone())
+    }
+
+    pub fn to_db(&self) -> db::RawLocationMove {
+        db::RawLocationMove {
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }
+    }
+}
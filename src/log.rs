@@ -20,6 +20,10 @@ impl<T> Log<T> {
         }
     }
 
+    pub fn earliest_dt(&self) -> Option<DateTime<Utc>> {
+        self.map.first_key_value().map(|(dt, _)| *dt)
+    }
+
     pub fn latest_dt(&self) -> Option<DateTime<Utc>> {
         self.map.last_key_value().map(|(dt, _)| *dt)
     }
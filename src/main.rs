@@ -11,16 +11,24 @@
 mod ai_chat_message;
 mod ai_chat_message_list;
 mod application;
+mod badge;
 mod camera;
+mod camera_profile;
+mod clock;
+mod code_parser;
 mod colors;
 mod config;
+mod custom_field;
 mod date_time;
 mod date_time_boxed;
 mod date_time_range;
 mod date_time_updater;
 mod db;
+mod debounce;
 mod detected_wo_id_item;
 mod detected_wo_id_list;
+mod detection_replay;
+mod detection_trace;
 mod detector;
 mod entity;
 mod entity_data;
@@ -29,16 +37,31 @@ mod entity_expiration;
 mod entity_expired_tracker;
 mod entity_id;
 mod entity_list;
+mod event_clip;
+mod fefo;
 mod format;
 mod fuzzy_filter;
 mod fuzzy_sorter;
+mod gs1;
 mod jpeg_image;
 mod limit_reached;
+mod local_reader;
+mod location;
+mod location_data;
+mod location_id;
+mod location_list;
+mod location_move;
 mod log;
 mod md2pango;
+mod motion_detector;
+mod mqtt_client;
 mod operation_mode_ext;
+mod pending_detection;
+mod pending_detection_list;
+mod quantity;
 mod relay;
 mod remote;
+mod reorder;
 mod report;
 mod report_table;
 mod rfid_reader;
@@ -47,20 +70,27 @@ mod search_query_ext;
 mod settings;
 mod sex;
 mod signal_handler_id_group;
+mod signed_qr;
 mod sound;
 mod stock;
 mod stock_data;
 mod stock_id;
 mod stock_limit_reached_tracker;
 mod stock_list;
+mod tailgating;
 mod time_graph;
 mod timeline;
 mod timeline_ext;
 mod timeline_item;
 mod timeline_item_kind;
+mod traffic_generator;
 mod ui;
 mod utils;
+mod valuation;
+mod webhook;
 mod wormhole_ext;
+mod zone;
+mod zone_move;
 
 use std::path::Path;
 
@@ -0,0 +1,267 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, ensure, Context, Error, Result};
+
+use crate::settings::Settings;
+
+/// Width of the grayscale frames fed into the detector.
+pub const FRAME_WIDTH: usize = 160;
+/// Height of the grayscale frames fed into the detector.
+pub const FRAME_HEIGHT: usize = 120;
+/// Rate of the frames fed into the detector.
+pub const FRAME_RATE: u32 = 5;
+
+/// Number of consecutive still frames before motion is considered inactive.
+const N_STILL_FRAMES_UNTIL_INACTIVE: u32 = 2 * FRAME_RATE;
+
+/// A region of interest with coordinates relative to the frame size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Region {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl FromStr for Region {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let values = s
+            .split(',')
+            .map(|v| {
+                v.trim()
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid region value `{}`", v))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let [x, y, width, height] = values.as_slice() else {
+            bail!("Expected `x,y,width,height`, got `{}`", s);
+        };
+
+        ensure!(
+            [x, y, width, height]
+                .iter()
+                .all(|v| (0.0..=1.0).contains(*v)),
+            "Region values must be within 0 and 1"
+        );
+
+        Ok(Self {
+            x: *x,
+            y: *y,
+            width: *width,
+            height: *height,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionDetectorConfig {
+    /// From 0 (least sensitive) to 1 (most sensitive).
+    pub sensitivity: f64,
+    /// Regions where motion is detected. If empty, the whole frame is used.
+    pub regions: Vec<Region>,
+}
+
+impl MotionDetectorConfig {
+    fn pixel_diff_threshold(&self) -> u8 {
+        (60.0 - 50.0 * self.sensitivity.clamp(0.0, 1.0)).round() as u8
+    }
+
+    fn changed_ratio_threshold(&self) -> f64 {
+        0.1 - 0.095 * self.sensitivity.clamp(0.0, 1.0)
+    }
+}
+
+/// Detects motion by differencing consecutive grayscale frames.
+pub struct MotionDetector {
+    config: MotionDetectorConfig,
+    mask: Vec<bool>,
+    n_mask_pixels: usize,
+
+    prev_frame: Option<Vec<u8>>,
+    is_active: bool,
+    n_still_frames: u32,
+}
+
+impl MotionDetector {
+    pub fn new(config: MotionDetectorConfig) -> Self {
+        let mask = (0..FRAME_WIDTH * FRAME_HEIGHT)
+            .map(|i| {
+                if config.regions.is_empty() {
+                    return true;
+                }
+
+                let x = (i % FRAME_WIDTH) as f64 / FRAME_WIDTH as f64;
+                let y = (i / FRAME_WIDTH) as f64 / FRAME_HEIGHT as f64;
+                config.regions.iter().any(|r| r.contains(x, y))
+            })
+            .collect::<Vec<_>>();
+        let n_mask_pixels = mask.iter().filter(|m| **m).count();
+
+        Self {
+            config,
+            mask,
+            n_mask_pixels,
+            prev_frame: None,
+            is_active: false,
+            n_still_frames: 0,
+        }
+    }
+
+    /// Processes a `FRAME_WIDTH`x`FRAME_HEIGHT` GRAY8 frame.
+    ///
+    /// Returns the new motion state if it changed.
+    pub fn process_frame(&mut self, frame: &[u8]) -> Option<bool> {
+        if frame.len() < FRAME_WIDTH * FRAME_HEIGHT {
+            tracing::warn!("Received frame with unexpected size {}", frame.len());
+            return None;
+        }
+
+        let frame = &frame[..FRAME_WIDTH * FRAME_HEIGHT];
+
+        let prev_frame = self.prev_frame.replace(frame.to_vec())?;
+
+        if self.n_mask_pixels == 0 {
+            return None;
+        }
+
+        let pixel_diff_threshold = self.config.pixel_diff_threshold();
+        let n_changed = frame
+            .iter()
+            .zip(prev_frame.iter())
+            .zip(self.mask.iter())
+            .filter(|((a, b), is_masked)| **is_masked && a.abs_diff(**b) > pixel_diff_threshold)
+            .count();
+        let changed_ratio = n_changed as f64 / self.n_mask_pixels as f64;
+
+        let has_motion = changed_ratio >= self.config.changed_ratio_threshold();
+
+        if has_motion {
+            self.n_still_frames = 0;
+
+            if !self.is_active {
+                self.is_active = true;
+                return Some(true);
+            }
+        } else if self.is_active {
+            self.n_still_frames += 1;
+
+            if self.n_still_frames >= N_STILL_FRAMES_UNTIL_INACTIVE {
+                self.is_active = false;
+                self.n_still_frames = 0;
+                return Some(false);
+            }
+        }
+
+        None
+    }
+}
+
+pub trait MotionDetectorSettingsExt {
+    /// Returns `None` if local motion detection is disabled.
+    fn local_motion_detector_config(&self) -> Option<MotionDetectorConfig>;
+}
+
+impl MotionDetectorSettingsExt for Settings {
+    fn local_motion_detector_config(&self) -> Option<MotionDetectorConfig> {
+        if !self.enable_local_motion_detection() {
+            return None;
+        }
+
+        let regions = self
+            .local_motion_detection_regions()
+            .iter()
+            .filter_map(|s| {
+                s.parse::<Region>()
+                    .inspect_err(|err| tracing::warn!("Failed to parse region: {:?}", err))
+                    .ok()
+            })
+            .collect();
+
+        Some(MotionDetectorConfig {
+            sensitivity: self.local_motion_detection_sensitivity(),
+            regions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(value: u8) -> Vec<u8> {
+        vec![value; FRAME_WIDTH * FRAME_HEIGHT]
+    }
+
+    fn config(regions: Vec<Region>) -> MotionDetectorConfig {
+        MotionDetectorConfig {
+            sensitivity: 0.5,
+            regions,
+        }
+    }
+
+    #[test]
+    fn parse_region() {
+        assert_eq!(
+            Region {
+                x: 0.0,
+                y: 0.25,
+                width: 0.5,
+                height: 1.0,
+            },
+            " 0, 0.25 ,0.5,1 ".parse().unwrap()
+        );
+
+        assert!("0,0,1".parse::<Region>().is_err());
+        assert!("0,0,1,2".parse::<Region>().is_err());
+        assert!("a,0,1,1".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn motion_state_changes() {
+        let mut detector = MotionDetector::new(config(Vec::new()));
+
+        assert_eq!(detector.process_frame(&frame(0)), None);
+        assert_eq!(detector.process_frame(&frame(0)), None);
+        assert_eq!(detector.process_frame(&frame(255)), Some(true));
+        assert_eq!(detector.process_frame(&frame(0)), None);
+
+        for _ in 0..N_STILL_FRAMES_UNTIL_INACTIVE - 1 {
+            assert_eq!(detector.process_frame(&frame(0)), None);
+        }
+        assert_eq!(detector.process_frame(&frame(0)), Some(false));
+    }
+
+    #[test]
+    fn motion_outside_regions_is_ignored() {
+        let mut detector = MotionDetector::new(config(vec![Region {
+            x: 0.5,
+            y: 0.0,
+            width: 0.5,
+            height: 1.0,
+        }]));
+
+        let mut left_changed = frame(0);
+        for row in left_changed.chunks_mut(FRAME_WIDTH) {
+            row[..FRAME_WIDTH / 2].fill(255);
+        }
+
+        assert_eq!(detector.process_frame(&frame(0)), None);
+        assert_eq!(detector.process_frame(&left_changed), None);
+        assert_eq!(detector.process_frame(&frame(255)), Some(true));
+    }
+}
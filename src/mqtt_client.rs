@@ -0,0 +1,791 @@
+use std::{
+    cell::Cell,
+    net::Shutdown,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use async_net::TcpStream;
+use futures_channel::mpsc;
+use futures_util::{future, AsyncReadExt, AsyncWriteExt, StreamExt};
+use gtk::{
+    glib::{self, clone, closure_local},
+    prelude::*,
+    subclass::prelude::*,
+};
+
+use crate::{remote::Remote, settings::Settings, signal_handler_id_group::SignalHandlerIdGroup};
+
+const DEFAULT_PORT: u16 = 1883;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+const PROTOCOL_LEVEL: u8 = 4;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xC0;
+const PINGRESP: u8 = 0xD0;
+
+const SUBSCRIBE_PACKET_ID: u16 = 1;
+
+/// Topics relative to the configured prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MqttTopic {
+    /// Published on every timeline item as JSON.
+    Timeline,
+    /// Published (retained) whenever the number of inside entities changes.
+    NInside,
+    /// Published (retained) whenever the number of overstayed entities changes.
+    Overstayed,
+    /// Published (retained) whenever the stock limit states change as JSON.
+    StockLimit,
+    /// Subscribed to for remote detections, with the entity ID as the payload.
+    /// Readers in zones publish to `detect/<reader>` instead.
+    Detect,
+    /// Subscribed to for relay commands, with `high` or `low` as the payload.
+    RelaySet,
+}
+
+impl MqttTopic {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Timeline => "timeline",
+            Self::NInside => "n-inside",
+            Self::Overstayed => "overstayed",
+            Self::StockLimit => "stock-limit",
+            Self::Detect => "detect",
+            Self::RelaySet => "relay/set",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+}
+
+impl MqttConfig {
+    pub fn topic(&self, topic: MqttTopic) -> String {
+        let prefix = self.topic_prefix.trim_end_matches('/');
+
+        if prefix.is_empty() {
+            topic.as_str().to_string()
+        } else {
+            format!("{}/{}", prefix, topic.as_str())
+        }
+    }
+
+    /// Returns the reader that published a detection on `topic`, which is
+    /// `Some(None)` for the detect topic itself, or `None` if `topic` is not
+    /// a detect topic.
+    pub fn detect_reader<'a>(&self, topic: &'a str) -> Option<Option<&'a str>> {
+        let suffix = topic.strip_prefix(&self.topic(MqttTopic::Detect))?;
+
+        if suffix.is_empty() {
+            return Some(None);
+        }
+
+        let reader = suffix.strip_prefix('/')?;
+        (!reader.is_empty() && !reader.contains('/')).then_some(Some(reader))
+    }
+}
+
+pub trait MqttSettingsExt {
+    /// Returns `None` if there is no broker configured.
+    fn mqtt_config(&self) -> Option<MqttConfig>;
+
+    fn connect_mqtt_config_changed(&self, f: impl Fn(&Self) + 'static) -> SignalHandlerIdGroup;
+}
+
+impl MqttSettingsExt for Settings {
+    fn mqtt_config(&self) -> Option<MqttConfig> {
+        let (host, port) = parse_broker_addr(&self.mqtt_broker_addr())?;
+
+        let username = self.mqtt_username();
+        let password = self.mqtt_password();
+
+        Some(MqttConfig {
+            host,
+            port,
+            username: (!username.is_empty()).then_some(username),
+            password: (!password.is_empty()).then_some(password),
+            topic_prefix: self.mqtt_topic_prefix(),
+        })
+    }
+
+    fn connect_mqtt_config_changed(&self, f: impl Fn(&Self) + 'static) -> SignalHandlerIdGroup {
+        let handler_ids = SignalHandlerIdGroup::new();
+
+        let f = Rc::new(f);
+
+        let handler_id = self.connect_mqtt_broker_addr_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        let handler_id = self.connect_mqtt_username_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        let handler_id = self.connect_mqtt_password_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        let handler_id = self.connect_mqtt_topic_prefix_changed(clone!(
+            #[strong]
+            f,
+            move |s| f(s)
+        ));
+        handler_ids.add(handler_id);
+
+        handler_ids
+    }
+}
+
+mod imp {
+    use std::{cell::RefCell, sync::OnceLock};
+
+    use glib::{subclass::Signal, JoinHandle};
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct MqttClient {
+        pub(super) config: RefCell<Option<MqttConfig>>,
+        pub(super) stream: RefCell<Option<TcpStream>>,
+        /// Feeds the only writer of the stream, so packets never interleave.
+        pub(super) packet_tx: RefCell<Option<mpsc::UnboundedSender<Vec<u8>>>>,
+        pub(super) handle: RefCell<Option<JoinHandle<()>>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MqttClient {
+        const NAME: &'static str = "UetsMqttClient";
+        type Type = super::MqttClient;
+    }
+
+    impl ObjectImpl for MqttClient {
+        fn dispose(&self) {
+            let obj = self.obj();
+
+            obj.disconnect();
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+
+            SIGNALS.get_or_init(|| {
+                vec![
+                    Signal::builder("connected").build(),
+                    Signal::builder("message-received")
+                        .param_types([String::static_type(), String::static_type()])
+                        .build(),
+                ]
+            })
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct MqttClient(ObjectSubclass<imp::MqttClient>);
+}
+
+impl MqttClient {
+    pub fn new(config: Option<MqttConfig>) -> Self {
+        let this = glib::Object::new::<Self>();
+
+        this.set_config(config);
+
+        this
+    }
+
+    pub fn connect_connected<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self) + 'static,
+    {
+        self.connect_closure("connected", false, closure_local!(|obj: &Self| f(obj)))
+    }
+
+    pub fn connect_message_received<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str) + 'static,
+    {
+        self.connect_closure(
+            "message-received",
+            false,
+            closure_local!(|obj: &Self, topic: &str, payload: &str| f(obj, topic, payload)),
+        )
+    }
+
+    /// Replaces the config and reconnects, or disconnects if `None`.
+    pub fn set_config(&self, config: Option<MqttConfig>) {
+        let imp = self.imp();
+
+        self.disconnect();
+
+        let is_configured = config.is_some();
+        imp.config.replace(config);
+
+        if is_configured {
+            self.connect();
+        }
+    }
+
+    /// Returns the full topic, or `None` if there is no broker configured.
+    pub fn topic(&self, topic: MqttTopic) -> Option<String> {
+        self.imp()
+            .config
+            .borrow()
+            .as_ref()
+            .map(|config| config.topic(topic))
+    }
+
+    /// Returns the reader that published a detection on `topic`, or `None`
+    /// if `topic` is not a detect topic or there is no broker configured.
+    pub fn detect_reader<'a>(&self, topic: &'a str) -> Option<Option<&'a str>> {
+        self.imp()
+            .config
+            .borrow()
+            .as_ref()
+            .and_then(|config| config.detect_reader(topic))
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.imp().config.borrow().is_some()
+    }
+
+    /// Publishes in the background with QoS 0.
+    ///
+    /// This is a no-op if there is no connection to the broker.
+    pub fn publish(&self, topic: MqttTopic, payload: &str, retain: bool) {
+        let imp = self.imp();
+
+        let Some(topic) = self.topic(topic) else {
+            return;
+        };
+
+        let Some(packet_tx) = imp.packet_tx.borrow().clone() else {
+            tracing::trace!("Not connected; dropping message for `{}`", topic);
+            return;
+        };
+
+        let packet = encode_publish(&topic, payload.as_bytes(), retain);
+        if packet_tx.unbounded_send(packet).is_err() {
+            tracing::trace!("Disconnected; dropping message for `{}`", topic);
+        }
+    }
+
+    fn connect(&self) {
+        let imp = self.imp();
+
+        let handle = glib::spawn_future_local(clone!(
+            #[strong(rename_to = obj)]
+            self,
+            async move {
+                loop {
+                    if let Err(err) = obj.connect_inner().await {
+                        tracing::error!("MQTT connection failed: {:?}", err);
+                    }
+
+                    obj.imp().stream.replace(None);
+                    obj.imp().packet_tx.replace(None);
+
+                    glib::timeout_future(RECONNECT_DELAY).await;
+                }
+            }
+        ));
+        imp.handle.replace(Some(handle));
+    }
+
+    async fn connect_inner(&self) -> Result<()> {
+        let imp = self.imp();
+
+        let config = imp.config.borrow().clone().context("No config")?;
+        tracing::debug!("Trying to connect to {}:{}", config.host, config.port);
+
+        let mut stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+
+        let client_id = format!("uets-{}", &glib::uuid_string_random()[..8]);
+        stream
+            .write_all(&encode_connect(
+                &client_id,
+                config.username.as_deref(),
+                config.password.as_deref(),
+                KEEP_ALIVE.as_secs() as u16,
+            ))
+            .await?;
+
+        let (header, body) = read_packet(&mut stream).await?;
+        match decode_packet(header, &body)? {
+            Packet::ConnAck { return_code: 0 } => {}
+            Packet::ConnAck { return_code } => {
+                bail!("Connection refused with return code {}", return_code)
+            }
+            other => bail!("Expected CONNACK, got {:?}", other),
+        }
+
+        stream
+            .write_all(&encode_subscribe(
+                SUBSCRIBE_PACKET_ID,
+                &[
+                    &config.topic(MqttTopic::Detect),
+                    &format!("{}/+", config.topic(MqttTopic::Detect)),
+                    &config.topic(MqttTopic::RelaySet),
+                ],
+            ))
+            .await?;
+
+        let (packet_tx, packet_rx) = mpsc::unbounded();
+        imp.stream.replace(Some(stream.clone()));
+        imp.packet_tx.replace(Some(packet_tx.clone()));
+
+        tracing::debug!("Connected to {:?}", stream.peer_addr());
+        self.emit_by_name::<()>("connected", &[]);
+
+        let last_ping_resp_instant = Rc::new(Cell::new(Instant::now()));
+        future::try_join3(
+            write_loop(stream.clone(), packet_rx),
+            ping_loop(packet_tx, last_ping_resp_instant.clone()),
+            self.read_loop(stream, last_ping_resp_instant),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn read_loop(
+        &self,
+        mut stream: TcpStream,
+        last_ping_resp_instant: Rc<Cell<Instant>>,
+    ) -> Result<()> {
+        loop {
+            let (header, body) = read_packet(&mut stream).await?;
+            match decode_packet(header, &body)? {
+                Packet::Publish { topic, payload } => {
+                    let payload = String::from_utf8_lossy(&payload).into_owned();
+                    tracing::debug!("Received message on `{}`: {}", topic, payload);
+                    self.emit_by_name::<()>("message-received", &[&topic, &payload]);
+                }
+                Packet::ConnAck { .. } => bail!("Unexpected CONNACK"),
+                Packet::PingResp => last_ping_resp_instant.set(Instant::now()),
+                Packet::SubAck | Packet::Other(_) => {}
+            }
+        }
+    }
+
+    fn disconnect(&self) {
+        let imp = self.imp();
+
+        imp.packet_tx.take();
+
+        if let Some(stream) = imp.stream.take() {
+            if let Err(err) = stream.shutdown(Shutdown::Both) {
+                tracing::error!("Failed to shutdown stream: {:?}", err);
+            }
+        }
+
+        if let Some(prev_handle) = imp.handle.take() {
+            prev_handle.abort();
+        }
+    }
+}
+
+impl Remote for MqttClient {
+    fn ip_addr(&self) -> String {
+        self.imp()
+            .config
+            .borrow()
+            .as_ref()
+            .map(|config| config.host.clone())
+            .unwrap_or_default()
+    }
+
+    fn port(&self) -> u16 {
+        self.imp()
+            .config
+            .borrow()
+            .as_ref()
+            .map_or(DEFAULT_PORT, |config| config.port)
+    }
+}
+
+async fn write_loop(
+    mut stream: TcpStream,
+    mut packet_rx: mpsc::UnboundedReceiver<Vec<u8>>,
+) -> Result<()> {
+    while let Some(packet) = packet_rx.next().await {
+        stream.write_all(&packet).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends PINGREQs, failing if the broker stopped responding to them, e.g., on
+/// a half-open connection.
+async fn ping_loop(
+    packet_tx: mpsc::UnboundedSender<Vec<u8>>,
+    last_ping_resp_instant: Rc<Cell<Instant>>,
+) -> Result<()> {
+    loop {
+        glib::timeout_future(KEEP_ALIVE / 2).await;
+
+        ensure!(
+            last_ping_resp_instant.get().elapsed() < KEEP_ALIVE,
+            "No PINGRESP within {:?}",
+            KEEP_ALIVE
+        );
+
+        packet_tx
+            .unbounded_send(vec![PINGREQ, 0])
+            .context("Writer stopped")?;
+    }
+}
+
+/// Parses `host[:port]` or `[ipv6-addr][:port]`, returning `None` if it is
+/// empty or invalid.
+fn parse_broker_addr(addr: &str) -> Option<(String, u16)> {
+    let addr = addr.trim();
+
+    if addr.is_empty() {
+        return None;
+    }
+
+    let (host, port) = if let Some(rest) = addr.strip_prefix('[') {
+        let Some((host, rest)) = rest.split_once(']') else {
+            tracing::warn!("Unclosed bracket in MQTT broker address `{}`", addr);
+            return None;
+        };
+        match rest.strip_prefix(':') {
+            Some(port) => (host, Some(port)),
+            None if rest.is_empty() => (host, None),
+            None => {
+                tracing::warn!("Invalid MQTT broker address `{}`", addr);
+                return None;
+            }
+        }
+    } else if addr.matches(':').count() > 1 {
+        // A bare IPv6 address, which can't have a port without brackets
+        (addr, None)
+    } else {
+        match addr.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (addr, None),
+        }
+    };
+
+    let port = match port {
+        Some(port) => match port.parse() {
+            Ok(port) => port,
+            Err(_) => {
+                tracing::warn!("Invalid MQTT broker port `{}`", port);
+                return None;
+            }
+        },
+        None => DEFAULT_PORT,
+    };
+
+    Some((host.to_string(), port))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Packet {
+    ConnAck { return_code: u8 },
+    Publish { topic: String, payload: Vec<u8> },
+    SubAck,
+    PingResp,
+    Other(u8),
+}
+
+async fn read_packet(stream: &mut TcpStream) -> Result<(u8, Vec<u8>)> {
+    let mut byte = [0; 1];
+
+    stream.read_exact(&mut byte).await?;
+    let header = byte[0];
+
+    let mut remaining_len = 0;
+    for i in 0..4 {
+        stream.read_exact(&mut byte).await?;
+        remaining_len |= ((byte[0] & 0x7F) as usize) << (7 * i);
+
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+
+        ensure!(i < 3, "Malformed remaining length");
+    }
+
+    let mut body = vec![0; remaining_len];
+    stream.read_exact(&mut body).await?;
+
+    Ok((header, body))
+}
+
+fn decode_packet(header: u8, body: &[u8]) -> Result<Packet> {
+    match header & 0xF0 {
+        CONNACK => {
+            ensure!(body.len() == 2, "Invalid CONNACK length");
+            Ok(Packet::ConnAck {
+                return_code: body[1],
+            })
+        }
+        PUBLISH => {
+            let qos = (header >> 1) & 0x03;
+
+            let (topic, rest) = decode_str(body)?;
+            let payload = if qos > 0 {
+                rest.get(2..).context("Missing packet ID")?
+            } else {
+                rest
+            };
+
+            Ok(Packet::Publish {
+                topic,
+                payload: payload.to_vec(),
+            })
+        }
+        SUBACK => Ok(Packet::SubAck),
+        PINGRESP => Ok(Packet::PingResp),
+        other => Ok(Packet::Other(other)),
+    }
+}
+
+fn decode_str(bytes: &[u8]) -> Result<(String, &[u8])> {
+    ensure!(bytes.len() >= 2, "Missing string length");
+
+    let len = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+    let str_bytes = bytes.get(2..2 + len).context("Truncated string")?;
+
+    Ok((String::from_utf8(str_bytes.to_vec())?, &bytes[2 + len..]))
+}
+
+fn encode_remaining_len(mut len: usize, buf: &mut Vec<u8>) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+
+        if len > 0 {
+            byte |= 0x80;
+        }
+
+        buf.push(byte);
+
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn encode_str(s: &str, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn encode_packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    encode_remaining_len(body.len(), &mut packet);
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn encode_connect(
+    client_id: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+    keep_alive_secs: u16,
+) -> Vec<u8> {
+    let mut flags = 0x02; // Clean session
+    if username.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+
+    let mut body = Vec::new();
+    encode_str("MQTT", &mut body);
+    body.push(PROTOCOL_LEVEL);
+    body.push(flags);
+    body.extend_from_slice(&keep_alive_secs.to_be_bytes());
+
+    encode_str(client_id, &mut body);
+    if let Some(username) = username {
+        encode_str(username, &mut body);
+    }
+    if let Some(password) = password {
+        encode_str(password, &mut body);
+    }
+
+    encode_packet(CONNECT, &body)
+}
+
+fn encode_publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    encode_str(topic, &mut body);
+    body.extend_from_slice(payload);
+
+    encode_packet(PUBLISH | retain as u8, &body)
+}
+
+fn encode_subscribe(packet_id: u16, topics: &[&str]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&packet_id.to_be_bytes());
+
+    for topic in topics {
+        encode_str(topic, &mut body);
+        body.push(0); // QoS 0
+    }
+
+    encode_packet(SUBSCRIBE, &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_len() {
+        let encode = |len| {
+            let mut buf = Vec::new();
+            encode_remaining_len(len, &mut buf);
+            buf
+        };
+
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(127), [0x7F]);
+        assert_eq!(encode(128), [0x80, 0x01]);
+        assert_eq!(encode(16_383), [0xFF, 0x7F]);
+        assert_eq!(encode(16_384), [0x80, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn connect() {
+        assert_eq!(
+            encode_connect("a", None, None, 30),
+            [
+                0x10, 13, // Fixed header
+                0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 30, // Variable header
+                0, 1, b'a', // Client ID
+            ]
+        );
+
+        let packet = encode_connect("a", Some("u"), Some("p"), 30);
+        assert_eq!(packet[9], 0xC2);
+        assert_eq!(&packet[15..], [0, 1, b'u', 0, 1, b'p']);
+    }
+
+    #[test]
+    fn publish_roundtrip() {
+        let packet = encode_publish("uets/n-inside", b"3", true);
+        assert_eq!(packet[0], 0x31);
+        assert_eq!(
+            decode_packet(packet[0], &packet[2..]).unwrap(),
+            Packet::Publish {
+                topic: "uets/n-inside".into(),
+                payload: b"3".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn decode_publish_qos1() {
+        let body = [0, 1, b't', 0, 42, b'h', b'i'];
+        assert_eq!(
+            decode_packet(0x32, &body).unwrap(),
+            Packet::Publish {
+                topic: "t".into(),
+                payload: b"hi".to_vec(),
+            }
+        );
+        assert!(decode_packet(0x32, &[0, 1, b't']).is_err());
+    }
+
+    #[test]
+    fn decode_connack() {
+        assert_eq!(
+            decode_packet(CONNACK, &[0, 5]).unwrap(),
+            Packet::ConnAck { return_code: 5 }
+        );
+        assert!(decode_packet(CONNACK, &[0]).is_err());
+    }
+
+    #[test]
+    fn subscribe() {
+        assert_eq!(
+            encode_subscribe(1, &["a", "b/c"]),
+            [0x82, 12, 0, 1, 0, 1, b'a', 0, 0, 3, b'b', b'/', b'c', 0]
+        );
+    }
+
+    #[test]
+    fn broker_addr() {
+        assert_eq!(parse_broker_addr(""), None);
+        assert_eq!(parse_broker_addr("  "), None);
+        assert_eq!(
+            parse_broker_addr("broker.local"),
+            Some(("broker.local".to_string(), DEFAULT_PORT))
+        );
+        assert_eq!(
+            parse_broker_addr("10.0.0.1:8883"),
+            Some(("10.0.0.1".to_string(), 8883))
+        );
+        assert_eq!(parse_broker_addr("10.0.0.1:x"), None);
+        assert_eq!(
+            parse_broker_addr("[fd00::1]:8883"),
+            Some(("fd00::1".to_string(), 8883))
+        );
+        assert_eq!(
+            parse_broker_addr("[fd00::1]"),
+            Some(("fd00::1".to_string(), DEFAULT_PORT))
+        );
+        assert_eq!(
+            parse_broker_addr("fd00::1"),
+            Some(("fd00::1".to_string(), DEFAULT_PORT))
+        );
+        assert_eq!(parse_broker_addr("[fd00::1"), None);
+        assert_eq!(parse_broker_addr("[fd00::1]8883"), None);
+    }
+
+    #[test]
+    fn topics() {
+        let mut config = MqttConfig {
+            host: "localhost".into(),
+            port: DEFAULT_PORT,
+            username: None,
+            password: None,
+            topic_prefix: "uets/".into(),
+        };
+        assert_eq!(config.topic(MqttTopic::RelaySet), "uets/relay/set");
+
+        assert_eq!(config.detect_reader("uets/detect"), Some(None));
+        assert_eq!(
+            config.detect_reader("uets/detect/lab-1"),
+            Some(Some("lab-1"))
+        );
+        assert_eq!(config.detect_reader("uets/detect/"), None);
+        assert_eq!(config.detect_reader("uets/detect/lab/1"), None);
+        assert_eq!(config.detect_reader("uets/detected"), None);
+        assert_eq!(config.detect_reader("uets/relay/set"), None);
+
+        config.topic_prefix = String::new();
+        assert_eq!(config.topic(MqttTopic::NInside), "n-inside");
+        assert_eq!(config.detect_reader("detect/lab-1"), Some(Some("lab-1")));
+    }
+}
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use gtk::{glib, subclass::prelude::*};
+
+use crate::{db, entity_id::EntityId};
+
+mod imp {
+    use std::cell::OnceCell;
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct PendingDetection {
+        pub(super) dt: OnceCell<DateTime<Utc>>,
+        pub(super) entity_id: OnceCell<EntityId>,
+        pub(super) zone: OnceCell<Option<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PendingDetection {
+        const NAME: &'static str = "UetsPendingDetection";
+        type Type = super::PendingDetection;
+    }
+
+    impl ObjectImpl for PendingDetection {}
+}
+
+glib::wrapper! {
+    /// A detection of an unknown entity that is waiting for its data.
+    pub struct PendingDetection(ObjectSubclass<imp::PendingDetection>);
+}
+
+impl PendingDetection {
+    pub fn new(dt: DateTime<Utc>, entity_id: EntityId, zone: Option<String>) -> Self {
+        let this = glib::Object::new::<Self>();
+
+        let imp = this.imp();
+        imp.dt.set(dt).unwrap();
+        imp.entity_id.set(entity_id).unwrap();
+        imp.zone.set(zone).unwrap();
+
+        this
+    }
+
+    pub fn from_db(dt: DateTime<Utc>, raw: db::RawPendingDetection) -> Self {
+        Self::new(dt, raw.entity_id, raw.zone)
+    }
+
+    pub fn to_db(&self) -> db::RawPendingDetection {
+        db::RawPendingDetection {
+            entity_id: self.entity_id().clone(),
+            zone: self.zone().map(|zone| zone.to_string()),
+        }
+    }
+
+    /// The time the entity was actually detected.
+    pub fn dt(&self) -> DateTime<Utc> {
+        *self.imp().dt.get().unwrap()
+    }
+
+    pub fn entity_id(&self) -> &EntityId {
+        self.imp().entity_id.get().unwrap()
+    }
+
+    /// The zone of the reader the entity was detected at.
+    pub fn zone(&self) -> Option<&str> {
+        self.imp().zone.get().unwrap().as_deref()
+    }
+}
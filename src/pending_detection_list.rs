@@ -0,0 +1,164 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use gtk::{gio, glib, prelude::*, subclass::prelude::*};
+use indexmap::{map::Entry, IndexMap};
+
+use crate::{
+    db::{self, EnvExt},
+    entity_id::EntityId,
+    pending_detection::PendingDetection,
+};
+
+mod imp {
+    use std::cell::{OnceCell, RefCell};
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct PendingDetectionList {
+        pub(super) list: RefCell<IndexMap<DateTime<Utc>, PendingDetection>>,
+
+        pub(super) db: OnceCell<(heed::Env, db::PendingDetectionsDbType)>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PendingDetectionList {
+        const NAME: &'static str = "UetsPendingDetectionList";
+        type Type = super::PendingDetectionList;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    impl ObjectImpl for PendingDetectionList {}
+
+    impl ListModelImpl for PendingDetectionList {
+        fn item_type(&self) -> glib::Type {
+            PendingDetection::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.list.borrow().len() as u32
+        }
+
+        fn item(&self, position: u32) -> Option<glib::Object> {
+            self.list
+                .borrow()
+                .get_index(position as usize)
+                .map(|(_, v)| v.upcast_ref::<glib::Object>())
+                .cloned()
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Persistent queue of detections of unknown entities, sorted by date-time.
+    pub struct PendingDetectionList(ObjectSubclass<imp::PendingDetectionList>)
+        @implements gio::ListModel;
+}
+
+impl PendingDetectionList {
+    pub fn load_from_env(env: heed::Env) -> Result<Self> {
+        let start_time = Instant::now();
+
+        let (db, items) = env.with_write_txn(|wtxn| {
+            let db: db::PendingDetectionsDbType =
+                env.create_database(wtxn, Some(db::PENDING_DETECTIONS_DB_NAME))?;
+            let items = db
+                .iter(wtxn)?
+                .map(|res| res.map(|(dt, raw)| (dt, PendingDetection::from_db(dt, raw))))
+                .collect::<Result<IndexMap<_, _>, _>>()?;
+            Ok((db, items))
+        })?;
+
+        tracing::debug!("Loaded {} items in {:?}", items.len(), start_time.elapsed());
+
+        let this = glib::Object::new::<Self>();
+
+        let imp = this.imp();
+        imp.list.replace(items);
+        imp.db.set((env, db)).unwrap();
+
+        Ok(this)
+    }
+
+    pub fn insert(&self, item: PendingDetection) -> Result<()> {
+        let imp = self.imp();
+
+        let (env, db) = self.db();
+        env.with_write_txn(|wtxn| {
+            db.put(wtxn, &item.dt(), &item.to_db())?;
+            Ok(())
+        })?;
+
+        let (index, removed, added) = match imp.list.borrow_mut().entry(item.dt()) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(item);
+                (entry.index(), 1, 1)
+            }
+            Entry::Vacant(entry) => {
+                let index = entry.index();
+                entry.insert(item);
+                (index, 0, 1)
+            }
+        };
+
+        self.items_changed(index as u32, removed, added);
+
+        debug_assert!(imp.list.borrow().keys().is_sorted());
+
+        Ok(())
+    }
+
+    /// Returns the pending detections of the entity, sorted by date-time.
+    pub fn for_entity(&self, entity_id: &EntityId) -> Vec<PendingDetection> {
+        self.imp()
+            .list
+            .borrow()
+            .values()
+            .filter(|item| item.entity_id() == entity_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn contains_entity(&self, entity_id: &EntityId) -> bool {
+        self.imp()
+            .list
+            .borrow()
+            .values()
+            .any(|item| item.entity_id() == entity_id)
+    }
+
+    pub fn remove_for_entity(&self, entity_id: &EntityId) -> Result<()> {
+        let imp = self.imp();
+
+        let dts = imp
+            .list
+            .borrow()
+            .values()
+            .filter(|item| item.entity_id() == entity_id)
+            .map(|item| item.dt())
+            .collect::<Vec<_>>();
+
+        let (env, db) = self.db();
+        env.with_write_txn(|wtxn| {
+            for dt in &dts {
+                db.delete(wtxn, dt)?;
+            }
+            Ok(())
+        })?;
+
+        for dt in &dts {
+            let entry = imp.list.borrow_mut().shift_remove_full(dt);
+            if let Some((index, _, _)) = entry {
+                self.items_changed(index as u32, 1, 0);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn db(&self) -> &(heed::Env, db::PendingDetectionsDbType) {
+        self.imp().db.get().unwrap()
+    }
+}
@@ -0,0 +1,237 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{ensure, Context, Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// How much of something an entity holds, e.g., "24 cans" or "25 kg".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quantity {
+    pub amount: f64,
+    pub unit: Option<String>,
+}
+
+// Amounts are always finite, as they are only created by parsing or
+// arithmetic on parsed amounts.
+impl Eq for Quantity {}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.amount, f)?;
+
+        if let Some(unit) = &self.unit {
+            write!(f, " {}", unit)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        let unit_idx = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (amount, unit) = s.split_at(unit_idx);
+
+        let amount = amount
+            .parse::<f64>()
+            .with_context(|| format!("Invalid amount in `{}`", s))?;
+        ensure!(amount.is_finite(), "Amount must be finite");
+
+        let unit = unit.trim();
+        ensure!(
+            !unit.contains(char::is_whitespace),
+            "Invalid unit `{}`",
+            unit
+        );
+
+        Ok(Self {
+            amount,
+            unit: (!unit.is_empty()).then(|| unit.to_string()),
+        })
+    }
+}
+
+impl Quantity {
+    /// Returns the amount in the given unit, or `None` if the units can't be
+    /// converted to each other.
+    ///
+    /// Amounts without unit, or converted to no unit, are taken as is.
+    pub fn amount_in(&self, unit: Option<&str>) -> Option<f64> {
+        match (self.unit.as_deref(), unit) {
+            (Some(from), Some(to)) => conversion_factor(from, to).map(|f| round(self.amount * f)),
+            _ => Some(self.amount),
+        }
+    }
+
+    pub fn checked_add(&self, other: &Quantity) -> Result<Quantity> {
+        let other_amount = self.other_amount(other)?;
+
+        Ok(Self {
+            amount: round(self.amount + other_amount),
+            unit: self.unit.clone(),
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Quantity) -> Result<Quantity> {
+        let other_amount = self.other_amount(other)?;
+        ensure!(
+            other_amount <= self.amount,
+            "Can't take {} out of {}",
+            other,
+            self
+        );
+
+        Ok(Self {
+            amount: round(self.amount - other_amount),
+            unit: self.unit.clone(),
+        })
+    }
+
+    fn other_amount(&self, other: &Quantity) -> Result<f64> {
+        other
+            .amount_in(self.unit.as_deref())
+            .with_context(|| format!("Can't convert {} to the unit of {}", other, self))
+    }
+}
+
+/// Returns how much the entity adds to a stock with the given unit.
+///
+/// Entities without quantity count as one. If the units can't be converted,
+/// the amount is taken as is.
+pub fn stock_amount(quantity: Option<&Quantity>, stock_unit: Option<&str>) -> f64 {
+    let Some(quantity) = quantity else {
+        return 1.0;
+    };
+
+    quantity.amount_in(stock_unit).unwrap_or_else(|| {
+        tracing::debug!(
+            "Can't convert {} to stock unit {:?}, taking the amount as is",
+            quantity,
+            stock_unit
+        );
+        quantity.amount
+    })
+}
+
+/// Rounds off the noise from adding and converting amounts.
+pub fn round(amount: f64) -> f64 {
+    (amount * 1e6).round() / 1e6
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Mass,
+    Volume,
+}
+
+/// Returns the dimension of the unit and how many of its base unit, grams or
+/// milliliters, it is.
+fn base_factor(unit: &str) -> Option<(Dimension, f64)> {
+    let ret = match unit.to_lowercase().as_str() {
+        "mg" => (Dimension::Mass, 0.001),
+        "g" => (Dimension::Mass, 1.0),
+        "kg" => (Dimension::Mass, 1000.0),
+        "oz" => (Dimension::Mass, 28.349523125),
+        "lb" | "lbs" => (Dimension::Mass, 453.59237),
+        "ml" => (Dimension::Volume, 1.0),
+        "cl" => (Dimension::Volume, 10.0),
+        "dl" => (Dimension::Volume, 100.0),
+        "l" => (Dimension::Volume, 1000.0),
+        _ => return None,
+    };
+    Some(ret)
+}
+
+fn conversion_factor(from: &str, to: &str) -> Option<f64> {
+    if from.eq_ignore_ascii_case(to) {
+        return Some(1.0);
+    }
+
+    let (from_dimension, from_factor) = base_factor(from)?;
+    let (to_dimension, to_factor) = base_factor(to)?;
+    (from_dimension == to_dimension).then(|| from_factor / to_factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q(s: &str) -> Quantity {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_and_display() {
+        assert_eq!(
+            q("25 kg"),
+            Quantity {
+                amount: 25.0,
+                unit: Some("kg".into())
+            }
+        );
+        assert_eq!(
+            q(" 1.5L "),
+            Quantity {
+                amount: 1.5,
+                unit: Some("L".into())
+            }
+        );
+        assert_eq!(
+            q("24"),
+            Quantity {
+                amount: 24.0,
+                unit: None
+            }
+        );
+
+        assert_eq!(q("25 kg").to_string(), "25 kg");
+        assert_eq!(q("24").to_string(), "24");
+        assert_eq!(q("0.5 cans").to_string(), "0.5 cans");
+
+        assert!("".parse::<Quantity>().is_err());
+        assert!("kg".parse::<Quantity>().is_err());
+        assert!("-1 kg".parse::<Quantity>().is_err());
+        assert!("1.2.3".parse::<Quantity>().is_err());
+        assert!("2 big boxes".parse::<Quantity>().is_err());
+    }
+
+    #[test]
+    fn amount_in() {
+        assert_eq!(q("25 kg").amount_in(Some("kg")), Some(25.0));
+        assert_eq!(q("25 KG").amount_in(Some("kg")), Some(25.0));
+        assert_eq!(q("250 g").amount_in(Some("kg")), Some(0.25));
+        assert_eq!(q("1 lb").amount_in(Some("g")), Some(453.59237));
+        assert_eq!(q("33 cl").amount_in(Some("l")), Some(0.33));
+        assert_eq!(q("24 cans").amount_in(Some("cans")), Some(24.0));
+
+        assert_eq!(q("1 l").amount_in(Some("kg")), None);
+        assert_eq!(q("24 cans").amount_in(Some("kg")), None);
+
+        assert_eq!(q("24").amount_in(Some("kg")), Some(24.0));
+        assert_eq!(q("25 kg").amount_in(None), Some(25.0));
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(q("25 kg").checked_sub(&q("500 g")).unwrap(), q("24.5 kg"));
+        assert_eq!(q("24").checked_sub(&q("24")).unwrap(), q("0"));
+        assert!(q("25 kg").checked_sub(&q("26 kg")).is_err());
+        assert!(q("25 kg").checked_sub(&q("1 l")).is_err());
+
+        assert_eq!(q("0.1 l").checked_add(&q("20 ml")).unwrap(), q("0.12 l"));
+        assert!(q("1 l").checked_add(&q("1 kg")).is_err());
+    }
+
+    #[test]
+    fn stock_amount_of() {
+        assert_eq!(stock_amount(None, Some("kg")), 1.0);
+        assert_eq!(stock_amount(Some(&q("500 g")), Some("kg")), 0.5);
+        assert_eq!(stock_amount(Some(&q("24 cans")), None), 24.0);
+        assert_eq!(stock_amount(Some(&q("3 l")), Some("kg")), 3.0);
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::{bail, ensure, Context, Result};
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Error, Result};
 use gtk::{glib, subclass::prelude::*};
 
 use crate::remote::Remote;
@@ -12,6 +14,18 @@ pub enum RelayState {
     High,
 }
 
+impl FromStr for RelayState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "0" | "low" | "off" => Ok(Self::Low),
+            "1" | "high" | "on" => Ok(Self::High),
+            other => bail!("Invalid relay state `{}`", other),
+        }
+    }
+}
+
 mod imp {
     use std::cell::RefCell;
 
@@ -0,0 +1,140 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{quantity, settings::Settings, stock::Stock};
+
+/// Windows the consumption rate is averaged over, so a change in demand
+/// shows within a week without a single busy day dominating.
+const RATE_WINDOWS: [TimeDelta; 2] = [TimeDelta::weeks(1), TimeDelta::weeks(4)];
+
+/// Returns the number of items taken out of the stock per day, averaged over
+/// the rate windows ending at `now`, or `None` if the stock has no history
+/// yet.
+///
+/// Windows are cut to when the stock first entered, so new stocks are not
+/// taken to be slower moving than they are.
+pub fn consumption_rate(stock: &Stock, now: DateTime<Utc>) -> Option<f64> {
+    let first_entry_dt = stock.first_entry_dt()?;
+    let n_exits = stock.n_exits_for_dt(now);
+
+    average_rate(RATE_WINDOWS.iter().map(|window| {
+        let start_dt = (now - *window).max(first_entry_dt);
+        let n_window_exits = n_exits.saturating_sub(stock.n_exits_for_dt(start_dt));
+        (n_window_exits, now - start_dt)
+    }))
+}
+
+fn average_rate(samples: impl IntoIterator<Item = (u32, TimeDelta)>) -> Option<f64> {
+    let rates = samples
+        .into_iter()
+        .filter(|(_, duration)| *duration > TimeDelta::zero())
+        .map(|(n_exits, duration)| {
+            let n_days = duration.num_seconds() as f64 / TimeDelta::days(1).num_seconds() as f64;
+            f64::from(n_exits) / n_days
+        })
+        .collect::<Vec<_>>();
+
+    (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64)
+}
+
+/// How much of a stock to order to last for its target days of cover.
+///
+/// This counts items, not quantities in the stock unit, as the rate is from
+/// the number of exits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReorderSuggestion {
+    pub n_items_inside: u32,
+    /// Items per day.
+    pub consumption_rate: f64,
+    /// `None` if nothing is being consumed, so the stock lasts indefinitely.
+    pub days_of_cover: Option<f64>,
+    pub target_days_of_cover: u32,
+    pub n_items_to_order: u32,
+}
+
+impl ReorderSuggestion {
+    pub fn new(n_items_inside: u32, consumption_rate: f64, target_days_of_cover: u32) -> Self {
+        let days_of_cover =
+            (consumption_rate > 0.0).then(|| f64::from(n_items_inside) / consumption_rate);
+
+        let n_items_needed = consumption_rate * f64::from(target_days_of_cover);
+        let n_items_to_order = quantity::round(n_items_needed - f64::from(n_items_inside))
+            .ceil()
+            .max(0.0) as u32;
+
+        Self {
+            n_items_inside,
+            consumption_rate,
+            days_of_cover,
+            target_days_of_cover,
+            n_items_to_order,
+        }
+    }
+
+    /// Returns the suggestion for the stock at the given time, or `None` if
+    /// the stock has no history yet.
+    pub fn for_stock(stock: &Stock, settings: &Settings, now: DateTime<Utc>) -> Option<Self> {
+        let consumption_rate = consumption_rate(stock, now)?;
+        let n_items_inside = stock
+            .n_entries_for_dt(now)
+            .saturating_sub(stock.n_exits_for_dt(now));
+        let target_days_of_cover = stock
+            .data()
+            .target_days_of_cover
+            .unwrap_or_else(|| settings.target_days_of_cover());
+
+        Some(Self::new(
+            n_items_inside,
+            consumption_rate,
+            target_days_of_cover,
+        ))
+    }
+
+    pub fn needs_reorder(&self) -> bool {
+        self.n_items_to_order > 0
+    }
+}
+
+/// Rounds rates and days for display.
+pub fn round_hundredths(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_rate_of_windows() {
+        assert_eq!(
+            average_rate([(7, TimeDelta::weeks(1)), (56, TimeDelta::weeks(4))]),
+            Some(1.5)
+        );
+        assert_eq!(average_rate([(3, TimeDelta::hours(12))]), Some(6.0));
+        assert_eq!(
+            average_rate([(0, TimeDelta::weeks(1)), (0, TimeDelta::zero())]),
+            Some(0.0)
+        );
+        assert_eq!(average_rate([(1, TimeDelta::zero())]), None);
+    }
+
+    #[test]
+    fn suggestion() {
+        let s = ReorderSuggestion::new(10, 2.0, 14);
+        assert_eq!(s.days_of_cover, Some(5.0));
+        assert_eq!(s.n_items_to_order, 18);
+        assert!(s.needs_reorder());
+
+        let s = ReorderSuggestion::new(30, 2.0, 14);
+        assert_eq!(s.days_of_cover, Some(15.0));
+        assert_eq!(s.n_items_to_order, 0);
+        assert!(!s.needs_reorder());
+
+        // Partial items are rounded up, but not float noise.
+        assert_eq!(ReorderSuggestion::new(0, 0.3, 10).n_items_to_order, 3);
+        assert_eq!(ReorderSuggestion::new(0, 0.35, 10).n_items_to_order, 4);
+
+        let s = ReorderSuggestion::new(5, 0.0, 14);
+        assert_eq!(s.days_of_cover, None);
+        assert_eq!(s.n_items_to_order, 0);
+    }
+}
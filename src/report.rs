@@ -72,7 +72,7 @@ impl ReportBuilder {
         debug_assert!(table
             .graphs
             .iter()
-            .all(|(_, _, val_col_idx)| table.rows.iter().all(|row| row[*val_col_idx].is_u32())));
+            .all(|(_, _, val_col_idx)| table.rows.iter().all(|row| row[*val_col_idx].is_number())));
 
         self.table = Some(table);
         self
@@ -98,7 +98,7 @@ impl ReportBuilder {
     }
 }
 
-mod pdf {
+pub mod pdf {
     use std::sync::LazyLock;
 
     use anyhow::Result;
@@ -116,12 +116,12 @@ mod pdf {
     };
 
     const DOC_LINE_SPACING_MM: f64 = 1.5;
-    const DOC_MARGINS_MM: f64 = 10.0;
+    pub const DOC_MARGINS_MM: f64 = 10.0;
 
     const TABLE_TOP_BOTTOM_PADDING_MM: f64 = 0.0;
     const TABLE_LEFT_RIGHT_PADDING_MM: f64 = 1.0;
 
-    static DEFAULT_FONT_FAMILY: LazyLock<FontFamily<FontData>> =
+    pub static DEFAULT_FONT_FAMILY: LazyLock<FontFamily<FontData>> =
         LazyLock::new(|| fonts::FontFamily {
             regular: font_data_from_resource("times.ttf").unwrap(),
             bold: font_data_from_resource("timesbd.ttf").unwrap(),
@@ -161,7 +161,7 @@ mod pdf {
                 doc.push(p_bold(graph_title).aligned(Alignment::Center));
 
                 let dts = t.rows.iter().map(|row| row[dt_col_idx].as_date().unwrap());
-                let vals = t.rows.iter().map(|row| row[val_col_idx].as_u32().unwrap());
+                let vals = t.rows.iter().map(|row| row[val_col_idx].as_f64().unwrap());
                 let image_data =
                     time_graph::draw_image((800, 500), &dts.zip(vals).collect::<Vec<_>>())?;
 
@@ -197,6 +197,7 @@ mod pdf {
                                     .format("%Y/%m/%d %H:%M:%S")
                                     .to_string(),
                                 ReportTableCell::U32(u32) => u32.to_string(),
+                                ReportTableCell::F64(f64) => f64.to_string(),
                                 ReportTableCell::String(string) => string,
                             };
                             Paragraph::new(cell_string)
@@ -346,6 +347,9 @@ mod spreadsheet {
                         ReportTableCell::U32(u32) => {
                             sheet.write_number(cur_row_idx, col_idx as ColNum, *u32 as f64)?;
                         }
+                        ReportTableCell::F64(f64) => {
+                            sheet.write_number(cur_row_idx, col_idx as ColNum, *f64)?;
+                        }
                         ReportTableCell::String(string) => {
                             sheet.write_string(cur_row_idx, col_idx as ColNum, string)?;
                         }
@@ -415,6 +419,7 @@ mod csv {
                             .format("%Y/%m/%d %H:%M:%S")
                             .to_string(),
                         ReportTableCell::U32(u32) => u32.to_string(),
+                        ReportTableCell::F64(f64) => f64.to_string(),
                         ReportTableCell::String(string) => string,
                     }
                 }))?;
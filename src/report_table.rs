@@ -77,6 +77,7 @@ impl ReportTableBuilder {
 pub enum ReportTableCell {
     DateTime(DateTime<Utc>),
     U32(u32),
+    F64(f64),
     String(String),
 }
 
@@ -88,9 +89,10 @@ impl ReportTableCell {
         }
     }
 
-    pub fn as_u32(&self) -> Option<u32> {
+    pub fn as_f64(&self) -> Option<f64> {
         match self {
-            ReportTableCell::U32(u32) => Some(*u32),
+            ReportTableCell::U32(u32) => Some(f64::from(*u32)),
+            ReportTableCell::F64(f64) => Some(*f64),
             _ => None,
         }
     }
@@ -99,8 +101,8 @@ impl ReportTableCell {
         matches!(self, ReportTableCell::DateTime(_))
     }
 
-    pub fn is_u32(&self) -> bool {
-        matches!(self, ReportTableCell::U32(_))
+    pub fn is_number(&self) -> bool {
+        matches!(self, ReportTableCell::U32(_) | ReportTableCell::F64(_))
     }
 }
 
@@ -116,6 +118,12 @@ impl From<u32> for ReportTableCell {
     }
 }
 
+impl From<f64> for ReportTableCell {
+    fn from(f64: f64) -> Self {
+        ReportTableCell::F64(f64)
+    }
+}
+
 impl From<String> for ReportTableCell {
     fn from(string: String) -> Self {
         ReportTableCell::String(string)
@@ -0,0 +1,166 @@
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, Utc};
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entity_data::{EntityData, EntityDataFieldTy},
+    entity_id::EntityId,
+};
+
+/// Prefix of signed codes, which also versions the format.
+const PREFIX: &str = "UETS1.";
+
+const SEPARATOR: char = '.';
+
+/// Payload of a signed code.
+///
+/// The code has the form `UETS1.<base64 JSON payload>.<hex HMAC-SHA256>`,
+/// where the HMAC is computed over the base64 payload with the key configured
+/// on the unit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedQrPayload {
+    pub id: EntityId,
+    #[serde(default)]
+    pub data: EntityData,
+    /// Unix timestamp after which the code is rejected.
+    #[serde(default, rename = "exp", skip_serializing_if = "Option::is_none")]
+    pub expiration_timestamp: Option<i64>,
+}
+
+impl SignedQrPayload {
+    /// Photos are stripped, as they won't fit in a QR code.
+    pub fn new(id: EntityId, data: &EntityData, expiration_dt: Option<DateTime<Utc>>) -> Self {
+        Self {
+            id,
+            data: EntityData::from_fields(
+                data.fields()
+                    .filter(|field| field.ty() != EntityDataFieldTy::Photo)
+                    .cloned(),
+            ),
+            expiration_timestamp: expiration_dt.map(|dt| dt.timestamp()),
+        }
+    }
+
+    pub fn sign(&self, key: &str) -> Result<String> {
+        ensure!(!key.is_empty(), "Signing key is empty");
+
+        let json = serde_json::to_string(self)?;
+        let encoded = glib::base64_encode(json.as_bytes());
+        let signature = hmac(key, &encoded)?;
+
+        Ok(format!("{PREFIX}{encoded}{SEPARATOR}{signature}"))
+    }
+}
+
+/// Returns whether the code is in the signed format, regardless of validity.
+pub fn is_signed(code: &str) -> bool {
+    code.starts_with(PREFIX)
+}
+
+/// Verifies the signature and expiration of a signed code.
+pub fn verify(code: &str, key: &str, now: DateTime<Utc>) -> Result<(EntityId, EntityData)> {
+    ensure!(!key.is_empty(), "Signing key is empty");
+
+    let rest = code.strip_prefix(PREFIX).context("Not a signed code")?;
+    let (encoded, signature) = rest.rsplit_once(SEPARATOR).context("Missing signature")?;
+
+    ensure!(
+        constant_time_eq(hmac(key, encoded)?.as_bytes(), signature.as_bytes()),
+        "Invalid signature"
+    );
+
+    let json = glib::base64_decode(encoded);
+    let payload = serde_json::from_slice::<SignedQrPayload>(&json)?;
+
+    if let Some(timestamp) = payload.expiration_timestamp {
+        if now.timestamp() > timestamp {
+            bail!("Code expired at {}", timestamp);
+        }
+    }
+
+    Ok((payload.id, payload.data))
+}
+
+fn hmac(key: &str, data: &str) -> Result<String> {
+    let hmac =
+        glib::compute_hmac_for_data(glib::ChecksumType::Sha256, key.as_bytes(), data.as_bytes());
+    Ok(hmac.to_string())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::{entity_data::EntityDataField, jpeg_image::JpegImage};
+
+    const KEY: &str = "secret";
+
+    fn payload(expiration_dt: Option<DateTime<Utc>>) -> SignedQrPayload {
+        SignedQrPayload::new(
+            EntityId::new("2021-00123"),
+            &EntityData::from_fields([EntityDataField::Name("Juan Dela Cruz".into())]),
+            expiration_dt,
+        )
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let code = payload(None).sign(KEY).unwrap();
+        assert!(is_signed(&code));
+
+        let (id, data) = verify(&code, KEY, Utc::now()).unwrap();
+        assert_eq!(id, EntityId::new("2021-00123"));
+        assert_eq!(data.name().map(|n| n.as_str()), Some("Juan Dela Cruz"));
+    }
+
+    #[test]
+    fn reject_wrong_key_or_tampered() {
+        let code = payload(None).sign(KEY).unwrap();
+        assert!(verify(&code, "other", Utc::now()).is_err());
+        assert!(verify(&code, "", Utc::now()).is_err());
+
+        let (_, signature) = code.rsplit_once(SEPARATOR).unwrap();
+        let forged = SignedQrPayload {
+            id: EntityId::new("2021-99999"),
+            ..payload(None)
+        };
+        let forged_encoded =
+            glib::base64_encode(serde_json::to_string(&forged).unwrap().as_bytes());
+        assert!(verify(
+            &format!("{PREFIX}{forged_encoded}{SEPARATOR}{signature}"),
+            KEY,
+            Utc::now()
+        )
+        .is_err());
+
+        assert!(verify("UETS1.abc", KEY, Utc::now()).is_err());
+        assert!(verify("name_id_email_program", KEY, Utc::now()).is_err());
+        assert!(!is_signed("name_id_email_program"));
+    }
+
+    #[test]
+    fn reject_expired() {
+        let now = Utc::now();
+
+        let code = payload(Some(now + TimeDelta::days(1))).sign(KEY).unwrap();
+        assert!(verify(&code, KEY, now).is_ok());
+        assert!(verify(&code, KEY, now + TimeDelta::days(2)).is_err());
+    }
+
+    #[test]
+    fn strip_photo() {
+        let data = EntityData::from_fields([
+            EntityDataField::Name("Juan".into()),
+            EntityDataField::Photo(JpegImage::from_bytes(vec![0; 16])),
+        ]);
+        let payload = SignedQrPayload::new(EntityId::new("1"), &data, None);
+        assert!(!payload.data.has_field(EntityDataFieldTy::Photo));
+        assert!(payload.data.has_field(EntityDataFieldTy::Name));
+    }
+}
@@ -8,10 +8,14 @@ use crate::{
     stock_data::StockData, stock_id::StockId,
 };
 
+/// Counts are in the stock unit, where entities without quantity count as
+/// one.
 #[derive(Default, Clone)]
 pub struct StockLogs {
-    pub n_inside: Log<u32>,
-    pub max_n_inside: Log<u32>,
+    pub n_inside: Log<f64>,
+    pub max_n_inside: Log<f64>,
+    /// Total quantity consumed while outside.
+    pub n_consumed: Log<f64>,
     pub n_entries: Log<u32>,
     pub n_exits: Log<u32>,
     pub last_entry_dt: Log<DateTime<Utc>>,
@@ -32,9 +36,11 @@ mod imp {
         #[property(get, set = Self::set_data, explicit_notify)]
         pub(super) data: RefCell<StockData>,
         #[property(get = Self::n_inside)]
-        pub(super) n_inside: PhantomData<u32>,
+        pub(super) n_inside: PhantomData<f64>,
         #[property(get = Self::max_n_inside)]
-        pub(super) max_n_inside: PhantomData<u32>,
+        pub(super) max_n_inside: PhantomData<f64>,
+        #[property(get = Self::n_consumed)]
+        pub(super) n_consumed: PhantomData<f64>,
         #[property(get = Self::n_entries)]
         pub(super) n_entries: PhantomData<u32>,
         #[property(get = Self::n_exits)]
@@ -70,17 +76,26 @@ mod imp {
             obj.notify_data();
         }
 
-        fn n_inside(&self) -> u32 {
-            self.logs.borrow().n_inside.latest().copied().unwrap_or(0)
+        fn n_inside(&self) -> f64 {
+            self.logs.borrow().n_inside.latest().copied().unwrap_or(0.0)
         }
 
-        fn max_n_inside(&self) -> u32 {
+        fn max_n_inside(&self) -> f64 {
             self.logs
                 .borrow()
                 .max_n_inside
                 .latest()
                 .copied()
-                .unwrap_or(0)
+                .unwrap_or(0.0)
+        }
+
+        fn n_consumed(&self) -> f64 {
+            self.logs
+                .borrow()
+                .n_consumed
+                .latest()
+                .copied()
+                .unwrap_or(0.0)
         }
 
         fn n_entries(&self) -> u32 {
@@ -131,17 +146,17 @@ impl Stock {
         self.imp().id.get().unwrap()
     }
 
-    pub fn n_inside_for_dt(&self, dt: DateTime<Utc>) -> u32 {
+    pub fn n_inside_for_dt(&self, dt: DateTime<Utc>) -> f64 {
         self.imp()
             .logs
             .borrow()
             .n_inside
             .for_dt(dt)
             .copied()
-            .unwrap_or(0)
+            .unwrap_or(0.0)
     }
 
-    pub fn n_inside_for_dt_range(&self, dt_range: &DateTimeRange) -> u32 {
+    pub fn n_inside_for_dt_range(&self, dt_range: &DateTimeRange) -> f64 {
         if let Some(end) = dt_range.end {
             self.n_inside_for_dt(end)
         } else {
@@ -153,17 +168,21 @@ impl Stock {
         self.imp().logs.borrow().n_inside.latest_dt()
     }
 
-    pub fn max_n_inside_for_dt(&self, dt: DateTime<Utc>) -> u32 {
+    pub fn first_entry_dt(&self) -> Option<DateTime<Utc>> {
+        self.imp().logs.borrow().n_entries.earliest_dt()
+    }
+
+    pub fn max_n_inside_for_dt(&self, dt: DateTime<Utc>) -> f64 {
         self.imp()
             .logs
             .borrow()
             .max_n_inside
             .for_dt(dt)
             .copied()
-            .unwrap_or(0)
+            .unwrap_or(0.0)
     }
 
-    pub fn max_n_inside_for_dt_range(&self, dt_range: &DateTimeRange) -> u32 {
+    pub fn max_n_inside_for_dt_range(&self, dt_range: &DateTimeRange) -> f64 {
         if let Some(end) = dt_range.end {
             self.max_n_inside_for_dt(end)
         } else {
@@ -171,6 +190,24 @@ impl Stock {
         }
     }
 
+    pub fn n_consumed_for_dt(&self, dt: DateTime<Utc>) -> f64 {
+        self.imp()
+            .logs
+            .borrow()
+            .n_consumed
+            .for_dt(dt)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    pub fn n_consumed_for_dt_range(&self, dt_range: &DateTimeRange) -> f64 {
+        if let Some(end) = dt_range.end {
+            self.n_consumed_for_dt(end)
+        } else {
+            self.n_consumed()
+        }
+    }
+
     pub fn n_entries_for_dt(&self, dt: DateTime<Utc>) -> u32 {
         self.imp()
             .logs
@@ -234,6 +271,7 @@ impl Stock {
     pub fn with_logs_mut(&self, f: impl FnOnce(&mut StockLogs)) {
         let prev_n_inside = self.n_inside();
         let prev_max_n_inside = self.max_n_inside();
+        let prev_n_consumed = self.n_consumed();
         let prev_n_entries = self.n_entries();
         let prev_n_exits = self.n_exits();
         let prev_last_entry_dt = self.last_entry_dt();
@@ -249,6 +287,10 @@ impl Stock {
             self.notify_max_n_inside();
         }
 
+        if prev_n_consumed != self.n_consumed() {
+            self.notify_n_consumed();
+        }
+
         if prev_n_entries != self.n_entries() {
             self.notify_n_entries();
         }
@@ -1,6 +1,31 @@
 use gtk::glib;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, glib::Boxed)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, glib::Boxed)]
 #[boxed_type(name = "UetsStockData")]
-pub struct StockData {}
+pub struct StockData {
+    /// Unit the quantities of the stock are counted in, e.g., "kg". If not
+    /// set, the entity quantities are taken as is.
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Days before expiration the entities of the stock are expiring soon. If
+    /// not set, the global window is used.
+    #[serde(default)]
+    pub expiring_soon_window_days: Option<u32>,
+    /// Days the stock should last after reordering. If not set, the global
+    /// target is used.
+    #[serde(default)]
+    pub target_days_of_cover: Option<u32>,
+    /// Who to order the stock from, e.g., name and contact details.
+    #[serde(default)]
+    pub supplier: Option<String>,
+    /// Cost of one stock unit, or of one item if the stock has no unit.
+    #[serde(default)]
+    pub unit_cost: Option<f64>,
+    /// Currency of the unit cost, e.g., "PHP".
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+// Unit costs are always finite, as they are only set from parsed costs.
+impl Eq for StockData {}
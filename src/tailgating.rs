@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+/// How long after the last ID read motion bursts are still attributed to it.
+const WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionAttribution {
+    /// There is no recent ID read to attribute the motion to.
+    Unattributed,
+    /// The motion is covered by a recent ID read.
+    Read,
+    /// There are more motion bursts than ID reads within the window.
+    Tailgating,
+}
+
+#[derive(Debug)]
+struct Window {
+    last_read_instant: Instant,
+    n_reads: u32,
+    n_motions: u32,
+}
+
+/// Compares motion bursts with ID reads, so that more people passing than
+/// reads within a window is reported as tailgating.
+#[derive(Debug, Default)]
+pub struct TailgatingTracker {
+    window: Option<Window>,
+}
+
+impl TailgatingTracker {
+    pub fn reset(&mut self) {
+        self.window = None;
+    }
+
+    /// Opens or extends the window for an ID read.
+    ///
+    /// `has_pending_motion` is whether there is a motion burst that was not
+    /// attributed yet, as it belongs to the entity that was just read.
+    pub fn register_read(&mut self, now: Instant, has_pending_motion: bool) {
+        match self.window.as_mut() {
+            Some(window) if is_within_window(window, now) => {
+                window.last_read_instant = now;
+                window.n_reads += 1;
+                window.n_motions += has_pending_motion as u32;
+            }
+            _ => {
+                self.window = Some(Window {
+                    last_read_instant: now,
+                    n_reads: 1,
+                    n_motions: has_pending_motion as u32,
+                });
+            }
+        }
+    }
+
+    pub fn attribute_motion(&mut self, now: Instant) -> MotionAttribution {
+        match self.window.as_mut() {
+            Some(window) if is_within_window(window, now) => {
+                window.n_motions += 1;
+
+                tracing::debug!(
+                    n_reads = window.n_reads,
+                    n_motions = window.n_motions,
+                    "Attributed motion to recent read"
+                );
+
+                if window.n_motions > window.n_reads {
+                    MotionAttribution::Tailgating
+                } else {
+                    MotionAttribution::Read
+                }
+            }
+            _ => {
+                self.window = None;
+                MotionAttribution::Unattributed
+            }
+        }
+    }
+}
+
+fn is_within_window(window: &Window, now: Instant) -> bool {
+    now.saturating_duration_since(window.last_read_instant) < WINDOW
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_read_per_burst() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut tracker = TailgatingTracker::default();
+        tracker.register_read(at(0), false);
+        assert_eq!(tracker.attribute_motion(at(1)), MotionAttribution::Read);
+
+        tracker.register_read(at(3), false);
+        assert_eq!(tracker.attribute_motion(at(4)), MotionAttribution::Read);
+    }
+
+    #[test]
+    fn burst_before_read() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // The burst that came just before the read is counted with it
+        let mut tracker = TailgatingTracker::default();
+        tracker.register_read(at(0), true);
+        assert_eq!(
+            tracker.attribute_motion(at(1)),
+            MotionAttribution::Tailgating
+        );
+    }
+
+    #[test]
+    fn second_burst_without_read() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut tracker = TailgatingTracker::default();
+        tracker.register_read(at(0), false);
+        assert_eq!(tracker.attribute_motion(at(1)), MotionAttribution::Read);
+        assert_eq!(
+            tracker.attribute_motion(at(2)),
+            MotionAttribution::Tailgating
+        );
+    }
+
+    #[test]
+    fn read_outside_window() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut tracker = TailgatingTracker::default();
+        tracker.register_read(at(0), false);
+        assert_eq!(tracker.attribute_motion(at(1)), MotionAttribution::Read);
+
+        // Starts a new window instead of being counted with the previous read
+        tracker.register_read(at(10), false);
+        assert_eq!(tracker.attribute_motion(at(11)), MotionAttribution::Read);
+        assert_eq!(
+            tracker.attribute_motion(at(12)),
+            MotionAttribution::Tailgating
+        );
+
+        assert_eq!(
+            tracker.attribute_motion(at(22)),
+            MotionAttribution::Unattributed
+        );
+    }
+}
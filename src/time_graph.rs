@@ -10,7 +10,7 @@ use crate::colors::{self, ColorExt};
 
 pub fn draw_image(
     (width, height): (u32, u32),
-    data: &[(chrono::DateTime<Utc>, u32)],
+    data: &[(chrono::DateTime<Utc>, f64)],
 ) -> Result<DynamicImage> {
     let mut raw = vec![0; width as usize * height as usize * RGBPixel::PIXEL_SIZE];
     let backend = BitMapBackend::<RGBPixel>::with_buffer(&mut raw, (width, height));
@@ -26,7 +26,7 @@ pub fn draw_image(
 pub fn draw<DB>(
     backend: DB,
     fill: Option<RGBColor>,
-    data: &[(chrono::DateTime<Utc>, u32)],
+    data: &[(chrono::DateTime<Utc>, f64)],
 ) -> Result<()>
 where
     DB: DrawingBackend,
@@ -56,8 +56,8 @@ where
         |dt: &chrono::DateTime<Utc>| dt.with_timezone(&Local).format("%H:%M:%S").to_string()
     };
 
-    let y_min = data.iter().map(|(_, y)| *y).min().unwrap_or(0);
-    let y_max = data.iter().map(|(_, y)| *y).max().unwrap_or(0);
+    let y_min = data.iter().map(|(_, y)| *y).reduce(f64::min).unwrap_or(0.0);
+    let y_max = data.iter().map(|(_, y)| *y).reduce(f64::max).unwrap_or(0.0);
 
     let mut chart = ChartBuilder::on(&root)
         .margin(20)
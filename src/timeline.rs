@@ -1,6 +1,9 @@
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    time::Instant,
+};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use chrono::{DateTime, Utc};
 use gtk::{
     gio,
@@ -11,6 +14,7 @@ use gtk::{
 use indexmap::IndexMap;
 
 use crate::{
+    clock::Clock,
     date_time_boxed::DateTimeBoxed,
     date_time_range::DateTimeRange,
     db::{self, EnvExt},
@@ -20,7 +24,13 @@ use crate::{
     entity_expired_tracker::EntityExpiredTracker,
     entity_id::EntityId,
     entity_list::EntityList,
+    location::Location,
+    location_data::LocationData,
+    location_id::LocationId,
+    location_list::LocationList,
+    location_move::LocationMove,
     log::Log,
+    quantity::{self, Quantity},
     stock::{Stock, StockLogs},
     stock_data::StockData,
     stock_id::StockId,
@@ -28,8 +38,18 @@ use crate::{
     stock_list::StockList,
     timeline_item::TimelineItem,
     timeline_item_kind::TimelineItemKind,
+    zone::{self, ZoneDef, ZoneStep},
+    zone_move::ZoneMove,
 };
 
+/// What a detection resulted in.
+#[derive(Debug)]
+pub enum Detection {
+    Item(TimelineItem),
+    /// The entity was inside and moved to another zone.
+    ZoneMove(ZoneMove),
+}
+
 mod imp {
     use std::{
         cell::{Cell, OnceCell, RefCell},
@@ -67,9 +87,16 @@ mod imp {
             db::EntitiesDbType,
             db::StocksDbType,
         )>,
+        pub(super) location_db: OnceCell<(db::LocationsDbType, db::LocationMovesDbType)>,
+        pub(super) zone_db: OnceCell<db::ZoneMovesDbType>,
 
         pub(super) entity_list: OnceCell<EntityList>,
         pub(super) stock_list: OnceCell<StockList>,
+        pub(super) location_list: OnceCell<LocationList>,
+        /// Sorted by date-time.
+        pub(super) location_moves: RefCell<Vec<LocationMove>>,
+        /// Sorted by date-time.
+        pub(super) zone_moves: RefCell<Vec<ZoneMove>>,
         pub(super) entity_entry_tracker: EntityEntryTracker,
         pub(super) stock_limit_reached_tracker: StockLimitReachedTracker,
         pub(super) entity_expired_tracker: EntityExpiredTracker,
@@ -78,6 +105,9 @@ mod imp {
         pub(super) max_n_inside_log: RefCell<Log<u32>>,
         pub(super) n_entries_log: RefCell<Log<u32>>,
         pub(super) n_exits_log: RefCell<Log<u32>>,
+        pub(super) zone_n_inside_logs: RefCell<HashMap<String, Log<u32>>>,
+
+        pub(super) clock: RefCell<Clock>,
     }
 
     #[glib::object_subclass]
@@ -160,40 +190,77 @@ impl Timeline {
     pub fn load_from_env(env: heed::Env) -> Result<Self> {
         let start_time = Instant::now();
 
-        let (tdb, items, edb, entities, sdb, stocks) = env.with_write_txn(|wtxn| {
-            let tdb: db::TimelineDbType = env.create_database(wtxn, Some(db::TIMELINE_DB_NAME))?;
-            let items = tdb
-                .iter(wtxn)?
-                .map(|res| res.map(|(dt, raw)| (dt, TimelineItem::from_db(dt, raw))))
-                .collect::<Result<IndexMap<_, _>, _>>()?;
-
-            let edb: db::EntitiesDbType = env.create_database(wtxn, Some(db::ENTITIES_DB_NAME))?;
-            let entities = edb
-                .iter(wtxn)?
-                .map(|res| {
-                    res.map(|(id, data)| {
-                        let entity = Entity::new(id.clone(), data);
-                        (id, entity)
+        let (tdb, items, edb, entities, sdb, stocks, ldb, locations, lmdb, mut moves) = env
+            .with_write_txn(|wtxn| {
+                let tdb: db::TimelineDbType =
+                    env.create_database(wtxn, Some(db::TIMELINE_DB_NAME))?;
+                let items = tdb
+                    .iter(wtxn)?
+                    .map(|res| res.map(|(dt, raw)| (dt, TimelineItem::from_db(dt, raw))))
+                    .collect::<Result<IndexMap<_, _>, _>>()?;
+
+                let edb: db::EntitiesDbType =
+                    env.create_database(wtxn, Some(db::ENTITIES_DB_NAME))?;
+                let entities = edb
+                    .iter(wtxn)?
+                    .map(|res| {
+                        res.map(|(id, data)| {
+                            let entity = Entity::new(id.clone(), data);
+                            (id, entity)
+                        })
                     })
-                })
-                .collect::<Result<IndexMap<_, _>, _>>()?;
-
-            let sdb: db::StocksDbType = env.create_database(wtxn, Some(db::STOCKS_DB_NAME))?;
-            let stocks = sdb
-                .iter(wtxn)?
-                .map(|res| {
-                    res.map(|(id, data)| {
-                        let stock = Stock::new(id.clone(), data);
-                        (id, stock)
+                    .collect::<Result<IndexMap<_, _>, _>>()?;
+
+                let sdb: db::StocksDbType = env.create_database(wtxn, Some(db::STOCKS_DB_NAME))?;
+                let stocks = sdb
+                    .iter(wtxn)?
+                    .map(|res| {
+                        res.map(|(id, data)| {
+                            let stock = Stock::new(id.clone(), data);
+                            (id, stock)
+                        })
                     })
-                })
-                .collect::<Result<IndexMap<_, _>, _>>()?;
+                    .collect::<Result<IndexMap<_, _>, _>>()?;
+
+                let ldb: db::LocationsDbType =
+                    env.create_database(wtxn, Some(db::LOCATIONS_DB_NAME))?;
+                let locations = ldb
+                    .iter(wtxn)?
+                    .map(|res| {
+                        res.map(|(id, data)| {
+                            let location = Location::new(id.clone(), data);
+                            (id, location)
+                        })
+                    })
+                    .collect::<Result<IndexMap<_, _>, _>>()?;
+
+                let lmdb: db::LocationMovesDbType =
+                    env.create_database(wtxn, Some(db::LOCATION_MOVES_DB_NAME))?;
+                let moves = lmdb
+                    .iter(wtxn)?
+                    .map(|res| res.map(|(key, raw)| LocationMove::from_db(key, raw)))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok((
+                    tdb, items, edb, entities, sdb, stocks, ldb, locations, lmdb, moves,
+                ))
+            })?;
+
+        let (zmdb, mut zone_moves) = env.with_write_txn(|wtxn| {
+            let zmdb: db::ZoneMovesDbType =
+                env.create_database(wtxn, Some(db::ZONE_MOVES_DB_NAME))?;
+            let zone_moves = zmdb
+                .iter(wtxn)?
+                .map(|res| res.map(|(key, raw)| ZoneMove::from_db(key, raw)))
+                .collect::<Result<Vec<_>, _>>()?;
 
-            Ok((tdb, items, edb, entities, sdb, stocks))
+            Ok((zmdb, zone_moves))
         })?;
+        moves.sort_by_key(|m| m.dt);
+        zone_moves.sort_by_key(|m| m.dt);
 
         tracing::debug!(
-            "Loaded {} items, entities, and stocks dbs in {:?}",
+            "Loaded {} items, entities, stocks, and locations dbs in {:?}",
             items.len(),
             start_time.elapsed()
         );
@@ -203,14 +270,42 @@ impl Timeline {
         let imp = this.imp();
         imp.list.replace(items);
         imp.db.set((env, tdb, edb, sdb)).unwrap();
+        imp.location_db.set((ldb, lmdb)).unwrap();
+        imp.zone_db.set(zmdb).unwrap();
         imp.entity_list.set(EntityList::from_raw(entities)).unwrap();
         imp.stock_list.set(StockList::from_raw(stocks)).unwrap();
+        imp.location_list
+            .set(LocationList::from_raw(locations))
+            .unwrap();
+        imp.location_moves.replace(moves);
+        imp.zone_moves.replace(zone_moves);
+
+        // Entities may have been given locations before they were tracked.
+        let entity_locations = this
+            .entity_list()
+            .iter()
+            .filter_map(|entity| entity.location())
+            .collect::<Vec<_>>();
+        let new_locations = this.new_locations_for(&entity_locations);
+        if !new_locations.is_empty() {
+            let (env, ..) = this.db();
+            let (ldb, _) = this.location_db();
+            env.with_write_txn(|wtxn| {
+                for location in &new_locations {
+                    ldb.put(wtxn, location.id(), &location.data())?;
+                }
+                Ok(())
+            })?;
+            this.location_list().insert_many(new_locations);
+        }
 
         this.setup_data();
 
         imp.stock_limit_reached_tracker
             .bind_stock_list(this.stock_list());
         imp.entity_expired_tracker
+            .bind_lists(this.entity_list(), this.stock_list());
+        imp.entity_entry_tracker
             .bind_entity_list(this.entity_list());
 
         for entity in this.entity_list().iter() {
@@ -234,6 +329,114 @@ impl Timeline {
         self.imp().stock_list.get().unwrap()
     }
 
+    pub fn location_list(&self) -> &LocationList {
+        self.imp().location_list.get().unwrap()
+    }
+
+    /// Returns the moves of the entity, the earliest first.
+    pub fn location_moves_for_entity(&self, entity_id: &EntityId) -> Vec<LocationMove> {
+        self.imp()
+            .location_moves
+            .borrow()
+            .iter()
+            .filter(|m| &m.entity_id == entity_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the number of entities of the stock inside each location,
+    /// the location with the most first.
+    pub fn stock_n_inside_by_location(
+        &self,
+        stock_id: &StockId,
+        dt_range: &DateTimeRange,
+    ) -> Vec<(Option<LocationId>, u32)> {
+        let mut counts = HashMap::<Option<LocationId>, u32>::new();
+        for entity in self.entity_list().iter().filter(|entity| {
+            entity.stock_id().as_ref() == Some(stock_id) && entity.is_inside_for_dt_range(dt_range)
+        }) {
+            *counts
+                .entry(entity.location_for_dt_range(dt_range))
+                .or_default() += 1;
+        }
+
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|(a_location, a_n), (b_location, b_n)| {
+            b_n.cmp(a_n).then_with(|| a_location.cmp(b_location))
+        });
+        counts
+    }
+
+    /// Returns the location where most of the entities of each stock are
+    /// inside.
+    pub fn stock_primary_locations(
+        &self,
+        dt_range: &DateTimeRange,
+    ) -> HashMap<StockId, LocationId> {
+        let mut counts = HashMap::<(StockId, LocationId), u32>::new();
+        for entity in self
+            .entity_list()
+            .iter()
+            .filter(|entity| entity.is_inside_for_dt_range(dt_range))
+        {
+            if let (Some(stock_id), Some(location)) =
+                (entity.stock_id(), entity.location_for_dt_range(dt_range))
+            {
+                *counts.entry((stock_id, location)).or_default() += 1;
+            }
+        }
+
+        let mut primary_locations = HashMap::<StockId, (LocationId, u32)>::new();
+        for ((stock_id, location), n) in counts {
+            match primary_locations.entry(stock_id) {
+                Entry::Occupied(mut entry) => {
+                    let (prev_location, prev_n) = entry.get();
+
+                    // Ties go to the first location by name, so it is stable.
+                    if n > *prev_n || (n == *prev_n && location < *prev_location) {
+                        entry.insert((location, n));
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((location, n));
+                }
+            }
+        }
+
+        primary_locations
+            .into_iter()
+            .map(|(stock_id, (location, _))| (stock_id, location))
+            .collect()
+    }
+
+    /// Returns the number of entities currently in the zone.
+    pub fn zone_n_inside(&self, zone: &str) -> u32 {
+        self.imp()
+            .zone_n_inside_logs
+            .borrow()
+            .get(zone)
+            .and_then(|log| log.latest().copied())
+            .unwrap_or(0)
+    }
+
+    /// Returns the entities that stayed in their current zone for longer than
+    /// it allows, with the zone.
+    pub fn zone_overstayed_entities(
+        &self,
+        zone_defs: &[ZoneDef],
+        now: DateTime<Utc>,
+    ) -> Vec<(Entity, String)> {
+        self.entity_list()
+            .iter()
+            .filter_map(|entity| {
+                let zone = entity.zone_path().pop().flatten()?;
+                let def = zone_defs.iter().find(|def| def.name == zone)?;
+                let entered_dt = entity.zone_entered_dt()?;
+                def.is_overstayed(entered_dt, now).then_some((entity, zone))
+            })
+            .collect()
+    }
+
     pub fn entity_entry_tracker(&self) -> &EntityEntryTracker {
         &self.imp().entity_entry_tracker
     }
@@ -246,6 +449,19 @@ impl Timeline {
         &self.imp().entity_expired_tracker
     }
 
+    pub fn clock(&self) -> Clock {
+        self.imp().clock.borrow().clone()
+    }
+
+    /// Sets the clock used for new detections and by the trackers.
+    pub fn set_clock(&self, clock: Clock) {
+        let imp = self.imp();
+
+        imp.entity_entry_tracker.set_clock(clock.clone());
+        imp.entity_expired_tracker.set_clock(clock.clone());
+        imp.clock.replace(clock);
+    }
+
     pub fn get(&self, dt: &DateTime<Utc>) -> Option<TimelineItem> {
         self.imp().list.borrow().get(dt).cloned()
     }
@@ -341,10 +557,252 @@ impl Timeline {
         }
     }
 
+    /// Handles a detection at a reader of `zone`, or at the main entrance if
+    /// `None`.
+    ///
+    /// An entity inside that is read at another zone moves into it, instead of
+    /// exiting.
     pub fn handle_detected(
         &self,
         entity_id: &EntityId,
         entity_data: EntityData,
+        zone: Option<String>,
+    ) -> Result<Detection> {
+        let now_dt = self.clock().now();
+        self.handle_read_at(entity_id, entity_data, zone, now_dt)
+    }
+
+    /// Handles detections that happened in the past, e.g., those that were
+    /// queued while waiting for the entity data, given the time and zone of
+    /// each read.
+    ///
+    /// The entity must not have any action or zone move on or after the
+    /// earliest read.
+    pub fn handle_detected_backdated(
+        &self,
+        entity_id: &EntityId,
+        entity_data: EntityData,
+        mut reads: Vec<(DateTime<Utc>, Option<String>)>,
+    ) -> Result<Vec<Detection>> {
+        let imp = self.imp();
+
+        reads.sort_by_key(|(dt, _)| *dt);
+        reads.dedup_by_key(|(dt, _)| *dt);
+
+        let Some(first_dt) = reads.first().map(|(dt, _)| *dt) else {
+            return Ok(Vec::new());
+        };
+
+        // Fast path, nothing happened after the detections.
+        if imp
+            .list
+            .borrow()
+            .last()
+            .map_or(true, |(dt, _)| &first_dt > dt)
+        {
+            return reads
+                .into_iter()
+                .map(|(dt, zone)| self.handle_read_at(entity_id, entity_data.clone(), zone, dt))
+                .collect();
+        }
+
+        let entity = self
+            .entity_list()
+            .get(entity_id)
+            .unwrap_or_else(|| Entity::new(entity_id.clone(), entity_data.clone()));
+
+        if entity_data.stock_id() != entity.stock_id().as_ref() {
+            bail!(
+                "Entity `{}` already handled with different stock id",
+                entity_id
+            );
+        }
+
+        if entity
+            .last_action_dt()
+            .is_some_and(|last_action_dt| last_action_dt >= first_dt)
+            || entity
+                .zone_entered_dt()
+                .is_some_and(|zone_entered_dt| zone_entered_dt >= first_dt)
+        {
+            bail!(
+                "Entity `{}` already has actions after {}",
+                entity_id,
+                first_dt
+            );
+        }
+
+        if let Some((dt, _)) = reads
+            .iter()
+            .find(|(dt, _)| imp.list.borrow().contains_key(dt))
+        {
+            bail!("Timeline already has an item at {}", dt);
+        }
+
+        let quantity = entity.data().quantity().cloned();
+
+        // Replay the reads the same way `handle_detected` would have.
+        let zones = reads
+            .iter()
+            .map(|(_, zone)| zone.clone())
+            .collect::<Vec<_>>();
+        let steps = zone::replay_reads(entity.is_inside(), entity.zone_path(), &zones);
+        let is_inside = steps
+            .iter()
+            .fold(entity.is_inside(), |is_inside, step| match step {
+                ZoneStep::Enter => true,
+                ZoneStep::Exit => false,
+                ZoneStep::MoveTo(_) => is_inside,
+            });
+        let mut path = entity.zone_path();
+        let detections = reads
+            .into_iter()
+            .zip(steps)
+            .map(|((dt, zone), step)| match step {
+                ZoneStep::MoveTo(to) => {
+                    let from = path.last().cloned().flatten();
+                    zone::apply_move(&mut path, to.clone());
+                    Detection::ZoneMove(ZoneMove {
+                        dt,
+                        entity_id: entity_id.clone(),
+                        from,
+                        to,
+                    })
+                }
+                ZoneStep::Exit => {
+                    path.clear();
+                    Detection::Item(TimelineItem::new(
+                        dt,
+                        TimelineItemKind::Exit,
+                        entity_id.clone(),
+                        None,
+                        zone,
+                    ))
+                }
+                ZoneStep::Enter => {
+                    path = vec![zone.clone()];
+                    Detection::Item(TimelineItem::new(
+                        dt,
+                        TimelineItemKind::Entry,
+                        entity_id.clone(),
+                        quantity.clone(),
+                        zone,
+                    ))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let items = detections
+            .iter()
+            .filter_map(|detection| match detection {
+                Detection::Item(item) => Some(item.clone()),
+                Detection::ZoneMove(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let zone_moves = detections
+            .iter()
+            .filter_map(|detection| match detection {
+                Detection::Item(_) => None,
+                Detection::ZoneMove(zone_move) => Some(zone_move.clone()),
+            })
+            .collect::<Vec<_>>();
+
+        let stock = entity.stock_id().map(|stock_id| {
+            self.stock_list()
+                .get(&stock_id)
+                .unwrap_or_else(|| Stock::new(stock_id.clone(), StockData::default()))
+        });
+
+        let new_locations = self.new_locations_for(entity.location().as_ref());
+
+        let (env, tdb, edb, sdb) = self.db();
+        let (ldb, _) = self.location_db();
+        let zmdb = self.zone_db();
+        env.with_write_txn(|wtxn| {
+            for item in &items {
+                tdb.put(wtxn, &item.dt(), &item.to_db())?;
+            }
+            for zone_move in &zone_moves {
+                zmdb.put(wtxn, &zone_move.db_key(), &zone_move.to_db())?;
+            }
+            edb.put(wtxn, entity.id(), &entity.data())?;
+            if let Some(stock) = &stock {
+                sdb.put(wtxn, stock.id(), &stock.data())?;
+            }
+            for location in &new_locations {
+                ldb.put(wtxn, location.id(), &location.data())?;
+            }
+            Ok(())
+        })?;
+
+        let prev_len = {
+            let mut list = imp.list.borrow_mut();
+            let prev_len = list.len();
+            list.extend(items.iter().map(|item| (item.dt(), item.clone())));
+            list.sort_keys();
+            prev_len
+        };
+
+        {
+            let mut all_zone_moves = imp.zone_moves.borrow_mut();
+            all_zone_moves.extend(zone_moves);
+            all_zone_moves.sort_by_key(|m| m.dt);
+        }
+
+        self.entity_list().insert(entity.clone());
+        if let Some(stock) = &stock {
+            self.stock_list().insert(stock.clone());
+        }
+        self.location_list().insert_many(new_locations);
+
+        // Logs after the first detection are all affected, so just rebuild them.
+        self.setup_data();
+
+        // Update the entity and stock again, so filters and sorters pick up the new logs.
+        self.entity_list().insert(entity);
+        if let Some(stock) = stock {
+            self.stock_list().insert(stock);
+        }
+
+        if is_inside {
+            imp.entity_entry_tracker.handle_entry(entity_id);
+        } else {
+            imp.entity_entry_tracker.handle_exit(entity_id);
+        }
+
+        self.items_changed(0, prev_len as u32, imp.list.borrow().len() as u32);
+
+        debug_assert!(imp.list.borrow().keys().is_sorted());
+
+        Ok(detections)
+    }
+
+    /// Handles a read at `dt`, which moves the entity between zones if it is
+    /// inside, or otherwise adds an entry or exit.
+    fn handle_read_at(
+        &self,
+        entity_id: &EntityId,
+        entity_data: EntityData,
+        zone: Option<String>,
+        dt: DateTime<Utc>,
+    ) -> Result<Detection> {
+        if let Some(entity) = self.entity_list().get(entity_id) {
+            if let ZoneStep::MoveTo(to) = ZoneStep::for_read(&entity.zone_path(), zone.as_deref()) {
+                let zone_move = self.handle_zone_move(&entity, to, dt)?;
+                return Ok(Detection::ZoneMove(zone_move));
+            }
+        }
+
+        let item = self.handle_detected_at(entity_id, entity_data, zone, dt)?;
+        Ok(Detection::Item(item))
+    }
+
+    fn handle_detected_at(
+        &self,
+        entity_id: &EntityId,
+        entity_data: EntityData,
+        zone: Option<String>,
+        now_dt: DateTime<Utc>,
     ) -> Result<TimelineItem> {
         let imp = self.imp();
 
@@ -366,7 +824,6 @@ impl Timeline {
             );
         }
 
-        let now_dt = Utc::now();
         debug_assert!(imp
             .list
             .borrow()
@@ -380,21 +837,32 @@ impl Timeline {
         } else {
             TimelineItemKind::Entry
         };
-        let item = TimelineItem::new(now_dt, item_kind, entity_id.clone());
+        let quantity = if is_exit {
+            None
+        } else {
+            entity.data().quantity().cloned()
+        };
+        let item = TimelineItem::new(now_dt, item_kind, entity_id.clone(), quantity, zone.clone());
 
         let stock = entity.stock_id().map(|stock_id| {
             self.stock_list()
                 .get(&stock_id)
-                .unwrap_or_else(|| Stock::new(stock_id.clone(), StockData {}))
+                .unwrap_or_else(|| Stock::new(stock_id.clone(), StockData::default()))
         });
 
+        let new_locations = self.new_locations_for(entity.location().as_ref());
+
         let (env, tdb, edb, sdb) = self.db();
+        let (ldb, _) = self.location_db();
         env.with_write_txn(|wtxn| {
             tdb.put(wtxn, &now_dt, &item.to_db())?;
             edb.put(wtxn, entity.id(), &entity.data())?;
             if let Some(stock) = &stock {
                 sdb.put(wtxn, stock.id(), &stock.data())?;
             }
+            for location in &new_locations {
+                ldb.put(wtxn, location.id(), &location.data())?;
+            }
             Ok(())
         })?;
 
@@ -438,19 +906,26 @@ impl Timeline {
         entity.with_action_log_mut(|map| {
             map.insert(now_dt, item_kind);
         });
+        self.set_entity_zone_path(
+            &entity,
+            now_dt,
+            if is_exit { Vec::new() } else { vec![zone] },
+        );
 
         if let Some(stock) = &stock {
-            let prev_n_inisde = stock.n_inside();
+            let amount = stock_amount_of(&item, stock.data().unit.as_deref());
+
+            let prev_n_inside = stock.n_inside();
             let new_n_inside = if is_exit {
-                prev_n_inisde - 1
+                quantity::round(prev_n_inside - amount)
             } else {
-                prev_n_inisde + 1
+                quantity::round(prev_n_inside + amount)
             };
 
             stock.with_logs_mut(|logs| {
                 logs.n_inside.insert(now_dt, new_n_inside);
 
-                let prev_max_n_inside = logs.max_n_inside.latest().copied().unwrap_or(0);
+                let prev_max_n_inside = logs.max_n_inside.latest().copied().unwrap_or(0.0);
                 if new_n_inside > prev_max_n_inside {
                     logs.max_n_inside.insert(now_dt, new_n_inside);
                 }
@@ -467,6 +942,11 @@ impl Timeline {
             });
         }
 
+        self.location_list().insert_many(new_locations);
+        if let Some(location) = entity.location() {
+            self.add_to_location_n_inside(&location, now_dt, if is_exit { -1 } else { 1 });
+        }
+
         let (index, prev_value) = imp.list.borrow_mut().insert_full(now_dt, item.clone());
         debug_assert_eq!(prev_value, None);
 
@@ -488,19 +968,45 @@ impl Timeline {
         Ok(item)
     }
 
-    pub fn replace_entity_data(&self, id: &EntityId, data: EntityData) -> Result<()> {
+    /// Replaces the data of the entity. If its location changed, it is
+    /// recorded as a move.
+    ///
+    /// Returns the move, if any.
+    pub fn replace_entity_data(
+        &self,
+        id: &EntityId,
+        data: EntityData,
+    ) -> Result<Option<LocationMove>> {
         let entity = self.entity_list().get(id).context("Unknown entity")?;
+
+        let now_dt = self.clock().now();
+        let prev_location = entity.location();
         entity.set_data(data.with_stock_id(entity.stock_id())); // FIXME Allow changing stock ID
+        let location_move = self.location_move_for(&entity, prev_location, now_dt);
+
+        let new_locations = self.new_locations_for(entity.location().as_ref());
 
         let (env, _, edb, _) = self.db();
+        let (ldb, lmdb) = self.location_db();
         env.with_write_txn(|wtxn| {
             edb.put(wtxn, entity.id(), &entity.data())?;
+            for location in &new_locations {
+                ldb.put(wtxn, location.id(), &location.data())?;
+            }
+            if let Some(location_move) = &location_move {
+                lmdb.put(wtxn, &location_move.db_key(), &location_move.to_db())?;
+            }
             Ok(())
         })?;
 
+        self.location_list().insert_many(new_locations);
+        if let Some(location_move) = &location_move {
+            self.apply_location_move(location_move.clone());
+        }
+
         self.entity_list().insert(entity);
 
-        Ok(())
+        Ok(location_move)
     }
 
     pub fn register_entity_data(&self, data_map: HashMap<EntityId, EntityData>) -> Result<()> {
@@ -508,14 +1014,18 @@ impl Timeline {
             .iter()
             .filter_map(|(_, data)| data.stock_id())
             .filter(|stock_id| !self.stock_list().contains(stock_id))
-            .map(|stock_id| Stock::new(stock_id.clone(), StockData {}))
+            .map(|stock_id| Stock::new(stock_id.clone(), StockData::default()))
             .collect::<Vec<_>>();
 
+        let now_dt = self.clock().now();
+        let mut location_moves = Vec::new();
         let entities = data_map
             .into_iter()
             .map(|(id, data)| {
                 if let Some(entity) = self.entity_list().get(&id) {
+                    let prev_location = entity.location();
                     entity.set_data(data.with_stock_id(entity.stock_id())); // FIXME Allow changing stock ID
+                    location_moves.extend(self.location_move_for(&entity, prev_location, now_dt));
                     entity
                 } else {
                     Entity::new(id, data)
@@ -523,7 +1033,14 @@ impl Timeline {
             })
             .collect::<Vec<_>>();
 
+        let entity_locations = entities
+            .iter()
+            .filter_map(|entity| entity.location())
+            .collect::<Vec<_>>();
+        let new_locations = self.new_locations_for(&entity_locations);
+
         let (env, _, edb, sdb) = self.db();
+        let (ldb, lmdb) = self.location_db();
         env.with_write_txn(|wtxn| {
             for entity in &entities {
                 edb.put(wtxn, entity.id(), &entity.data())?;
@@ -531,9 +1048,22 @@ impl Timeline {
             for stock in &stocks {
                 sdb.put(wtxn, stock.id(), &stock.data())?;
             }
+            for location in &new_locations {
+                ldb.put(wtxn, location.id(), &location.data())?;
+            }
+            for location_move in &location_moves {
+                lmdb.put(wtxn, &location_move.db_key(), &location_move.to_db())?;
+            }
             Ok(())
         })?;
 
+        let n_appended_locations = self.location_list().insert_many(new_locations);
+        tracing::debug!("Appended `{}` new locations", n_appended_locations);
+
+        for location_move in location_moves {
+            self.apply_location_move(location_move);
+        }
+
         let n_appended_entities = self.entity_list().insert_many(entities);
         tracing::debug!("Appended `{}` new entities", n_appended_entities);
 
@@ -564,37 +1094,214 @@ impl Timeline {
             Ok(())
         })?;
 
+        // Counts depend on the stock units, so rebuild them.
+        self.setup_data();
+
         let n_appended_stocks = self.stock_list().insert_many(stocks);
         tracing::debug!("Appended `{}` new stocks", n_appended_stocks);
 
         Ok(())
     }
 
+    pub fn register_location_data(
+        &self,
+        data_map: HashMap<LocationId, LocationData>,
+    ) -> Result<()> {
+        let (env, ..) = self.db();
+        let (ldb, _) = self.location_db();
+        env.with_write_txn(|wtxn| {
+            for (id, data) in &data_map {
+                ldb.put(wtxn, id, data)?;
+            }
+            Ok(())
+        })?;
+
+        // Known locations are updated in place, so that rows showing them are
+        // not recreated while being edited.
+        let mut new_locations = Vec::new();
+        for (id, data) in data_map {
+            if let Some(location) = self.location_list().get(&id) {
+                location.set_data(data);
+            } else {
+                new_locations.push(Location::new(id, data));
+            }
+        }
+
+        let n_appended_locations = self.location_list().insert_many(new_locations);
+        tracing::debug!("Appended `{}` new locations", n_appended_locations);
+
+        Ok(())
+    }
+
+    /// Records that some of the quantity of an entity was consumed since it
+    /// last exited, so it enters again with the rest.
+    pub fn record_consumption(&self, entity_id: &EntityId, consumed: Quantity) -> Result<()> {
+        let entity = self
+            .entity_list()
+            .get(entity_id)
+            .context("Unknown entity")?;
+        ensure!(!entity.is_inside(), "Entity `{}` is not outside", entity_id);
+
+        let quantity = entity
+            .data()
+            .quantity()
+            .cloned()
+            .with_context(|| format!("Entity `{}` has no quantity", entity_id))?;
+        let remaining = quantity.checked_sub(&consumed)?;
+
+        let exit_dt = entity
+            .last_action_dt()
+            .with_context(|| format!("Entity `{}` has not exited yet", entity_id))?;
+        let exit_item = self.get(&exit_dt).expect("exit item must be known");
+        let total_consumed = match exit_item.consumed() {
+            Some(prev_consumed) => prev_consumed.checked_add(&consumed)?,
+            None => consumed,
+        };
+
+        exit_item.set_consumed(Some(total_consumed));
+        entity.set_data(entity.data().with_quantity(Some(remaining)));
+
+        let (env, tdb, edb, _) = self.db();
+        env.with_write_txn(|wtxn| {
+            tdb.put(wtxn, &exit_dt, &exit_item.to_db())?;
+            edb.put(wtxn, entity.id(), &entity.data())?;
+            Ok(())
+        })?;
+
+        // Consumed counts after the exit are all affected, so just rebuild them.
+        self.setup_data();
+
+        // Update the entity and stock again, so filters and sorters pick up the changes.
+        if let Some(stock) = entity
+            .stock_id()
+            .and_then(|stock_id| self.stock_list().get(&stock_id))
+        {
+            self.stock_list().insert(stock);
+        }
+        self.entity_list().insert(entity);
+
+        Ok(())
+    }
+
+    /// Fills an empty timeline with the entities and their detections at once,
+    /// which is much faster than handling each detection.
+    ///
+    /// Detections of each entity alternate between entry and exit.
+    pub fn populate(
+        &self,
+        data_map: HashMap<EntityId, EntityData>,
+        mut detections: Vec<(DateTime<Utc>, EntityId)>,
+    ) -> Result<()> {
+        let imp = self.imp();
+
+        ensure!(imp.list.borrow().is_empty(), "Timeline must be empty");
+
+        detections.sort_by_key(|(dt, _)| *dt);
+
+        if let Some(window) = detections.windows(2).find(|w| w[0].0 == w[1].0) {
+            bail!("Multiple detections at {}", window[0].0);
+        }
+
+        if let Some((_, entity_id)) = detections.iter().find(|(_, entity_id)| {
+            !data_map.contains_key(entity_id) && self.entity_list().get(entity_id).is_none()
+        }) {
+            bail!("Unknown entity `{}`", entity_id);
+        }
+
+        self.register_entity_data(data_map)?;
+
+        let mut is_inside = HashMap::<&EntityId, bool>::new();
+        let items = detections
+            .iter()
+            .map(|(dt, entity_id)| {
+                let is_inside = is_inside.entry(entity_id).or_default();
+                let item = if *is_inside {
+                    TimelineItem::new(*dt, TimelineItemKind::Exit, entity_id.clone(), None, None)
+                } else {
+                    let quantity = self
+                        .entity_list()
+                        .get(entity_id)
+                        .and_then(|entity| entity.data().quantity().cloned());
+                    TimelineItem::new(
+                        *dt,
+                        TimelineItemKind::Entry,
+                        entity_id.clone(),
+                        quantity,
+                        None,
+                    )
+                };
+                *is_inside = !*is_inside;
+                item
+            })
+            .collect::<Vec<_>>();
+
+        let (env, tdb, _, _) = self.db();
+        env.with_write_txn(|wtxn| {
+            for item in &items {
+                tdb.put(wtxn, &item.dt(), &item.to_db())?;
+            }
+            Ok(())
+        })?;
+
+        imp.list
+            .borrow_mut()
+            .extend(items.into_iter().map(|item| (item.dt(), item)));
+
+        self.setup_data();
+
+        // Update the entities and stocks again, so filters and sorters pick up the new logs.
+        self.entity_list()
+            .insert_many(self.entity_list().iter().collect());
+        self.stock_list()
+            .insert_many(self.stock_list().iter().collect());
+
+        for entity in self.entity_list().iter() {
+            if entity.is_inside() {
+                imp.entity_entry_tracker.handle_entry(entity.id());
+            }
+        }
+
+        self.items_changed(0, 0, imp.list.borrow().len() as u32);
+
+        debug_assert!(imp.list.borrow().keys().is_sorted());
+
+        Ok(())
+    }
+
     pub fn reset(&self) -> Result<()> {
         let imp = self.imp();
 
         let prev_len = imp.list.borrow().len();
 
         let (env, tdb, edb, sdb) = self.db();
+        let (ldb, lmdb) = self.location_db();
+        let zmdb = self.zone_db();
         env.with_write_txn(|wtxn| {
             tdb.clear(wtxn)?;
             edb.clear(wtxn)?;
             sdb.clear(wtxn)?;
+            ldb.clear(wtxn)?;
+            lmdb.clear(wtxn)?;
+            zmdb.clear(wtxn)?;
             Ok(())
         })?;
 
         imp.list.borrow_mut().clear();
+        imp.location_moves.borrow_mut().clear();
+        imp.zone_moves.borrow_mut().clear();
 
         imp.n_inside_log.borrow_mut().clear();
         imp.max_n_inside_log.borrow_mut().clear();
         imp.n_entries_log.borrow_mut().clear();
         imp.n_exits_log.borrow_mut().clear();
+        imp.zone_n_inside_logs.borrow_mut().clear();
 
         self.set_last_entry_dt(None);
         self.set_last_exit_dt(None);
 
         self.entity_list().clear();
         self.stock_list().clear();
+        self.location_list().clear();
 
         self.entity_entry_tracker().reset();
 
@@ -642,6 +1349,278 @@ impl Timeline {
         self.imp().db.get().unwrap()
     }
 
+    fn location_db(&self) -> &(db::LocationsDbType, db::LocationMovesDbType) {
+        self.imp().location_db.get().unwrap()
+    }
+
+    fn zone_db(&self) -> &db::ZoneMovesDbType {
+        self.imp().zone_db.get().unwrap()
+    }
+
+    /// Returns the given locations and the locations containing them that are
+    /// not known yet.
+    fn new_locations_for<'a>(
+        &self,
+        location_ids: impl IntoIterator<Item = &'a LocationId>,
+    ) -> Vec<Location> {
+        let mut new_locations = IndexMap::new();
+        for location_id in location_ids {
+            for id in location_id.ancestors() {
+                if !self.location_list().contains(&id) && !new_locations.contains_key(&id) {
+                    new_locations.insert(id.clone(), Location::new(id, LocationData::default()));
+                }
+            }
+        }
+        new_locations.into_values().collect()
+    }
+
+    /// Returns the move to record if the entity's location changed from
+    /// `prev_location`.
+    fn location_move_for(
+        &self,
+        entity: &Entity,
+        prev_location: Option<LocationId>,
+        dt: DateTime<Utc>,
+    ) -> Option<LocationMove> {
+        let location = entity.location();
+        (location != prev_location).then(|| LocationMove {
+            dt,
+            entity_id: entity.id().clone(),
+            from: prev_location,
+            to: location,
+        })
+    }
+
+    fn apply_location_move(&self, location_move: LocationMove) {
+        let imp = self.imp();
+
+        let entity = self
+            .entity_list()
+            .get(&location_move.entity_id)
+            .expect("entity must be known");
+
+        entity.with_location_log_mut(|log| {
+            // Before its first move, the entity was where it was moved from.
+            if log.latest_dt().is_none() {
+                log.insert(DateTime::<Utc>::MIN_UTC, location_move.from.clone());
+            }
+            log.insert(location_move.dt, location_move.to.clone());
+        });
+
+        if entity.is_inside_for_dt(location_move.dt) {
+            if let Some(from) = &location_move.from {
+                self.add_to_location_n_inside(from, location_move.dt, -1);
+            }
+            if let Some(to) = &location_move.to {
+                self.add_to_location_n_inside(to, location_move.dt, 1);
+            }
+        }
+
+        imp.location_moves.borrow_mut().push(location_move);
+    }
+
+    /// Adds `delta` to the count of the location and the locations containing
+    /// it.
+    fn add_to_location_n_inside(&self, location_id: &LocationId, dt: DateTime<Utc>, delta: i32) {
+        for id in location_id.ancestors() {
+            let location = self
+                .location_list()
+                .get(&id)
+                .expect("location must be known");
+            location.with_n_inside_log_mut(|log| {
+                let prev_n_inside = log.latest().copied().unwrap_or(0);
+                log.insert(dt, prev_n_inside.saturating_add_signed(delta));
+            });
+
+            // Update the location again, so filters and sorters pick up the new count.
+            self.location_list().insert(location);
+        }
+    }
+
+    fn setup_location_data(&self) {
+        let imp = self.imp();
+
+        let mut entity_location_logs = HashMap::<EntityId, Log<Option<LocationId>>>::new();
+        for location_move in imp.location_moves.borrow().iter() {
+            let log = entity_location_logs
+                .entry(location_move.entity_id.clone())
+                .or_insert_with(|| {
+                    // Before its first move, the entity was where it was moved from.
+                    let mut log = Log::new();
+                    log.insert(DateTime::<Utc>::MIN_UTC, location_move.from.clone());
+                    log
+                });
+            log.insert(location_move.dt, location_move.to.clone());
+        }
+
+        for entity in self.entity_list().iter() {
+            let log = entity_location_logs.remove(entity.id()).unwrap_or_default();
+            entity.with_location_log_mut(|l| {
+                *l = log;
+            });
+        }
+
+        let mut deltas = Vec::new();
+        for item in imp.list.borrow().values() {
+            let entity = self
+                .entity_list()
+                .get(item.entity_id())
+                .expect("entity must be known");
+
+            if let Some(location) = entity.location_for_dt(item.dt()) {
+                let delta = if item.kind().is_entry() { 1 } else { -1 };
+                deltas.push((item.dt(), location, delta));
+            }
+        }
+        for location_move in imp.location_moves.borrow().iter() {
+            let entity = self
+                .entity_list()
+                .get(&location_move.entity_id)
+                .expect("entity must be known");
+
+            if entity.is_inside_for_dt(location_move.dt) {
+                if let Some(from) = &location_move.from {
+                    deltas.push((location_move.dt, from.clone(), -1));
+                }
+                if let Some(to) = &location_move.to {
+                    deltas.push((location_move.dt, to.clone(), 1));
+                }
+            }
+        }
+        deltas.sort_by_key(|(dt, _, _)| *dt);
+
+        let mut n_insides = HashMap::<LocationId, u32>::new();
+        let mut n_inside_logs = HashMap::<LocationId, Log<u32>>::new();
+        for (dt, location, delta) in deltas {
+            for id in location.ancestors() {
+                let n_inside = n_insides.entry(id.clone()).or_default();
+                *n_inside = n_inside.saturating_add_signed(delta);
+                n_inside_logs.entry(id).or_default().insert(dt, *n_inside);
+            }
+        }
+
+        for location in self.location_list().iter() {
+            let log = n_inside_logs.remove(location.id()).unwrap_or_default();
+            location.with_n_inside_log_mut(|l| {
+                *l = log;
+            });
+        }
+    }
+
+    fn handle_zone_move(
+        &self,
+        entity: &Entity,
+        to: Option<String>,
+        dt: DateTime<Utc>,
+    ) -> Result<ZoneMove> {
+        let imp = self.imp();
+
+        let zone_move = ZoneMove {
+            dt,
+            entity_id: entity.id().clone(),
+            from: entity.zone_path().pop().flatten(),
+            to,
+        };
+
+        let (env, ..) = self.db();
+        let zmdb = self.zone_db();
+        env.with_write_txn(|wtxn| {
+            zmdb.put(wtxn, &zone_move.db_key(), &zone_move.to_db())?;
+            Ok(())
+        })?;
+
+        let mut path = entity.zone_path();
+        zone::apply_move(&mut path, zone_move.to.clone());
+        self.set_entity_zone_path(entity, dt, path);
+
+        imp.zone_moves.borrow_mut().push(zone_move.clone());
+
+        // Update the entity again, so filters and sorters pick up the new path.
+        self.entity_list().insert(entity.clone());
+
+        Ok(zone_move)
+    }
+
+    /// Sets the zones the entity went through, and updates the counts of the
+    /// zone it left and the zone it got into.
+    fn set_entity_zone_path(&self, entity: &Entity, dt: DateTime<Utc>, path: Vec<Option<String>>) {
+        let imp = self.imp();
+
+        {
+            let mut n_inside_logs = imp.zone_n_inside_logs.borrow_mut();
+            if let Some(prev_zone) = entity.zone_path().pop().flatten() {
+                add_to_zone_n_inside(&mut n_inside_logs, &prev_zone, dt, -1);
+            }
+            if let Some(Some(zone)) = path.last() {
+                add_to_zone_n_inside(&mut n_inside_logs, zone, dt, 1);
+            }
+        }
+
+        entity.with_zone_path_log_mut(|log| {
+            log.insert(dt, path);
+        });
+    }
+
+    fn setup_zone_data(&self) {
+        let imp = self.imp();
+
+        // `None` exits, while `Some` moves into the zone, which also starts
+        // the path on entries.
+        let mut steps = imp
+            .list
+            .borrow()
+            .values()
+            .map(|item| {
+                let to = item
+                    .kind()
+                    .is_entry()
+                    .then(|| item.zone().map(|zone| zone.to_string()));
+                (item.dt(), item.entity_id().clone(), to)
+            })
+            .collect::<Vec<_>>();
+        steps.extend(
+            imp.zone_moves
+                .borrow()
+                .iter()
+                .map(|m| (m.dt, m.entity_id.clone(), Some(m.to.clone()))),
+        );
+        steps.sort_by_key(|(dt, _, _)| *dt);
+
+        let mut paths = HashMap::<EntityId, Vec<Option<String>>>::new();
+        let mut path_logs = HashMap::<EntityId, Log<Vec<Option<String>>>>::new();
+        let mut n_inside_logs = HashMap::<String, Log<u32>>::new();
+        for (dt, entity_id, to) in steps {
+            let path = paths.entry(entity_id.clone()).or_default();
+
+            if let Some(Some(prev_zone)) = path.last() {
+                add_to_zone_n_inside(&mut n_inside_logs, prev_zone, dt, -1);
+            }
+
+            match to {
+                Some(to) => zone::apply_move(path, to),
+                None => path.clear(),
+            }
+
+            if let Some(Some(zone)) = path.last() {
+                add_to_zone_n_inside(&mut n_inside_logs, zone, dt, 1);
+            }
+
+            path_logs
+                .entry(entity_id)
+                .or_default()
+                .insert(dt, path.clone());
+        }
+
+        for entity in self.entity_list().iter() {
+            let log = path_logs.remove(entity.id()).unwrap_or_default();
+            entity.with_zone_path_log_mut(|l| {
+                *l = log;
+            });
+        }
+
+        imp.zone_n_inside_logs.replace(n_inside_logs);
+    }
+
     fn setup_data(&self) {
         let imp = self.imp();
 
@@ -658,6 +1637,12 @@ impl Timeline {
         let mut entity_action_logs = HashMap::<EntityId, Log<TimelineItemKind>>::new();
         let mut stock_logs: HashMap<StockId, StockLogs> = HashMap::new();
 
+        let stock_units = self
+            .stock_list()
+            .iter()
+            .map(|stock| (stock.id().clone(), stock.data().unit))
+            .collect::<HashMap<_, _>>();
+
         for item in imp.list.borrow().values() {
             let entity = self
                 .entity_list()
@@ -699,16 +1684,18 @@ impl Timeline {
 
             if let Some(stock_id) = entity.stock_id() {
                 let logs = stock_logs.entry(stock_id.clone()).or_default();
+                let stock_unit = stock_units.get(&stock_id).and_then(|unit| unit.as_deref());
 
-                let prev_n_inside = logs.n_inside.latest().copied().unwrap_or(0);
+                let amount = stock_amount_of(item, stock_unit);
+                let prev_n_inside = logs.n_inside.latest().copied().unwrap_or(0.0);
                 let new_n_inside = if item.kind().is_exit() {
-                    prev_n_inside - 1
+                    quantity::round(prev_n_inside - amount)
                 } else {
-                    prev_n_inside + 1
+                    quantity::round(prev_n_inside + amount)
                 };
                 logs.n_inside.insert(item.dt(), new_n_inside);
 
-                let prev_max_n_inside = logs.max_n_inside.latest().copied().unwrap_or(0);
+                let prev_max_n_inside = logs.max_n_inside.latest().copied().unwrap_or(0.0);
                 if new_n_inside > prev_max_n_inside {
                     logs.max_n_inside.insert(item.dt(), new_n_inside);
                 }
@@ -717,6 +1704,15 @@ impl Timeline {
                     let prev_n_exits = logs.n_exits.latest().copied().unwrap_or(0);
                     logs.n_exits.insert(item.dt(), prev_n_exits + 1);
                     logs.last_exit_dt.insert(item.dt(), item.dt());
+
+                    if let Some(consumed) = item.consumed() {
+                        let prev_n_consumed = logs.n_consumed.latest().copied().unwrap_or(0.0);
+                        let consumed_amount = quantity::stock_amount(Some(&consumed), stock_unit);
+                        logs.n_consumed.insert(
+                            item.dt(),
+                            quantity::round(prev_n_consumed + consumed_amount),
+                        );
+                    }
                 } else {
                     let prev_n_entries = logs.n_entries.latest().copied().unwrap_or(0);
                     logs.n_entries.insert(item.dt(), prev_n_entries + 1);
@@ -767,6 +1763,9 @@ impl Timeline {
             });
         }
 
+        self.setup_location_data();
+        self.setup_zone_data();
+
         debug_assert_eq!(
             self.n_entries(),
             imp.list
@@ -785,3 +1784,23 @@ impl Timeline {
         );
     }
 }
+
+fn add_to_zone_n_inside(
+    n_inside_logs: &mut HashMap<String, Log<u32>>,
+    zone: &str,
+    dt: DateTime<Utc>,
+    delta: i32,
+) {
+    let log = n_inside_logs.entry(zone.to_string()).or_default();
+    let prev_n_inside = log.latest().copied().unwrap_or(0);
+    log.insert(dt, prev_n_inside.saturating_add_signed(delta));
+}
+
+/// Returns how much the item adds to or takes from the count of its stock.
+pub fn stock_amount_of(item: &TimelineItem, stock_unit: Option<&str>) -> f64 {
+    let entry_item = match item.kind() {
+        TimelineItemKind::Entry => item.clone(),
+        TimelineItemKind::Exit => item.pair().expect("exit item must have an entry pair"),
+    };
+    quantity::stock_amount(entry_item.quantity(), stock_unit)
+}
@@ -4,19 +4,24 @@ use anyhow::{Context, Result};
 use calamine::{Data, DataType, Reader};
 
 use crate::{
+    custom_field::{CustomFieldDef, CustomFields},
     date_time,
     date_time_range::DateTimeRange,
     entity_data::{EntityData, EntityDataField, EntityDataFieldTy},
     entity_id::EntityId,
     jpeg_image::JpegImage,
+    quantity::Quantity,
     sex::Sex,
-    stock_data::StockData,
     stock_id::StockId,
     timeline::Timeline,
 };
 
 impl Timeline {
-    pub fn register_data_from_workbook_bytes(&self, workbook_bytes: &[u8]) -> Result<()> {
+    pub fn register_data_from_workbook_bytes(
+        &self,
+        workbook_bytes: &[u8],
+        custom_field_defs: &[CustomFieldDef],
+    ) -> Result<()> {
         let mut book = calamine::open_workbook_auto_from_rs(Cursor::new(workbook_bytes))?;
         let range = book.worksheet_range_at(0).context("Empty sheets")??;
 
@@ -27,43 +32,94 @@ impl Timeline {
         let entity_id_col_idx = find_position(col_title_row, |s| {
             matches!(s.to_lowercase().as_str(), "entity" | "entity id" | "id")
         });
+
+        // Custom fields are matched by their exact title first, so that, e.g.,
+        // a "Supplier Name" column is not taken as the name.
+        let custom_col_idxs = custom_field_defs
+            .iter()
+            .filter_map(|def| {
+                let col_idx = find_position(col_title_row, |s| def.matches_col_title(s))?;
+                Some((def, col_idx))
+            })
+            .collect::<Vec<_>>();
+        let builtin_col_title_row = col_title_row
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| {
+                if custom_col_idxs.iter().any(|&(_, i)| i == idx) {
+                    Data::Empty
+                } else {
+                    cell.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
         let col_idxs = EntityDataFieldTy::all()
             .iter()
             .filter_map(|field_ty| {
                 let col_idx = match field_ty {
-                    EntityDataFieldTy::StockId => find_position(col_title_row, |s| {
+                    EntityDataFieldTy::StockId => find_position(&builtin_col_title_row, |s| {
                         s.to_lowercase().as_str().contains("stock")
                     }),
-                    EntityDataFieldTy::Location => find_position(col_title_row, |s| {
+                    EntityDataFieldTy::Location => find_position(&builtin_col_title_row, |s| {
                         s.to_lowercase().as_str().contains("location")
                     }),
-                    EntityDataFieldTy::ExpirationDt => find_position(col_title_row, |s| {
+                    EntityDataFieldTy::ExpirationDt => find_position(&builtin_col_title_row, |s| {
                         s.to_lowercase().as_str().contains("expiration")
                     }),
-                    EntityDataFieldTy::AllowedDtRange => find_position(col_title_row, |s| {
-                        s.to_lowercase().as_str().contains("date range")
+                    EntityDataFieldTy::Lot => find_position(&builtin_col_title_row, |s| {
+                        let s = s.to_lowercase();
+                        s.contains("lot") || s.contains("batch")
                     }),
-                    EntityDataFieldTy::Photo => find_position(col_title_row, |s| {
+                    EntityDataFieldTy::Quantity => find_position(&builtin_col_title_row, |s| {
+                        let s = s.to_lowercase();
+                        s.contains("quantity") || s.contains("qty")
+                    }),
+                    EntityDataFieldTy::AllowedDtRange => {
+                        find_position(&builtin_col_title_row, |s| {
+                            s.to_lowercase().as_str().contains("date range")
+                        })
+                    }
+                    EntityDataFieldTy::Photo => find_position(&builtin_col_title_row, |s| {
                         s.to_lowercase().as_str().contains("photo")
                     }),
-                    EntityDataFieldTy::Name => find_position(col_title_row, |s| {
+                    EntityDataFieldTy::Name => find_position(&builtin_col_title_row, |s| {
                         s.to_lowercase().as_str().contains("name")
                     }),
-                    EntityDataFieldTy::Sex => {
-                        find_position(col_title_row, |s| s.to_lowercase().as_str().contains("sex"))
-                    }
-                    EntityDataFieldTy::Email => find_position(col_title_row, |s| {
+                    EntityDataFieldTy::Sex => find_position(&builtin_col_title_row, |s| {
+                        s.to_lowercase().as_str().contains("sex")
+                    }),
+                    EntityDataFieldTy::Email => find_position(&builtin_col_title_row, |s| {
                         s.to_lowercase().as_str().contains("email")
                     }),
-                    EntityDataFieldTy::Program => find_position(col_title_row, |s| {
+                    EntityDataFieldTy::Program => find_position(&builtin_col_title_row, |s| {
                         s.to_lowercase().as_str().contains("program")
                     }),
+                    // Matched per custom field above.
+                    EntityDataFieldTy::Custom => None,
                 };
 
                 col_idx.map(|col_idx| (field_ty, col_idx))
             })
             .collect::<HashMap<_, _>>();
         let stock_id_col_idx = col_idxs.get(&EntityDataFieldTy::StockId).copied();
+        let stock_unit_col_idx =
+            find_position(col_title_row, |s| s.trim().eq_ignore_ascii_case("unit"));
+        let stock_expiring_soon_window_days_col_idx = find_position(col_title_row, |s| {
+            s.to_lowercase().as_str().contains("expiring soon")
+        });
+        let stock_target_days_of_cover_col_idx = find_position(col_title_row, |s| {
+            s.to_lowercase().as_str().contains("days of cover")
+        });
+        let stock_supplier_col_idx = find_position(col_title_row, |s| {
+            s.to_lowercase().as_str().contains("supplier")
+        });
+        let stock_unit_cost_col_idx = find_position(col_title_row, |s| {
+            s.to_lowercase().as_str().contains("cost")
+        });
+        let stock_currency_col_idx = find_position(col_title_row, |s| {
+            s.to_lowercase().as_str().contains("currency")
+        });
 
         let mut entity_data = HashMap::new();
         let mut stock_data = HashMap::new();
@@ -93,6 +149,17 @@ impl Timeline {
                                     .ok()
                             })
                             .map(EntityDataField::ExpirationDt),
+                        EntityDataFieldTy::Lot => row[idx].as_string().map(EntityDataField::Lot),
+                        EntityDataFieldTy::Quantity => row[idx]
+                            .as_string()
+                            .and_then(|s| {
+                                s.parse::<Quantity>()
+                                    .inspect_err(|err| {
+                                        tracing::warn!("Failed to parse quantity: {:?}", err)
+                                    })
+                                    .ok()
+                            })
+                            .map(EntityDataField::Quantity),
                         EntityDataFieldTy::AllowedDtRange => row[idx]
                             .as_string()
                             .and_then(|s| {
@@ -124,15 +191,75 @@ impl Timeline {
                         EntityDataFieldTy::Program => {
                             row[idx].as_string().map(EntityDataField::Program)
                         }
+                        EntityDataFieldTy::Custom => unreachable!(),
                     });
 
-                entity_data.insert(entity_id, EntityData::from_fields(fields));
+                let custom_fields = custom_col_idxs
+                    .iter()
+                    .filter_map(|&(def, idx)| {
+                        let string = row[idx].as_string()?;
+                        let value = def
+                            .parse_value(&string)
+                            .inspect_err(|err| {
+                                tracing::warn!("Failed to parse {}: {:?}", def.label(), err)
+                            })
+                            .ok()?;
+                        Some((def.key.clone(), value))
+                    })
+                    .collect::<CustomFields>();
+
+                entity_data.insert(
+                    entity_id,
+                    EntityData::from_fields(
+                        fields.chain(
+                            (!custom_fields.is_empty())
+                                .then_some(EntityDataField::Custom(custom_fields)),
+                        ),
+                    ),
+                );
             } else if let Some(stock_id_col_idx) = stock_id_col_idx {
                 let Some(stock_id) = row[stock_id_col_idx].as_string().map(StockId::new) else {
                     continue;
                 };
 
-                stock_data.insert(stock_id, StockData {});
+                // Keep the current values if the sheet doesn't have them.
+                let mut data = self
+                    .stock_list()
+                    .get(&stock_id)
+                    .map(|stock| stock.data())
+                    .unwrap_or_default();
+                if let Some(idx) = stock_unit_col_idx {
+                    data.unit = row[idx]
+                        .as_string()
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty());
+                }
+                if let Some(idx) = stock_expiring_soon_window_days_col_idx {
+                    data.expiring_soon_window_days =
+                        row[idx].as_i64().and_then(|days| u32::try_from(days).ok());
+                }
+                if let Some(idx) = stock_target_days_of_cover_col_idx {
+                    data.target_days_of_cover =
+                        row[idx].as_i64().and_then(|days| u32::try_from(days).ok());
+                }
+                if let Some(idx) = stock_supplier_col_idx {
+                    data.supplier = row[idx]
+                        .as_string()
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty());
+                }
+                if let Some(idx) = stock_unit_cost_col_idx {
+                    data.unit_cost = row[idx]
+                        .as_f64()
+                        .filter(|cost| cost.is_finite() && *cost >= 0.0);
+                }
+                if let Some(idx) = stock_currency_col_idx {
+                    data.currency = row[idx]
+                        .as_string()
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty());
+                }
+                stock_data.insert(stock_id, data);
             }
         }
 
@@ -1,10 +1,10 @@
 use chrono::{DateTime, TimeDelta, Utc};
 use gtk::{glib, subclass::prelude::*};
 
-use crate::{db, entity_id::EntityId, timeline_item_kind::TimelineItemKind};
+use crate::{db, entity_id::EntityId, quantity::Quantity, timeline_item_kind::TimelineItemKind};
 
 mod imp {
-    use std::cell::OnceCell;
+    use std::cell::{OnceCell, RefCell};
 
     use glib::WeakRef;
 
@@ -15,6 +15,9 @@ mod imp {
         pub(super) dt: OnceCell<DateTime<Utc>>,
         pub(super) kind: OnceCell<TimelineItemKind>,
         pub(super) entity_id: OnceCell<EntityId>,
+        pub(super) quantity: OnceCell<Option<Quantity>>,
+        pub(super) zone: OnceCell<Option<String>>,
+        pub(super) consumed: RefCell<Option<Quantity>>,
 
         pub(super) pair: WeakRef<super::TimelineItem>,
     }
@@ -33,13 +36,23 @@ glib::wrapper! {
 }
 
 impl TimelineItem {
-    pub fn new(dt: DateTime<Utc>, kind: TimelineItemKind, entity_id: EntityId) -> Self {
+    pub fn new(
+        dt: DateTime<Utc>,
+        kind: TimelineItemKind,
+        entity_id: EntityId,
+        quantity: Option<Quantity>,
+        zone: Option<String>,
+    ) -> Self {
+        debug_assert!(kind.is_entry() || quantity.is_none());
+
         let this = glib::Object::new::<Self>();
 
         let imp = this.imp();
         imp.dt.set(dt).unwrap();
         imp.kind.set(kind).unwrap();
         imp.entity_id.set(entity_id).unwrap();
+        imp.quantity.set(quantity).unwrap();
+        imp.zone.set(zone).unwrap();
 
         this
     }
@@ -50,13 +63,18 @@ impl TimelineItem {
         } else {
             TimelineItemKind::Exit
         };
-        Self::new(dt, kind, raw.entity_id)
+        let this = Self::new(dt, kind, raw.entity_id, raw.quantity, raw.zone);
+        this.imp().consumed.replace(raw.consumed);
+        this
     }
 
     pub fn to_db(&self) -> db::RawTimelineItem {
         db::RawTimelineItem {
             is_entry: self.kind().is_entry(),
             entity_id: self.entity_id().clone(),
+            quantity: self.imp().quantity.get().unwrap().clone(),
+            consumed: self.consumed(),
+            zone: self.zone().map(|zone| zone.to_string()),
         }
     }
 
@@ -72,6 +90,30 @@ impl TimelineItem {
         self.imp().entity_id.get().unwrap()
     }
 
+    /// Quantity of the entity when it entered, which is also what leaves on
+    /// the paired exit.
+    ///
+    /// This is only set on entries.
+    pub fn quantity(&self) -> Option<&Quantity> {
+        self.imp().quantity.get().unwrap().as_ref()
+    }
+
+    /// Zone of the reader that detected the entity, if it belongs to one.
+    pub fn zone(&self) -> Option<&str> {
+        self.imp().zone.get().unwrap().as_deref()
+    }
+
+    /// Quantity consumed while the entity was outside after this exit.
+    pub fn consumed(&self) -> Option<Quantity> {
+        self.imp().consumed.borrow().clone()
+    }
+
+    pub fn set_consumed(&self, consumed: Option<Quantity>) {
+        debug_assert!(self.kind().is_exit());
+
+        self.imp().consumed.replace(consumed);
+    }
+
     pub fn pair(&self) -> Option<TimelineItem> {
         self.imp().pair.upgrade()
     }
@@ -0,0 +1,500 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+
+use crate::{
+    date_time_range::DateTimeRange,
+    entity_data::{EntityData, EntityDataField},
+    entity_id::EntityId,
+    settings::OperationMode,
+    sex::Sex,
+    stock_id::StockId,
+};
+
+const FIRST_NAMES: &[(&str, Sex)] = &[
+    ("Juan", Sex::Male),
+    ("Jose", Sex::Male),
+    ("Mark", Sex::Male),
+    ("Paolo", Sex::Male),
+    ("Miguel", Sex::Male),
+    ("Maria", Sex::Female),
+    ("Angel", Sex::Female),
+    ("Kristine", Sex::Female),
+    ("Patricia", Sex::Female),
+    ("Andrea", Sex::Female),
+];
+const LAST_NAMES: &[&str] = &[
+    "Santos",
+    "Reyes",
+    "Cruz",
+    "Bautista",
+    "Garcia",
+    "Mendoza",
+    "Torres",
+    "Flores",
+    "Villanueva",
+    "Ramos",
+];
+const PROGRAMS: &[&str] = &["BSCpE", "BSEE", "BSME", "BSCE", "BSIT"];
+
+const INVENTORY_STOCKS: &[(&str, u32)] = &[
+    ("Paracetamol", 720),
+    ("Amoxicillin", 540),
+    ("Gloves", 1080),
+    ("Syringes", 1440),
+    ("Bandages", 1080),
+    ("Alcohol", 720),
+    ("Face Masks", 1080),
+    ("Vitamin C", 540),
+];
+const REFRIGERATOR_STOCKS: &[(&str, u32)] = &[
+    ("Milk", 7),
+    ("Eggs", 21),
+    ("Cheese", 30),
+    ("Yogurt", 14),
+    ("Butter", 60),
+    ("Lettuce", 5),
+    ("Chicken", 3),
+    ("Juice", 10),
+];
+
+#[derive(Debug, Clone)]
+pub struct TrafficConfig {
+    pub operation_mode: OperationMode,
+    pub seed: u64,
+    pub n_days: u32,
+    pub n_entities: u32,
+    /// Nothing is generated after this.
+    pub end_dt: DateTime<Utc>,
+}
+
+/// Generated entities and their detections, which alternate between entry and
+/// exit for each entity.
+#[derive(Debug, Default, PartialEq)]
+pub struct Traffic {
+    pub entities: HashMap<EntityId, EntityData>,
+    /// Sorted by date-time, with no two detections at the same date-time.
+    pub detections: Vec<(DateTime<Utc>, EntityId)>,
+}
+
+/// Generates realistic multi-day traffic for the operation mode.
+///
+/// The same config always generates the same traffic.
+pub fn generate(config: &TrafficConfig) -> Traffic {
+    let mut generator = Generator {
+        config,
+        rng: Rng::new(config.seed),
+        start_dt: config.end_dt - TimeDelta::days(config.n_days as i64),
+        entities: HashMap::new(),
+        detections: BTreeMap::new(),
+    };
+
+    match config.operation_mode {
+        OperationMode::Counter | OperationMode::Attendance => generator.people(),
+        OperationMode::Parking => generator.vehicles(),
+        OperationMode::Inventory => generator.stocks(INVENTORY_STOCKS, true),
+        OperationMode::Refrigerator => generator.stocks(REFRIGERATOR_STOCKS, false),
+    }
+
+    Traffic {
+        entities: generator.entities,
+        detections: generator.detections.into_iter().collect(),
+    }
+}
+
+struct Generator<'a> {
+    config: &'a TrafficConfig,
+    rng: Rng,
+    start_dt: DateTime<Utc>,
+    entities: HashMap<EntityId, EntityData>,
+    detections: BTreeMap<DateTime<Utc>, EntityId>,
+}
+
+impl Generator<'_> {
+    /// Students or employees that come in on most weekdays and stay for
+    /// most of the day.
+    fn people(&mut self) {
+        for index in 0..self.config.n_entities {
+            let id = self.entity_id(index);
+
+            let data = if self.config.operation_mode == OperationMode::Attendance {
+                self.person_data()
+            } else {
+                EntityData::new()
+            };
+            self.entities.insert(id.clone(), data);
+
+            let attendance_rate = self.rng.range(0.75, 0.98);
+            let mean_arrival_hours = self.rng.range(7.0, 9.5);
+            let mean_dwell_hours = self.rng.range(6.0, 9.0);
+
+            let mut last_exit_dt = self.start_dt;
+            for date in self.dates() {
+                let rate = if matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+                    attendance_rate * 0.1
+                } else {
+                    attendance_rate
+                };
+                if self.rng.next_f64() >= rate {
+                    continue;
+                }
+
+                let arrival_hours = self.rng.normal(mean_arrival_hours, 0.5);
+                let mut dwell_hours = self.rng.normal(mean_dwell_hours, 1.0).max(0.5);
+
+                // Overstays
+                if self.rng.next_f64() < 0.02 {
+                    dwell_hours += self.rng.range(3.0, 6.0);
+                }
+
+                if let Some(entry_dt) = local_dt(date, arrival_hours) {
+                    if entry_dt > last_exit_dt {
+                        last_exit_dt = self.visit(&id, entry_dt, hours(dwell_hours));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Vehicles that come at any time of the day and park for a few hours.
+    fn vehicles(&mut self) {
+        for index in 0..self.config.n_entities {
+            let id = self.plate_number(index);
+
+            let mut fields = vec![EntityDataField::Location(format!(
+                "Slot {}",
+                self.rng.below(200) + 1
+            ))];
+            if let Some(dt_range) = self.maybe_allowed_dt_range() {
+                fields.push(EntityDataField::AllowedDtRange(dt_range));
+            }
+            self.entities
+                .insert(id.clone(), EntityData::from_fields(fields));
+
+            let visit_rate = self.rng.range(0.2, 0.7);
+
+            let mut last_exit_dt = self.start_dt;
+            for date in self.dates() {
+                if self.rng.next_f64() >= visit_rate {
+                    continue;
+                }
+
+                let arrival_hours = self.rng.range(6.0, 20.0);
+                let dwell_hours = self.rng.log_normal(2.0, 0.7).min(16.0);
+
+                if let Some(entry_dt) = local_dt(date, arrival_hours) {
+                    if entry_dt > last_exit_dt {
+                        last_exit_dt = self.visit(&id, entry_dt, hours(dwell_hours));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Items that are delivered in batches, then consumed, some only after
+    /// they have expired.
+    fn stocks(&mut self, stocks: &[(&str, u32)], has_location: bool) {
+        let mut index = 0;
+
+        while index < self.config.n_entities {
+            let &(stock_name, shelf_life_days) = self.rng.choose(stocks).unwrap();
+
+            let batch_size = (self.rng.below(10) + 3).min(self.config.n_entities - index);
+            let delivery_date = self
+                .dates()
+                .nth(self.rng.below(self.config.n_days.max(1)) as usize)
+                .unwrap_or_else(|| self.config.end_dt.with_timezone(&Local).date_naive());
+            let Some(delivery_dt) = local_dt(delivery_date, self.rng.range(8.0, 17.0)) else {
+                continue;
+            };
+
+            let shelf_life = days(shelf_life_days as f64 * self.rng.range(0.8, 1.2));
+            let expiration_dt = delivery_dt + shelf_life;
+            let lot = format!(
+                "L{}{:02}",
+                delivery_date.format("%y%m%d"),
+                self.rng.below(100)
+            );
+            let location = format!(
+                "Shelf {}{}",
+                (b'A' + self.rng.below(6) as u8) as char,
+                self.rng.below(5) + 1
+            );
+
+            for _ in 0..batch_size {
+                let id = self.entity_id(index);
+                index += 1;
+
+                let mut fields = vec![
+                    EntityDataField::StockId(StockId::new(stock_name)),
+                    EntityDataField::ExpirationDt(expiration_dt),
+                    EntityDataField::Lot(lot.clone()),
+                ];
+                if has_location {
+                    fields.push(EntityDataField::Location(location.clone()));
+                }
+                self.entities
+                    .insert(id.clone(), EntityData::from_fields(fields));
+
+                // Items are unpacked one after another.
+                let mut entry_dt = delivery_dt + TimeDelta::seconds(self.rng.below(600) as i64);
+
+                // Some items are taken out and put back before being consumed.
+                if !has_location && self.rng.next_f64() < 0.2 {
+                    let dwell = days(self.rng.range(0.1, 0.5) * shelf_life_days as f64);
+                    let exit_dt = self.visit(&id, entry_dt, dwell);
+                    entry_dt = exit_dt + TimeDelta::minutes(self.rng.range(5.0, 60.0) as i64);
+                }
+
+                // Consumption is slow enough that a few items expire first.
+                let dwell = days(self.rng.exponential(shelf_life_days as f64 * 0.6));
+                self.visit(&id, entry_dt, dwell);
+            }
+        }
+    }
+
+    /// Adds an entry and an exit after the dwell time, and returns the exit
+    /// date-time.
+    ///
+    /// Detections after the end are dropped, so entities can be left inside.
+    fn visit(&mut self, id: &EntityId, entry_dt: DateTime<Utc>, dwell: TimeDelta) -> DateTime<Utc> {
+        let exit_dt = entry_dt + dwell;
+
+        for dt in [entry_dt, exit_dt] {
+            if dt < self.start_dt || dt > self.config.end_dt {
+                break;
+            }
+
+            // Bump colliding detections, which is small enough to never
+            // reorder the detections of the same entity.
+            let mut dt = dt;
+            while self.detections.contains_key(&dt) {
+                dt += TimeDelta::milliseconds(1);
+            }
+            self.detections.insert(dt, id.clone());
+        }
+
+        exit_dt
+    }
+
+    fn dates(&self) -> impl Iterator<Item = NaiveDate> {
+        let start_date = self.start_dt.with_timezone(&Local).date_naive();
+        (0..self.config.n_days as u64)
+            .filter_map(move |n| start_date.checked_add_days(Days::new(n)))
+    }
+
+    fn entity_id(&mut self, index: u32) -> EntityId {
+        EntityId::new(format!("{:04x}{:04x}", index, self.rng.below(0x10000)))
+    }
+
+    fn plate_number(&mut self, index: u32) -> EntityId {
+        let letters = (0..3)
+            .map(|_| (b'A' + self.rng.below(26) as u8) as char)
+            .collect::<String>();
+        EntityId::new(format!("{letters} {index:04}"))
+    }
+
+    fn person_data(&mut self) -> EntityData {
+        let &(first_name, sex) = self.rng.choose(FIRST_NAMES).unwrap();
+        let &last_name = self.rng.choose(LAST_NAMES).unwrap();
+        let &program = self.rng.choose(PROGRAMS).unwrap();
+
+        let mut fields = vec![
+            EntityDataField::Name(format!("{first_name} {last_name}")),
+            EntityDataField::Sex(sex),
+            EntityDataField::Email(format!(
+                "{}.{}@example.com",
+                first_name.to_lowercase(),
+                last_name.to_lowercase()
+            )),
+            EntityDataField::Program(program.to_string()),
+        ];
+        if let Some(dt_range) = self.maybe_allowed_dt_range() {
+            fields.push(EntityDataField::AllowedDtRange(dt_range));
+        }
+
+        EntityData::from_fields(fields)
+    }
+
+    /// Returns a range that ends before the generated period does for some
+    /// entities, so later detections of them are allowed range violations.
+    fn maybe_allowed_dt_range(&mut self) -> Option<DateTimeRange> {
+        if self.rng.next_f64() >= 0.1 {
+            return None;
+        }
+
+        let n_days = self.config.n_days as f64;
+        Some(DateTimeRange {
+            start: Some(self.start_dt),
+            end: Some(self.start_dt + days(self.rng.range(0.3, 0.9) * n_days)),
+        })
+    }
+}
+
+fn local_dt(date: NaiveDate, hours: f64) -> Option<DateTime<Utc>> {
+    let naive = date.and_time(NaiveTime::MIN) + TimeDelta::seconds((hours * 3600.0) as i64);
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|dt| dt.to_utc())
+}
+
+fn hours(hours: f64) -> TimeDelta {
+    TimeDelta::seconds((hours * 3600.0) as i64)
+}
+
+fn days(days: f64) -> TimeDelta {
+    TimeDelta::seconds((days * 86_400.0) as i64)
+}
+
+/// Small seeded PRNG (SplitMix64), so generated traffic is reproducible.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a value in `[0, n)`.
+    fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n.max(1) as u64) as u32
+    }
+
+    fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + self.next_f64() * (max - min)
+    }
+
+    fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+
+        Some(&items[self.below(items.len() as u32) as usize])
+    }
+
+    /// Box-Muller transform.
+    fn normal(&mut self, mean: f64, std_dev: f64) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        mean + std_dev * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+
+    /// Returns a value whose median is `median`.
+    fn log_normal(&mut self, median: f64, sigma: f64) -> f64 {
+        self.normal(median.ln(), sigma).exp()
+    }
+
+    fn exponential(&mut self, mean: f64) -> f64 {
+        -(1.0 - self.next_f64()).ln() * mean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::entity_data::ValidEntityFields;
+
+    const ALL_OPERATION_MODES: [OperationMode; 5] = [
+        OperationMode::Counter,
+        OperationMode::Attendance,
+        OperationMode::Parking,
+        OperationMode::Inventory,
+        OperationMode::Refrigerator,
+    ];
+
+    fn config(operation_mode: OperationMode, seed: u64) -> TrafficConfig {
+        TrafficConfig {
+            operation_mode,
+            seed,
+            n_days: 60,
+            n_entities: 40,
+            end_dt: Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        for operation_mode in ALL_OPERATION_MODES {
+            assert_eq!(
+                generate(&config(operation_mode, 1)),
+                generate(&config(operation_mode, 1))
+            );
+            assert_ne!(
+                generate(&config(operation_mode, 1)),
+                generate(&config(operation_mode, 2))
+            );
+        }
+    }
+
+    #[test]
+    fn consistent() {
+        for operation_mode in ALL_OPERATION_MODES {
+            let config = config(operation_mode, 3);
+            let traffic = generate(&config);
+
+            assert_eq!(traffic.entities.len(), config.n_entities as usize);
+            assert!(!traffic.detections.is_empty());
+
+            let valid_fields = ValidEntityFields::for_operation_mode(operation_mode);
+            for data in traffic.entities.values() {
+                assert!(valid_fields.is_valid_entity_data(data));
+                assert!(data.fields().all(|f| valid_fields.contains(f.ty())));
+            }
+
+            let start_dt = config.end_dt - TimeDelta::days(config.n_days as i64);
+            for window in traffic.detections.windows(2) {
+                assert!(window[0].0 < window[1].0);
+            }
+            for (dt, id) in &traffic.detections {
+                assert!(*dt >= start_dt && *dt <= config.end_dt);
+                assert!(traffic.entities.contains_key(id));
+            }
+        }
+    }
+
+    #[test]
+    fn stocks_have_consumption_and_expirations() {
+        let config = TrafficConfig {
+            n_days: 365,
+            n_entities: 200,
+            ..config(OperationMode::Refrigerator, 4)
+        };
+        let traffic = generate(&config);
+
+        let mut n_detections = HashMap::<&EntityId, u32>::new();
+        for (_, id) in &traffic.detections {
+            *n_detections.entry(id).or_default() += 1;
+        }
+
+        // Some are consumed, while some are still inside.
+        assert!(n_detections.values().any(|n| n % 2 == 0));
+        assert!(n_detections.values().any(|n| n % 2 == 1));
+
+        let n_expired_inside = n_detections
+            .iter()
+            .filter(|(_, n)| *n % 2 == 1)
+            .filter(|(id, _)| {
+                traffic.entities[**id]
+                    .expiration_dt()
+                    .is_some_and(|dt| *dt < config.end_dt)
+            })
+            .count();
+        assert!(n_expired_inside > 0);
+    }
+}
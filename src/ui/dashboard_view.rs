@@ -7,6 +7,7 @@ use inflections::case;
 
 use crate::{
     ai_chat_message_list::AiChatMessageList,
+    custom_field::{CustomFieldDef, CustomFieldSettingsExt, CustomFieldTy},
     date_time,
     date_time_range::DateTimeRange,
     entity_data::EntityDataFieldTy,
@@ -22,9 +23,11 @@ use crate::{
         detected_wo_id_dialog::DetectedWoIdDialog,
         entity_photo_gallery_dialog::EntityPhotoGalleryDialog,
         information_row::InformationRow,
+        pending_detections_dialog::PendingDetectionsDialog,
         receive_dialog::{InvalidFileExtension, ReceiveDialog},
         time_graph::TimeGraph,
     },
+    zone::ZoneSettingsExt,
     Application,
 };
 
@@ -39,7 +42,7 @@ Take note of the following contexts:
 - The data are retrieved from a system where entities can enter and exit a location.
 - Entities refer to uniquely identified people, foods, vehicles, animals, or objects.
 - Stocks refer to a group of entities that are the same type.
-- Zone refers to whether the entity is inside or outside the location; don't refer to it as "zone", use different phrasings.
+- Zones are parts of the location with their own readers; the path of an entity is the zones it went through since it entered, and "Entrance" is the main entrance.
 
 Take note of the following instructions:
 - Use markdown format for the response.
@@ -59,11 +62,12 @@ pub enum DashboardViewShowRequest {
     InsideEntities,
     OverstayedEntities,
     LimitReachedStocks(LimitReached),
+    ExpiringEntities,
     ExpiredEntities,
 }
 
 mod imp {
-    use std::sync::OnceLock;
+    use std::{cell::RefCell, sync::OnceLock};
 
     use glib::subclass::Signal;
 
@@ -95,8 +99,12 @@ mod imp {
         #[template_child]
         pub(super) n_upper_limit_reached_stocks_row: TemplateChild<InformationRow>,
         #[template_child]
+        pub(super) n_expiring_entities_row: TemplateChild<InformationRow>,
+        #[template_child]
         pub(super) n_expired_entities_row: TemplateChild<InformationRow>,
         #[template_child]
+        pub(super) zones_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
         pub(super) n_inside_graph: TemplateChild<TimeGraph>,
         #[template_child]
         pub(super) max_n_inside_graph: TemplateChild<TimeGraph>,
@@ -105,6 +113,8 @@ mod imp {
         #[template_child]
         pub(super) n_exits_graph: TemplateChild<TimeGraph>,
 
+        pub(super) zones_group_rows: RefCell<Vec<InformationRow>>,
+
         pub(super) ai_chat_message_list: AiChatMessageList,
     }
 
@@ -155,6 +165,13 @@ mod imp {
                     ));
                 },
             );
+            klass.install_action(
+                "dashboard-view.show-expiring-entities",
+                None,
+                |obj, _, _| {
+                    obj.emit_show_request(DashboardViewShowRequest::ExpiringEntities);
+                },
+            );
             klass.install_action("dashboard-view.show-expired-entities", None, |obj, _, _| {
                 obj.emit_show_request(DashboardViewShowRequest::ExpiredEntities);
             });
@@ -183,6 +200,19 @@ mod imp {
                     dialog.present(Some(obj));
                 },
             );
+            klass.install_action(
+                "dashboard-view.show-pending-detections-dialog",
+                None,
+                |obj, _, _| {
+                    let dialog = PendingDetectionsDialog::new();
+
+                    let app = Application::get();
+                    let list = app.pending_detection_list();
+                    dialog.set_model(Some(list));
+
+                    dialog.present(Some(obj));
+                },
+            );
             klass.install_action(
                 "dashboard-view.show-entity-gallery-dialog",
                 None,
@@ -234,6 +264,7 @@ mod imp {
                             settings.lower_limit_reached_threshold(),
                             settings.upper_limit_reached_threshold()
                         )),
+                        custom_field_defs_instruction(&settings.custom_field_defs()),
                         Some(format!("The datetime now is {}", Local::now())),
                         Some(
                             "Your response should be based on the following csv documents:".into(),
@@ -314,9 +345,10 @@ mod imp {
                         &[".xls", ".xlsx", ".xlsm", ".xlsb", ".xla", ".xlam", ".ods"];
                     match ReceiveDialog::receive(valid_file_extensions, Some(&obj)).await {
                         Ok((_, bytes)) => {
-                            if let Err(err) =
-                                app.timeline().register_data_from_workbook_bytes(&bytes)
-                            {
+                            if let Err(err) = app.timeline().register_data_from_workbook_bytes(
+                                &bytes,
+                                &app.settings().custom_field_defs(),
+                            ) {
                                 tracing::error!("Failed to register data: {:?}", err);
 
                                 app.add_message_toast("Failed to register data");
@@ -358,7 +390,7 @@ mod imp {
                 move |_| {
                     obj.update_n_inside_title_label();
                     obj.update_n_limit_reached_stocks_rows_visibility();
-                    obj.update_n_expired_entities_row_visibility();
+                    obj.update_n_expiration_entities_rows_visibility();
                 }
             ));
             settings.connect_limit_reached_threshold_changed(clone!(
@@ -368,6 +400,13 @@ mod imp {
                     obj.update_n_inside_label();
                 }
             ));
+            settings.connect_zones_json_changed(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.update_zones_group();
+                }
+            ));
 
             let timeline = app.timeline();
             timeline.connect_items_changed(clone!(
@@ -420,6 +459,14 @@ mod imp {
                 }
             ));
 
+            timeline.entity_list().connect_items_changed(clone!(
+                #[weak]
+                obj,
+                move |_, _, _, _| {
+                    obj.update_zones_group();
+                }
+            ));
+
             timeline
                 .entity_entry_tracker()
                 .connect_overstayed_changed(clone!(
@@ -447,6 +494,15 @@ mod imp {
                         obj.update_n_upper_limit_reached_stocks_row();
                     }
                 ));
+            timeline
+                .entity_expired_tracker()
+                .connect_n_expiring_notify(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_n_expiring_entities_row();
+                    }
+                ));
             timeline
                 .entity_expired_tracker()
                 .connect_n_expired_notify(clone!(
@@ -469,8 +525,10 @@ mod imp {
             obj.update_n_lower_limit_reached_stocks_row();
             obj.update_n_upper_limit_reached_stocks_row();
             obj.update_n_limit_reached_stocks_rows_visibility();
+            obj.update_n_expiring_entities_row();
             obj.update_n_expired_entities_row();
-            obj.update_n_expired_entities_row_visibility();
+            obj.update_n_expiration_entities_rows_visibility();
+            obj.update_zones_group();
         }
 
         fn dispose(&self) {
@@ -655,6 +713,16 @@ impl DashboardView {
         imp.n_upper_limit_reached_stocks_row.set_visible(is_visible);
     }
 
+    fn update_n_expiring_entities_row(&self) {
+        let imp = self.imp();
+
+        let n_expiring = Application::get()
+            .timeline()
+            .entity_expired_tracker()
+            .n_expiring();
+        imp.n_expiring_entities_row.set_text(n_expiring.to_string());
+    }
+
     fn update_n_expired_entities_row(&self) {
         let imp = self.imp();
 
@@ -665,15 +733,59 @@ impl DashboardView {
         imp.n_expired_entities_row.set_text(n_expired.to_string());
     }
 
-    fn update_n_expired_entities_row_visibility(&self) {
+    fn update_n_expiration_entities_rows_visibility(&self) {
         let imp = self.imp();
 
         let is_visible = Application::get()
             .settings()
             .operation_mode()
             .is_valid_entity_data_field_ty(EntityDataFieldTy::ExpirationDt);
+        imp.n_expiring_entities_row.set_visible(is_visible);
         imp.n_expired_entities_row.set_visible(is_visible);
     }
+
+    fn update_zones_group(&self) {
+        let imp = self.imp();
+
+        for row in imp.zones_group_rows.take() {
+            imp.zones_group.remove(&row);
+        }
+
+        let app = Application::get();
+        for def in app.settings().zone_defs() {
+            let row = InformationRow::new();
+            row.set_title(&def.name);
+            row.set_text(app.timeline().zone_n_inside(&def.name).to_string());
+
+            imp.zones_group.add(&row);
+            imp.zones_group_rows.borrow_mut().push(row);
+        }
+
+        imp.zones_group
+            .set_visible(!imp.zones_group_rows.borrow().is_empty());
+    }
+}
+
+fn custom_field_defs_instruction(defs: &[CustomFieldDef]) -> Option<String> {
+    if defs.is_empty() {
+        return None;
+    }
+
+    let descriptions = defs
+        .iter()
+        .map(|def| match &def.ty {
+            CustomFieldTy::Text => format!("{} (text)", def.label()),
+            CustomFieldTy::Number => format!("{} (number)", def.label()),
+            CustomFieldTy::Date => format!("{} (date)", def.label()),
+            CustomFieldTy::Choice { choices } => {
+                format!("{} (one of {})", def.label(), choices.join(", "))
+            }
+        })
+        .collect::<Vec<_>>();
+    Some(format!(
+        "Entities also have the following deployment-specific fields: {}.",
+        descriptions.join("; ")
+    ))
 }
 
 fn csv_bytes_res_to_string(title: &str, bytes: Result<Vec<u8>>) -> Option<String> {
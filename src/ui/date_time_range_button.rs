@@ -1,6 +1,8 @@
 use gtk::{glib, prelude::*, subclass::prelude::*};
 
-use crate::{date_time_range::DateTimeRange, ui::date_time_range_dialog::DateTimeRangeDialog};
+use crate::{
+    date_time_range::DateTimeRange, ui::date_time_range_dialog::DateTimeRangeDialog, Application,
+};
 
 mod imp {
     use std::cell::Cell;
@@ -35,7 +37,7 @@ mod imp {
                 |obj, _, _| async move {
                     let range = obj.range();
                     let initial_range = if range.is_all_time() {
-                        DateTimeRange::today()
+                        DateTimeRange::today(&Application::get().timeline().clock())
                     } else {
                         range
                     };
@@ -100,6 +102,7 @@ impl DateTimeRangeButton {
         let imp = self.imp();
 
         let range = self.range();
-        imp.label.set_markup(&range.short_label_markup());
+        imp.label
+            .set_markup(&range.short_label_markup(&Application::get().timeline().clock()));
     }
 }
@@ -1,10 +1,11 @@
 use adw::{prelude::*, subclass::prelude::*};
-use chrono::{DateTime, Datelike, Local, Utc};
+use chrono::{DateTime, Datelike, Utc};
 use futures_channel::oneshot;
 use gtk::glib::{self, clone, closure};
 
 use crate::{
-    date_time_range::DateTimeRange, list_model_enum, ui::date_time_picker::DateTimePicker,
+    clock::Clock, date_time_range::DateTimeRange, list_model_enum,
+    ui::date_time_picker::DateTimePicker, Application,
 };
 
 mod imp {
@@ -129,7 +130,7 @@ mod imp {
                 }
             ));
 
-            let today = Local::now().day();
+            let today = Application::get().timeline().clock().now_local().day();
             self.start_dt_picker.mark_day(today);
             self.end_dt_picker.mark_day(today);
 
@@ -219,7 +220,8 @@ impl DateTimeRangeDialog {
             return;
         }
 
-        let range_kind = DateTimeRangeKind::for_range(&new_range);
+        let clock = Application::get().timeline().clock();
+        let range_kind = DateTimeRangeKind::for_range(&new_range, &clock);
         self.set_selected_range_kind_no_notify(range_kind);
 
         self.update_range_label();
@@ -240,10 +242,11 @@ impl DateTimeRangeDialog {
     }
 
     fn update_ui_from_selected_range_kind(&self) {
+        let clock = Application::get().timeline().clock();
         let range = self
             .selected_range_kind()
-            .to_range()
-            .unwrap_or_else(DateTimeRange::today);
+            .to_range(&clock)
+            .unwrap_or_else(|| DateTimeRange::today(&clock));
         self.update_ui_from_range(range);
     }
 
@@ -310,30 +313,30 @@ impl DateTimeRangeKind {
         }
     }
 
-    fn to_range(self) -> Option<DateTimeRange> {
+    fn to_range(self, clock: &Clock) -> Option<DateTimeRange> {
         Some(match self {
             DateTimeRangeKind::Custom => return None,
             DateTimeRangeKind::AllTime => DateTimeRange::all_time(),
-            DateTimeRangeKind::Today => DateTimeRange::today(),
-            DateTimeRangeKind::Yesterday => DateTimeRange::yesterday(),
-            DateTimeRangeKind::ThisWeek => DateTimeRange::this_week(),
-            DateTimeRangeKind::ThisMonth => DateTimeRange::this_month(),
-            DateTimeRangeKind::ThisYear => DateTimeRange::this_year(),
+            DateTimeRangeKind::Today => DateTimeRange::today(clock),
+            DateTimeRangeKind::Yesterday => DateTimeRange::yesterday(clock),
+            DateTimeRangeKind::ThisWeek => DateTimeRange::this_week(clock),
+            DateTimeRangeKind::ThisMonth => DateTimeRange::this_month(clock),
+            DateTimeRangeKind::ThisYear => DateTimeRange::this_year(clock),
         })
     }
 
-    fn for_range(range: &DateTimeRange) -> Self {
+    fn for_range(range: &DateTimeRange, clock: &Clock) -> Self {
         if range.is_all_time() {
             Self::AllTime
-        } else if range.is_today() {
+        } else if range.is_today(clock) {
             Self::Today
-        } else if range.is_yesterday() {
+        } else if range.is_yesterday(clock) {
             Self::Yesterday
-        } else if range.is_this_week() {
+        } else if range.is_this_week(clock) {
             Self::ThisWeek
-        } else if range.is_this_month() {
+        } else if range.is_this_month(clock) {
             Self::ThisMonth
-        } else if range.is_this_year() {
+        } else if range.is_this_year(clock) {
             Self::ThisYear
         } else {
             Self::Custom
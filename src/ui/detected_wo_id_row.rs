@@ -19,6 +19,10 @@ mod imp {
         #[template_child]
         pub(super) dt_label: TemplateChild<gtk::Label>,
         #[template_child]
+        pub(super) kind_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) play_clip_button: TemplateChild<gtk::Button>,
+        #[template_child]
         pub(super) picture: TemplateChild<gtk::Picture>,
     }
 
@@ -31,6 +35,21 @@ mod imp {
         fn class_init(klass: &mut Self::Class) {
             klass.bind_template();
 
+            klass.install_action_async(
+                "detected-wo-id-row.play-clip",
+                None,
+                |obj, _, _| async move {
+                    let Some(clip) = obj.item().and_then(|item| item.clip()) else {
+                        return;
+                    };
+
+                    let app = Application::get();
+                    if let Err(err) = clip.launch(&app.window()).await {
+                        tracing::error!("Failed to launch clip: {:?}", err);
+                        app.add_message_toast("Clip is not available yet");
+                    }
+                },
+            );
             klass.install_action("detected-wo-id-row.delete", None, |obj, _, _| {
                 let imp = obj.imp();
 
@@ -66,6 +85,9 @@ mod imp {
 
             if let Some(item) = &item {
                 self.dt_label.set_text(&date_time::format::fuzzy(item.dt()));
+                self.kind_label.set_text(&item.kind().to_string());
+                self.kind_label.set_visible(item.kind().is_tailgating());
+                self.play_clip_button.set_visible(item.clip().is_some());
                 self.picture
                     .set_paintable(item.image().as_ref().and_then(|i| {
                         i.texture()
@@ -76,6 +98,9 @@ mod imp {
                     }));
             } else {
                 self.dt_label.set_text("");
+                self.kind_label.set_text("");
+                self.kind_label.set_visible(false);
+                self.play_clip_button.set_visible(false);
                 self.picture.set_paintable(gdk::Paintable::NONE);
             }
 
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use chrono::{TimeDelta, Utc};
 use gtk::{
     glib::{self, clone, closure, closure_local},
     prelude::*,
@@ -6,6 +7,8 @@ use gtk::{
 };
 
 use crate::{
+    badge::{self, Badge},
+    custom_field::CustomFieldSettingsExt,
     date_time,
     date_time_range::DateTimeRange,
     entity::Entity,
@@ -15,17 +18,19 @@ use crate::{
     entity_list::EntityList,
     fuzzy_filter::FuzzyFilter,
     list_model_enum,
+    location_id::LocationId,
     report::{self, ReportKind},
     report_table,
     search_query::SearchQueries,
     search_query_ext::SearchQueriesDateTimeRangeExt,
+    signed_qr::SignedQrPayload,
     stock_id::StockId,
     ui::{
         date_time_range_button::DateTimeRangeButton, entity_details_pane::EntityDetailsPane,
         entity_row::EntityRow, search_entry::SearchEntry, send_dialog::SendDialog,
     },
-    utils::{new_filter, new_sorter},
-    Application,
+    utils::{new_filter, new_header_factory, new_sorter},
+    zone, Application,
 };
 
 struct S;
@@ -61,6 +66,7 @@ impl S {
     const TO: &str = "to";
 
     const STOCK: &str = "stock";
+    const LOCATION: &str = "location";
 
     const SORT: &str = "sort";
     const SORT_VALUES: &[&str] = &[
@@ -68,6 +74,8 @@ impl S {
         Self::ID_DESC,
         Self::STOCK_ASC,
         Self::STOCK_DESC,
+        Self::LOCATION_ASC,
+        Self::LOCATION_DESC,
         Self::UPDATED_ASC,
         Self::UPDATED_DESC,
     ];
@@ -76,6 +84,8 @@ impl S {
     const ID_DESC: &str = "id-desc";
     const STOCK_ASC: &str = "stock-asc";
     const STOCK_DESC: &str = "stock-desc";
+    const LOCATION_ASC: &str = "location-asc";
+    const LOCATION_DESC: &str = "location-desc";
     const UPDATED_ASC: &str = "updated-asc";
     const UPDATED_DESC: &str = "updated-desc";
 }
@@ -143,6 +153,8 @@ enum EntitySort {
     IdDesc,
     StockAsc,
     StockDesc,
+    LocationAsc,
+    LocationDesc,
     UpdatedAsc,
     UpdatedDesc,
 }
@@ -156,6 +168,8 @@ impl EntitySort {
             Self::IdDesc => "Z-A",
             Self::StockAsc => "Stock (A-Z)",
             Self::StockDesc => "Stock (Z-A)",
+            Self::LocationAsc => "Location (A-Z)",
+            Self::LocationDesc => "Location (Z-A)",
             Self::UpdatedAsc => "Least Recently Updated",
             Self::UpdatedDesc => "Recently Updated",
         }
@@ -253,6 +267,29 @@ mod imp {
                     }
                 },
             );
+            klass.install_action_async(
+                "entities-view.share-badges",
+                None,
+                |obj, _, _| async move {
+                    if Application::get().settings().qr_signing_key().is_empty() {
+                        Application::get()
+                            .add_message_toast("Set a QR signing key in the settings first");
+                        return;
+                    }
+
+                    if let Err(err) = SendDialog::send(
+                        &report::file_name("Entity Badges", ReportKind::Pdf),
+                        obj.create_badges(),
+                        Some(&obj),
+                    )
+                    .await
+                    {
+                        tracing::error!("Failed to send badges: {:?}", err);
+
+                        Application::get().add_message_toast("Failed to share badges");
+                    }
+                },
+            );
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -486,12 +523,19 @@ mod imp {
                     Some(entity.id().to_string()),
                     entity.stock_id().map(|s| s.to_string()),
                     entity_data.location().cloned(),
+                    entity_data.lot().cloned(),
                     entity_data.name().cloned(),
                     entity_data.email().cloned(),
                     entity_data.program().cloned(),
                 ]
                 .into_iter()
                 .flatten()
+                .chain(
+                    entity_data
+                        .custom_fields()
+                        .into_iter()
+                        .flat_map(|fields| fields.iter().map(|(_, value)| value.to_string())),
+                )
                 .collect::<Vec<_>>()
                 .join(" ")
             });
@@ -618,6 +662,17 @@ impl EntitiesView {
         imp.search_entry.set_queries(queries);
     }
 
+    pub fn show_entities_expiring(&self) {
+        let imp = self.imp();
+
+        let mut queries = imp.search_entry.queries();
+        queries.remove_all_standalones();
+        queries.remove_all_iden(S::IS);
+        queries.remove_all_iden(S::STOCK);
+        queries.replace_all_or_insert(S::IS, S::ENTITY_EXPIRATION_VALUES, S::EXPIRING);
+        imp.search_entry.set_queries(queries);
+    }
+
     pub fn show_entities_expired(&self) {
         let imp = self.imp();
 
@@ -629,6 +684,41 @@ impl EntitiesView {
         imp.search_entry.set_queries(queries);
     }
 
+    /// Creates a PDF sheet of signed QR badges for the shown entities.
+    pub async fn create_badges(&self) -> Result<Vec<u8>> {
+        let imp = self.imp();
+
+        let app = Application::get();
+        let settings = app.settings();
+        let key = settings.qr_signing_key();
+        let validity_days = settings.signed_qr_validity_days();
+        let expiration_dt =
+            (validity_days > 0).then(|| Utc::now() + TimeDelta::days(validity_days as i64));
+
+        let badges = imp
+            .selection_model
+            .iter::<glib::Object>()
+            .map(|o| {
+                let entity = o.unwrap().downcast::<Entity>().unwrap();
+                let data = entity.data();
+
+                let code =
+                    SignedQrPayload::new(entity.id().clone(), &data, expiration_dt).sign(&key)?;
+
+                Ok(Badge {
+                    title: data
+                        .name()
+                        .cloned()
+                        .unwrap_or_else(|| entity.id().to_string()),
+                    subtitle: entity.id().to_string(),
+                    code,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        badge::build_pdf(badges).await
+    }
+
     pub async fn create_report(&self, kind: ReportKind) -> Result<Vec<u8>> {
         let imp = self.imp();
 
@@ -641,18 +731,28 @@ impl EntitiesView {
         let operation_mode = Application::get().settings().operation_mode();
         let valid_entity_field_tys = ValidEntityFields::for_operation_mode(operation_mode)
             .iter()
-            .filter(|field_ty| !matches!(field_ty, EntityDataFieldTy::Photo))
+            .filter(|field_ty| {
+                !matches!(
+                    field_ty,
+                    EntityDataFieldTy::Photo | EntityDataFieldTy::Custom
+                )
+            })
             .collect::<Vec<_>>();
+        let custom_field_defs = Application::get().settings().custom_field_defs();
 
         let mut table = report_table::builder("Entities")
             .column("ID")
             .column("Status")
+            .column("Path")
             .rows(entities.iter().map(|entity| {
-                let status_text = entity.status_text(&imp.dt_range.borrow(), operation_mode);
+                let dt_range = imp.dt_range.borrow();
+                let status_text = entity.status_text(&dt_range, operation_mode);
+                let path = zone::format_path(&entity.zone_path_for_dt_range(&dt_range));
 
                 let mut cells = report_table::row_builder()
                     .cell(entity.id().to_string())
                     .cell(status_text)
+                    .cell(path)
                     .build();
 
                 let data = entity.data();
@@ -665,12 +765,15 @@ impl EntitiesView {
                                 EntityDataField::ExpirationDt(dt) => {
                                     date_time::format::human_readable_date(*dt)
                                 }
+                                EntityDataField::Lot(l) => l.to_owned(),
+                                EntityDataField::Quantity(q) => q.to_string(),
                                 EntityDataField::AllowedDtRange(dt_range) => dt_range.to_string(),
                                 EntityDataField::Photo(_) => unreachable!(),
                                 EntityDataField::Name(n) => n.to_owned(),
                                 EntityDataField::Sex(s) => s.to_string(),
                                 EntityDataField::Email(e) => e.to_owned(),
                                 EntityDataField::Program(p) => p.to_owned(),
+                                EntityDataField::Custom(_) => unreachable!(),
                             };
                             cells.push(string.into());
                         }
@@ -683,6 +786,14 @@ impl EntitiesView {
                     }
                 }
 
+                for def in &custom_field_defs {
+                    let string = data
+                        .custom_value(&def.key)
+                        .map(|value| value.to_string())
+                        .unwrap_or_default();
+                    cells.push(string.into());
+                }
+
                 cells
             }))
             .build();
@@ -690,6 +801,9 @@ impl EntitiesView {
         for field_ty in valid_entity_field_tys {
             table.columns.push(field_ty.to_string());
         }
+        for def in &custom_field_defs {
+            table.columns.push(def.label().to_string());
+        }
 
         report::builder(kind, "Entities Report")
             .prop("Total Entities", entities.len())
@@ -841,35 +955,37 @@ impl EntitiesView {
             }
         }
 
+        let now = Application::get().timeline().clock().now();
+        let expiration_of = move |entity: &Entity| {
+            let app = Application::get();
+            entity.expiration(now, app.settings(), app.timeline().stock_list())
+        };
         match entity_expiration {
             EntityExpirationFilter::All => {}
             EntityExpirationFilter::NoExpiration => {
-                every_filter.append(new_filter(|entity: &Entity| entity.expiration().is_none()));
+                every_filter.append(new_filter(move |entity: &Entity| {
+                    expiration_of(entity).is_none()
+                }));
             }
             EntityExpirationFilter::NotExpiring => {
-                every_filter.append(new_filter(|entity: &Entity| {
-                    entity
-                        .expiration()
+                every_filter.append(new_filter(move |entity: &Entity| {
+                    expiration_of(entity)
                         .is_some_and(|e| matches!(e, EntityExpiration::NotExpiring))
                 }));
             }
             EntityExpirationFilter::Expiring => {
-                every_filter.append(new_filter(|entity: &Entity| {
-                    entity
-                        .expiration()
-                        .is_some_and(|e| matches!(e, EntityExpiration::Expiring))
+                every_filter.append(new_filter(move |entity: &Entity| {
+                    expiration_of(entity).is_some_and(|e| matches!(e, EntityExpiration::Expiring))
                 }));
             }
             EntityExpirationFilter::Expired => {
-                every_filter.append(new_filter(|entity: &Entity| {
-                    entity
-                        .expiration()
-                        .is_some_and(|e| matches!(e, EntityExpiration::Expired))
+                every_filter.append(new_filter(move |entity: &Entity| {
+                    expiration_of(entity).is_some_and(|e| matches!(e, EntityExpiration::Expired))
                 }));
             }
             EntityExpirationFilter::ExpiringOrExpired => {
-                every_filter.append(new_filter(|entity: &Entity| {
-                    entity.expiration().is_some_and(|e| {
+                every_filter.append(new_filter(move |entity: &Entity| {
+                    expiration_of(entity).is_some_and(|e| {
                         matches!(e, EntityExpiration::Expiring | EntityExpiration::Expired)
                     })
                 }));
@@ -902,6 +1018,45 @@ impl EntitiesView {
         }
 
         every_filter.append(any_stock_filter);
+
+        let any_location_filter = gtk::AnyFilter::new();
+        for location_id in queries
+            .all_values(S::LOCATION)
+            .into_iter()
+            .filter_map(LocationId::parse)
+        {
+            any_location_filter.append(new_filter(move |entity: &Entity| {
+                entity
+                    .location_for_dt_range(&dt_range)
+                    .is_some_and(|l_id| location_id.contains(&l_id))
+            }));
+        }
+
+        if any_location_filter.n_items() == 0 {
+            any_location_filter.append(new_filter(|_: &Entity| true));
+        }
+
+        every_filter.append(any_location_filter);
+
+        for def in Application::get().settings().custom_field_defs() {
+            let values = queries.all_values(&def.key);
+            if values.is_empty() {
+                continue;
+            }
+
+            let any_value_filter = gtk::AnyFilter::new();
+            for value in values.into_iter().map(|v| v.to_string()) {
+                let key = def.key.clone();
+                any_value_filter.append(new_filter(move |entity: &Entity| {
+                    entity
+                        .data()
+                        .custom_value(&key)
+                        .is_some_and(|v| v.matches_query(&value))
+                }));
+            }
+            every_filter.append(any_value_filter);
+        }
+
         imp.filter_list_model.set_filter(Some(&every_filter));
 
         self.update_fallback_sorter();
@@ -1054,6 +1209,12 @@ impl EntitiesView {
             EntitySort::StockDesc => {
                 queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::STOCK_DESC)
             }
+            EntitySort::LocationAsc => {
+                queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::LOCATION_ASC)
+            }
+            EntitySort::LocationDesc => {
+                queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::LOCATION_DESC)
+            }
             EntitySort::UpdatedAsc => {
                 queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::UPDATED_ASC)
             }
@@ -1075,6 +1236,8 @@ impl EntitiesView {
             Some(S::ID_DESC) => EntitySort::IdDesc,
             Some(S::STOCK_ASC) => EntitySort::StockAsc,
             Some(S::STOCK_DESC) => EntitySort::StockDesc,
+            Some(S::LOCATION_ASC) => EntitySort::LocationAsc,
+            Some(S::LOCATION_DESC) => EntitySort::LocationDesc,
             Some(S::UPDATED_ASC) => EntitySort::UpdatedAsc,
             Some(S::UPDATED_DESC) => EntitySort::UpdatedDesc,
             None => EntitySort::default(),
@@ -1089,6 +1252,7 @@ impl EntitiesView {
         imp.entity_sort_dropdown
             .unblock_signal(selected_item_notify_id);
 
+        let dt_range = *imp.dt_range.borrow();
         let sorter = match entity_sort {
             EntitySort::IdAsc | EntitySort::IdDesc => new_sorter(
                 matches!(entity_sort, EntitySort::IdDesc),
@@ -1098,6 +1262,13 @@ impl EntitiesView {
                 matches!(entity_sort, EntitySort::StockDesc),
                 |a: &Entity, b| a.stock_id().cmp(&b.stock_id()),
             ),
+            EntitySort::LocationAsc | EntitySort::LocationDesc => new_sorter(
+                matches!(entity_sort, EntitySort::LocationDesc),
+                move |a: &Entity, b| {
+                    a.location_for_dt_range(&dt_range)
+                        .cmp(&b.location_for_dt_range(&dt_range))
+                },
+            ),
             EntitySort::UpdatedAsc | EntitySort::UpdatedDesc => new_sorter(
                 matches!(entity_sort, EntitySort::UpdatedDesc),
                 |a: &Entity, b| a.last_action_dt().cmp(&b.last_action_dt()),
@@ -1109,6 +1280,29 @@ impl EntitiesView {
             .unwrap()
             .sorter()
             .set_fallback_sorter(Some(sorter));
+
+        // Group by location when sorted by it.
+        if matches!(
+            entity_sort,
+            EntitySort::LocationAsc | EntitySort::LocationDesc
+        ) {
+            imp.sort_list_model.set_section_sorter(Some(&new_sorter(
+                matches!(entity_sort, EntitySort::LocationDesc),
+                move |a: &Entity, b| {
+                    a.location_for_dt_range(&dt_range)
+                        .cmp(&b.location_for_dt_range(&dt_range))
+                },
+            )));
+            imp.list_view
+                .set_header_factory(Some(&new_header_factory(move |entity: &Entity| {
+                    entity
+                        .location_for_dt_range(&dt_range)
+                        .map_or_else(|| "No Location".to_string(), |l| l.to_string())
+                })));
+        } else {
+            imp.sort_list_model.set_section_sorter(gtk::Sorter::NONE);
+            imp.list_view.set_header_factory(gtk::ListItemFactory::NONE);
+        }
     }
 
     fn update_entity_expiration_dropdown_visibility(&self) {
@@ -5,10 +5,14 @@ use futures_channel::oneshot;
 use gtk::glib::{self, clone, closure};
 
 use crate::{
+    custom_field::{
+        CustomFieldDef, CustomFieldSettingsExt, CustomFieldTy, CustomFieldValue, CustomFields,
+    },
     date_time_boxed::DateTimeBoxed,
     entity_data::{EntityData, EntityDataField, EntityDataFieldTy},
     entity_id::EntityId,
     list_model_enum,
+    quantity::Quantity,
     sex::Sex,
     stock::Stock,
     stock_id::StockId,
@@ -21,6 +25,14 @@ use crate::{
 
 list_model_enum!(Sex);
 
+/// Input row of a custom field.
+enum CustomFieldRow {
+    Entry(adw::EntryRow),
+    Date(DateTimeButton),
+    /// The first item is for no value.
+    Choice(adw::ComboRow),
+}
+
 mod imp {
     use std::cell::RefCell;
 
@@ -44,6 +56,10 @@ mod imp {
         #[template_child]
         pub(super) expiration_dt_button: TemplateChild<DateTimeButton>,
         #[template_child]
+        pub(super) lot_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) quantity_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
         pub(super) allowed_dt_range_row: TemplateChild<adw::ActionRow>,
         #[template_child]
         pub(super) allowed_dt_range_button: TemplateChild<DateTimeRangeButton>,
@@ -56,10 +72,15 @@ mod imp {
         #[template_child]
         pub(super) program_row: TemplateChild<adw::EntryRow>,
         #[template_child]
+        pub(super) custom_fields_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
         pub(super) photo_viewfinder_group: TemplateChild<adw::PreferencesGroup>,
         #[template_child]
         pub(super) photo_viewfinder: TemplateChild<CameraViewfinder>,
 
+        pub(super) custom_field_rows: RefCell<Vec<(CustomFieldDef, CustomFieldRow)>>,
+        pub(super) initial_custom_fields: RefCell<CustomFields>,
+
         pub(super) result_tx: RefCell<Option<oneshot::Sender<()>>>,
     }
 
@@ -116,6 +137,15 @@ mod imp {
                 }
             ));
 
+            self.quantity_row.connect_text_notify(|entry| {
+                let text = entry.text();
+                if text.trim().is_empty() || text.parse::<Quantity>().is_ok() {
+                    entry.remove_css_class("error");
+                } else {
+                    entry.add_css_class("error");
+                }
+            });
+
             self.sex_row
                 .set_expression(Some(&adw::EnumListItem::this_expression("name")));
             self.sex_row.set_model(Some(&Sex::new_model()));
@@ -152,23 +182,35 @@ impl EntityDataDialog {
         let imp = this.imp();
         imp.window_title.set_subtitle(&entity_id.to_string());
 
-        let operation_mode = Application::get().settings().operation_mode();
+        let app = Application::get();
+        let settings = app.settings();
+
+        let operation_mode = settings.operation_mode();
         let ignored_data_field_tys = ignored_data_field_ty.into_iter().collect::<HashSet<_>>();
+
+        let custom_field_defs = settings.custom_field_defs();
+        let has_custom_fields = !custom_field_defs.is_empty();
+        this.add_custom_field_rows(custom_field_defs);
+
         for field_ty in EntityDataFieldTy::all() {
             let widget = match field_ty {
                 EntityDataFieldTy::StockId => imp.stock_id_group.upcast_ref::<gtk::Widget>(),
                 EntityDataFieldTy::Location => imp.location_row.upcast_ref(),
                 EntityDataFieldTy::ExpirationDt => imp.expiration_dt_row.upcast_ref(),
+                EntityDataFieldTy::Lot => imp.lot_row.upcast_ref(),
+                EntityDataFieldTy::Quantity => imp.quantity_row.upcast_ref(),
                 EntityDataFieldTy::AllowedDtRange => imp.allowed_dt_range_row.upcast_ref(),
                 EntityDataFieldTy::Photo => imp.photo_viewfinder_group.upcast_ref(),
                 EntityDataFieldTy::Name => imp.name_row.upcast_ref(),
                 EntityDataFieldTy::Sex => imp.sex_row.upcast_ref(),
                 EntityDataFieldTy::Email => imp.email_row.upcast_ref(),
                 EntityDataFieldTy::Program => imp.program_row.upcast_ref(),
+                EntityDataFieldTy::Custom => imp.custom_fields_group.upcast_ref(),
             };
             widget.set_visible(
                 operation_mode.is_valid_entity_data_field_ty(*field_ty)
-                    && !ignored_data_field_tys.contains(field_ty),
+                    && !ignored_data_field_tys.contains(field_ty)
+                    && (*field_ty != EntityDataFieldTy::Custom || has_custom_fields),
             );
         }
 
@@ -196,6 +238,12 @@ impl EntityDataDialog {
                 EntityDataField::ExpirationDt(dt) => {
                     imp.expiration_dt_button.set_dt(Some(DateTimeBoxed(*dt)));
                 }
+                EntityDataField::Lot(lot) => {
+                    imp.lot_row.set_text(lot);
+                }
+                EntityDataField::Quantity(quantity) => {
+                    imp.quantity_row.set_text(&quantity.to_string());
+                }
                 EntityDataField::AllowedDtRange(dt_range) => {
                     imp.allowed_dt_range_button.set_range(dt_range);
                 }
@@ -214,6 +262,9 @@ impl EntityDataDialog {
                 EntityDataField::Program(program) => {
                     imp.program_row.set_text(program);
                 }
+                EntityDataField::Custom(custom_fields) => {
+                    this.set_custom_fields(custom_fields);
+                }
             }
         }
 
@@ -264,6 +315,29 @@ impl EntityDataDialog {
                             .map(|dt| EntityDataField::ExpirationDt(dt.0))
                     })
                     .flatten(),
+                operation_mode
+                    .is_valid_entity_data_field_ty(EntityDataFieldTy::Lot)
+                    .then(|| {
+                        Some(imp.lot_row.text().to_string())
+                            .filter(|t| !t.is_empty())
+                            .map(EntityDataField::Lot)
+                    })
+                    .flatten(),
+                operation_mode
+                    .is_valid_entity_data_field_ty(EntityDataFieldTy::Quantity)
+                    .then(|| {
+                        Some(imp.quantity_row.text())
+                            .filter(|t| !t.trim().is_empty())
+                            .and_then(|t| {
+                                t.parse::<Quantity>()
+                                    .inspect_err(|err| {
+                                        tracing::warn!("Ignored invalid quantity: {:?}", err)
+                                    })
+                                    .ok()
+                            })
+                            .map(EntityDataField::Quantity)
+                    })
+                    .flatten(),
                 operation_mode
                     .is_valid_entity_data_field_ty(EntityDataFieldTy::AllowedDtRange)
                     .then(|| {
@@ -299,6 +373,9 @@ impl EntityDataDialog {
                 Some(imp.program_row.text().to_string())
                     .filter(|t| !t.is_empty())
                     .map(EntityDataField::Program),
+                Some(self.custom_fields())
+                    .filter(|f| !f.is_empty())
+                    .map(EntityDataField::Custom),
             ]
             .into_iter()
             .flatten(),
@@ -311,6 +388,132 @@ impl EntityDataDialog {
         data
     }
 
+    fn add_custom_field_rows(&self, defs: Vec<CustomFieldDef>) {
+        let imp = self.imp();
+
+        let mut custom_field_rows = imp.custom_field_rows.borrow_mut();
+        for def in defs {
+            let (row, widget) = match &def.ty {
+                CustomFieldTy::Text | CustomFieldTy::Number => {
+                    let entry_row = adw::EntryRow::builder().title(def.label()).build();
+                    if def.ty == CustomFieldTy::Number {
+                        entry_row.set_input_purpose(gtk::InputPurpose::Number);
+                    }
+                    (
+                        CustomFieldRow::Entry(entry_row.clone()),
+                        entry_row.upcast::<gtk::Widget>(),
+                    )
+                }
+                CustomFieldTy::Date => {
+                    let button = DateTimeButton::new();
+                    button.set_show_time(false);
+                    button.set_valign(gtk::Align::Center);
+
+                    let action_row = adw::ActionRow::builder().title(def.label()).build();
+                    action_row.add_suffix(&button);
+                    (CustomFieldRow::Date(button), action_row.upcast())
+                }
+                CustomFieldTy::Choice { choices } => {
+                    let model = gtk::StringList::new(&["None"]);
+                    for choice in choices {
+                        model.append(choice);
+                    }
+
+                    let combo_row = adw::ComboRow::builder()
+                        .title(def.label())
+                        .model(&model)
+                        .build();
+                    (
+                        CustomFieldRow::Choice(combo_row.clone()),
+                        combo_row.upcast(),
+                    )
+                }
+            };
+
+            imp.custom_fields_group.add(&widget);
+            custom_field_rows.push((def, row));
+        }
+    }
+
+    fn set_custom_fields(&self, custom_fields: &CustomFields) {
+        let imp = self.imp();
+
+        for (def, row) in imp.custom_field_rows.borrow().iter() {
+            let Some(value) = custom_fields.get(&def.key) else {
+                continue;
+            };
+
+            match (row, value) {
+                (CustomFieldRow::Entry(entry_row), value) => {
+                    entry_row.set_text(&value.to_string());
+                }
+                (CustomFieldRow::Date(button), CustomFieldValue::Date(dt)) => {
+                    button.set_dt(Some(DateTimeBoxed(*dt)));
+                }
+                (CustomFieldRow::Choice(combo_row), CustomFieldValue::Choice(choice)) => {
+                    if let CustomFieldTy::Choice { choices } = &def.ty {
+                        if let Some(position) = choices.iter().position(|c| c == choice) {
+                            combo_row.set_selected(position as u32 + 1);
+                        }
+                    }
+                }
+                (_, value) => {
+                    tracing::warn!(
+                        "Mismatched value for custom field `{}`: {:?}",
+                        def.key,
+                        value
+                    );
+                }
+            }
+        }
+
+        imp.initial_custom_fields.replace(custom_fields.clone());
+    }
+
+    /// Returns the entered values, along with the initial values of the
+    /// fields that are not shown, so they are not lost.
+    fn custom_fields(&self) -> CustomFields {
+        let imp = self.imp();
+
+        let custom_field_rows = imp.custom_field_rows.borrow();
+
+        let hidden_values = imp
+            .initial_custom_fields
+            .borrow()
+            .iter()
+            .filter(|(key, _)| !custom_field_rows.iter().any(|(def, _)| def.key == *key))
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<Vec<_>>();
+
+        let entered_values = custom_field_rows.iter().filter_map(|(def, row)| {
+            let value = match row {
+                CustomFieldRow::Entry(entry_row) => {
+                    let text = entry_row.text();
+                    if text.trim().is_empty() {
+                        return None;
+                    }
+
+                    def.parse_value(&text)
+                        .inspect_err(|err| {
+                            tracing::warn!("Failed to parse {}: {:?}", def.label(), err)
+                        })
+                        .ok()?
+                }
+                CustomFieldRow::Date(button) => CustomFieldValue::Date(button.dt()?.0),
+                CustomFieldRow::Choice(combo_row) => {
+                    let CustomFieldTy::Choice { choices } = &def.ty else {
+                        unreachable!()
+                    };
+                    let position = combo_row.selected().checked_sub(1)?;
+                    CustomFieldValue::Choice(choices.get(position as usize)?.clone())
+                }
+            };
+            Some((def.key.clone(), value))
+        });
+
+        hidden_values.into_iter().chain(entered_values).collect()
+    }
+
     fn update_stock_id_row_sensitivity(&self) {
         let imp = self.imp();
 
@@ -6,15 +6,19 @@ use gtk::{
 };
 
 use crate::{
+    application,
+    custom_field::CustomFieldSettingsExt,
     date_time,
     date_time_range::DateTimeRange,
     entity::Entity,
     entity_data::{EntityDataField, EntityDataFieldTy},
     entity_entry_tracker::EntityIdSet,
-    entity_expiration::EntityExpiration,
+    entity_expiration::{EntityExpiration, EntityExpirationEntityExt},
+    event_clip::EventClip,
     format,
+    quantity::Quantity,
     ui::{entity_data_dialog::EntityDataDialog, information_row::InformationRow},
-    Application,
+    zone, Application,
 };
 
 mod imp {
@@ -45,14 +49,24 @@ mod imp {
         #[template_child]
         pub(super) status_row: TemplateChild<InformationRow>,
         #[template_child]
+        pub(super) path_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) record_consumption_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
         pub(super) data_group: TemplateChild<adw::PreferencesGroup>,
         #[template_child]
         pub(super) photo_picture_group: TemplateChild<adw::PreferencesGroup>,
         #[template_child]
         pub(super) photo_picture: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub(super) location_history_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub(super) clips_group: TemplateChild<adw::PreferencesGroup>,
 
         pub(super) dt_range: RefCell<DateTimeRange>,
         pub(super) data_group_rows: RefCell<Vec<InformationRow>>,
+        pub(super) location_history_group_rows: RefCell<Vec<adw::ActionRow>>,
+        pub(super) clips_group_rows: RefCell<Vec<adw::ActionRow>>,
 
         pub(super) entity_signals: OnceCell<glib::SignalGroup>,
     }
@@ -69,6 +83,13 @@ mod imp {
             klass.install_action("entity-details-pane.show-timeline", None, |obj, _, _| {
                 obj.emit_by_name::<()>("show-timeline-request", &[]);
             });
+            klass.install_action(
+                "entity-details-pane.record-consumption",
+                None,
+                |obj, _, _| {
+                    obj.present_record_consumption_dialog();
+                },
+            );
             klass.install_action_async(
                 "entity-details-pane.edit-data",
                 None,
@@ -91,11 +112,24 @@ mod imp {
                         }
                     };
 
-                    if let Err(err) = Application::get()
+                    let app = Application::get();
+                    match app
                         .timeline()
                         .replace_entity_data(entity.id(), updated_data)
                     {
-                        tracing::error!("Failed to update entity data: {:?}", err);
+                        Ok(Some(location_move)) => {
+                            obj.update_location_history_group();
+
+                            if let Some(message) = location_move.to.as_ref().and_then(|to| {
+                                application::over_capacity_message(app.timeline(), to)
+                            }) {
+                                app.add_message_toast(&message);
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            tracing::error!("Failed to update entity data: {:?}", err);
+                        }
                     }
                 },
             );
@@ -169,6 +203,7 @@ mod imp {
                     move |_, _| {
                         obj.update_data_group_rows();
                         obj.update_photo_picture_group();
+                        obj.update_record_consumption_row();
                     }
                 ),
             );
@@ -179,6 +214,17 @@ mod imp {
                     obj,
                     move |_, _| {
                         obj.update_status_row();
+                        obj.update_record_consumption_row();
+                    }
+                ),
+            );
+            entity_signals.connect_notify_local(
+                Some("path"),
+                clone!(
+                    #[weak]
+                    obj,
+                    move |_, _| {
+                        obj.update_path_row();
                     }
                 ),
             );
@@ -186,7 +232,11 @@ mod imp {
 
             obj.update_data_group_rows();
             obj.update_photo_picture_group();
+            obj.update_location_history_group();
+            obj.update_clips_group();
             obj.update_status_row();
+            obj.update_path_row();
+            obj.update_record_consumption_row();
         }
 
         fn dispose(&self) {
@@ -237,7 +287,11 @@ mod imp {
             self.entity.replace(entity);
             obj.update_data_group_rows();
             obj.update_photo_picture_group();
+            obj.update_location_history_group();
+            obj.update_clips_group();
             obj.update_status_row();
+            obj.update_path_row();
+            obj.update_record_consumption_row();
             obj.notify_entity();
         }
     }
@@ -286,6 +340,51 @@ impl EntityDetailsPane {
         let imp = self.imp();
         imp.dt_range.replace(dt_range);
         self.update_status_row();
+        self.update_path_row();
+    }
+
+    fn present_record_consumption_dialog(&self) {
+        let Some(entity) = self.entity() else {
+            return;
+        };
+        let Some(quantity) = entity.data().quantity().cloned() else {
+            return;
+        };
+
+        let entry = gtk::Entry::builder()
+            .placeholder_text(quantity.to_string())
+            .activates_default(true)
+            .build();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading("Record Consumption")
+            .body(format!(
+                "How much of the {} of “{}” was used up?",
+                quantity,
+                entity.id()
+            ))
+            .extra_child(&entry)
+            .default_response("record")
+            .close_response("cancel")
+            .build();
+        dialog.add_responses(&[("cancel", "_Cancel"), ("record", "_Record")]);
+        dialog.set_response_appearance("record", adw::ResponseAppearance::Suggested);
+
+        dialog.connect_response(Some("record"), move |_, _| {
+            let app = Application::get();
+
+            if let Err(err) = entry
+                .text()
+                .parse::<Quantity>()
+                .and_then(|consumed| app.timeline().record_consumption(entity.id(), consumed))
+            {
+                tracing::error!("Failed to record consumption: {:?}", err);
+
+                app.add_message_toast("Failed to record consumption");
+            }
+        });
+
+        dialog.present(Some(self));
     }
 
     fn update_data_group_rows(&self) {
@@ -299,6 +398,11 @@ impl EntityDetailsPane {
             let entity_data = entity.data();
             let mut fields = entity_data.fields().collect::<Vec<_>>();
 
+            let app = Application::get();
+            let now = app.timeline().clock().now();
+            let expiration = entity.expiration(now, app.settings(), app.timeline().stock_list());
+            let custom_field_defs = app.settings().custom_field_defs();
+
             let default_allowed_dt_range_field =
                 EntityDataField::AllowedDtRange(DateTimeRange::default());
             if !entity_data.has_field(EntityDataFieldTy::AllowedDtRange) {
@@ -313,13 +417,30 @@ impl EntityDetailsPane {
                     continue;
                 }
 
+                if let EntityDataField::Custom(custom_fields) = field {
+                    for (key, value) in custom_fields.iter() {
+                        let title = custom_field_defs
+                            .iter()
+                            .find(|def| def.key == key)
+                            .map_or(key, |def| def.label());
+
+                        let row = InformationRow::new();
+                        row.set_title(title);
+                        row.set_text(value.to_string());
+
+                        imp.data_group.add(&row);
+                        imp.data_group_rows.borrow_mut().push(row);
+                    }
+                    continue;
+                }
+
                 let row = InformationRow::new();
                 row.set_title(&field.ty().to_string());
 
                 match field {
                     EntityDataField::ExpirationDt(dt) => {
                         let date_fmt = date_time::format::human_readable_date(*dt);
-                        if EntityExpiration::for_expiration_dt(*dt).is_some_and(|e| {
+                        if expiration.is_some_and(|e| {
                             matches!(e, EntityExpiration::Expiring | EntityExpiration::Expired)
                         }) {
                             row.set_markup(format::red_markup(&date_fmt));
@@ -355,6 +476,88 @@ impl EntityDetailsPane {
         }
     }
 
+    fn update_location_history_group(&self) {
+        let imp = self.imp();
+
+        for row in imp.location_history_group_rows.take() {
+            imp.location_history_group.remove(&row);
+        }
+
+        let location_moves = self.entity().map_or_else(Vec::new, |entity| {
+            Application::get()
+                .timeline()
+                .location_moves_for_entity(entity.id())
+        });
+
+        for location_move in location_moves.into_iter().rev() {
+            let title = match (&location_move.from, &location_move.to) {
+                (Some(from), Some(to)) => format!("{} → {}", from, to),
+                (None, Some(to)) => format!("Moved to {}", to),
+                (Some(from), None) => format!("Removed from {}", from),
+                (None, None) => continue,
+            };
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&title))
+                .subtitle(date_time::format::human_readable(location_move.dt))
+                .build();
+
+            imp.location_history_group.add(&row);
+            imp.location_history_group_rows.borrow_mut().push(row);
+        }
+
+        imp.location_history_group
+            .set_visible(!imp.location_history_group_rows.borrow().is_empty());
+    }
+
+    fn update_clips_group(&self) {
+        let imp = self.imp();
+
+        for row in imp.clips_group_rows.take() {
+            imp.clips_group.remove(&row);
+        }
+
+        let clips = self.entity().map_or_else(Vec::new, |entity| {
+            EventClip::all_for_entity(entity.id())
+                .inspect_err(|err| tracing::warn!("Failed to get entity clips: {:?}", err))
+                .unwrap_or_default()
+        });
+
+        for clip in clips.into_iter().rev() {
+            let row = adw::ActionRow::builder()
+                .title(clip.kind().to_string())
+                .subtitle(date_time::format::human_readable(clip.dt()))
+                .activatable(true)
+                .build();
+            row.add_suffix(&gtk::Image::from_icon_name("media-playback-start-symbolic"));
+            row.connect_activated(move |_| {
+                let clip = clip.clone();
+                glib::spawn_future_local(async move {
+                    let app = Application::get();
+                    if let Err(err) = clip.launch(&app.window()).await {
+                        tracing::error!("Failed to launch clip: {:?}", err);
+                        app.add_message_toast("Failed to play clip");
+                    }
+                });
+            });
+
+            imp.clips_group.add(&row);
+            imp.clips_group_rows.borrow_mut().push(row);
+        }
+
+        imp.clips_group
+            .set_visible(!imp.clips_group_rows.borrow().is_empty());
+    }
+
+    fn update_record_consumption_row(&self) {
+        let imp = self.imp();
+
+        let can_record_consumption = self.entity().is_some_and(|e| {
+            !e.is_inside() && e.last_action_dt().is_some() && e.data().quantity().is_some()
+        });
+        imp.record_consumption_row
+            .set_visible(can_record_consumption);
+    }
+
     fn update_status_row(&self) {
         let imp = self.imp();
 
@@ -373,4 +576,13 @@ impl EntityDetailsPane {
             imp.status_row.set_text("");
         }
     }
+
+    fn update_path_row(&self) {
+        let imp = self.imp();
+
+        let path = self.entity().map_or_else(String::new, |entity| {
+            zone::format_path(&entity.zone_path_for_dt_range(&imp.dt_range.borrow()))
+        });
+        imp.path_row.set_text(path);
+    }
 }
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::glib::{self, clone};
+
+use crate::{location::Location, location_list::LocationList, Application};
+
+mod imp {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/seadve/Uets/ui/locations_dialog.ui")]
+    pub struct LocationsDialog {
+        #[template_child]
+        pub(super) stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub(super) empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        pub(super) main_page: TemplateChild<gtk::ScrolledWindow>,
+        #[template_child]
+        pub(super) list_box: TemplateChild<gtk::ListBox>,
+
+        pub(super) model: RefCell<Option<(LocationList, glib::SignalHandlerId)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for LocationsDialog {
+        const NAME: &'static str = "UetsLocationsDialog";
+        type Type = super::LocationsDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for LocationsDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.obj().update_stack();
+        }
+
+        fn dispose(&self) {
+            if let Some((model, handler_id)) = self.model.take() {
+                model.disconnect(handler_id);
+            }
+
+            self.dispose_template();
+        }
+    }
+
+    impl WidgetImpl for LocationsDialog {}
+    impl AdwDialogImpl for LocationsDialog {}
+}
+
+glib::wrapper! {
+    pub struct LocationsDialog(ObjectSubclass<imp::LocationsDialog>)
+        @extends gtk::Widget, adw::Dialog;
+}
+
+impl LocationsDialog {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn set_model(&self, list: Option<&LocationList>) {
+        let imp = self.imp();
+
+        if let Some((prev_list, handler_id)) = imp.model.take() {
+            prev_list.disconnect(handler_id);
+        }
+
+        // Sorting by path puts locations right after the ones containing them.
+        let sorter = gtk::CustomSorter::new(|a, b| {
+            let a = a.downcast_ref::<Location>().unwrap();
+            let b = b.downcast_ref::<Location>().unwrap();
+            a.id().cmp(b.id()).into()
+        });
+        let sort_model = list.map(|list| gtk::SortListModel::new(Some(list.clone()), Some(sorter)));
+
+        imp.list_box.bind_model(sort_model.as_ref(), |o| {
+            let location = o.downcast_ref::<Location>().unwrap();
+            create_row(location).upcast()
+        });
+
+        if let Some(list) = list {
+            let handler_id = list.connect_items_changed(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |_, _, _, _| {
+                    obj.update_stack();
+                }
+            ));
+            imp.model.replace(Some((list.clone(), handler_id)));
+        }
+
+        self.update_stack();
+    }
+
+    fn update_stack(&self) {
+        let imp = self.imp();
+
+        let is_empty = imp
+            .model
+            .borrow()
+            .as_ref()
+            .map_or(true, |(list, _)| list.n_items() == 0);
+
+        if is_empty {
+            imp.stack.set_visible_child(&*imp.empty_page);
+        } else {
+            imp.stack.set_visible_child(&*imp.main_page);
+        }
+    }
+}
+
+fn create_row(location: &Location) -> adw::SpinRow {
+    let id = location.id().clone();
+
+    let row = adw::SpinRow::builder()
+        .title(glib::markup_escape_text(id.as_str()))
+        .adjustment(&gtk::Adjustment::new(
+            0.0,
+            0.0,
+            u32::MAX as f64,
+            1.0,
+            10.0,
+            0.0,
+        ))
+        .tooltip_text("Capacity, or 0 for no limit")
+        .build();
+    row.set_value(location.data().capacity.unwrap_or(0) as f64);
+    update_row_subtitle(&row, location);
+
+    location.connect_data_notify(clone!(
+        #[weak]
+        row,
+        move |location| {
+            update_row_subtitle(&row, location);
+        }
+    ));
+    location.connect_n_inside_notify(clone!(
+        #[weak]
+        row,
+        move |location| {
+            update_row_subtitle(&row, location);
+        }
+    ));
+
+    row.connect_value_notify(move |row| {
+        let capacity = row.value() as u32;
+
+        let app = Application::get();
+        let timeline = app.timeline();
+        let mut data = timeline
+            .location_list()
+            .get(&id)
+            .map(|location| location.data())
+            .unwrap_or_default();
+        data.capacity = (capacity != 0).then_some(capacity);
+
+        if let Err(err) = timeline.register_location_data(HashMap::from([(id.clone(), data)])) {
+            tracing::error!("Failed to update location capacity: {:?}", err);
+
+            app.add_message_toast("Failed to update capacity");
+        }
+    });
+
+    row
+}
+
+fn update_row_subtitle(row: &adw::SpinRow, location: &Location) {
+    let n_inside = location.n_inside();
+    let subtitle = match location.data().capacity {
+        Some(capacity) if n_inside > capacity => {
+            format!("{} inside, over capacity of {}", n_inside, capacity)
+        }
+        Some(capacity) => format!("{} of {} inside", n_inside, capacity),
+        None => format!("{} inside", n_inside),
+    };
+    row.set_subtitle(&subtitle);
+}
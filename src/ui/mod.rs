@@ -16,6 +16,8 @@ mod entity_photo_gallery_cell;
 mod entity_photo_gallery_dialog;
 mod entity_row;
 mod information_row;
+mod locations_dialog;
+mod pending_detections_dialog;
 mod receive_dialog;
 mod search_entry;
 mod send_dialog;
@@ -0,0 +1,167 @@
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::glib::{self, clone};
+
+use crate::{
+    date_time, pending_detection::PendingDetection, pending_detection_list::PendingDetectionList,
+    Application,
+};
+
+mod imp {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/seadve/Uets/ui/pending_detections_dialog.ui")]
+    pub struct PendingDetectionsDialog {
+        #[template_child]
+        pub(super) stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub(super) empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        pub(super) main_page: TemplateChild<gtk::ScrolledWindow>,
+        #[template_child]
+        pub(super) list_box: TemplateChild<gtk::ListBox>,
+
+        pub(super) model: RefCell<Option<(PendingDetectionList, glib::SignalHandlerId)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PendingDetectionsDialog {
+        const NAME: &'static str = "UetsPendingDetectionsDialog";
+        type Type = super::PendingDetectionsDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for PendingDetectionsDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.obj().update_stack();
+        }
+
+        fn dispose(&self) {
+            if let Some((model, handler_id)) = self.model.take() {
+                model.disconnect(handler_id);
+            }
+
+            self.dispose_template();
+        }
+    }
+
+    impl WidgetImpl for PendingDetectionsDialog {}
+    impl AdwDialogImpl for PendingDetectionsDialog {}
+}
+
+glib::wrapper! {
+    pub struct PendingDetectionsDialog(ObjectSubclass<imp::PendingDetectionsDialog>)
+        @extends gtk::Widget, adw::Dialog;
+}
+
+impl PendingDetectionsDialog {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn set_model(&self, list: Option<&PendingDetectionList>) {
+        let imp = self.imp();
+
+        if let Some((prev_list, handler_id)) = imp.model.take() {
+            prev_list.disconnect(handler_id);
+        }
+
+        imp.list_box.bind_model(list, |o| {
+            let pending = o.downcast_ref::<PendingDetection>().unwrap();
+            create_row(pending).upcast()
+        });
+
+        if let Some(list) = list {
+            let handler_id = list.connect_items_changed(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |_, _, _, _| {
+                    obj.update_stack();
+                }
+            ));
+            imp.model.replace(Some((list.clone(), handler_id)));
+        }
+
+        self.update_stack();
+    }
+
+    fn update_stack(&self) {
+        let imp = self.imp();
+
+        let is_empty = imp
+            .model
+            .borrow()
+            .as_ref()
+            .map_or(true, |(list, _)| list.n_items() == 0);
+
+        if is_empty {
+            imp.stack.set_visible_child(&*imp.empty_page);
+        } else {
+            imp.stack.set_visible_child(&*imp.main_page);
+        }
+    }
+}
+
+fn create_row(pending: &PendingDetection) -> adw::ActionRow {
+    let entity_id = pending.entity_id().clone();
+
+    let row = adw::ActionRow::builder()
+        .title(pending.entity_id().to_string())
+        .subtitle(date_time::format::fuzzy(pending.dt()))
+        .build();
+
+    let resolve_button = gtk::Button::builder()
+        .label("Enter Data")
+        .valign(gtk::Align::Center)
+        .build();
+    resolve_button.connect_clicked(clone!(
+        #[strong]
+        entity_id,
+        move |_| {
+            glib::spawn_future_local(clone!(
+                #[strong]
+                entity_id,
+                async move {
+                    Application::get()
+                        .resolve_pending_detections(&entity_id)
+                        .await;
+                }
+            ));
+        }
+    ));
+    row.add_suffix(&resolve_button);
+
+    let delete_button = gtk::Button::builder()
+        .icon_name("user-trash-symbolic")
+        .tooltip_text("Discard")
+        .valign(gtk::Align::Center)
+        .css_classes(["flat", "circular"])
+        .build();
+    delete_button.connect_clicked(clone!(
+        #[strong]
+        entity_id,
+        move |_| {
+            if let Err(err) = Application::get()
+                .pending_detection_list()
+                .remove_for_entity(&entity_id)
+            {
+                tracing::error!("Failed to remove pending detections: {:?}", err);
+            }
+        }
+    ));
+    row.add_suffix(&delete_button);
+
+    row
+}
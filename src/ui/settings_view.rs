@@ -8,7 +8,7 @@ use gtk::{
 };
 use std::process::Command;
 
-use crate::{format, remote::Remote, settings::OperationMode, Application};
+use crate::{camera::CameraStats, format, remote::Remote, settings::OperationMode, Application};
 
 mod imp {
     use super::*;
@@ -21,6 +21,14 @@ mod imp {
         #[template_child]
         pub(super) operation_mode_group: TemplateChild<adw::PreferencesGroup>,
         #[template_child]
+        pub(super) enable_event_clip_recording_row: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        pub(super) event_clip_pre_event_secs_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) event_clip_post_event_secs_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) event_clip_storage_quota_mb_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
         pub(super) enable_n_inside_hook_row: TemplateChild<adw::ExpanderRow>,
         #[template_child]
         pub(super) n_inside_hook_threshold_row: TemplateChild<adw::SpinRow>,
@@ -31,6 +39,10 @@ mod imp {
         #[template_child]
         pub(super) max_entry_to_exit_duration_row: TemplateChild<adw::SpinRow>,
         #[template_child]
+        pub(super) expiring_soon_window_days_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) target_days_of_cover_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
         pub(super) fullscreen_window_button: TemplateChild<gtk::Button>,
         #[template_child]
         pub(super) show_test_window_button: TemplateChild<gtk::Button>,
@@ -39,10 +51,48 @@ mod imp {
         #[template_child]
         pub(super) aux_camera_ip_addrs_row: TemplateChild<adw::EntryRow>,
         #[template_child]
+        pub(super) camera_profiles_json_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) enable_local_motion_detection_row: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        pub(super) local_motion_detection_sensitivity_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) local_motion_detection_regions_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
         pub(super) rfid_reader_ip_addr_row: TemplateChild<adw::EntryRow>,
         #[template_child]
+        pub(super) local_reader_devices_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) camera_debounce_secs_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) rfid_reader_debounce_secs_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) local_reader_debounce_secs_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) mqtt_debounce_secs_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) qr_signing_key_row: TemplateChild<adw::PasswordEntryRow>,
+        #[template_child]
+        pub(super) signed_qr_validity_days_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) code_parsers_json_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) custom_entity_fields_json_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
         pub(super) relay_ip_addr_row: TemplateChild<adw::EntryRow>,
         #[template_child]
+        pub(super) mqtt_broker_addr_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) mqtt_username_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) mqtt_password_row: TemplateChild<adw::PasswordEntryRow>,
+        #[template_child]
+        pub(super) mqtt_topic_prefix_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) webhooks_json_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) zones_json_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
         pub(super) quit_button: TemplateChild<gtk::Button>,
         #[template_child]
         pub(super) shutdown_button: TemplateChild<gtk::Button>,
@@ -104,8 +154,26 @@ mod imp {
             action_group.add_action(&settings.create_enable_lower_limit_reached_alert_action());
             action_group.add_action(&settings.create_enable_upper_limit_reached_alert_action());
             action_group.add_action(&settings.create_enable_detection_wo_id_action());
+            action_group.add_action(&settings.create_enable_tailgating_detection_action());
+            action_group.add_action(&settings.create_require_signed_qr_codes_action());
             obj.insert_action_group("settings-view", Some(&action_group));
 
+            settings
+                .bind_enable_event_clip_recording(
+                    &*self.enable_event_clip_recording_row,
+                    "enable-expansion",
+                )
+                .build();
+            settings
+                .bind_event_clip_pre_event_secs(&*self.event_clip_pre_event_secs_row, "value")
+                .build();
+            settings
+                .bind_event_clip_post_event_secs(&*self.event_clip_post_event_secs_row, "value")
+                .build();
+            settings
+                .bind_event_clip_storage_quota_mb(&*self.event_clip_storage_quota_mb_row, "value")
+                .build();
+
             settings
                 .bind_lower_limit_reached_threshold(
                     &*self.lower_limit_reached_threshold_row,
@@ -126,6 +194,13 @@ mod imp {
                 )
                 .build();
 
+            settings
+                .bind_expiring_soon_window_days(&*self.expiring_soon_window_days_row, "value")
+                .build();
+            settings
+                .bind_target_days_of_cover(&*self.target_days_of_cover_row, "value")
+                .build();
+
             self.max_entry_to_exit_duration_row
                 .bind_property("value", &*self.max_entry_to_exit_duration_row, "subtitle")
                 .transform_to(|_, value: f64| {
@@ -185,6 +260,43 @@ mod imp {
                 );
             });
 
+            self.camera_profiles_json_row
+                .set_text(&settings.camera_profiles_json());
+            self.camera_profiles_json_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_camera_profiles_json(entry.text().trim());
+            });
+
+            settings
+                .bind_enable_local_motion_detection(
+                    &*self.enable_local_motion_detection_row,
+                    "enable-expansion",
+                )
+                .build();
+            settings
+                .bind_local_motion_detection_sensitivity(
+                    &*self.local_motion_detection_sensitivity_row,
+                    "value",
+                )
+                .build();
+
+            self.local_motion_detection_regions_row
+                .set_text(&settings.local_motion_detection_regions().join("; "));
+            self.local_motion_detection_regions_row
+                .connect_apply(|entry| {
+                    Application::get()
+                        .settings()
+                        .set_local_motion_detection_regions(
+                            &entry
+                                .text()
+                                .split(";")
+                                .map(|s| s.trim())
+                                .filter(|s| !s.is_empty())
+                                .collect::<Vec<_>>(),
+                        );
+                });
+
             self.rfid_reader_ip_addr_row
                 .set_text(&settings.rfid_reader_ip_addr());
             self.rfid_reader_ip_addr_row.connect_apply(|entry| {
@@ -193,6 +305,59 @@ mod imp {
                     .set_rfid_reader_ip_addr(entry.text().trim());
             });
 
+            self.local_reader_devices_row
+                .set_text(&settings.local_reader_devices().join("; "));
+            self.local_reader_devices_row.connect_apply(|entry| {
+                Application::get().settings().set_local_reader_devices(
+                    &entry
+                        .text()
+                        .split(";")
+                        .map(|s| s.trim())
+                        .filter(|s| !s.is_empty())
+                        .collect::<Vec<_>>(),
+                );
+            });
+
+            settings
+                .bind_camera_debounce_secs(&*self.camera_debounce_secs_row, "value")
+                .build();
+            settings
+                .bind_rfid_reader_debounce_secs(&*self.rfid_reader_debounce_secs_row, "value")
+                .build();
+            settings
+                .bind_local_reader_debounce_secs(&*self.local_reader_debounce_secs_row, "value")
+                .build();
+            settings
+                .bind_mqtt_debounce_secs(&*self.mqtt_debounce_secs_row, "value")
+                .build();
+
+            self.qr_signing_key_row.set_text(&settings.qr_signing_key());
+            self.qr_signing_key_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_qr_signing_key(&entry.text());
+            });
+
+            settings
+                .bind_signed_qr_validity_days(&*self.signed_qr_validity_days_row, "value")
+                .build();
+
+            self.code_parsers_json_row
+                .set_text(&settings.code_parsers_json());
+            self.code_parsers_json_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_code_parsers_json(entry.text().trim());
+            });
+
+            self.custom_entity_fields_json_row
+                .set_text(&settings.custom_entity_fields_json());
+            self.custom_entity_fields_json_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_custom_entity_fields_json(entry.text().trim());
+            });
+
             self.relay_ip_addr_row.set_text(&settings.relay_ip_addr());
             self.relay_ip_addr_row.connect_apply(|entry| {
                 Application::get()
@@ -200,6 +365,50 @@ mod imp {
                     .set_relay_ip_addr(entry.text().trim());
             });
 
+            self.mqtt_broker_addr_row
+                .set_text(&settings.mqtt_broker_addr());
+            self.mqtt_broker_addr_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_mqtt_broker_addr(entry.text().trim());
+            });
+
+            self.mqtt_username_row.set_text(&settings.mqtt_username());
+            self.mqtt_username_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_mqtt_username(entry.text().trim());
+            });
+
+            self.mqtt_password_row.set_text(&settings.mqtt_password());
+            self.mqtt_password_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_mqtt_password(&entry.text());
+            });
+
+            self.mqtt_topic_prefix_row
+                .set_text(&settings.mqtt_topic_prefix());
+            self.mqtt_topic_prefix_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_mqtt_topic_prefix(entry.text().trim());
+            });
+
+            self.webhooks_json_row.set_text(&settings.webhooks_json());
+            self.webhooks_json_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_webhooks_json(entry.text().trim());
+            });
+
+            self.zones_json_row.set_text(&settings.zones_json());
+            self.zones_json_row.connect_apply(|entry| {
+                Application::get()
+                    .settings()
+                    .set_zones_json(entry.text().trim());
+            });
+
             self.quit_button.connect_clicked(|_| {
                 Application::get().quit();
             });
@@ -251,6 +460,7 @@ impl SettingsView {
             ip_addr: String,
             port: u16,
             port_reachability: Result<()>,
+            camera_stats: Option<CameraStats>,
         }
 
         let imp = self.imp();
@@ -267,32 +477,53 @@ impl SettingsView {
         );
 
         let app = Application::get();
-        let mut statuses = vec![
-            RemoteStatus {
+        let mut statuses = Vec::new();
+        if app.camera().profile().remote_addr().is_some() {
+            statuses.push(RemoteStatus {
                 name: "Camera",
                 ip_addr: app.camera().ip_addr(),
                 port: app.camera().port(),
                 port_reachability: app.camera().check_port_reachability().await,
-            },
+                camera_stats: Some(app.camera().stats()),
+            });
+        }
+        statuses.extend([
             RemoteStatus {
                 name: "RFID Reader",
                 ip_addr: app.rfid_reader().ip_addr(),
                 port: app.rfid_reader().port(),
                 port_reachability: app.rfid_reader().check_port_reachability().await,
+                camera_stats: None,
             },
             RemoteStatus {
                 name: "Relay",
                 ip_addr: app.relay().ip_addr(),
                 port: app.relay().port(),
                 port_reachability: app.relay().check_port_reachability().await,
+                camera_stats: None,
             },
-        ];
-        for camera in app.detector().aux_cameras() {
+        ]);
+        if app.mqtt_client().is_configured() {
+            statuses.push(RemoteStatus {
+                name: "MQTT Broker",
+                ip_addr: app.mqtt_client().ip_addr(),
+                port: app.mqtt_client().port(),
+                port_reachability: app.mqtt_client().check_port_reachability().await,
+                camera_stats: None,
+            });
+        }
+        for camera in app
+            .detector()
+            .aux_cameras()
+            .into_iter()
+            .filter(|camera| camera.profile().remote_addr().is_some())
+        {
             statuses.push(RemoteStatus {
                 name: "Aux Camera",
                 ip_addr: camera.ip_addr(),
                 port: camera.port(),
                 port_reachability: camera.check_port_reachability().await,
+                camera_stats: Some(camera.stats()),
             });
         }
 
@@ -307,11 +538,19 @@ impl SettingsView {
         imp.remote_status_box.remove_all();
 
         for status in statuses {
+            let mut subtitle = format!("{}:{}", status.ip_addr, status.port);
+            if let Some(stats) = status.camera_stats {
+                subtitle.push_str(&format!(
+                    "\n{} restarts, {} errors, {} freezes",
+                    stats.n_restarts, stats.n_errors, stats.n_frozen
+                ));
+            }
+
             let row = adw::ActionRow::builder()
                 .activatable(false)
                 .selectable(false)
                 .title(status.name)
-                .subtitle(format!("{}:{}", status.ip_addr, status.port))
+                .subtitle(subtitle)
                 .build();
 
             let label = gtk::Label::builder()
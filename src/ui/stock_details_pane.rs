@@ -1,18 +1,24 @@
+use std::collections::HashMap;
+
+use adw::prelude::*;
+use chrono::{DateTime, Utc};
 use gtk::{
     glib::{self, clone, closure_local},
-    prelude::*,
     subclass::prelude::*,
 };
 
 use crate::{
     date_time,
     date_time_range::DateTimeRange,
+    fefo,
     limit_reached::{LimitReachedInformationRowExt, LimitReachedSettingsExt},
+    quantity,
+    reorder::{self, ReorderSuggestion},
     report::{self, ReportKind},
     report_table,
     stock::Stock,
     ui::{information_row::InformationRow, send_dialog::SendDialog, time_graph::TimeGraph},
-    Application,
+    valuation, Application,
 };
 
 mod imp {
@@ -39,6 +45,10 @@ mod imp {
         #[template_child]
         pub(super) id_row: TemplateChild<InformationRow>,
         #[template_child]
+        pub(super) unit_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) expiring_soon_window_days_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
         pub(super) n_inside_row: TemplateChild<InformationRow>,
         #[template_child]
         pub(super) max_n_inside_row: TemplateChild<InformationRow>,
@@ -47,10 +57,38 @@ mod imp {
         #[template_child]
         pub(super) n_exits_row: TemplateChild<InformationRow>,
         #[template_child]
+        pub(super) n_consumed_row: TemplateChild<InformationRow>,
+        #[template_child]
         pub(super) last_entry_dt_row: TemplateChild<InformationRow>,
         #[template_child]
         pub(super) last_exit_dt_row: TemplateChild<InformationRow>,
         #[template_child]
+        pub(super) supplier_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) target_days_of_cover_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) consumption_rate_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) days_of_cover_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) n_items_to_order_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) unit_cost_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) currency_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) current_value_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) entered_value_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) exited_value_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) expired_value_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) next_to_use_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub(super) lots_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
         pub(super) n_inside_graph: TemplateChild<TimeGraph>,
         #[template_child]
         pub(super) max_n_inside_graph: TemplateChild<TimeGraph>,
@@ -58,9 +96,14 @@ mod imp {
         pub(super) n_entries_graph: TemplateChild<TimeGraph>,
         #[template_child]
         pub(super) n_exits_graph: TemplateChild<TimeGraph>,
+        #[template_child]
+        pub(super) fefo_compliance_graph: TemplateChild<TimeGraph>,
 
         pub(super) dt_range: RefCell<DateTimeRange>,
 
+        pub(super) next_to_use_group_rows: RefCell<Vec<adw::ActionRow>>,
+        pub(super) lots_group_rows: RefCell<Vec<adw::ActionRow>>,
+
         pub(super) stock_signals: OnceCell<glib::SignalGroup>,
     }
 
@@ -113,7 +156,70 @@ mod imp {
             ));
             self.close_image.add_controller(gesture_click);
 
+            self.unit_row.connect_apply(clone!(
+                #[weak]
+                obj,
+                move |entry| {
+                    obj.handle_unit_row_apply(entry.text().trim());
+                }
+            ));
+
+            self.expiring_soon_window_days_row.connect_apply(clone!(
+                #[weak]
+                obj,
+                move |entry| {
+                    obj.handle_expiring_soon_window_days_row_apply(entry.text().trim());
+                }
+            ));
+
+            self.supplier_row.connect_apply(clone!(
+                #[weak]
+                obj,
+                move |entry| {
+                    obj.handle_supplier_row_apply(entry.text().trim());
+                }
+            ));
+            self.target_days_of_cover_row.connect_apply(clone!(
+                #[weak]
+                obj,
+                move |entry| {
+                    obj.handle_target_days_of_cover_row_apply(entry.text().trim());
+                }
+            ));
+
+            self.unit_cost_row.connect_apply(clone!(
+                #[weak]
+                obj,
+                move |entry| {
+                    obj.handle_unit_cost_row_apply(entry.text().trim());
+                }
+            ));
+            self.currency_row.connect_apply(clone!(
+                #[weak]
+                obj,
+                move |entry| {
+                    obj.handle_currency_row_apply(entry.text().trim());
+                }
+            ));
+
             let stock_signals = glib::SignalGroup::new::<Stock>();
+            stock_signals.connect_notify_local(
+                Some("data"),
+                clone!(
+                    #[weak]
+                    obj,
+                    move |_, _| {
+                        obj.update_unit_row();
+                        obj.update_expiring_soon_window_days_row();
+                        obj.update_supplier_row();
+                        obj.update_target_days_of_cover_row();
+                        obj.update_reorder_rows();
+                        obj.update_unit_cost_row();
+                        obj.update_currency_row();
+                        obj.update_valuation_rows();
+                    }
+                ),
+            );
             stock_signals.connect_notify_local(
                 Some("n-inside"),
                 clone!(
@@ -141,6 +247,7 @@ mod imp {
                     obj,
                     move |_, _| {
                         obj.update_n_entries_row();
+                        obj.update_reorder_rows();
                     }
                 ),
             );
@@ -151,6 +258,17 @@ mod imp {
                     obj,
                     move |_, _| {
                         obj.update_n_exits_row();
+                        obj.update_reorder_rows();
+                    }
+                ),
+            );
+            stock_signals.connect_notify_local(
+                Some("n-consumed"),
+                clone!(
+                    #[weak]
+                    obj,
+                    move |_, _| {
+                        obj.update_n_consumed_row();
                     }
                 ),
             );
@@ -182,10 +300,19 @@ mod imp {
                 #[weak]
                 obj,
                 move |_, _, _, _| {
+                    obj.update_valuation_rows();
+                    obj.update_fefo_groups();
                     obj.update_graphs_data();
                 }
             ));
 
+            app.settings().connect_target_days_of_cover_changed(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.update_reorder_rows();
+                }
+            ));
             app.settings()
                 .connect_limit_reached_threshold_changed(clone!(
                     #[weak]
@@ -195,12 +322,22 @@ mod imp {
                     }
                 ));
 
+            obj.update_unit_row();
+            obj.update_expiring_soon_window_days_row();
+            obj.update_supplier_row();
+            obj.update_target_days_of_cover_row();
+            obj.update_reorder_rows();
+            obj.update_unit_cost_row();
+            obj.update_currency_row();
+            obj.update_valuation_rows();
             obj.update_n_inside_row();
             obj.update_max_n_inside_row();
             obj.update_n_entries_row();
             obj.update_n_exits_row();
+            obj.update_n_consumed_row();
             obj.update_last_entry_dt_row();
             obj.update_last_exit_dt_row();
+            obj.update_fefo_groups();
             obj.update_graphs_data();
         }
 
@@ -241,12 +378,22 @@ mod imp {
             self.stock_signals.get().unwrap().set_target(stock.as_ref());
 
             self.stock.replace(stock);
+            obj.update_unit_row();
+            obj.update_expiring_soon_window_days_row();
+            obj.update_supplier_row();
+            obj.update_target_days_of_cover_row();
+            obj.update_reorder_rows();
+            obj.update_unit_cost_row();
+            obj.update_currency_row();
+            obj.update_valuation_rows();
             obj.update_n_inside_row();
             obj.update_max_n_inside_row();
             obj.update_n_entries_row();
             obj.update_n_exits_row();
+            obj.update_n_consumed_row();
             obj.update_last_entry_dt_row();
             obj.update_last_exit_dt_row();
+            obj.update_fefo_groups();
             obj.update_graphs_data();
             obj.notify_stock();
         }
@@ -299,8 +446,12 @@ impl StockDetailsPane {
         self.update_max_n_inside_row();
         self.update_n_entries_row();
         self.update_n_exits_row();
+        self.update_n_consumed_row();
         self.update_last_entry_dt_row();
         self.update_last_exit_dt_row();
+        self.update_reorder_rows();
+        self.update_valuation_rows();
+        self.update_fefo_groups();
         self.update_graphs_data();
     }
 
@@ -313,9 +464,29 @@ impl StockDetailsPane {
         let app = Application::get();
         let timeline = app.timeline();
 
+        let reorder_suggestion = ReorderSuggestion::for_stock(
+            &stock,
+            app.settings(),
+            dt_range.end.unwrap_or_else(|| timeline.clock().now()),
+        );
+
+        let valuation = timeline.stock_valuation(&stock, &dt_range, timeline.clock().now());
+
+        let fefo_checks = timeline.fefo_checks(stock.id(), &dt_range);
+        let fefo_compliance = fefo::compliance_over_time(&fefo_checks);
+        let fefo_check_for_dt = |dt: DateTime<Utc>| fefo_checks.iter().find(|check| check.dt == dt);
+        let fefo_compliance_for_dt = |dt: DateTime<Utc>| {
+            fefo_compliance
+                .iter()
+                .take_while(|(check_dt, _)| *check_dt <= dt)
+                .last()
+                .map_or(100.0, |(_, percent)| quantity::round(*percent))
+        };
+
         let bytes_fut = async {
             report::builder(kind, "Stock Report")
                 .prop("Stock Name", stock.id())
+                .prop("Unit", stock.data().unit.unwrap_or_default())
                 .prop("Current Count", stock.n_inside_for_dt_range(&dt_range))
                 .prop(
                     "Current Max Count",
@@ -323,6 +494,68 @@ impl StockDetailsPane {
                 )
                 .prop("Total Entries", stock.n_entries_for_dt_range(&dt_range))
                 .prop("Total Exits", stock.n_exits_for_dt_range(&dt_range))
+                .prop("Total Consumed", stock.n_consumed_for_dt_range(&dt_range))
+                .prop("Supplier", stock.data().supplier.unwrap_or_default())
+                .prop(
+                    "Consumption Rate (Items per Day)",
+                    reorder_suggestion
+                        .map(|s| reorder::round_hundredths(s.consumption_rate).to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "Days of Cover",
+                    reorder_suggestion
+                        .and_then(|s| s.days_of_cover)
+                        .map(|days| reorder::round_hundredths(days).to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "Suggested Reorder (Items)",
+                    reorder_suggestion
+                        .map(|s| s.n_items_to_order.to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "Unit Cost",
+                    valuation
+                        .as_ref()
+                        .map(|v| v.unit_cost.to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "Current Value",
+                    valuation
+                        .as_ref()
+                        .map(|v| v.current.to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "Value In",
+                    valuation
+                        .as_ref()
+                        .map(|v| v.entered.to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "Value Out",
+                    valuation
+                        .as_ref()
+                        .map(|v| v.exited.to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "Expired Value",
+                    valuation
+                        .as_ref()
+                        .map(|v| v.expired.to_string())
+                        .unwrap_or_default(),
+                )
+                .prop(
+                    "FEFO Compliance (%)",
+                    fefo::compliance_percent(&fefo_checks)
+                        .map(|percent| quantity::round(percent).to_string())
+                        .unwrap_or_default(),
+                )
                 .table(
                     report_table::builder("Timeline")
                         .column("Timestamp")
@@ -332,6 +565,8 @@ impl StockDetailsPane {
                         .column("Max Count")
                         .column("Entry Count")
                         .column("Exit Count")
+                        .column("FEFO")
+                        .column("FEFO Compliance (%)")
                         .rows(timeline.iter_stock(&dt_range, stock.id()).map(|item| {
                             report_table::row_builder()
                                 .cell(item.dt())
@@ -341,12 +576,19 @@ impl StockDetailsPane {
                                 .cell(stock.max_n_inside_for_dt(item.dt()))
                                 .cell(stock.n_entries_for_dt(item.dt()))
                                 .cell(stock.n_exits_for_dt(item.dt()))
+                                .cell(match fefo_check_for_dt(item.dt()) {
+                                    Some(check) if check.is_compliant() => "Yes".to_string(),
+                                    Some(_) => "No".to_string(),
+                                    None => String::new(),
+                                })
+                                .cell(fefo_compliance_for_dt(item.dt()))
                                 .build()
                         }))
                         .graph("Count Over Time", 0, 3)
                         .graph("Max Count Over Time", 0, 4)
                         .graph("Entry Count Over Time", 0, 5)
                         .graph("Exit Count Over Time", 0, 6)
+                        .graph("FEFO Compliance Over Time", 0, 8)
                         .build(),
                 )
                 .build()
@@ -366,6 +608,271 @@ impl StockDetailsPane {
         }
     }
 
+    fn handle_unit_row_apply(&self, unit: &str) {
+        let Some(stock) = self.stock() else {
+            return;
+        };
+
+        let mut data = stock.data();
+        data.unit = (!unit.is_empty()).then(|| unit.to_string());
+
+        if let Err(err) = Application::get()
+            .timeline()
+            .register_stock_data(HashMap::from([(stock.id().clone(), data)]))
+        {
+            tracing::error!("Failed to set stock unit: {:?}", err);
+
+            Application::get().add_message_toast("Failed to set unit");
+        }
+    }
+
+    fn handle_expiring_soon_window_days_row_apply(&self, days: &str) {
+        let Some(stock) = self.stock() else {
+            return;
+        };
+
+        let app = Application::get();
+
+        let days = if days.is_empty() {
+            None
+        } else if let Ok(days) = days.parse::<u32>() {
+            Some(days)
+        } else {
+            app.add_message_toast("Invalid number of days");
+            self.update_expiring_soon_window_days_row();
+            return;
+        };
+
+        let mut data = stock.data();
+        data.expiring_soon_window_days = days;
+
+        if let Err(err) = app
+            .timeline()
+            .register_stock_data(HashMap::from([(stock.id().clone(), data)]))
+        {
+            tracing::error!("Failed to set stock expiring soon window: {:?}", err);
+
+            app.add_message_toast("Failed to set expiring soon window");
+        }
+    }
+
+    fn handle_supplier_row_apply(&self, supplier: &str) {
+        let Some(stock) = self.stock() else {
+            return;
+        };
+
+        let mut data = stock.data();
+        data.supplier = (!supplier.is_empty()).then(|| supplier.to_string());
+
+        if let Err(err) = Application::get()
+            .timeline()
+            .register_stock_data(HashMap::from([(stock.id().clone(), data)]))
+        {
+            tracing::error!("Failed to set stock supplier: {:?}", err);
+
+            Application::get().add_message_toast("Failed to set supplier");
+        }
+    }
+
+    fn handle_target_days_of_cover_row_apply(&self, days: &str) {
+        let Some(stock) = self.stock() else {
+            return;
+        };
+
+        let app = Application::get();
+
+        let days = if days.is_empty() {
+            None
+        } else if let Ok(days) = days.parse::<u32>() {
+            Some(days)
+        } else {
+            app.add_message_toast("Invalid number of days");
+            self.update_target_days_of_cover_row();
+            return;
+        };
+
+        let mut data = stock.data();
+        data.target_days_of_cover = days;
+
+        if let Err(err) = app
+            .timeline()
+            .register_stock_data(HashMap::from([(stock.id().clone(), data)]))
+        {
+            tracing::error!("Failed to set stock target days of cover: {:?}", err);
+
+            app.add_message_toast("Failed to set target days of cover");
+        }
+    }
+
+    fn handle_unit_cost_row_apply(&self, unit_cost: &str) {
+        let Some(stock) = self.stock() else {
+            return;
+        };
+
+        let app = Application::get();
+
+        let unit_cost = if unit_cost.is_empty() {
+            None
+        } else if let Ok(unit_cost) = valuation::parse_unit_cost(unit_cost) {
+            Some(unit_cost)
+        } else {
+            app.add_message_toast("Invalid unit cost");
+            self.update_unit_cost_row();
+            return;
+        };
+
+        let mut data = stock.data();
+        data.unit_cost = unit_cost;
+
+        if let Err(err) = app
+            .timeline()
+            .register_stock_data(HashMap::from([(stock.id().clone(), data)]))
+        {
+            tracing::error!("Failed to set stock unit cost: {:?}", err);
+
+            app.add_message_toast("Failed to set unit cost");
+        }
+    }
+
+    fn handle_currency_row_apply(&self, currency: &str) {
+        let Some(stock) = self.stock() else {
+            return;
+        };
+
+        let mut data = stock.data();
+        data.currency = (!currency.is_empty()).then(|| currency.to_string());
+
+        if let Err(err) = Application::get()
+            .timeline()
+            .register_stock_data(HashMap::from([(stock.id().clone(), data)]))
+        {
+            tracing::error!("Failed to set stock currency: {:?}", err);
+
+            Application::get().add_message_toast("Failed to set currency");
+        }
+    }
+
+    fn update_unit_row(&self) {
+        let imp = self.imp();
+
+        let unit = self
+            .stock()
+            .and_then(|stock| stock.data().unit)
+            .unwrap_or_default();
+        imp.unit_row.set_text(&unit);
+    }
+
+    fn update_expiring_soon_window_days_row(&self) {
+        let imp = self.imp();
+
+        let days = self
+            .stock()
+            .and_then(|stock| stock.data().expiring_soon_window_days)
+            .map(|days| days.to_string())
+            .unwrap_or_default();
+        imp.expiring_soon_window_days_row.set_text(&days);
+    }
+
+    fn update_supplier_row(&self) {
+        let imp = self.imp();
+
+        let supplier = self
+            .stock()
+            .and_then(|stock| stock.data().supplier)
+            .unwrap_or_default();
+        imp.supplier_row.set_text(&supplier);
+    }
+
+    fn update_target_days_of_cover_row(&self) {
+        let imp = self.imp();
+
+        let days = self
+            .stock()
+            .and_then(|stock| stock.data().target_days_of_cover)
+            .map(|days| days.to_string())
+            .unwrap_or_default();
+        imp.target_days_of_cover_row.set_text(&days);
+    }
+
+    fn update_reorder_rows(&self) {
+        let imp = self.imp();
+
+        let app = Application::get();
+
+        let suggestion = self.stock().and_then(|stock| {
+            let now = imp
+                .dt_range
+                .borrow()
+                .end
+                .unwrap_or_else(|| app.timeline().clock().now());
+            ReorderSuggestion::for_stock(&stock, app.settings(), now)
+        });
+
+        if let Some(suggestion) = suggestion {
+            imp.consumption_rate_row
+                .set_text(reorder::round_hundredths(suggestion.consumption_rate).to_string());
+            imp.days_of_cover_row.set_text(
+                suggestion
+                    .days_of_cover
+                    .map(|days| reorder::round_hundredths(days).to_string())
+                    .unwrap_or_else(|| "Indefinite".to_string()),
+            );
+            imp.n_items_to_order_row
+                .set_text(suggestion.n_items_to_order.to_string());
+        } else {
+            imp.consumption_rate_row.set_text("");
+            imp.days_of_cover_row.set_text("");
+            imp.n_items_to_order_row.set_text("");
+        }
+    }
+
+    fn update_unit_cost_row(&self) {
+        let imp = self.imp();
+
+        let unit_cost = self
+            .stock()
+            .and_then(|stock| stock.data().unit_cost)
+            .map(|unit_cost| unit_cost.to_string())
+            .unwrap_or_default();
+        imp.unit_cost_row.set_text(&unit_cost);
+    }
+
+    fn update_currency_row(&self) {
+        let imp = self.imp();
+
+        let currency = self
+            .stock()
+            .and_then(|stock| stock.data().currency)
+            .unwrap_or_default();
+        imp.currency_row.set_text(&currency);
+    }
+
+    fn update_valuation_rows(&self) {
+        let imp = self.imp();
+
+        let app = Application::get();
+        let timeline = app.timeline();
+
+        let valuation = self.stock().and_then(|stock| {
+            timeline.stock_valuation(&stock, &imp.dt_range.borrow(), timeline.clock().now())
+        });
+
+        if let Some(valuation) = valuation {
+            imp.current_value_row
+                .set_text(valuation.current.to_string());
+            imp.entered_value_row
+                .set_text(valuation.entered.to_string());
+            imp.exited_value_row.set_text(valuation.exited.to_string());
+            imp.expired_value_row
+                .set_text(valuation.expired.to_string());
+        } else {
+            imp.current_value_row.set_text("");
+            imp.entered_value_row.set_text("");
+            imp.exited_value_row.set_text("");
+            imp.expired_value_row.set_text("");
+        }
+    }
+
     fn update_n_inside_row(&self) {
         let imp = self.imp();
 
@@ -411,6 +918,17 @@ impl StockDetailsPane {
         imp.n_exits_row.set_text(n_exits.to_string());
     }
 
+    fn update_n_consumed_row(&self) {
+        let imp = self.imp();
+
+        let stock = imp.stock.borrow();
+        let n_consumed = stock
+            .as_ref()
+            .map(|s| s.n_consumed_for_dt_range(&imp.dt_range.borrow()))
+            .unwrap_or_default();
+        imp.n_consumed_row.set_text(n_consumed.to_string());
+    }
+
     fn update_last_entry_dt_row(&self) {
         let imp = self.imp();
 
@@ -441,6 +959,96 @@ impl StockDetailsPane {
         );
     }
 
+    fn update_fefo_groups(&self) {
+        let imp = self.imp();
+
+        for row in imp.next_to_use_group_rows.take() {
+            imp.next_to_use_group.remove(&row);
+        }
+        for row in imp.lots_group_rows.take() {
+            imp.lots_group.remove(&row);
+        }
+
+        let Some(stock) = self.stock() else {
+            imp.next_to_use_group.set_visible(false);
+            imp.lots_group.set_visible(false);
+            return;
+        };
+
+        let app = Application::get();
+        let timeline = app.timeline();
+
+        let dt = imp
+            .dt_range
+            .borrow()
+            .end
+            .unwrap_or_else(|| timeline.clock().now());
+        let entities = timeline.pick_order_for_dt(stock.id(), dt);
+
+        for entity in &entities {
+            let data = entity.data();
+
+            let mut subtitle_parts = Vec::new();
+            if let Some(lot) = data.lot() {
+                subtitle_parts.push(format!("Lot {}", lot));
+            }
+            if let Some(expiration_dt) = data.expiration_dt() {
+                subtitle_parts.push(format!(
+                    "Expires {}",
+                    date_time::format::human_readable_date(*expiration_dt)
+                ));
+            }
+
+            let row = adw::ActionRow::builder()
+                .title(
+                    data.name()
+                        .map_or_else(|| entity.id().to_string(), |name| name.clone()),
+                )
+                .subtitle(subtitle_parts.join(" · "))
+                .build();
+            imp.next_to_use_group.add(&row);
+            imp.next_to_use_group_rows.borrow_mut().push(row);
+        }
+
+        let unit = stock.data().unit;
+        for lot_group in fefo::group_by_lot(entities) {
+            let amount = lot_group
+                .entities
+                .iter()
+                .map(|entity| quantity::stock_amount(entity.data().quantity(), unit.as_deref()))
+                .sum::<f64>();
+            let earliest_expiration_dt = lot_group
+                .entities
+                .iter()
+                .filter_map(|entity| entity.data().expiration_dt().copied())
+                .min();
+
+            let mut subtitle = format!(
+                "{} {}",
+                quantity::round(amount),
+                unit.as_deref().unwrap_or("inside")
+            );
+            if let Some(expiration_dt) = earliest_expiration_dt {
+                subtitle.push_str(&format!(
+                    ", expires {}",
+                    date_time::format::human_readable_date(expiration_dt)
+                ));
+            }
+
+            let row = adw::ActionRow::builder()
+                .title(lot_group.lot.unwrap_or_else(|| "No Lot".to_string()))
+                .subtitle(subtitle)
+                .build();
+            imp.lots_group.add(&row);
+            imp.lots_group_rows.borrow_mut().push(row);
+        }
+
+        imp.next_to_use_group
+            .set_visible(!imp.next_to_use_group_rows.borrow().is_empty());
+        imp.lots_group
+            .set_visible(!imp.lots_group_rows.borrow().is_empty());
+    }
+
     fn update_graphs_data(&self) {
         let imp = self.imp();
 
@@ -490,5 +1098,14 @@ impl StockDetailsPane {
             })
             .unwrap_or_default();
         imp.n_exits_graph.set_data(n_exits_data);
+
+        let fefo_compliance_data = self
+            .stock()
+            .map(|stock| {
+                let checks = timeline.fefo_checks(stock.id(), &imp.dt_range.borrow());
+                fefo::compliance_over_time(&checks)
+            })
+            .unwrap_or_default();
+        imp.fefo_compliance_graph.set_data(fefo_compliance_data);
     }
 }
@@ -1,15 +1,17 @@
+use std::rc::Rc;
+
+use adw::{prelude::*, subclass::prelude::*};
 use anyhow::Result;
-use gtk::{
-    glib::{self, clone, closure, closure_local},
-    prelude::*,
-    subclass::prelude::*,
-};
+use gtk::glib::{self, clone, closure, closure_local};
 
 use crate::{
     date_time_range::DateTimeRange,
     fuzzy_filter::FuzzyFilter,
     limit_reached::{LimitReached, LimitReachedSettingsExt},
     list_model_enum,
+    location_id::LocationId,
+    quantity,
+    reorder::{self, ReorderSuggestion},
     report::{self, ReportKind},
     report_table,
     search_query::SearchQueries,
@@ -19,10 +21,12 @@ use crate::{
     stock_id::StockId,
     stock_list::StockList,
     ui::{
-        date_time_range_button::DateTimeRangeButton, search_entry::SearchEntry,
-        send_dialog::SendDialog, stock_details_pane::StockDetailsPane, stock_row::StockRow,
+        date_time_range_button::DateTimeRangeButton, locations_dialog::LocationsDialog,
+        search_entry::SearchEntry, send_dialog::SendDialog, stock_details_pane::StockDetailsPane,
+        stock_row::StockRow,
     },
-    utils::{new_filter, new_sorter},
+    utils::{new_filter, new_header_factory, new_sorter},
+    valuation::{self, Money, StockValuation},
     Application,
 };
 
@@ -43,12 +47,16 @@ impl S {
     const LOWER_LIMIT_REACHED: &str = "lower-limit-reached";
     const UPPER_LIMIT_REACHED: &str = "upper-limit-reached";
 
+    const LOCATION: &str = "location";
+
     const SORT: &str = "sort";
     const SORT_VALUES: &[&str] = &[
         Self::ID_ASC,
         Self::ID_DESC,
         Self::COUNT_ASC,
         Self::COUNT_DESC,
+        Self::LOCATION_ASC,
+        Self::LOCATION_DESC,
         Self::UPDATED_ASC,
         Self::UPDATED_DESC,
     ];
@@ -57,6 +65,8 @@ impl S {
     const ID_DESC: &str = "id-desc";
     const COUNT_ASC: &str = "count-asc";
     const COUNT_DESC: &str = "count-desc";
+    const LOCATION_ASC: &str = "location-asc";
+    const LOCATION_DESC: &str = "location-desc";
     const UPDATED_ASC: &str = "updated-asc";
     const UPDATED_DESC: &str = "updated-desc";
 }
@@ -91,6 +101,8 @@ enum StockSort {
     IdDesc,
     CountAsc,
     CountDesc,
+    LocationAsc,
+    LocationDesc,
     UpdatedAsc,
     UpdatedDesc,
 }
@@ -104,10 +116,16 @@ impl StockSort {
             StockSort::IdDesc => "Z-A",
             StockSort::CountAsc => "Least Count",
             StockSort::CountDesc => "Most Count",
+            StockSort::LocationAsc => "Location (A-Z)",
+            StockSort::LocationDesc => "Location (Z-A)",
             StockSort::UpdatedAsc => "Least Recently Updated",
             StockSort::UpdatedDesc => "Recently Updated",
         }
     }
+
+    fn is_by_location(&self) -> bool {
+        matches!(self, StockSort::LocationAsc | StockSort::LocationDesc)
+    }
 }
 
 #[allow(deprecated)]
@@ -175,6 +193,15 @@ mod imp {
         fn class_init(klass: &mut Self::Class) {
             klass.bind_template();
 
+            klass.install_action("stocks-view.show-locations-dialog", None, |obj, _, _| {
+                let dialog = LocationsDialog::new();
+
+                let app = Application::get();
+                let list = app.timeline().location_list();
+                dialog.set_model(Some(list));
+
+                dialog.present(Some(obj));
+            });
             klass.install_action_async(
                 "stocks-view.share-report",
                 Some(&ReportKind::static_variant_type()),
@@ -194,6 +221,44 @@ mod imp {
                     }
                 },
             );
+            klass.install_action_async(
+                "stocks-view.share-purchase-list",
+                Some(&ReportKind::static_variant_type()),
+                |obj, _, kind| async move {
+                    let kind = kind.unwrap().get::<ReportKind>().unwrap();
+
+                    if let Err(err) = SendDialog::send(
+                        &report::file_name("Purchase List", kind),
+                        obj.create_purchase_list(kind),
+                        Some(&obj),
+                    )
+                    .await
+                    {
+                        tracing::error!("Failed to send purchase list: {:?}", err);
+
+                        Application::get().add_message_toast("Failed to share purchase list");
+                    }
+                },
+            );
+            klass.install_action_async(
+                "stocks-view.share-valuation-report",
+                Some(&ReportKind::static_variant_type()),
+                |obj, _, kind| async move {
+                    let kind = kind.unwrap().get::<ReportKind>().unwrap();
+
+                    if let Err(err) = SendDialog::send(
+                        &report::file_name("Valuation Report", kind),
+                        obj.create_valuation_report(kind),
+                        Some(&obj),
+                    )
+                    .await
+                    {
+                        tracing::error!("Failed to send valuation report: {:?}", err);
+
+                        Application::get().add_message_toast("Failed to share valuation report");
+                    }
+                },
+            );
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -358,6 +423,20 @@ mod imp {
                 }
             ));
 
+            // Primary locations change as entities enter, exit or move.
+            Application::get()
+                .timeline()
+                .location_list()
+                .connect_items_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_, _, _, _| {
+                        if obj.stock_sort().is_by_location() {
+                            obj.update_fallback_sorter();
+                        }
+                    }
+                ));
+
             let fuzzy_filter = FuzzyFilter::new(|o| {
                 let stock = o.downcast_ref::<Stock>().unwrap();
                 [Some(stock.id().to_string())]
@@ -483,20 +562,171 @@ impl StocksView {
         report::builder(kind, "Stocks Report")
             .prop(
                 "Total Stock Count",
-                stocks
-                    .iter()
-                    .map(|s| s.n_inside_for_dt_range(&imp.dt_range.borrow()))
-                    .sum::<u32>(),
+                quantity::round(
+                    stocks
+                        .iter()
+                        .map(|s| s.n_inside_for_dt_range(&imp.dt_range.borrow()))
+                        .sum::<f64>(),
+                ),
+            )
+            .prop(
+                "Total Value",
+                valuation::format_totals(
+                    stocks
+                        .iter()
+                        .filter_map(|s| valuation::current_value(s, &imp.dt_range.borrow())),
+                ),
             )
             .prop("Search Query", imp.search_entry.queries())
             .table(
                 report_table::builder("Stocks")
                     .column("ID")
                     .column("Count")
+                    .column("Value")
                     .rows(stocks.iter().map(|stock| {
                         report_table::row_builder()
                             .cell(stock.id().to_string())
                             .cell(stock.n_inside_for_dt_range(&imp.dt_range.borrow()))
+                            .cell(
+                                valuation::current_value(stock, &imp.dt_range.borrow())
+                                    .map(|value| value.to_string())
+                                    .unwrap_or_default(),
+                            )
+                            .build()
+                    }))
+                    .build(),
+            )
+            .build()
+            .await
+    }
+
+    /// Creates a list of the shown stocks that need to be reordered to last
+    /// for their target days of cover, grouped by supplier.
+    pub async fn create_purchase_list(&self, kind: ReportKind) -> Result<Vec<u8>> {
+        let imp = self.imp();
+
+        let app = Application::get();
+        let settings = app.settings();
+
+        let now = imp
+            .dt_range
+            .borrow()
+            .end
+            .unwrap_or_else(|| app.timeline().clock().now());
+
+        let mut items = imp
+            .selection_model
+            .iter::<glib::Object>()
+            .map(|o| o.unwrap().downcast::<Stock>().unwrap())
+            .filter_map(|stock| {
+                let suggestion = ReorderSuggestion::for_stock(&stock, settings, now)?;
+                suggestion
+                    .needs_reorder()
+                    .then(|| (stock.data().supplier, stock, suggestion))
+            })
+            .collect::<Vec<_>>();
+        items.sort_by(|(a_supplier, a_stock, _), (b_supplier, b_stock, _)| {
+            // Stocks without supplier go last.
+            (a_supplier.is_none(), a_supplier, a_stock.id()).cmp(&(
+                b_supplier.is_none(),
+                b_supplier,
+                b_stock.id(),
+            ))
+        });
+
+        report::builder(kind, "Purchase List")
+            .prop(
+                "Total Items to Order",
+                items
+                    .iter()
+                    .map(|(_, _, suggestion)| suggestion.n_items_to_order)
+                    .sum::<u32>(),
+            )
+            .prop("Search Query", imp.search_entry.queries())
+            .table(
+                report_table::builder("Purchase List")
+                    .column("Supplier")
+                    .column("Stock ID")
+                    .column("Items Inside")
+                    .column("Consumption Rate (Items per Day)")
+                    .column("Days of Cover")
+                    .column("Target Days of Cover")
+                    .column("Items to Order")
+                    .rows(items.iter().map(|(supplier, stock, suggestion)| {
+                        report_table::row_builder()
+                            .cell(supplier.clone().unwrap_or_default())
+                            .cell(stock.id().to_string())
+                            .cell(suggestion.n_items_inside)
+                            .cell(reorder::round_hundredths(suggestion.consumption_rate))
+                            .cell(
+                                suggestion
+                                    .days_of_cover
+                                    .map(reorder::round_hundredths)
+                                    .unwrap_or_default(),
+                            )
+                            .cell(suggestion.target_days_of_cover)
+                            .cell(suggestion.n_items_to_order)
+                            .build()
+                    }))
+                    .build(),
+            )
+            .build()
+            .await
+    }
+
+    /// Creates a report of the value of the shown stocks that have a unit
+    /// cost, with totals per currency.
+    pub async fn create_valuation_report(&self, kind: ReportKind) -> Result<Vec<u8>> {
+        let imp = self.imp();
+
+        let app = Application::get();
+        let timeline = app.timeline();
+        let now = timeline.clock().now();
+        let dt_range = *imp.dt_range.borrow();
+
+        let items = imp
+            .selection_model
+            .iter::<glib::Object>()
+            .map(|o| o.unwrap().downcast::<Stock>().unwrap())
+            .filter_map(|stock| {
+                let valuation = timeline.stock_valuation(&stock, &dt_range, now)?;
+                Some((stock, valuation))
+            })
+            .collect::<Vec<_>>();
+
+        let total_of = |f: fn(&StockValuation) -> &Money| {
+            valuation::format_totals(items.iter().map(|(_, v)| f(v).clone()))
+        };
+
+        report::builder(kind, "Valuation Report")
+            .prop("Total Current Value", total_of(|v| &v.current))
+            .prop("Total Value In", total_of(|v| &v.entered))
+            .prop("Total Value Out", total_of(|v| &v.exited))
+            .prop("Total Expired Value", total_of(|v| &v.expired))
+            .prop("Search Query", imp.search_entry.queries())
+            .table(
+                report_table::builder("Valuation")
+                    .column("Stock ID")
+                    .column("Unit")
+                    .column("Unit Cost")
+                    .column("Currency")
+                    .column("Count")
+                    .column("Current Value")
+                    .column("Value In")
+                    .column("Value Out")
+                    .column("Expired Value")
+                    .rows(items.iter().map(|(stock, valuation)| {
+                        let data = stock.data();
+                        report_table::row_builder()
+                            .cell(stock.id().to_string())
+                            .cell(data.unit.unwrap_or_default())
+                            .cell(valuation.unit_cost.amount)
+                            .cell(data.currency.unwrap_or_default())
+                            .cell(stock.n_inside_for_dt_range(&dt_range))
+                            .cell(reorder::round_hundredths(valuation.current.amount))
+                            .cell(reorder::round_hundredths(valuation.entered.amount))
+                            .cell(reorder::round_hundredths(valuation.exited.amount))
+                            .cell(reorder::round_hundredths(valuation.expired.amount))
                             .build()
                     }))
                     .build(),
@@ -625,7 +855,29 @@ impl StocksView {
             }
         };
 
+        let any_location_filter = gtk::AnyFilter::new();
+        for location_id in queries
+            .all_values(S::LOCATION)
+            .into_iter()
+            .filter_map(LocationId::parse)
+        {
+            any_location_filter.append(new_filter(move |stock: &Stock| {
+                Application::get()
+                    .timeline()
+                    .stock_n_inside_by_location(stock.id(), &dt_range)
+                    .into_iter()
+                    .any(|(l_id, _)| l_id.is_some_and(|l_id| location_id.contains(&l_id)))
+            }));
+        }
+
+        if any_location_filter.n_items() == 0 {
+            any_location_filter.append(new_filter(|_: &Stock| true));
+        }
+
+        every_filter.append(any_location_filter);
+
         imp.filter_list_model.set_filter(Some(&every_filter));
+        self.update_fallback_sorter();
     }
 
     fn handle_limit_reached_dropdown_selected_item_notify(&self, dropdown: &gtk::DropDown) {
@@ -685,6 +937,12 @@ impl StocksView {
             StockSort::CountDesc => {
                 queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::COUNT_DESC)
             }
+            StockSort::LocationAsc => {
+                queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::LOCATION_ASC)
+            }
+            StockSort::LocationDesc => {
+                queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::LOCATION_DESC)
+            }
             StockSort::UpdatedAsc => {
                 queries.replace_all_or_insert(S::SORT, S::SORT_VALUES, S::UPDATED_ASC)
             }
@@ -706,20 +964,28 @@ impl StocksView {
         imp.search_entry.set_queries(queries);
     }
 
-    fn update_fallback_sorter(&self) {
+    fn stock_sort(&self) -> StockSort {
         let imp = self.imp();
 
         let queries = imp.search_entry.queries();
-        let stock_sort = match queries.find_last_with_values(S::SORT, S::SORT_VALUES) {
+        match queries.find_last_with_values(S::SORT, S::SORT_VALUES) {
             Some(S::ID_ASC) => StockSort::IdAsc,
             Some(S::ID_DESC) => StockSort::IdDesc,
             Some(S::COUNT_ASC) => StockSort::CountAsc,
             Some(S::COUNT_DESC) => StockSort::CountDesc,
+            Some(S::LOCATION_ASC) => StockSort::LocationAsc,
+            Some(S::LOCATION_DESC) => StockSort::LocationDesc,
             Some(S::UPDATED_ASC) => StockSort::UpdatedAsc,
             Some(S::UPDATED_DESC) => StockSort::UpdatedDesc,
             None => StockSort::default(),
             Some(_) => unreachable!(),
-        };
+        }
+    }
+
+    fn update_fallback_sorter(&self) {
+        let imp = self.imp();
+
+        let stock_sort = self.stock_sort();
 
         let selected_item_notify_id = imp.stock_sort_dropdown_selected_item_id.get().unwrap();
         imp.stock_sort_dropdown
@@ -730,6 +996,13 @@ impl StocksView {
             .unblock_signal(selected_item_notify_id);
 
         let dt_range = *imp.dt_range.borrow();
+        let primary_locations = Rc::new(if stock_sort.is_by_location() {
+            Application::get()
+                .timeline()
+                .stock_primary_locations(&dt_range)
+        } else {
+            Default::default()
+        });
         let sorter = match stock_sort {
             StockSort::IdAsc | StockSort::IdDesc => {
                 new_sorter(matches!(stock_sort, StockSort::IdDesc), |a: &Stock, b| {
@@ -740,9 +1013,21 @@ impl StocksView {
                 matches!(stock_sort, StockSort::CountDesc),
                 move |a: &Stock, b| {
                     a.n_inside_for_dt_range(&dt_range)
-                        .cmp(&b.n_inside_for_dt_range(&dt_range))
+                        .total_cmp(&b.n_inside_for_dt_range(&dt_range))
                 },
             ),
+            StockSort::LocationAsc | StockSort::LocationDesc => {
+                let primary_locations = Rc::clone(&primary_locations);
+                new_sorter(
+                    matches!(stock_sort, StockSort::LocationDesc),
+                    move |a: &Stock, b| {
+                        primary_locations
+                            .get(a.id())
+                            .cmp(&primary_locations.get(b.id()))
+                            .then_with(|| a.id().cmp(b.id()))
+                    },
+                )
+            }
             StockSort::UpdatedAsc | StockSort::UpdatedDesc => new_sorter(
                 matches!(stock_sort, StockSort::UpdatedDesc),
                 |a: &Stock, b| a.last_action_dt().cmp(&b.last_action_dt()),
@@ -754,24 +1039,59 @@ impl StocksView {
             .unwrap()
             .sorter()
             .set_fallback_sorter(Some(sorter));
+
+        // Group by primary location when sorted by it.
+        if stock_sort.is_by_location() {
+            let section_primary_locations = Rc::clone(&primary_locations);
+            imp.sort_list_model.set_section_sorter(Some(&new_sorter(
+                matches!(stock_sort, StockSort::LocationDesc),
+                move |a: &Stock, b| {
+                    section_primary_locations
+                        .get(a.id())
+                        .cmp(&section_primary_locations.get(b.id()))
+                },
+            )));
+            imp.list_view
+                .set_header_factory(Some(&new_header_factory(move |stock: &Stock| {
+                    primary_locations
+                        .get(stock.id())
+                        .map_or_else(|| "No Location".to_string(), |l| l.to_string())
+                })));
+        } else {
+            imp.sort_list_model.set_section_sorter(gtk::Sorter::NONE);
+            imp.list_view.set_header_factory(gtk::ListItemFactory::NONE);
+        }
     }
 
     fn update_n_results_label(&self) {
         let imp = self.imp();
 
-        let n_total = imp
+        let n_total = quantity::round(
+            imp.selection_model
+                .iter::<glib::Object>()
+                .map(|o| {
+                    let stock = o.unwrap().downcast::<Stock>().unwrap();
+                    stock.n_inside_for_dt_range(&imp.dt_range.borrow())
+                })
+                .sum::<f64>(),
+        );
+        let values = imp
             .selection_model
             .iter::<glib::Object>()
-            .map(|o| {
+            .filter_map(|o| {
                 let stock = o.unwrap().downcast::<Stock>().unwrap();
-                stock.n_inside_for_dt_range(&imp.dt_range.borrow())
+                valuation::current_value(&stock, &imp.dt_range.borrow())
             })
-            .sum::<u32>();
-        let text = if imp.search_entry.queries().is_empty() {
+            .collect::<Vec<_>>();
+
+        let mut text = if imp.search_entry.queries().is_empty() {
             format!("Total: {}", n_total)
         } else {
             format!("Results: {}", n_total)
         };
+        if !values.is_empty() {
+            text.push_str(&format!(" · Value: {}", valuation::format_totals(values)));
+        }
 
         imp.n_results_label.set_text(&text);
     }
@@ -1,10 +1,20 @@
+use std::{fs, path::PathBuf, time::Instant};
+
 use adw::{prelude::*, subclass::prelude::*};
+use anyhow::{Context, Result};
+use chrono::Utc;
 use gtk::{
     gdk,
     glib::{self, clone},
 };
 
-use crate::{entity_id::EntityId, Application};
+use crate::{
+    detection_replay,
+    detection_trace::DetectionTrace,
+    entity_id::EntityId,
+    traffic_generator::{self, TrafficConfig},
+    Application,
+};
 
 mod imp {
     use super::*;
@@ -18,6 +28,20 @@ mod imp {
         pub(super) enter_button: TemplateChild<gtk::Button>,
         #[template_child]
         pub(super) reset_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) n_days_spin_button: TemplateChild<gtk::SpinButton>,
+        #[template_child]
+        pub(super) n_entities_spin_button: TemplateChild<gtk::SpinButton>,
+        #[template_child]
+        pub(super) seed_spin_button: TemplateChild<gtk::SpinButton>,
+        #[template_child]
+        pub(super) generate_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) trace_path_entry: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub(super) record_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub(super) replay_button: TemplateChild<gtk::Button>,
     }
 
     #[glib::object_subclass]
@@ -62,6 +86,45 @@ mod imp {
                     tracing::error!("Failed to reset timeline: {:?}", err);
                 }
             });
+            self.generate_button.connect_clicked(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    if let Err(err) = obj.handle_generate() {
+                        tracing::error!("Failed to generate traffic: {:?}", err);
+                    }
+                }
+            ));
+
+            let default_trace_dir = glib::user_special_dir(glib::UserDirectory::Downloads)
+                .unwrap_or_else(glib::home_dir);
+            self.trace_path_entry.set_text(
+                &default_trace_dir
+                    .join("uets-trace.jsonl")
+                    .display()
+                    .to_string(),
+            );
+            let is_recording = Application::get().detector().is_recording();
+            self.record_button.set_active(is_recording);
+            self.trace_path_entry.set_sensitive(!is_recording);
+            self.record_button.connect_toggled(clone!(
+                #[weak]
+                obj,
+                move |button| {
+                    obj.handle_record_toggled(button.is_active());
+                }
+            ));
+            self.replay_button.connect_clicked(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    if let Err(err) = obj.handle_replay() {
+                        tracing::error!("Failed to replay trace: {:?}", err);
+
+                        Application::get().add_message_toast("Failed to replay trace");
+                    }
+                }
+            ));
         }
     }
 
@@ -92,4 +155,87 @@ impl TestWindow {
 
         Application::get().detector().simulate_detected(&id, None);
     }
+
+    fn trace_path(&self) -> PathBuf {
+        PathBuf::from(self.imp().trace_path_entry.text().as_str())
+    }
+
+    fn handle_record_toggled(&self, is_active: bool) {
+        let imp = self.imp();
+
+        let app = Application::get();
+        let detector = app.detector();
+
+        if !is_active {
+            detector.stop_recording();
+            imp.trace_path_entry.set_sensitive(true);
+            return;
+        }
+
+        if let Err(err) = detector.start_recording(&self.trace_path()) {
+            tracing::error!("Failed to start recording: {:?}", err);
+
+            imp.record_button.set_active(false);
+            return;
+        }
+
+        imp.trace_path_entry.set_sensitive(false);
+    }
+
+    fn handle_replay(&self) -> Result<()> {
+        let path = self.trace_path();
+        let trace = DetectionTrace::load(&path)?;
+
+        let start_time = Instant::now();
+        let report = detection_replay::replay(&trace)?;
+        tracing::debug!(
+            "Replayed {} inputs in {:?}",
+            trace.inputs.len(),
+            start_time.elapsed()
+        );
+
+        let report_path = path.with_extension("replay.txt");
+        fs::write(&report_path, report.to_string())
+            .with_context(|| format!("Failed to write report at {}", report_path.display()))?;
+
+        Application::get().add_message_toast(&format!(
+            "Replayed {} inputs into {} items",
+            trace.inputs.len(),
+            report.n_items()
+        ));
+
+        Ok(())
+    }
+
+    fn handle_generate(&self) -> Result<()> {
+        let imp = self.imp();
+
+        let app = Application::get();
+
+        let config = TrafficConfig {
+            operation_mode: app.settings().operation_mode(),
+            seed: imp.seed_spin_button.value_as_int() as u64,
+            n_days: imp.n_days_spin_button.value_as_int() as u32,
+            n_entities: imp.n_entities_spin_button.value_as_int() as u32,
+            end_dt: Utc::now(),
+        };
+
+        let start_time = Instant::now();
+        let traffic = traffic_generator::generate(&config);
+        tracing::debug!(
+            ?config,
+            "Generated {} detections in {:?}",
+            traffic.detections.len(),
+            start_time.elapsed()
+        );
+
+        let timeline = app.timeline();
+        timeline.reset()?;
+
+        let start_time = Instant::now();
+        timeline.populate(traffic.entities, traffic.detections)?;
+        tracing::debug!("Populated timeline in {:?}", start_time.elapsed());
+
+        Ok(())
+    }
 }
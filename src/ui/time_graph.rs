@@ -15,7 +15,7 @@ mod imp {
         #[template_child]
         pub(super) no_data_revealer: TemplateChild<gtk::Revealer>,
 
-        pub(super) data: RefCell<Vec<(chrono::DateTime<Utc>, u32)>>,
+        pub(super) data: RefCell<Vec<(chrono::DateTime<Utc>, f64)>>,
     }
 
     #[glib::object_subclass]
@@ -76,10 +76,14 @@ impl TimeGraph {
         glib::Object::new()
     }
 
-    pub fn set_data(&self, data: Vec<(chrono::DateTime<Utc>, u32)>) {
+    pub fn set_data(
+        &self,
+        data: impl IntoIterator<Item = (chrono::DateTime<Utc>, impl Into<f64>)>,
+    ) {
         let imp = self.imp();
 
-        imp.data.replace(data);
+        imp.data
+            .replace(data.into_iter().map(|(dt, y)| (dt, y.into())).collect());
 
         self.queue_draw();
         self.update_no_data_revealer();
@@ -23,7 +23,7 @@ use crate::{
         send_dialog::SendDialog, timeline_row::TimelineRow,
     },
     utils::new_filter,
-    Application,
+    zone, Application,
 };
 
 struct S;
@@ -450,6 +450,7 @@ impl TimelineView {
                     .column("Max Inside Count")
                     .column("Entry Count")
                     .column("Exit Count")
+                    .column("Zone")
                     .rows(items.iter().map(|item| {
                         report_table::row_builder()
                             .cell(item.dt())
@@ -459,6 +460,7 @@ impl TimelineView {
                             .cell(timeline.max_n_inside_for_dt(item.dt()))
                             .cell(timeline.n_entries_for_dt(item.dt()))
                             .cell(timeline.n_exits_for_dt(item.dt()))
+                            .cell(item.zone().unwrap_or(zone::NO_ZONE_NAME).to_string())
                             .build()
                     }))
                     .graph("Inside Count Over Time", 0, 3)
@@ -111,6 +111,10 @@ mod imp {
                             imp.stocks_view.show_stocks_limit_reached(*limit_reached);
                             imp.view_stack.set_visible_child_name("stocks");
                         }
+                        DashboardViewShowRequest::ExpiringEntities => {
+                            imp.entities_view.show_entities_expiring();
+                            imp.view_stack.set_visible_child_name("entities");
+                        }
                         DashboardViewShowRequest::ExpiredEntities => {
                             imp.entities_view.show_entities_expired();
                             imp.view_stack.set_visible_child_name("entities");
@@ -55,6 +55,29 @@ pub fn new_filter<T: IsA<glib::Object>>(
     })
 }
 
+/// Returns a factory for list headers that show the label for the first item
+/// of each section.
+pub fn new_header_factory<T: IsA<glib::Object>>(
+    label: impl Fn(&T) -> String + 'static,
+) -> gtk::SignalListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(|_, list_header| {
+        let list_header = list_header.downcast_ref::<gtk::ListHeader>().unwrap();
+        let header_label = gtk::Label::builder()
+            .xalign(0.0)
+            .css_classes(["heading"])
+            .build();
+        list_header.set_child(Some(&header_label));
+    });
+    factory.connect_bind(move |_, list_header| {
+        let list_header = list_header.downcast_ref::<gtk::ListHeader>().unwrap();
+        let header_label = list_header.child().and_downcast::<gtk::Label>().unwrap();
+        let item = list_header.item().and_downcast::<T>().unwrap();
+        header_label.set_label(&label(&item));
+    });
+    factory
+}
+
 pub fn spawn_future_local_idle<R: 'static, F: std::future::Future<Output = R> + 'static>(
     f: F,
 ) -> glib::JoinHandle<R> {
@@ -0,0 +1,183 @@
+use std::fmt;
+
+use anyhow::{ensure, Result};
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+
+use crate::{
+    date_time_range::DateTimeRange,
+    quantity,
+    stock::Stock,
+    timeline::{self, Timeline},
+    timeline_item_kind::TimelineItemKind,
+};
+
+/// An amount of money, in the currency of a stock if it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Money {
+    pub amount: f64,
+    pub currency: Option<String>,
+}
+
+impl Money {
+    pub fn new(amount: f64, currency: Option<&str>) -> Self {
+        Self {
+            amount,
+            currency: currency.map(|s| s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2}", self.amount)?;
+
+        if let Some(currency) = &self.currency {
+            write!(f, " {}", currency)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the sum of the values per currency, e.g., "120.00 PHP, 5.50 USD".
+pub fn format_totals(values: impl IntoIterator<Item = Money>) -> String {
+    let mut totals = IndexMap::<Option<String>, f64>::new();
+    for value in values {
+        *totals.entry(value.currency).or_default() += value.amount;
+    }
+
+    if totals.is_empty() {
+        return Money::new(0.0, None).to_string();
+    }
+
+    totals
+        .into_iter()
+        .map(|(currency, amount)| Money { amount, currency }.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parses a cost per stock unit, which must be a non-negative number.
+pub fn parse_unit_cost(s: &str) -> Result<f64> {
+    let unit_cost = s.trim().parse::<f64>()?;
+    ensure!(
+        unit_cost.is_finite() && unit_cost >= 0.0,
+        "Unit cost must be a non-negative number"
+    );
+    Ok(unit_cost)
+}
+
+/// Returns the value of what is inside the stock at the end of the range, or
+/// `None` if the stock has no unit cost.
+pub fn current_value(stock: &Stock, dt_range: &DateTimeRange) -> Option<Money> {
+    let data = stock.data();
+    let unit_cost = data.unit_cost?;
+    Some(Money::new(
+        stock.n_inside_for_dt_range(dt_range) * unit_cost,
+        data.currency.as_deref(),
+    ))
+}
+
+/// Value of a stock within a range, at its unit cost.
+///
+/// Past costs are not kept, so everything is valued at the current one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockValuation {
+    pub unit_cost: Money,
+    pub current: Money,
+    pub entered: Money,
+    pub exited: Money,
+    /// Items that expired while inside.
+    pub expired: Money,
+}
+
+impl Timeline {
+    /// Returns the valuation of the stock within the range, or `None` if the
+    /// stock has no unit cost.
+    pub fn stock_valuation(
+        &self,
+        stock: &Stock,
+        dt_range: &DateTimeRange,
+        now: DateTime<Utc>,
+    ) -> Option<StockValuation> {
+        let data = stock.data();
+        let unit_cost = data.unit_cost?;
+        let unit = data.unit.as_deref();
+        let currency = data.currency.as_deref();
+
+        let (n_entered, n_exited) = self.iter_stock(dt_range, stock.id()).fold(
+            (0.0, 0.0),
+            |(n_entered, n_exited), item| {
+                let amount = timeline::stock_amount_of(&item, unit);
+                match item.kind() {
+                    TimelineItemKind::Entry => (n_entered + amount, n_exited),
+                    TimelineItemKind::Exit => (n_entered, n_exited + amount),
+                }
+            },
+        );
+
+        let n_expired = self
+            .entity_list()
+            .iter()
+            .filter(|entity| entity.stock_id().as_ref() == Some(stock.id()))
+            .filter(|entity| {
+                let data = entity.data();
+                data.expiration_dt().is_some_and(|&expiration_dt| {
+                    expiration_dt <= now
+                        && dt_range.contains(expiration_dt)
+                        && entity.is_inside_for_dt(expiration_dt)
+                })
+            })
+            .map(|entity| quantity::stock_amount(entity.data().quantity(), unit))
+            .sum::<f64>();
+
+        let value_of = |n: f64| Money::new(quantity::round(n) * unit_cost, currency);
+        Some(StockValuation {
+            unit_cost: Money::new(unit_cost, currency),
+            current: current_value(stock, dt_range)?,
+            entered: value_of(n_entered),
+            exited: value_of(n_exited),
+            expired: value_of(n_expired),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn money_display() {
+        assert_eq!(Money::new(12.5, Some("PHP")).to_string(), "12.50 PHP");
+        assert_eq!(Money::new(12.345678, None).to_string(), "12.35");
+        assert_eq!(Money::new(3.0, None).to_string(), "3.00");
+    }
+
+    #[test]
+    fn totals() {
+        assert_eq!(format_totals([]), "0.00");
+        assert_eq!(
+            format_totals([
+                Money::new(100.0, Some("PHP")),
+                Money::new(5.5, Some("USD")),
+                Money::new(20.0, Some("PHP")),
+            ]),
+            "120.00 PHP, 5.50 USD"
+        );
+        assert_eq!(
+            format_totals([Money::new(1.0, None), Money::new(2.25, None)]),
+            "3.25"
+        );
+    }
+
+    #[test]
+    fn unit_cost() {
+        assert_eq!(parse_unit_cost(" 12.75 ").unwrap(), 12.75);
+        assert_eq!(parse_unit_cost("0").unwrap(), 0.0);
+        assert!(parse_unit_cost("-1").is_err());
+        assert!(parse_unit_cost("inf").is_err());
+        assert!(parse_unit_cost("NaN").is_err());
+        assert!(parse_unit_cost("abc").is_err());
+    }
+}
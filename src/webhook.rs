@@ -0,0 +1,562 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use gtk::{
+    glib::{self, clone},
+    subclass::prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{self, EnvExt},
+    entity_id::EntityId,
+    settings::Settings,
+    stock_id::StockId,
+};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const RETRY_INITIAL_DELAY: Duration = Duration::from_secs(10);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Failed deliveries older than this are dropped instead of retried.
+const MAX_DELIVERY_AGE: TimeDelta = TimeDelta::days(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebhookEvent {
+    Entry,
+    Exit,
+    Overstayed,
+    DetectedWoId,
+    LimitReached,
+    ExpiredEntity,
+}
+
+impl WebhookEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Entry => "entry",
+            Self::Exit => "exit",
+            Self::Overstayed => "overstayed",
+            Self::DetectedWoId => "detected-wo-id",
+            Self::LimitReached => "limit-reached",
+            Self::ExpiredEntity => "expired-entity",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookTarget {
+    pub url: String,
+    /// Events to deliver. All events are delivered if empty.
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// JSON body with `{{placeholder}}`s, see [`WebhookEventData::render_body`].
+    #[serde(default)]
+    pub body_template: Option<String>,
+}
+
+impl WebhookTarget {
+    fn accepts(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookEventData {
+    pub event: WebhookEvent,
+    pub dt: DateTime<Utc>,
+    pub entity_id: Option<EntityId>,
+    pub entity_name: Option<String>,
+    pub stock_id: Option<StockId>,
+    pub message: String,
+}
+
+impl WebhookEventData {
+    pub fn new(event: WebhookEvent, dt: DateTime<Utc>, message: impl Into<String>) -> Self {
+        Self {
+            event,
+            dt,
+            entity_id: None,
+            entity_name: None,
+            stock_id: None,
+            message: message.into(),
+        }
+    }
+
+    fn vars(&self) -> [(&'static str, Option<String>); 6] {
+        [
+            ("event", Some(self.event.as_str().to_string())),
+            ("dt", Some(self.dt.to_rfc3339())),
+            (
+                "entity_id",
+                self.entity_id.as_ref().map(|id| id.to_string()),
+            ),
+            ("entity_name", self.entity_name.clone()),
+            ("stock_id", self.stock_id.as_ref().map(|id| id.to_string())),
+            ("message", Some(self.message.clone())),
+        ]
+    }
+
+    /// Replaces `{{event}}`, `{{dt}}`, `{{entity_id}}`, `{{entity_name}}`,
+    /// `{{stock_id}}` and `{{message}}` in the template with JSON-escaped
+    /// values, or empty strings if not available.
+    ///
+    /// If there is no template, a JSON object with all values is returned.
+    fn render_body(&self, template: Option<&str>) -> String {
+        let vars = self.vars();
+
+        let Some(template) = template else {
+            let object = vars
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), serde_json::Value::from(value)))
+                .collect::<serde_json::Map<_, _>>();
+            return serde_json::Value::Object(object).to_string();
+        };
+
+        vars.iter()
+            .fold(template.to_string(), |body, (name, value)| {
+                let escaped = value.as_deref().map(json_escape).unwrap_or_default();
+                body.replace(&format!("{{{{{name}}}}}"), &escaped)
+            })
+    }
+}
+
+pub trait WebhookSettingsExt {
+    /// Returns `None` if the webhooks JSON can't be parsed at all, so pending
+    /// deliveries are not mistaken as being for removed targets.
+    fn webhook_targets(&self) -> Option<Vec<WebhookTarget>>;
+}
+
+impl WebhookSettingsExt for Settings {
+    fn webhook_targets(&self) -> Option<Vec<WebhookTarget>> {
+        parse_targets(&self.webhooks_json())
+            .inspect_err(|err| tracing::warn!("Failed to parse webhooks: {:?}", err))
+            .ok()
+    }
+}
+
+mod imp {
+    use std::cell::{Cell, OnceCell, RefCell};
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct WebhookQueue {
+        pub(super) targets: RefCell<Vec<WebhookTarget>>,
+        pub(super) is_flushing: Cell<bool>,
+
+        pub(super) db: OnceCell<(heed::Env, db::WebhookQueueDbType)>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for WebhookQueue {
+        const NAME: &'static str = "UetsWebhookQueue";
+        type Type = super::WebhookQueue;
+    }
+
+    impl ObjectImpl for WebhookQueue {}
+}
+
+glib::wrapper! {
+    pub struct WebhookQueue(ObjectSubclass<imp::WebhookQueue>);
+}
+
+impl WebhookQueue {
+    /// Loads the queue and periodically retries pending deliveries.
+    pub fn load_from_env(env: heed::Env) -> Result<Self> {
+        let (db, n_pending) = env.with_write_txn(|wtxn| {
+            let db: db::WebhookQueueDbType =
+                env.create_database(wtxn, Some(db::WEBHOOK_QUEUE_DB_NAME))?;
+            let n_pending = db.len(wtxn)?;
+            Ok((db, n_pending))
+        })?;
+
+        tracing::debug!("Loaded {} pending webhook deliveries", n_pending);
+
+        let this = glib::Object::new::<Self>();
+
+        let imp = this.imp();
+        imp.db.set((env, db)).unwrap();
+
+        glib::timeout_add_local(
+            FLUSH_INTERVAL,
+            clone!(
+                #[weak]
+                this,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    this.flush();
+                    glib::ControlFlow::Continue
+                }
+            ),
+        );
+
+        this.flush();
+
+        Ok(this)
+    }
+
+    /// Sets the targets, dropping pending deliveries to targets that are no
+    /// longer configured, then flushes the rest.
+    pub fn set_targets(&self, targets: Vec<WebhookTarget>) {
+        let imp = self.imp();
+
+        imp.targets.replace(targets);
+
+        if let Err(err) = self.purge_removed_targets() {
+            tracing::error!("Failed to purge webhook deliveries: {:?}", err);
+        }
+
+        self.flush();
+    }
+
+    fn purge_removed_targets(&self) -> Result<()> {
+        let imp = self.imp();
+
+        let (env, db) = imp.db.get().unwrap();
+
+        let targets = imp.targets.borrow();
+        let n_purged = env.with_write_txn(|wtxn| {
+            let ids = db
+                .iter(wtxn)?
+                .filter_map(|res| match res {
+                    Ok((id, delivery)) => {
+                        (!targets.iter().any(|target| target.url == delivery.url)).then_some(Ok(id))
+                    }
+                    Err(err) => Some(Err(err)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for id in &ids {
+                db.delete(wtxn, id)?;
+            }
+
+            Ok(ids.len())
+        })?;
+
+        if n_purged > 0 {
+            tracing::debug!("Purged {} webhook deliveries to removed targets", n_purged);
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, id: String) -> Result<()> {
+        let (env, db) = self.imp().db.get().unwrap();
+        env.with_write_txn(|wtxn| {
+            db.delete(wtxn, &id)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Queues a delivery for each target that accepts the event, then
+    /// tries to deliver them right away.
+    pub fn enqueue(&self, data: &WebhookEventData) -> Result<()> {
+        let imp = self.imp();
+
+        let now = Utc::now();
+        let deliveries = imp
+            .targets
+            .borrow()
+            .iter()
+            .filter(|target| target.accepts(data.event))
+            .map(|target| db::RawWebhookDelivery {
+                url: target.url.clone(),
+                body: data.render_body(target.body_template.as_deref()),
+                created_dt: now,
+                n_attempts: 0,
+                next_attempt_dt: now,
+            })
+            .collect::<Vec<_>>();
+
+        if deliveries.is_empty() {
+            return Ok(());
+        }
+
+        let (env, db) = imp.db.get().unwrap();
+        env.with_write_txn(|wtxn| {
+            for delivery in &deliveries {
+                db.put(wtxn, &glib::uuid_string_random().to_string(), delivery)?;
+            }
+            Ok(())
+        })?;
+
+        tracing::debug!(
+            "Queued {} webhook deliveries for {:?}",
+            deliveries.len(),
+            data.event
+        );
+
+        self.flush();
+
+        Ok(())
+    }
+
+    fn flush(&self) {
+        let imp = self.imp();
+
+        if imp.is_flushing.get() {
+            return;
+        }
+
+        imp.is_flushing.set(true);
+
+        glib::spawn_future_local(clone!(
+            #[strong(rename_to = obj)]
+            self,
+            async move {
+                if let Err(err) = obj.flush_inner().await {
+                    tracing::error!("Failed to flush webhook queue: {:?}", err);
+                }
+
+                obj.imp().is_flushing.set(false);
+            }
+        ));
+    }
+
+    async fn flush_inner(&self) -> Result<()> {
+        let imp = self.imp();
+
+        let (env, db) = imp.db.get().unwrap();
+
+        let now = Utc::now();
+        let mut due = {
+            let rtxn = env.read_txn()?;
+            let due = db
+                .iter(&rtxn)?
+                .filter(|res| {
+                    res.as_ref()
+                        .map_or(true, |(_, delivery)| delivery.next_attempt_dt <= now)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            due
+        };
+        due.sort_by_key(|(_, delivery)| delivery.created_dt);
+
+        for (id, mut delivery) in due {
+            let url = match surf::Url::parse(&delivery.url) {
+                Ok(url) => url,
+                Err(err) => {
+                    self.remove(id)?;
+
+                    tracing::warn!(
+                        "Dropped webhook delivery to invalid URL `{}`: {:?}",
+                        delivery.url,
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            let err = match deliver(url, delivery.body.clone()).await {
+                Ok(status) if status.is_success() => {
+                    self.remove(id)?;
+
+                    tracing::debug!("Delivered webhook to {}", delivery.url);
+                    continue;
+                }
+                Ok(status) if !is_retryable_status(status) => {
+                    self.remove(id)?;
+
+                    tracing::warn!(
+                        "Dropped webhook delivery to {}: received status {}",
+                        delivery.url,
+                        status
+                    );
+                    continue;
+                }
+                Ok(status) => anyhow!("Received status {}", status),
+                Err(err) => err,
+            };
+
+            delivery.n_attempts += 1;
+
+            if Utc::now() - delivery.created_dt > MAX_DELIVERY_AGE {
+                self.remove(id)?;
+
+                tracing::warn!(
+                    "Dropped webhook delivery to {} after {} attempts: {:?}",
+                    delivery.url,
+                    delivery.n_attempts,
+                    err
+                );
+                continue;
+            }
+
+            delivery.next_attempt_dt =
+                Utc::now() + chrono::Duration::from_std(retry_delay(delivery.n_attempts)).unwrap();
+
+            env.with_write_txn(|wtxn| {
+                db.put(wtxn, &id, &delivery)?;
+                Ok(())
+            })?;
+
+            tracing::warn!(
+                "Failed to deliver webhook to {} (attempt {}): {:?}",
+                delivery.url,
+                delivery.n_attempts,
+                err
+            );
+        }
+
+        Ok(())
+    }
+}
+
+async fn deliver(url: surf::Url, body: String) -> Result<surf::StatusCode> {
+    let request = surf::RequestBuilder::new(surf::http::Method::Post, url)
+        .content_type(surf::http::mime::JSON)
+        .body_string(body);
+
+    let response = glib::future_with_timeout(DELIVERY_TIMEOUT, request)
+        .await
+        .ok()
+        .context("Timed out")?
+        .map_err(|err| err.into_inner())?;
+
+    Ok(response.status())
+}
+
+/// Whether a failed delivery may succeed later. Client errors won't, except
+/// for timeouts and rate limiting.
+fn is_retryable_status(status: surf::StatusCode) -> bool {
+    !status.is_client_error()
+        || matches!(
+            status,
+            surf::StatusCode::RequestTimeout | surf::StatusCode::TooManyRequests
+        )
+}
+
+fn retry_delay(n_attempts: u32) -> Duration {
+    RETRY_INITIAL_DELAY
+        .saturating_mul(2_u32.saturating_pow(n_attempts.saturating_sub(1)))
+        .min(RETRY_MAX_DELAY)
+}
+
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// Parses the targets, skipping invalid ones, so a typo in one target
+/// doesn't affect the others.
+fn parse_targets(json: &str) -> Result<Vec<WebhookTarget>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let values: Vec<serde_json::Value> =
+        serde_json::from_str(json).context("Invalid webhooks JSON")?;
+
+    let targets = values
+        .into_iter()
+        .filter_map(|value| {
+            let target = serde_json::from_value::<WebhookTarget>(value)
+                .context("Invalid webhook")
+                .and_then(|target| {
+                    surf::Url::parse(&target.url)
+                        .with_context(|| format!("Invalid webhook URL `{}`", target.url))?;
+                    Ok(target)
+                });
+            target
+                .inspect_err(|err| tracing::warn!("Skipped webhook: {:?}", err))
+                .ok()
+        })
+        .collect();
+
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_data() -> WebhookEventData {
+        WebhookEventData {
+            entity_id: Some(EntityId::new("1234")),
+            entity_name: Some("Juan \"JD\" Dela Cruz".into()),
+            ..WebhookEventData::new(
+                WebhookEvent::Entry,
+                DateTime::from_timestamp(0, 0).unwrap(),
+                "Welcome!",
+            )
+        }
+    }
+
+    #[test]
+    fn render_body_template() {
+        let body = event_data().render_body(Some(
+            r#"{"text": "{{entity_name}} ({{entity_id}}) {{event}}{{stock_id}}"}"#,
+        ));
+        assert_eq!(body, r#"{"text": "Juan \"JD\" Dela Cruz (1234) entry"}"#);
+        assert!(serde_json::from_str::<serde_json::Value>(&body).is_ok());
+    }
+
+    #[test]
+    fn render_body_default() {
+        let body = event_data().render_body(None);
+        let value = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(value["event"], "entry");
+        assert_eq!(value["dt"], "1970-01-01T00:00:00+00:00");
+        assert_eq!(value["entity_id"], "1234");
+        assert_eq!(value["stock_id"], serde_json::Value::Null);
+        assert_eq!(value["message"], "Welcome!");
+    }
+
+    #[test]
+    fn retry_delay_backoff() {
+        assert_eq!(retry_delay(1), Duration::from_secs(10));
+        assert_eq!(retry_delay(2), Duration::from_secs(20));
+        assert_eq!(retry_delay(3), Duration::from_secs(40));
+        assert_eq!(retry_delay(20), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn retryable_status() {
+        assert!(is_retryable_status(surf::StatusCode::InternalServerError));
+        assert!(is_retryable_status(surf::StatusCode::BadGateway));
+        assert!(is_retryable_status(surf::StatusCode::RequestTimeout));
+        assert!(is_retryable_status(surf::StatusCode::TooManyRequests));
+        assert!(!is_retryable_status(surf::StatusCode::BadRequest));
+        assert!(!is_retryable_status(surf::StatusCode::NotFound));
+        assert!(!is_retryable_status(surf::StatusCode::Gone));
+    }
+
+    #[test]
+    fn parse_targets_json() {
+        assert_eq!(parse_targets(" ").unwrap(), Vec::new());
+        assert!(parse_targets("{}").is_err());
+        assert!(parse_targets("[{\"url\": ").is_err());
+        assert_eq!(parse_targets("[{}]").unwrap(), Vec::new());
+        assert_eq!(
+            parse_targets(r#"[{"url": "x", "events": ["unknown"]}]"#).unwrap(),
+            Vec::new()
+        );
+        assert_eq!(
+            parse_targets(r#"[{"url": "hooks.local/x"}, {"url": "https://erp.local/uets"}]"#)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let targets = parse_targets(
+            r#"[
+                { "url": "https://hooks.slack.com/services/x", "events": ["overstayed", "detected-wo-id"] },
+                { "url": "https://erp.local/uets" }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert!(targets[0].accepts(WebhookEvent::DetectedWoId));
+        assert!(!targets[0].accepts(WebhookEvent::Entry));
+        assert!(targets[1].accepts(WebhookEvent::ExpiredEntity));
+        assert_eq!(targets[1].body_template, None);
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{date_time_range::DateTimeRange, debounce::DetectionSource, settings::Settings};
+
+/// Shown in paths in place of the zone of readers that don't belong to any.
+pub const NO_ZONE_NAME: &str = "Entrance";
+
+/// A part of the site with its own readers, e.g., "Library".
+///
+/// Readers that don't belong to any zone are the main entrance of the site.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneDef {
+    pub name: String,
+    /// Readers in the zone, either a reader type, i.e., "camera",
+    /// "rfid-reader", "local-reader", or "mqtt", for all readers of that
+    /// type, or a single reader as "<type>:<instance>", e.g.,
+    /// "local-reader:/dev/ttyUSB0", "rfid-reader:192.168.1.20", or
+    /// "mqtt:lab-1" for IDs published to the "detect/lab-1" topic.
+    #[serde(default)]
+    pub readers: Vec<String>,
+    /// How long an entity may stay in the zone before it is overstayed.
+    #[serde(default)]
+    pub max_stay_secs: Option<u32>,
+    /// When entities are allowed to enter the zone. Always allowed if not set.
+    #[serde(default)]
+    pub allowed_dt_range: Option<DateTimeRange>,
+}
+
+impl ZoneDef {
+    pub fn is_allowed(&self, dt: DateTime<Utc>) -> bool {
+        self.allowed_dt_range
+            .map_or(true, |dt_range| dt_range.contains(dt))
+    }
+
+    /// Returns whether an entity that entered the zone at `entered_dt` has
+    /// stayed for too long.
+    pub fn is_overstayed(&self, entered_dt: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.max_stay_secs
+            .is_some_and(|secs| now - entered_dt > TimeDelta::seconds(secs as i64))
+    }
+
+    /// Returns whether the reader is in the zone, either by itself, if
+    /// `instance` is given, or by its type.
+    fn has_reader(&self, source: DetectionSource, instance: Option<&str>) -> bool {
+        let name = match instance {
+            Some(instance) => format!("{}:{}", reader_name(source), instance),
+            None => reader_name(source).to_string(),
+        };
+        self.readers.contains(&name)
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(!self.name.trim().is_empty(), "Name must not be empty");
+
+        for name in &self.readers {
+            let (type_name, instance) = match name.split_once(':') {
+                Some((type_name, instance)) => (type_name, Some(instance)),
+                None => (name.as_str(), None),
+            };
+
+            if !DetectionSource::all()
+                .iter()
+                .any(|&source| reader_name(source) == type_name)
+            {
+                bail!("Unknown reader `{}`", name);
+            }
+
+            if instance.is_some_and(|instance| instance.trim().is_empty()) {
+                bail!("Reader `{}` has no instance", name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn reader_name(source: DetectionSource) -> &'static str {
+    match source {
+        DetectionSource::Camera => "camera",
+        DetectionSource::RfidReader => "rfid-reader",
+        DetectionSource::LocalReader => "local-reader",
+        DetectionSource::Mqtt => "mqtt",
+    }
+}
+
+/// What a read at a reader does to an entity, given the zones it went
+/// through since it entered.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ZoneStep {
+    Enter,
+    Exit,
+    MoveTo(Option<String>),
+}
+
+impl ZoneStep {
+    /// A read at the reader of the zone the entity entered through, or at
+    /// the main entrance, exits. A read at the zone the entity is in goes
+    /// back to the zone it came from, while a read at any other zone moves
+    /// into it.
+    pub fn for_read(path: &[Option<String>], zone: Option<&str>) -> Self {
+        let Some(entered_zone) = path.first() else {
+            return Self::Enter;
+        };
+
+        let Some(zone) = zone else {
+            return Self::Exit;
+        };
+
+        if entered_zone.as_deref() == Some(zone) {
+            Self::Exit
+        } else if path.last().unwrap().as_deref() == Some(zone) {
+            Self::MoveTo(path[path.len() - 2].clone())
+        } else {
+            Self::MoveTo(Some(zone.to_string()))
+        }
+    }
+}
+
+/// Applies a move to the zones the entity went through. Moving back to the
+/// zone it came from leaves the current one.
+pub fn apply_move(path: &mut Vec<Option<String>>, to: Option<String>) {
+    if path.len() >= 2 && path[path.len() - 2] == to {
+        path.pop();
+    } else {
+        path.push(to);
+    }
+}
+
+/// Returns what each of the reads at the given zones does, in order, given
+/// whether the entity is inside and the zones it went through.
+pub fn replay_reads(
+    mut is_inside: bool,
+    mut path: Vec<Option<String>>,
+    zones: &[Option<String>],
+) -> Vec<ZoneStep> {
+    zones
+        .iter()
+        .map(|zone| {
+            let step = match ZoneStep::for_read(&path, zone.as_deref()) {
+                ZoneStep::MoveTo(to) if is_inside => {
+                    apply_move(&mut path, to.clone());
+                    ZoneStep::MoveTo(to)
+                }
+                _ if is_inside => {
+                    path.clear();
+                    ZoneStep::Exit
+                }
+                _ => {
+                    path = vec![zone.clone()];
+                    ZoneStep::Enter
+                }
+            };
+            is_inside = step != ZoneStep::Exit;
+            step
+        })
+        .collect()
+}
+
+/// Returns the path, e.g., "Gate → Library → Lab 2".
+pub fn format_path(path: &[Option<String>]) -> String {
+    path.iter()
+        .map(|zone| zone.as_deref().unwrap_or(NO_ZONE_NAME))
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+pub trait ZoneSettingsExt {
+    fn zone_defs(&self) -> Vec<ZoneDef>;
+
+    /// Returns the name of the zone the reader belongs to, where `instance`
+    /// identifies the reader among the ones of its type, e.g., the device
+    /// path of a local reader.
+    fn zone_for_reader(&self, source: DetectionSource, instance: Option<&str>) -> Option<String>;
+}
+
+impl ZoneSettingsExt for Settings {
+    fn zone_defs(&self) -> Vec<ZoneDef> {
+        parse_defs(&self.zones_json())
+            .inspect_err(|err| tracing::warn!("Failed to parse zones: {:?}", err))
+            .unwrap_or_default()
+    }
+
+    fn zone_for_reader(&self, source: DetectionSource, instance: Option<&str>) -> Option<String> {
+        find_zone(&self.zone_defs(), source, instance).map(|def| def.name.clone())
+    }
+}
+
+/// Returns the zone the reader belongs to. A zone with the reader itself
+/// takes precedence over one with all readers of its type.
+fn find_zone<'a>(
+    defs: &'a [ZoneDef],
+    source: DetectionSource,
+    instance: Option<&str>,
+) -> Option<&'a ZoneDef> {
+    instance
+        .and_then(|instance| {
+            defs.iter()
+                .find(|def| def.has_reader(source, Some(instance)))
+        })
+        .or_else(|| defs.iter().find(|def| def.has_reader(source, None)))
+}
+
+/// Parses the definitions, skipping the invalid ones, the ones with duplicate
+/// names, and readers that already belong to another zone.
+fn parse_defs(json: &str) -> Result<Vec<ZoneDef>> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let defs = serde_json::from_str::<Vec<ZoneDef>>(json).context("Invalid zones JSON")?;
+
+    let mut names = HashSet::new();
+    let mut readers = HashSet::new();
+    let valid_defs = defs
+        .into_iter()
+        .filter(|def| {
+            if let Err(err) = def.validate() {
+                tracing::warn!("Skipping invalid zone `{}`: {:?}", def.name, err);
+                return false;
+            }
+
+            if !names.insert(def.name.clone()) {
+                tracing::warn!("Skipping duplicate zone `{}`", def.name);
+                return false;
+            }
+
+            true
+        })
+        .map(|mut def| {
+            def.readers.retain(|name| {
+                let is_new = readers.insert(name.clone());
+                if !is_new {
+                    tracing::warn!("Reader `{}` already belongs to another zone", name);
+                }
+                is_new
+            });
+            def
+        })
+        .collect();
+    Ok(valid_defs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn z(name: &str) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    #[test]
+    fn parse() {
+        let defs = parse_defs(
+            r#"[
+                { "name": "Gate", "readers": ["rfid-reader"] },
+                { "name": "Library", "readers": ["camera", "rfid-reader"], "max_stay_secs": 60 },
+                { "name": "", "readers": ["mqtt"] },
+                { "name": "Lab", "readers": ["printer"] },
+                { "name": "Gate", "readers": ["mqtt"] }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            defs.iter().map(|def| def.name.as_str()).collect::<Vec<_>>(),
+            ["Gate", "Library"]
+        );
+        assert_eq!(defs[1].readers, ["camera"]);
+        assert!(defs[0].has_reader(DetectionSource::RfidReader, None));
+        assert!(!defs[1].has_reader(DetectionSource::RfidReader, None));
+
+        assert!(parse_defs("").unwrap().is_empty());
+        assert!(parse_defs("[{\"readers\": []}]").is_err());
+    }
+
+    #[test]
+    fn find_zone_by_reader() {
+        let defs = parse_defs(
+            r#"[
+                { "name": "Gate", "readers": ["local-reader:/dev/ttyUSB0", "rfid-reader"] },
+                { "name": "Library", "readers": ["local-reader:/dev/ttyUSB1"] },
+                { "name": "Lab 1", "readers": ["mqtt:lab-1", "local-reader:/dev/ttyUSB1"] },
+                { "name": "Lab 2", "readers": ["mqtt:lab-2", "mqtt"] },
+                { "name": "Lab 3", "readers": ["mqtt:"] },
+                { "name": "Lab 4", "readers": ["printer:lab-4"] }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            defs.iter().map(|def| def.name.as_str()).collect::<Vec<_>>(),
+            ["Gate", "Library", "Lab 1", "Lab 2"]
+        );
+        assert_eq!(defs[2].readers, ["mqtt:lab-1"]);
+
+        let zone =
+            |source, instance| find_zone(&defs, source, instance).map(|def| def.name.as_str());
+        assert_eq!(
+            zone(DetectionSource::LocalReader, Some("/dev/ttyUSB0")),
+            Some("Gate")
+        );
+        assert_eq!(
+            zone(DetectionSource::LocalReader, Some("/dev/ttyUSB1")),
+            Some("Library")
+        );
+        assert_eq!(
+            zone(DetectionSource::LocalReader, Some("/dev/ttyUSB2")),
+            None
+        );
+        assert_eq!(
+            zone(DetectionSource::RfidReader, Some("192.168.1.20")),
+            Some("Gate")
+        );
+        assert_eq!(zone(DetectionSource::Mqtt, Some("lab-1")), Some("Lab 1"));
+        assert_eq!(zone(DetectionSource::Mqtt, Some("lab-3")), Some("Lab 2"));
+        assert_eq!(zone(DetectionSource::Mqtt, None), Some("Lab 2"));
+        assert_eq!(zone(DetectionSource::Camera, None), None);
+    }
+
+    #[test]
+    fn overstayed() {
+        let def = ZoneDef {
+            name: "Library".into(),
+            readers: Vec::new(),
+            max_stay_secs: Some(60),
+            allowed_dt_range: None,
+        };
+        let entered_dt = Utc::now();
+        assert!(!def.is_overstayed(entered_dt, entered_dt + TimeDelta::seconds(60)));
+        assert!(def.is_overstayed(entered_dt, entered_dt + TimeDelta::seconds(61)));
+        assert!(def.is_allowed(entered_dt));
+    }
+
+    #[test]
+    fn step_for_read() {
+        assert_eq!(ZoneStep::for_read(&[], Some("Gate")), ZoneStep::Enter);
+        assert_eq!(ZoneStep::for_read(&[], None), ZoneStep::Enter);
+
+        let path = [z("Gate"), z("Library"), z("Lab 2")];
+        assert_eq!(ZoneStep::for_read(&path, Some("Gate")), ZoneStep::Exit);
+        assert_eq!(ZoneStep::for_read(&path, None), ZoneStep::Exit);
+        assert_eq!(
+            ZoneStep::for_read(&path, Some("Lab 2")),
+            ZoneStep::MoveTo(z("Library"))
+        );
+        assert_eq!(
+            ZoneStep::for_read(&path, Some("Cafeteria")),
+            ZoneStep::MoveTo(z("Cafeteria"))
+        );
+
+        // Entered through the main entrance
+        assert_eq!(
+            ZoneStep::for_read(&[None, z("Library")], Some("Library")),
+            ZoneStep::MoveTo(None)
+        );
+    }
+
+    #[test]
+    fn replay() {
+        assert_eq!(
+            replay_reads(false, Vec::new(), &[z("Gate"), z("Library")]),
+            [ZoneStep::Enter, ZoneStep::MoveTo(z("Library"))]
+        );
+        assert_eq!(
+            replay_reads(
+                false,
+                Vec::new(),
+                &[z("Gate"), z("Library"), z("Library"), z("Gate"), None]
+            ),
+            [
+                ZoneStep::Enter,
+                ZoneStep::MoveTo(z("Library")),
+                ZoneStep::MoveTo(z("Gate")),
+                ZoneStep::Exit,
+                ZoneStep::Enter,
+            ]
+        );
+        assert_eq!(
+            replay_reads(true, vec![z("Gate"), z("Library")], &[None, None]),
+            [ZoneStep::Exit, ZoneStep::Enter]
+        );
+
+        // Entered before zones were configured
+        assert_eq!(
+            replay_reads(true, Vec::new(), &[z("Library")]),
+            [ZoneStep::Exit]
+        );
+    }
+
+    #[test]
+    fn path() {
+        let mut path = vec![z("Gate")];
+        apply_move(&mut path, z("Library"));
+        apply_move(&mut path, z("Lab 2"));
+        assert_eq!(format_path(&path), "Gate → Library → Lab 2");
+
+        apply_move(&mut path, z("Library"));
+        assert_eq!(format_path(&path), "Gate → Library");
+
+        let mut path = vec![None];
+        apply_move(&mut path, z("Library"));
+        assert_eq!(format_path(&path), "Entrance → Library");
+        apply_move(&mut path, None);
+        assert_eq!(format_path(&path), "Entrance");
+    }
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+
+use crate::{db, entity_id::EntityId};
+
+/// A record of an entity inside moving from one zone to another. `None` is
+/// the area of the readers that don't belong to any zone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneMove {
+    pub dt: DateTime<Utc>,
+    pub entity_id: EntityId,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl ZoneMove {
+    pub fn from_db((dt, entity_id): (DateTime<Utc>, EntityId), raw: db::RawZoneMove) -> Self {
+        Self {
+            dt,
+            entity_id,
+            from: raw.from,
+            to: raw.to,
+        }
+    }
+
+    pub fn db_key(&self) -> (DateTime<Utc>, EntityId) {
+        (self.dt, self.entity_id.clone())
+    }
+
+    pub fn to_db(&self) -> db::RawZoneMove {
+        db::RawZoneMove {
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }
+    }
+}